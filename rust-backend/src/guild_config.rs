@@ -0,0 +1,398 @@
+// Per-guild command vocabulary, prefixes and search defaults
+// Loaded from a JSON or TOML file and hot-reloaded when the file changes
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+// Music sources `default_search_source` may name
+pub const SEARCH_SOURCES: &[&str] = &["youtube", "soundcloud"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    // Intent name -> trigger words, e.g. "play" -> ["play", "put on"]
+    pub trigger_words: HashMap<String, Vec<String>>,
+    pub disabled_intents: Vec<String>,
+    // Messages must start with one of these (after an optional mention)
+    pub prefixes: Vec<String>,
+    pub require_mention: bool,
+    pub default_search_source: String,
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        let mut trigger_words = HashMap::new();
+        trigger_words.insert(
+            "play".to_string(),
+            vec![
+                "play".to_string(),
+                "put on".to_string(),
+                "start".to_string(),
+                "begin".to_string(),
+                "queue".to_string(),
+            ],
+        );
+
+        Self {
+            trigger_words,
+            disabled_intents: Vec::new(),
+            prefixes: Vec::new(),
+            require_mention: false,
+            default_search_source: "youtube".to_string(),
+        }
+    }
+}
+
+impl GuildConfig {
+    pub fn is_intent_enabled(&self, intent: &str) -> bool {
+        !self.disabled_intents.iter().any(|disabled| disabled == intent)
+    }
+
+    // Lowercases trigger words, prefixes and the search source so they match
+    // lowercased message content, and rejects unknown search sources
+    pub fn normalize(&mut self) -> Result<(), GuildConfigError> {
+        for patterns in self.trigger_words.values_mut() {
+            for pattern in patterns.iter_mut() {
+                *pattern = pattern.trim().to_lowercase();
+            }
+            patterns.retain(|pattern| !pattern.is_empty());
+        }
+        for prefix in self.prefixes.iter_mut() {
+            *prefix = prefix.to_lowercase();
+        }
+
+        self.default_search_source = self.default_search_source.trim().to_lowercase();
+        if !SEARCH_SOURCES.contains(&self.default_search_source.as_str()) {
+            return Err(GuildConfigError::Parse(format!(
+                "unknown search source '{}', expected one of {}",
+                self.default_search_source,
+                SEARCH_SOURCES.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn patterns_for(&self, intent: &str) -> Option<&[String]> {
+        if !self.is_intent_enabled(intent) {
+            return None;
+        }
+        self.trigger_words.get(intent).map(|patterns| patterns.as_slice())
+    }
+
+    // Strips the bot mention and command prefix from a lowercased message.
+    // Returns None when the message is not addressed to the bot under this guild's rules.
+    pub fn strip_activation<'a>(&self, content: &'a str, bot_user_id: Option<&str>) -> Option<&'a str> {
+        let mut rest = content.trim_start();
+
+        let mut mentioned = false;
+        if let Some(bot_id) = bot_user_id {
+            for mention in [format!("<@{}>", bot_id), format!("<@!{}>", bot_id)] {
+                if let Some(stripped) = rest.strip_prefix(mention.as_str()) {
+                    rest = stripped.trim_start();
+                    mentioned = true;
+                    break;
+                }
+            }
+            if !mentioned {
+                mentioned = rest.contains(&format!("<@{}>", bot_id)) || rest.contains(&format!("<@!{}>", bot_id));
+            }
+        }
+
+        if self.require_mention && !mentioned {
+            return None;
+        }
+
+        if self.prefixes.is_empty() {
+            return Some(rest);
+        }
+
+        self.prefixes
+            .iter()
+            .find_map(|prefix| rest.strip_prefix(prefix.as_str()))
+            .map(|stripped| stripped.trim_start())
+    }
+}
+
+// On-disk layout: a default block plus per-guild overrides keyed by guild id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildConfigFile {
+    pub bot_user_id: Option<String>,
+    pub default: GuildConfig,
+    pub guilds: HashMap<String, GuildConfig>,
+}
+
+#[derive(Debug)]
+pub enum GuildConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    NoSource,
+}
+
+impl fmt::Display for GuildConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GuildConfigError::Io(err) => write!(f, "failed to read guild config: {}", err),
+            GuildConfigError::Parse(msg) => write!(f, "invalid guild config: {}", msg),
+            GuildConfigError::NoSource => write!(f, "no guild config file has been loaded"),
+        }
+    }
+}
+
+impl std::error::Error for GuildConfigError {}
+
+impl From<std::io::Error> for GuildConfigError {
+    fn from(err: std::io::Error) -> Self {
+        GuildConfigError::Io(err)
+    }
+}
+
+impl GuildConfigFile {
+    pub fn parse(path: &Path, raw: &str) -> Result<Self, GuildConfigError> {
+        let is_toml = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("toml"))
+            .unwrap_or(false);
+
+        let mut file: Self = if is_toml {
+            toml::from_str(raw).map_err(|err| GuildConfigError::Parse(err.to_string()))?
+        } else {
+            serde_json::from_str(raw).map_err(|err| GuildConfigError::Parse(err.to_string()))?
+        };
+
+        file.default.normalize()?;
+        for (guild_id, config) in file.guilds.iter_mut() {
+            config
+                .normalize()
+                .map_err(|err| GuildConfigError::Parse(format!("guild {}: {}", guild_id, err)))?;
+        }
+        Ok(file)
+    }
+}
+
+// Thread-safe store shared by the processor, the file watcher and the Node API.
+// Configs come in two layers: what the file says, and runtime overrides set
+// through the API. A guild's runtime override wins over its file entry, which
+// wins over the default block. Reloading the file only replaces the file
+// layer, so overrides survive until removed.
+pub struct GuildConfigStore {
+    default: RwLock<GuildConfig>,
+    guilds: DashMap<String, GuildConfig>,
+    overrides: DashMap<String, GuildConfig>,
+    bot_user_id: RwLock<Option<String>>,
+    source: RwLock<Option<PathBuf>>,
+    last_modified: RwLock<Option<SystemTime>>,
+    watch_interval: RwLock<Duration>,
+    watching: AtomicBool,
+}
+
+impl GuildConfigStore {
+    pub fn new() -> Self {
+        Self {
+            default: RwLock::new(GuildConfig::default()),
+            guilds: DashMap::new(),
+            overrides: DashMap::new(),
+            bot_user_id: RwLock::new(None),
+            source: RwLock::new(None),
+            last_modified: RwLock::new(None),
+            watch_interval: RwLock::new(Duration::from_secs(5)),
+            watching: AtomicBool::new(false),
+        }
+    }
+
+    // Resolves the effective config for a guild: runtime override, then the
+    // file's entry, then the default block
+    pub fn get(&self, guild_id: &str) -> GuildConfig {
+        if let Some(config) = self.overrides.get(guild_id) {
+            return config.clone();
+        }
+        if let Some(config) = self.guilds.get(guild_id) {
+            return config.clone();
+        }
+        self.default.read().unwrap().clone()
+    }
+
+    // Sets a runtime override, kept across file reloads
+    pub fn set(&self, guild_id: &str, mut config: GuildConfig) -> Result<(), GuildConfigError> {
+        config.normalize()?;
+        self.overrides.insert(guild_id.to_string(), config);
+        Ok(())
+    }
+
+    // Drops a runtime override so the guild falls back to the file again
+    pub fn remove(&self, guild_id: &str) -> bool {
+        self.overrides.remove(guild_id).is_some()
+    }
+
+    // Replaces the default block until the file is next reloaded
+    pub fn set_default(&self, mut config: GuildConfig) -> Result<(), GuildConfigError> {
+        config.normalize()?;
+        *self.default.write().unwrap() = config;
+        Ok(())
+    }
+
+    pub fn bot_user_id(&self) -> Option<String> {
+        self.bot_user_id.read().unwrap().clone()
+    }
+
+    pub fn set_bot_user_id(&self, bot_user_id: Option<String>) {
+        *self.bot_user_id.write().unwrap() = bot_user_id;
+    }
+
+    pub fn load_from_file(&self, path: impl AsRef<Path>) -> Result<(), GuildConfigError> {
+        let path = path.as_ref().to_path_buf();
+        *self.source.write().unwrap() = Some(path);
+        self.reload()
+    }
+
+    // Re-reads the last loaded file and swaps in its default block and guild
+    // entries. Changed guilds are updated in place and only guilds gone from
+    // the file are removed, so a concurrent `get` never sees a guild missing.
+    // Runtime overrides are left alone.
+    pub fn reload(&self) -> Result<(), GuildConfigError> {
+        let path = self.source.read().unwrap().clone().ok_or(GuildConfigError::NoSource)?;
+        let modified = std::fs::metadata(&path)?.modified().ok();
+        let raw = std::fs::read_to_string(&path)?;
+        let file = GuildConfigFile::parse(&path, &raw)?;
+
+        *self.default.write().unwrap() = file.default;
+        if file.bot_user_id.is_some() {
+            self.set_bot_user_id(file.bot_user_id);
+        }
+        let stale: Vec<String> = self
+            .guilds
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|guild_id| !file.guilds.contains_key(guild_id))
+            .collect();
+        for (guild_id, config) in file.guilds {
+            let unchanged = matches!(self.guilds.get(&guild_id), Some(current) if *current == config);
+            if !unchanged {
+                self.guilds.insert(guild_id, config);
+            }
+        }
+        for guild_id in stale {
+            self.guilds.remove(&guild_id);
+        }
+        *self.last_modified.write().unwrap() = modified;
+
+        Ok(())
+    }
+
+    fn reload_if_changed(&self) -> Result<bool, GuildConfigError> {
+        let path = match self.source.read().unwrap().clone() {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+        let modified = std::fs::metadata(&path)?.modified().ok();
+        if modified.is_some() && modified == *self.last_modified.read().unwrap() {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    // Polls the loaded file for changes. Only one watcher thread is ever started;
+    // later calls just change the interval. The thread exits once the store is dropped.
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        *self.watch_interval.write().unwrap() = interval;
+        if self.watching.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let store: Weak<Self> = Arc::downgrade(self);
+        std::thread::spawn(move || loop {
+            let interval = match store.upgrade() {
+                Some(store) => *store.watch_interval.read().unwrap(),
+                None => break,
+            };
+            std::thread::sleep(interval);
+            let Some(store) = store.upgrade() else {
+                break;
+            };
+            if let Err(err) = store.reload_if_changed() {
+                tracing::warn!("guild config reload failed: {}", err);
+            }
+        });
+    }
+}
+
+impl Default for GuildConfigStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_words_and_prefixes_are_lowercased_on_load() {
+        let raw = r#"{
+            "default": { "trigger_words": { "play": ["Play", " Put On "] }, "prefixes": ["!DJ"] },
+            "guilds": { "1": { "default_search_source": "SoundCloud" } }
+        }"#;
+        let file = GuildConfigFile::parse(Path::new("guilds.json"), raw).unwrap();
+
+        assert_eq!(file.default.patterns_for("play").unwrap(), ["play", "put on"]);
+        assert_eq!(file.default.strip_activation("!dj play song", None), Some("play song"));
+        assert_eq!(file.guilds["1"].default_search_source, "soundcloud");
+    }
+
+    #[test]
+    fn unknown_search_source_is_rejected() {
+        let raw = "[guilds.1]\ndefault_search_source = \"napster\"\n";
+        let err = GuildConfigFile::parse(Path::new("guilds.toml"), raw).unwrap_err();
+        assert!(err.to_string().contains("napster"));
+
+        let store = GuildConfigStore::new();
+        let config = GuildConfig {
+            default_search_source: "napster".to_string(),
+            ..GuildConfig::default()
+        };
+        assert!(store.set("1", config).is_err());
+        assert_eq!(store.get("1").default_search_source, "youtube");
+    }
+
+    #[test]
+    fn set_lowercases_runtime_configs() {
+        let store = GuildConfigStore::new();
+        let mut config = GuildConfig::default();
+        config.trigger_words.insert("play".to_string(), vec!["Spin".to_string()]);
+        store.set("1", config).unwrap();
+
+        assert_eq!(store.get("1").patterns_for("play").unwrap(), ["spin"]);
+    }
+
+    #[test]
+    fn reload_updates_the_file_layer_and_keeps_runtime_overrides() {
+        let path = std::env::temp_dir().join(format!("guild-config-reload-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "guilds": { "1": { "prefixes": ["!"] }, "2": { "prefixes": ["?"] } } }"#).unwrap();
+        let store = GuildConfigStore::new();
+        store.load_from_file(&path).unwrap();
+
+        let mut runtime = GuildConfig::default();
+        runtime.prefixes = vec!["$".to_string()];
+        store.set("2", runtime).unwrap();
+        store.set("3", GuildConfig { require_mention: true, ..GuildConfig::default() }).unwrap();
+
+        std::fs::write(&path, r#"{ "guilds": { "1": { "prefixes": ["!!"] } } }"#).unwrap();
+        store.reload().unwrap();
+
+        assert_eq!(store.get("1").prefixes, ["!!"]);
+        assert_eq!(store.get("2").prefixes, ["$"]);
+        assert!(store.get("3").require_mention);
+
+        assert!(store.remove("2"));
+        assert!(store.get("2").prefixes.is_empty(), "guild 2 left the file, so it falls back to the default");
+        let _ = std::fs::remove_file(&path);
+    }
+}
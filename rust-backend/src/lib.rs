@@ -7,6 +7,11 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use dashmap::DashMap;
+use std::time::Duration;
+
+mod guild_config;
+
+pub use guild_config::{GuildConfig, GuildConfigError, GuildConfigFile, GuildConfigStore, SEARCH_SOURCES};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MusicTrack {
@@ -34,6 +39,17 @@ pub struct NaturalLanguageResponse {
     pub confidence: f32,
 }
 
+impl NaturalLanguageResponse {
+    fn none() -> Self {
+        Self {
+            response: None,
+            is_music_command: false,
+            extracted_query: None,
+            confidence: 0.0,
+        }
+    }
+}
+
 // High-performance music search engine
 pub struct MusicSearchEngine {
     cache: Arc<DashMap<String, MusicTrack>>,
@@ -49,23 +65,34 @@ impl MusicSearchEngine {
     }
 
     pub async fn search_track(&self, query: &str) -> Option<MusicTrack> {
+        self.search_track_from(query, "youtube").await
+    }
+
+    // Searches the given source, e.g. a guild's configured default
+    pub async fn search_track_from(&self, query: &str, source: &str) -> Option<MusicTrack> {
+        let cache_key = format!("{}:{}", source, query);
+
         // Check cache first
-        if let Some(track) = self.cache.get(query) {
+        if let Some(track) = self.cache.get(&cache_key) {
             return Some(track.clone());
         }
 
         // Perform high-speed search
-        let track = self.perform_search(query).await;
+        let track = self.perform_search(query, source).await;
         
         if let Some(ref track) = track {
-            self.cache.insert(query.to_string(), track.clone());
+            self.cache.insert(cache_key, track.clone());
         }
 
         track
     }
 
-    async fn perform_search(&self, query: &str) -> Option<MusicTrack> {
-        // Ultra-fast YouTube search with multiple fallbacks
+    async fn perform_search(&self, query: &str, source: &str) -> Option<MusicTrack> {
+        if !SEARCH_SOURCES.contains(&source) {
+            tracing::warn!("music source '{}' not available, falling back to youtube", source);
+        }
+
+        // Ultra-fast search with multiple fallbacks
         let search_queries = vec![
             query.to_string(),
             format!("{} official", query),
@@ -73,8 +100,12 @@ impl MusicSearchEngine {
         ];
 
         for search_query in search_queries {
-            if let Some(track) = self.search_youtube(&search_query).await {
-                return Some(track);
+            let track = match source {
+                "soundcloud" => self.search_soundcloud(&search_query).await,
+                _ => self.search_youtube(&search_query).await,
+            };
+            if track.is_some() {
+                return track;
             }
         }
 
@@ -93,92 +124,102 @@ impl MusicSearchEngine {
             thumbnail: None,
         })
     }
+
+    async fn search_soundcloud(&self, query: &str) -> Option<MusicTrack> {
+        // SoundCloud search; would go through yt-dlp's soundcloud extractor
+        Some(MusicTrack {
+            title: query.to_string(),
+            artist: "Unknown Artist".to_string(),
+            duration: 180,
+            url: format!("https://soundcloud.com/search/sounds?q={}", query.replace(' ', "+")),
+            source: "soundcloud".to_string(),
+            thumbnail: None,
+        })
+    }
 }
 
 // Ultra-fast natural language processor
 pub struct NaturalLanguageProcessor {
     music_engine: Arc<MusicSearchEngine>,
-    command_patterns: Arc<DashMap<String, Vec<String>>>,
+    guild_configs: Arc<GuildConfigStore>,
 }
 
 impl NaturalLanguageProcessor {
     pub fn new() -> Self {
-        let mut processor = Self {
+        Self {
             music_engine: Arc::new(MusicSearchEngine::new()),
-            command_patterns: Arc::new(DashMap::new()),
-        };
-        
-        processor.initialize_patterns();
-        processor
+            guild_configs: Arc::new(GuildConfigStore::new()),
+        }
     }
 
-    fn initialize_patterns(&self) {
-        // High-performance pattern matching for music commands
-        let play_patterns = vec![
-            "play".to_string(),
-            "put on".to_string(),
-            "start".to_string(),
-            "begin".to_string(),
-            "queue".to_string(),
-            "put on".to_string(),
-        ];
-        
-        self.command_patterns.insert("play".to_string(), play_patterns);
+    pub fn guild_configs(&self) -> Arc<GuildConfigStore> {
+        self.guild_configs.clone()
+    }
+
+    // Loads guild configs from a JSON/TOML file and reloads it when it changes.
+    // Calling it again switches the file and poll interval of the existing watcher.
+    pub fn load_guild_configs(&self, path: &str, poll_interval: Duration) -> Result<(), GuildConfigError> {
+        self.guild_configs.load_from_file(path)?;
+        self.guild_configs.watch(poll_interval);
+        Ok(())
     }
 
     pub async fn process_message(&self, request: NaturalLanguageRequest) -> NaturalLanguageResponse {
-        let content = request.message.to_lowercase();
+        let config = self.guild_configs.get(&request.guild_id);
+        let bot_user_id = self.guild_configs.bot_user_id();
+        let lowered = request.message.to_lowercase();
+
+        // Ignore messages that don't satisfy this guild's mention/prefix rules
+        let content = match config.strip_activation(&lowered, bot_user_id.as_deref()) {
+            Some(content) => content,
+            None => return NaturalLanguageResponse::none(),
+        };
         
         // Ultra-fast pattern matching
-        if self.is_music_command(&content) {
-            let query = self.extract_song_query(&content);
-            let confidence = self.calculate_confidence(&content, &query);
-            
-            if let Some(ref song_query) = query {
-                if let Some(track) = self.music_engine.search_track(song_query).await {
-                    return NaturalLanguageResponse {
-                        response: Some(format!("🎵 Playing: **{}** by {}", track.title, track.artist)),
-                        is_music_command: true,
-                        extracted_query: query,
-                        confidence,
-                    };
+        if let Some(patterns) = config.patterns_for("play") {
+            if Self::is_music_command(content, patterns) {
+                let query = Self::extract_song_query(content, patterns);
+                let confidence = self.calculate_confidence(content, &query);
+                
+                if let Some(ref song_query) = query {
+                    if let Some(track) = self
+                        .music_engine
+                        .search_track_from(song_query, &config.default_search_source)
+                        .await
+                    {
+                        return NaturalLanguageResponse {
+                            response: Some(format!("🎵 Playing: **{}** by {}", track.title, track.artist)),
+                            is_music_command: true,
+                            extracted_query: query,
+                            confidence,
+                        };
+                    }
                 }
             }
         }
 
-        NaturalLanguageResponse {
-            response: None,
-            is_music_command: false,
-            extracted_query: None,
-            confidence: 0.0,
-        }
+        NaturalLanguageResponse::none()
     }
 
-    fn is_music_command(&self, content: &str) -> bool {
-        if let Some(patterns) = self.command_patterns.get("play") {
-            patterns.iter().any(|pattern| content.contains(pattern))
-        } else {
-            false
-        }
+    fn is_music_command(content: &str, patterns: &[String]) -> bool {
+        patterns.iter().any(|pattern| content.contains(pattern.as_str()))
     }
 
-    fn extract_song_query(&self, content: &str) -> Option<String> {
-        if let Some(patterns) = self.command_patterns.get("play") {
-            for pattern in patterns.iter() {
-                if content.contains(pattern) {
-                    let index = content.find(pattern)?;
-                    let after_keyword = &content[index + pattern.len()..];
-                    let cleaned = after_keyword
-                        .trim()
-                        .replace("please", "")
-                        .replace("now", "")
-                        .replace("for me", "")
-                        .trim()
-                        .to_string();
-                    
-                    if !cleaned.is_empty() {
-                        return Some(cleaned);
-                    }
+    fn extract_song_query(content: &str, patterns: &[String]) -> Option<String> {
+        for pattern in patterns.iter() {
+            if content.contains(pattern.as_str()) {
+                let index = content.find(pattern.as_str())?;
+                let after_keyword = &content[index + pattern.len()..];
+                let cleaned = after_keyword
+                    .trim()
+                    .replace("please", "")
+                    .replace("now", "")
+                    .replace("for me", "")
+                    .trim()
+                    .to_string();
+                
+                if !cleaned.is_empty() {
+                    return Some(cleaned);
                 }
             }
         }
@@ -255,6 +296,79 @@ declare_types! {
             
             Ok(promise)
         }
+
+        // Loads a JSON/TOML guild config file and hot-reloads it every `pollMs`
+        method loadGuildConfig(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+            let this = cx.this();
+            let path = cx.argument::<JsString>(0)?.value(&mut cx);
+            let poll_ms = match cx.argument_opt(1) {
+                Some(arg) => arg.downcast_or_throw::<JsNumber, _>(&mut cx)?.value(&mut cx) as u64,
+                None => 5000,
+            };
+
+            let result = cx.borrow(&this, |processor| {
+                processor.load_guild_configs(&path, Duration::from_millis(poll_ms))
+            });
+            if let Err(err) = result {
+                return cx.throw_error(err.to_string());
+            }
+
+            Ok(cx.undefined())
+        }
+
+        method reloadGuildConfig(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+            let this = cx.this();
+            let store = cx.borrow(&this, |processor| processor.guild_configs());
+            if let Err(err) = store.reload() {
+                return cx.throw_error(err.to_string());
+            }
+
+            Ok(cx.undefined())
+        }
+
+        // Takes the guild config as a JSON string; missing fields use defaults
+        method setGuildConfig(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+            let this = cx.this();
+            let guild_id = cx.argument::<JsString>(0)?.value(&mut cx);
+            let raw = cx.argument::<JsString>(1)?.value(&mut cx);
+
+            let config: GuildConfig = match serde_json::from_str(&raw) {
+                Ok(config) => config,
+                Err(err) => return cx.throw_error(format!("invalid guild config: {}", err)),
+            };
+            let store = cx.borrow(&this, |processor| processor.guild_configs());
+            if let Err(err) = store.set(&guild_id, config) {
+                return cx.throw_error(err.to_string());
+            }
+
+            Ok(cx.undefined())
+        }
+
+        method getGuildConfig(mut cx: FunctionContext) -> JsResult<JsString> {
+            let this = cx.this();
+            let guild_id = cx.argument::<JsString>(0)?.value(&mut cx);
+            let store = cx.borrow(&this, |processor| processor.guild_configs());
+
+            let raw = serde_json::to_string(&store.get(&guild_id)).unwrap_or_default();
+            Ok(cx.string(raw))
+        }
+
+        method removeGuildConfig(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+            let this = cx.this();
+            let guild_id = cx.argument::<JsString>(0)?.value(&mut cx);
+            let store = cx.borrow(&this, |processor| processor.guild_configs());
+
+            Ok(cx.boolean(store.remove(&guild_id)))
+        }
+
+        method setBotUserId(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+            let this = cx.this();
+            let bot_user_id = cx.argument::<JsString>(0)?.value(&mut cx);
+            let store = cx.borrow(&this, |processor| processor.guild_configs());
+            store.set_bot_user_id(Some(bot_user_id));
+
+            Ok(cx.undefined())
+        }
     }
 }
 
@@ -300,3 +414,32 @@ register_module!(mut cx, {
     Ok(())
 });
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guild_trigger_words_and_search_source_drive_playback() {
+        let processor = NaturalLanguageProcessor::new();
+        let mut config = GuildConfig::default();
+        config.trigger_words.insert("play".to_string(), vec!["Spin".to_string()]);
+        config.default_search_source = "soundcloud".to_string();
+        processor.guild_configs().set("1", config).unwrap();
+
+        let request = NaturalLanguageRequest {
+            message: "Spin Blue Monday".to_string(),
+            user_id: "u".to_string(),
+            guild_id: "1".to_string(),
+            channel_id: "c".to_string(),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let response = rt.block_on(processor.process_message(request));
+
+        assert!(response.is_music_command);
+        assert_eq!(response.extracted_query.as_deref(), Some("blue monday"));
+
+        let track = rt.block_on(processor.music_engine.search_track_from("blue monday", "soundcloud")).unwrap();
+        assert_eq!(track.source, "soundcloud");
+    }
+}
@@ -0,0 +1,421 @@
+/*!
+ * gunnchAI3k Performance Engine - Alert Lifecycle
+ * Rule-based alerts with for-durations, hysteresis, deduplication and silencing
+ */
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Deserializer, Serialize};
+use anyhow::{bail, Context, Result};
+
+use crate::{AlertSeverity, PerformanceAlert, PerformanceMetrics};
+
+/// A `PerformanceMetrics` field an alert rule can watch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    CpuUsage,
+    SystemCpuUsage,
+    MemoryUsage,
+    MessageProcessingRate,
+    /// Milliseconds
+    AverageResponseTime,
+    ErrorRate,
+    CacheHitRate,
+    OpenFds,
+    Threads,
+    TokioAliveTasks,
+    TokioGlobalQueueDepth,
+}
+
+impl AlertMetric {
+    pub fn value(&self, metrics: &PerformanceMetrics) -> f64 {
+        match self {
+            AlertMetric::CpuUsage => metrics.cpu_usage,
+            AlertMetric::SystemCpuUsage => metrics.system_cpu_usage,
+            AlertMetric::MemoryUsage => metrics.memory_usage,
+            AlertMetric::MessageProcessingRate => metrics.message_processing_rate,
+            AlertMetric::AverageResponseTime => metrics.average_response_time.as_secs_f64() * 1000.0,
+            AlertMetric::ErrorRate => metrics.error_rate,
+            AlertMetric::CacheHitRate => metrics.cache_hit_rate,
+            AlertMetric::OpenFds => metrics.open_fds as f64,
+            AlertMetric::Threads => metrics.threads as f64,
+            AlertMetric::TokioAliveTasks => metrics.tokio_alive_tasks as f64,
+            AlertMetric::TokioGlobalQueueDepth => metrics.tokio_global_queue_depth as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparator {
+    #[serde(alias = ">")]
+    Above,
+    #[serde(alias = "<")]
+    Below,
+}
+
+impl Comparator {
+    fn breaches(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparator::Above => value > threshold,
+            Comparator::Below => value < threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    /// Identifies the rule; at most one alert per key is active at a time
+    pub key: String,
+    pub metric: AlertMetric,
+    pub comparator: Comparator,
+    pub threshold: f64,
+    /// The value must get back past this before the alert resolves. Defaults
+    /// to `threshold`, i.e. no hysteresis.
+    #[serde(default)]
+    pub clear_threshold: Option<f64>,
+    /// How long the threshold must stay breached before the alert fires.
+    /// In rule files: seconds, or a string like "30s", "5m", "500ms".
+    #[serde(default, alias = "duration", deserialize_with = "deserialize_duration")]
+    pub for_duration: Duration,
+    pub severity: AlertSeverity,
+    /// Message prefix; the current value is appended
+    pub summary: String,
+}
+
+impl AlertRule {
+    pub fn new(key: &str, metric: AlertMetric, comparator: Comparator, threshold: f64, severity: AlertSeverity, summary: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            metric,
+            comparator,
+            threshold,
+            clear_threshold: None,
+            for_duration: Duration::ZERO,
+            severity,
+            summary: summary.to_string(),
+        }
+    }
+
+    pub fn with_clear_threshold(mut self, clear_threshold: f64) -> Self {
+        self.clear_threshold = Some(clear_threshold);
+        self
+    }
+
+    pub fn with_for_duration(mut self, for_duration: Duration) -> Self {
+        self.for_duration = for_duration;
+        self
+    }
+
+    fn cleared(&self, value: f64) -> bool {
+        let clear = self.clear_threshold.unwrap_or(self.threshold);
+        !self.comparator.breaches(value, clear)
+    }
+
+    /// The rules the monitor shipped with: CPU above 80%, memory above 90%,
+    /// error rate above 5%, each clearing a little below its threshold
+    pub fn defaults() -> Vec<AlertRule> {
+        vec![
+            AlertRule::new("high_cpu", AlertMetric::CpuUsage, Comparator::Above, 80.0, AlertSeverity::High, "High CPU usage")
+                .with_clear_threshold(70.0),
+            AlertRule::new("high_memory", AlertMetric::MemoryUsage, Comparator::Above, 90.0, AlertSeverity::Critical, "High memory usage")
+                .with_clear_threshold(85.0),
+            AlertRule::new("high_error_rate", AlertMetric::ErrorRate, Comparator::Above, 5.0, AlertSeverity::Medium, "High error rate")
+                .with_clear_threshold(3.0),
+        ]
+    }
+}
+
+/// Contents of an alert rules file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRuleSet {
+    pub rules: Vec<AlertRule>,
+}
+
+impl AlertRuleSet {
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let set: Self = serde_json::from_str(raw).context("invalid alert rules")?;
+        let mut keys = HashSet::new();
+        for rule in &set.rules {
+            if !keys.insert(rule.key.as_str()) {
+                bail!("duplicate alert rule key '{}'", rule.key);
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read alert rules {}", path.display()))?;
+        Self::from_json(&raw)
+    }
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(f64),
+        Text(String),
+        Struct { secs: u64, nanos: u32 },
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(seconds) => Duration::try_from_secs_f64(seconds)
+            .map_err(|err| serde::de::Error::custom(format!("invalid duration {}: {}", seconds, err))),
+        Raw::Text(text) => parse_duration(&text).ok_or_else(|| serde::de::Error::custom(format!("invalid duration '{}'", text))),
+        Raw::Struct { secs, nanos } => Ok(Duration::new(secs, nanos)),
+    }
+}
+
+/// "500ms", "30s", "5m", "1h"; a bare number is seconds
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let value: f64 = number.parse().ok()?;
+    let seconds = match unit.trim() {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// A state change worth notifying about
+#[derive(Debug, Clone)]
+pub enum AlertEvent {
+    Fired(PerformanceAlert),
+    Resolved(PerformanceAlert),
+}
+
+impl AlertEvent {
+    pub fn alert(&self) -> &PerformanceAlert {
+        match self {
+            AlertEvent::Fired(alert) | AlertEvent::Resolved(alert) => alert,
+        }
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        matches!(self, AlertEvent::Resolved(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RuleState {
+    Inactive,
+    Pending { since: Instant },
+    Firing,
+}
+
+/// Evaluates rules against each metrics sample and tracks the resulting alerts
+#[derive(Debug, Clone)]
+pub struct AlertManager {
+    rules: Vec<AlertRule>,
+    states: HashMap<String, RuleState>,
+    active: HashMap<String, PerformanceAlert>,
+    history: VecDeque<PerformanceAlert>,
+    history_limit: usize,
+    silences: HashMap<String, Instant>,
+    /// Active alerts whose `Fired` event went out; only these get a `Resolved`
+    notified: HashSet<String>,
+}
+
+impl AlertManager {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            states: HashMap::new(),
+            active: HashMap::new(),
+            history: VecDeque::new(),
+            history_limit: 500,
+            silences: HashMap::new(),
+            notified: HashSet::new(),
+        }
+    }
+
+    pub fn with_history_limit(mut self, history_limit: usize) -> Self {
+        self.history_limit = history_limit;
+        self
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    /// Replaces the rule set. Alerts whose rule disappeared are resolved.
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) -> Vec<AlertEvent> {
+        self.rules = rules;
+        let removed: Vec<String> = self
+            .states
+            .keys()
+            .filter(|key| !self.rules.iter().any(|rule| &rule.key == *key))
+            .cloned()
+            .collect();
+
+        let mut events = Vec::new();
+        for key in removed {
+            self.states.remove(&key);
+            if let Some(event) = self.resolve(&key) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    pub fn evaluate(&mut self, metrics: &PerformanceMetrics) -> Vec<AlertEvent> {
+        self.evaluate_at(metrics, Instant::now())
+    }
+
+    /// Advances every rule with one sample taken at `now`
+    pub fn evaluate_at(&mut self, metrics: &PerformanceMetrics, now: Instant) -> Vec<AlertEvent> {
+        self.silences.retain(|_, until| *until > now);
+        for (key, alert) in self.active.iter_mut() {
+            alert.silenced = self.silences.contains_key(key);
+        }
+
+        let mut events = Vec::new();
+        for rule in self.rules.clone() {
+            let value = rule.metric.value(metrics);
+            let state = self.states.get(&rule.key).copied().unwrap_or(RuleState::Inactive);
+
+            let next = match state {
+                RuleState::Firing if rule.cleared(value) => {
+                    events.extend(self.resolve(&rule.key));
+                    RuleState::Inactive
+                }
+                RuleState::Firing => {
+                    if let Some(alert) = self.active.get_mut(&rule.key) {
+                        alert.value = value;
+                        alert.message = format!("{}: {:.1}", rule.summary, value);
+                        // Fired while silenced and still firing now the silence is over
+                        if !alert.silenced && self.notified.insert(rule.key.clone()) {
+                            events.push(AlertEvent::Fired(alert.clone()));
+                        }
+                    }
+                    RuleState::Firing
+                }
+                _ if !rule.comparator.breaches(value, rule.threshold) => RuleState::Inactive,
+                RuleState::Pending { since } if now.duration_since(since) < rule.for_duration => state,
+                RuleState::Inactive if !rule.for_duration.is_zero() => RuleState::Pending { since: now },
+                _ => {
+                    events.extend(self.fire(&rule, value, now));
+                    RuleState::Firing
+                }
+            };
+            self.states.insert(rule.key.clone(), next);
+        }
+        events
+    }
+
+    fn fire(&mut self, rule: &AlertRule, value: f64, now: Instant) -> Option<AlertEvent> {
+        let silenced = self.silences.get(&rule.key).is_some_and(|until| *until > now);
+        let alert = PerformanceAlert {
+            id: uuid::Uuid::new_v4().to_string(),
+            rule_key: rule.key.clone(),
+            severity: rule.severity,
+            message: format!("{}: {:.1}", rule.summary, value),
+            value,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            resolved: false,
+            resolved_at: None,
+            acknowledged: false,
+            silenced,
+        };
+        self.active.insert(rule.key.clone(), alert.clone());
+        if silenced {
+            return None;
+        }
+        self.notified.insert(rule.key.clone());
+        Some(AlertEvent::Fired(alert))
+    }
+
+    fn resolve(&mut self, key: &str) -> Option<AlertEvent> {
+        let mut alert = self.active.remove(key)?;
+        alert.resolved = true;
+        alert.resolved_at = Some(chrono::Utc::now().timestamp() as u64);
+
+        self.history.push_back(alert.clone());
+        while self.history.len() > self.history_limit {
+            self.history.pop_front();
+        }
+        // Whoever was told about the alert hears that it is over, silenced or not
+        self.notified.remove(key).then_some(AlertEvent::Resolved(alert))
+    }
+
+    /// Marks an active alert, by id or rule key, as seen by a human
+    pub fn acknowledge(&mut self, id_or_key: &str) -> bool {
+        match self
+            .active
+            .values_mut()
+            .find(|alert| alert.id == id_or_key || alert.rule_key == id_or_key)
+        {
+            Some(alert) => {
+                alert.acknowledged = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Suppresses `Fired` notifications for a rule until `duration` has passed.
+    /// Alerts are still tracked, just flagged as silenced, and an alert that was
+    /// already announced still notifies when it resolves.
+    pub fn silence(&mut self, rule_key: &str, duration: Duration) {
+        self.silences.insert(rule_key.to_string(), Instant::now() + duration);
+        if let Some(alert) = self.active.get_mut(rule_key) {
+            alert.silenced = true;
+        }
+    }
+
+    pub fn unsilence(&mut self, rule_key: &str) -> bool {
+        if let Some(alert) = self.active.get_mut(rule_key) {
+            alert.silenced = false;
+        }
+        self.silences.remove(rule_key).is_some()
+    }
+
+    pub fn is_silenced(&self, rule_key: &str) -> bool {
+        self.silences.get(rule_key).is_some_and(|until| *until > Instant::now())
+    }
+
+    /// Currently firing alerts, most severe first
+    pub fn active_alerts(&self) -> Vec<PerformanceAlert> {
+        let mut alerts: Vec<PerformanceAlert> = self.active.values().cloned().collect();
+        alerts.sort_by(|a, b| b.severity.cmp(&a.severity).then_with(|| a.rule_key.cmp(&b.rule_key)));
+        alerts
+    }
+
+    /// Resolved alerts, oldest first, capped at the history limit
+    pub fn history(&self) -> Vec<PerformanceAlert> {
+        self.history.iter().cloned().collect()
+    }
+}
+
+impl Default for AlertManager {
+    fn default() -> Self {
+        Self::new(AlertRule::defaults())
+    }
+}
+
+/// A running rules-file watcher; stops when shut down or dropped
+pub struct RuleFileWatcher {
+    pub(crate) task: tokio::task::JoinHandle<()>,
+}
+
+impl RuleFileWatcher {
+    pub fn shutdown(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for RuleFileWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,197 @@
+/*!
+ * gunnchAI3k Performance Engine - Built-in Analyzers
+ * Default stages registered by MessageProcessor::new
+ */
+
+use std::sync::Arc;
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::entities::extract_entities;
+use crate::intent::{IntentClassifier, IntentScore};
+use crate::pipeline::{AnalysisInput, AnalyzerOutput, MessageAnalysis, MessageAnalyzer};
+use crate::safety::SafetyClassifier;
+use crate::sentiment::SentimentLexicon;
+
+/// Lexicon-based sentiment; swap the lexicon with `SentimentAnalyzer::with_lexicon`
+pub struct SentimentAnalyzer {
+    lexicon: Arc<SentimentLexicon>,
+}
+
+impl SentimentAnalyzer {
+    pub fn new() -> Self {
+        Self::with_lexicon(SentimentLexicon::default())
+    }
+
+    pub fn with_lexicon(lexicon: SentimentLexicon) -> Self {
+        Self { lexicon: Arc::new(lexicon) }
+    }
+}
+
+impl Default for SentimentAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MessageAnalyzer for SentimentAnalyzer {
+    fn name(&self) -> &str {
+        "sentiment"
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, _analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+        Ok(vec![AnalyzerOutput::Sentiment(self.lexicon.score(input.message))])
+    }
+}
+
+/// Multi-label intent classification; configure rules or a model via `IntentAnalyzer::with_classifier`.
+/// The classifier is shared so the worker pool can triage with the same rules.
+pub struct IntentAnalyzer {
+    classifier: Arc<RwLock<IntentClassifier>>,
+}
+
+impl IntentAnalyzer {
+    pub fn new() -> Self {
+        Self::with_classifier(IntentClassifier::default())
+    }
+
+    pub fn with_classifier(classifier: IntentClassifier) -> Self {
+        Self::shared(Arc::new(RwLock::new(classifier)))
+    }
+
+    pub fn shared(classifier: Arc<RwLock<IntentClassifier>>) -> Self {
+        Self { classifier }
+    }
+}
+
+impl Default for IntentAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MessageAnalyzer for IntentAnalyzer {
+    fn name(&self) -> &str {
+        "intent"
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, _analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+        let classifier = self.classifier.read().await;
+        let mut intents = classifier.classify(input.message);
+
+        // A follow-up like "and the second one?" carries no intent keywords of its
+        // own; continue the previous turn's intent at reduced confidence
+        let unclassified = intents.len() == 1 && intents[0].intent == classifier.fallback_intent();
+        if unclassified && input.conversation.referent.is_some() {
+            if let Some(previous) = input.conversation.last_turn() {
+                if previous.intent != classifier.fallback_intent() {
+                    intents = vec![IntentScore {
+                        intent: previous.intent.clone(),
+                        confidence: 0.6,
+                    }];
+                }
+            }
+        }
+
+        Ok(vec![AnalyzerOutput::Intents(intents)])
+    }
+}
+
+pub struct EntityAnalyzer;
+
+#[async_trait]
+impl MessageAnalyzer for EntityAnalyzer {
+    fn name(&self) -> &str {
+        "entities"
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, _analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+        Ok(vec![AnalyzerOutput::Entities(extract_entities(input.message))])
+    }
+}
+
+/// Wordlist content safety, run alongside sentiment. The classifier is shared
+/// so allowlists and wordlists can change while the pipeline runs.
+pub struct SafetyAnalyzer {
+    classifier: Arc<RwLock<SafetyClassifier>>,
+}
+
+impl SafetyAnalyzer {
+    pub fn new() -> Self {
+        Self::shared(Arc::new(RwLock::new(SafetyClassifier::default())))
+    }
+
+    pub fn shared(classifier: Arc<RwLock<SafetyClassifier>>) -> Self {
+        Self { classifier }
+    }
+}
+
+impl Default for SafetyAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MessageAnalyzer for SafetyAnalyzer {
+    fn name(&self) -> &str {
+        "safety"
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, _analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+        let scores = self.classifier.read().await.classify(input.message, input.guild_id);
+        Ok(vec![AnalyzerOutput::Safety(scores)])
+    }
+}
+
+pub struct PriorityAnalyzer;
+
+#[async_trait]
+impl MessageAnalyzer for PriorityAnalyzer {
+    fn name(&self) -> &str {
+        "priority"
+    }
+
+    fn depends_on(&self) -> Vec<String> {
+        vec!["intent".to_string()]
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+        Ok(vec![AnalyzerOutput::Priority(message_priority(input.message, &analysis.intents))])
+    }
+}
+
+/// 0-10 priority from content and the full intent distribution: each intent
+/// pulls the default of 5 towards its own priority by its confidence
+pub fn message_priority(message: &str, intents: &[IntentScore]) -> u8 {
+    let mut priority: u8 = intents
+        .iter()
+        .map(|score| {
+            let target = f64::from(intent_priority(&score.intent));
+            5.0 + (target - 5.0) * score.confidence.clamp(0.0, 1.0)
+        })
+        .fold(5.0, f64::max)
+        .round() as u8;
+
+    if message.contains('@') {
+        priority += 2;
+    }
+
+    if message.contains("urgent") || message.contains("emergency") {
+        priority = 10;
+    }
+
+    priority.min(10)
+}
+
+fn intent_priority(intent: &str) -> u8 {
+    match intent {
+        "help" => 8,
+        "music" => 6,
+        "study" => 9,
+        _ => 5, // Default priority
+    }
+}
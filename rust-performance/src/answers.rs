@@ -0,0 +1,683 @@
+/*!
+ * gunnchAI3k Performance Engine - Answer Checking
+ * Numeric, symbolic, free-text and multi-part answers for practice questions
+ */
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::expression::Expression;
+use crate::sessions::SplitMix64;
+use crate::Question;
+
+/// SI prefixes accepted in front of a unit; `d` is left out so `dB` stays decibels
+const PREFIXES: &[(&str, f64)] = &[
+    ("T", 1e12),
+    ("G", 1e9),
+    ("M", 1e6),
+    ("k", 1e3),
+    ("c", 1e-2),
+    ("m", 1e-3),
+    ("µ", 1e-6),
+    ("μ", 1e-6),
+    ("u", 1e-6),
+    ("n", 1e-9),
+    ("p", 1e-12),
+    ("f", 1e-15),
+];
+
+/// Points at which symbolic answers are compared
+const EXPRESSION_SAMPLES: usize = 8;
+/// Fixed so the same answer always gets the same grade
+const EXPRESSION_SEED: u64 = 0x6a09_e667_f3bc_c908;
+/// Where variables are sampled unless a question says otherwise; safe for
+/// probabilities, logarithms and roots
+const DEFAULT_RANGE: (f64, f64) = (0.1, 0.9);
+
+/// How close a numeric answer must be: within either bound passes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tolerance {
+    #[serde(default)]
+    pub absolute: f64,
+    /// Fraction of the expected value, e.g. 0.01 for 1%
+    #[serde(default)]
+    pub relative: f64,
+}
+
+impl Tolerance {
+    pub fn absolute(absolute: f64) -> Self {
+        Self { absolute, relative: 0.0 }
+    }
+
+    pub fn relative(relative: f64) -> Self {
+        Self { absolute: 0.0, relative }
+    }
+
+    pub fn accepts(&self, expected: f64, actual: f64) -> bool {
+        let error = (actual - expected).abs();
+        error <= self.absolute || error <= self.relative * expected.abs() || error <= f64::EPSILON * expected.abs()
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self::relative(0.01)
+    }
+}
+
+/// Where a variable is sampled when comparing symbolic answers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariableRange {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl VariableRange {
+    pub fn new(name: &str, min: f64, max: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            min,
+            max,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionPart {
+    pub prompt: String,
+    pub answer: AnswerSpec,
+    /// Share of the question's credit, relative to the other parts
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+impl QuestionPart {
+    pub fn new(prompt: &str, answer: AnswerSpec) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            answer,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// What counts as a correct answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnswerSpec {
+    /// One of `options`; inside a multi-part question it is answered by
+    /// letter, number or option text
+    Choice { options: Vec<String>, correct: u8 },
+    /// A number, optionally with a unit; SI prefixes are converted
+    Numeric {
+        value: f64,
+        #[serde(default)]
+        tolerance: Tolerance,
+        #[serde(default)]
+        unit: Option<String>,
+    },
+    /// A formula, checked by evaluating both at sample points
+    Expression {
+        expected: String,
+        /// Defaults to every variable in `expected`, sampled in 0.1..0.9
+        #[serde(default)]
+        variables: Vec<VariableRange>,
+        #[serde(default = "default_expression_tolerance")]
+        tolerance: f64,
+    },
+    /// Short text, matched fuzzily after normalisation
+    FreeText {
+        accepted: Vec<String>,
+        /// Similarity (0..1) needed to count as a match
+        #[serde(default = "default_text_threshold")]
+        threshold: f64,
+    },
+    /// Several sub-answers; credit is the weighted share of parts right
+    MultiPart { parts: Vec<QuestionPart> },
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_expression_tolerance() -> f64 {
+    1e-6
+}
+
+fn default_text_threshold() -> f64 {
+    0.85
+}
+
+/// The result of checking one answer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnswerCheck {
+    /// 0 (wrong) ..= 1 (right); in between for partial credit
+    pub credit: f64,
+    pub feedback: Option<String>,
+}
+
+impl AnswerCheck {
+    fn right() -> Self {
+        Self { credit: 1.0, feedback: None }
+    }
+
+    fn wrong(feedback: Option<String>) -> Self {
+        Self { credit: 0.0, feedback }
+    }
+
+    pub fn is_correct(&self) -> bool {
+        self.credit >= 1.0
+    }
+}
+
+/// How a question is answered, without giving the answer away
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnswerFormat {
+    Choice { options: Vec<String> },
+    Numeric { unit: Option<String> },
+    Expression { variables: Vec<String> },
+    FreeText,
+    MultiPart { parts: Vec<PartFormat> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartFormat {
+    pub prompt: String,
+    pub format: AnswerFormat,
+}
+
+/// A student's answer as stored on an attempt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Response {
+    /// Original index of the chosen option
+    Choice(u8),
+    Text(String),
+    /// One entry per part; None for parts left blank
+    Parts(Vec<Option<String>>),
+}
+
+impl AnswerSpec {
+    pub fn numeric(value: f64, tolerance: Tolerance, unit: Option<&str>) -> Self {
+        AnswerSpec::Numeric {
+            value,
+            tolerance,
+            unit: unit.map(str::to_string),
+        }
+    }
+
+    /// Fails if `expected` itself doesn't parse
+    pub fn expression(expected: &str) -> Result<Self, crate::expression::ExpressionError> {
+        Expression::parse(expected)?;
+        Ok(AnswerSpec::Expression {
+            expected: expected.to_string(),
+            variables: Vec::new(),
+            tolerance: default_expression_tolerance(),
+        })
+    }
+
+    pub fn free_text(accepted: &[&str]) -> Self {
+        AnswerSpec::FreeText {
+            accepted: accepted.iter().map(|answer| answer.to_string()).collect(),
+            threshold: default_text_threshold(),
+        }
+    }
+
+    pub fn format(&self) -> AnswerFormat {
+        match self {
+            AnswerSpec::Choice { options, .. } => AnswerFormat::Choice { options: options.clone() },
+            AnswerSpec::Numeric { unit, .. } => AnswerFormat::Numeric { unit: unit.clone() },
+            AnswerSpec::Expression { expected, variables, .. } => AnswerFormat::Expression {
+                variables: if variables.is_empty() {
+                    Expression::parse(expected)
+                        .map(|expression| expression.variables().into_iter().collect())
+                        .unwrap_or_default()
+                } else {
+                    variables.iter().map(|range| range.name.clone()).collect()
+                },
+            },
+            AnswerSpec::FreeText { .. } => AnswerFormat::FreeText,
+            AnswerSpec::MultiPart { parts } => AnswerFormat::MultiPart {
+                parts: parts
+                    .iter()
+                    .map(|part| PartFormat {
+                        prompt: part.prompt.clone(),
+                        format: part.answer.format(),
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    /// The correct answer as shown after grading
+    pub fn display(&self) -> String {
+        match self {
+            AnswerSpec::Choice { options, correct } => options.get(usize::from(*correct)).cloned().unwrap_or_default(),
+            AnswerSpec::Numeric { value, unit, .. } => match unit {
+                Some(unit) => format!("{} {}", format_significant(*value, 6), unit),
+                None => format_significant(*value, 6),
+            },
+            AnswerSpec::Expression { expected, .. } => expected.clone(),
+            AnswerSpec::FreeText { accepted, .. } => accepted.first().cloned().unwrap_or_default(),
+            AnswerSpec::MultiPart { parts } => parts
+                .iter()
+                .enumerate()
+                .map(|(index, part)| format!("({}) {}", part_label(index), part.answer.display()))
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+
+    /// Checks a typed answer; multi-part answers go through `check_parts`
+    pub fn check(&self, response: &str) -> AnswerCheck {
+        match self {
+            AnswerSpec::Choice { options, correct } => match choice_index(options, response) {
+                Some(index) if index == usize::from(*correct) => AnswerCheck::right(),
+                Some(_) => AnswerCheck::wrong(None),
+                None => AnswerCheck::wrong(Some(format!("'{}' is not one of the options", response.trim()))),
+            },
+            AnswerSpec::Numeric { value, tolerance, unit } => check_numeric(*value, tolerance, unit.as_deref(), response),
+            AnswerSpec::Expression { expected, variables, tolerance } => check_expression(expected, variables, *tolerance, response),
+            AnswerSpec::FreeText { accepted, threshold } => {
+                if accepted.iter().any(|answer| text_similarity(answer, response) >= *threshold) {
+                    AnswerCheck::right()
+                } else {
+                    AnswerCheck::wrong(None)
+                }
+            }
+            AnswerSpec::MultiPart { .. } => AnswerCheck::wrong(Some("answer each part separately".to_string())),
+        }
+    }
+
+    /// Checks each part of a multi-part answer. Returns the overall check and
+    /// one per part; blank parts get no credit.
+    pub fn check_parts(&self, responses: &[Option<String>]) -> (AnswerCheck, Vec<AnswerCheck>) {
+        let AnswerSpec::MultiPart { parts } = self else {
+            let check = match responses {
+                [Some(response)] => self.check(response),
+                _ => AnswerCheck::wrong(None),
+            };
+            return (check.clone(), vec![check]);
+        };
+        let checks: Vec<AnswerCheck> = parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| match responses.get(index) {
+                Some(Some(response)) if !response.trim().is_empty() => part.answer.check(response),
+                _ => AnswerCheck::wrong(None),
+            })
+            .collect();
+        let total: f64 = parts.iter().map(|part| part.weight.max(0.0)).sum();
+        let earned: f64 = parts.iter().zip(&checks).map(|(part, check)| part.weight.max(0.0) * check.credit).sum();
+        let credit = if total > 0.0 { earned / total } else { 0.0 };
+        (AnswerCheck { credit, feedback: None }, checks)
+    }
+}
+
+impl AnswerSpec {
+    /// Grades a stored response (None if unanswered): the overall check and,
+    /// for multi-part questions, one per part
+    pub fn grade(&self, response: Option<&Response>) -> (AnswerCheck, Vec<AnswerCheck>) {
+        match (self, response) {
+            (AnswerSpec::Choice { correct, .. }, Some(Response::Choice(index))) if index == correct => {
+                (AnswerCheck::right(), Vec::new())
+            }
+            (AnswerSpec::MultiPart { .. }, Some(Response::Parts(responses))) => self.check_parts(responses),
+            (AnswerSpec::MultiPart { .. }, _) => self.check_parts(&[]),
+            (_, Some(Response::Text(text))) => (self.check(text), Vec::new()),
+            _ => (AnswerCheck::wrong(None), Vec::new()),
+        }
+    }
+}
+
+impl Response {
+    /// The answer as the student gave it, for showing next to the correct one
+    pub fn display(&self, spec: &AnswerSpec) -> String {
+        match (self, spec) {
+            (Response::Choice(index), AnswerSpec::Choice { options, .. }) => {
+                options.get(usize::from(*index)).cloned().unwrap_or_default()
+            }
+            (Response::Choice(index), _) => index.to_string(),
+            (Response::Text(text), _) => text.clone(),
+            (Response::Parts(parts), _) => parts
+                .iter()
+                .enumerate()
+                .map(|(index, part)| format!("({}) {}", part_label(index), part.as_deref().unwrap_or("—")))
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+}
+
+impl Question {
+    /// A question with any kind of answer; a `Choice` fills `options` and
+    /// `correct_answer` as plain multiple-choice questions always have
+    pub fn new(question: &str, answer: AnswerSpec, explanation: &str) -> Self {
+        let (options, correct_answer, answer) = match answer {
+            AnswerSpec::Choice { options, correct } => (options, correct, None),
+            other => (Vec::new(), 0, Some(other)),
+        };
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: question.to_string(),
+            options,
+            correct_answer,
+            explanation: explanation.to_string(),
+            topic: None,
+            answer,
+        }
+    }
+
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    /// Multiple choice from `options` unless `answer` says otherwise
+    pub fn answer_spec(&self) -> AnswerSpec {
+        self.answer.clone().unwrap_or_else(|| AnswerSpec::Choice {
+            options: self.options.clone(),
+            correct: self.correct_answer,
+        })
+    }
+
+    /// The options of a multiple-choice question; empty for other kinds
+    pub fn choices(&self) -> &[String] {
+        match &self.answer {
+            None => &self.options,
+            Some(AnswerSpec::Choice { options, .. }) => options,
+            Some(_) => &[],
+        }
+    }
+}
+
+/// `value` to `digits` significant figures, without trailing zeros
+pub(crate) fn format_significant(value: f64, digits: i32) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+    let magnitude = value.abs().log10().floor() as i32;
+    if !(-5..=9).contains(&magnitude) {
+        return format!("{:.*e}", (digits - 1).max(0) as usize, value);
+    }
+    let decimals = (digits - 1 - magnitude).max(0) as usize;
+    let text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+fn part_label(index: usize) -> char {
+    char::from(b'a' + (index % 26) as u8)
+}
+
+/// Reads "b", "2" (1-based) or the option's own text
+fn choice_index(options: &[String], response: &str) -> Option<usize> {
+    let response = response.trim();
+    let mut chars = response.chars();
+    if let (Some(letter), None) = (chars.next(), chars.next()) {
+        if letter.is_ascii_alphabetic() {
+            let index = usize::from(letter.to_ascii_lowercase() as u8 - b'a');
+            return (index < options.len()).then_some(index);
+        }
+    }
+    if let Ok(number) = response.parse::<usize>() {
+        return (1..=options.len()).contains(&number).then(|| number - 1);
+    }
+    let wanted = normalise_text(response);
+    options.iter().position(|option| normalise_text(option) == wanted)
+}
+
+/// Splits "4.7 kΩ" or "3/8" into a value and an optional unit
+fn parse_quantity(response: &str) -> Result<(f64, Option<String>), crate::expression::ExpressionError> {
+    let response = response.trim();
+    let whole = Expression::parse(response).and_then(|expression| expression.constant());
+    if whole.is_ok() {
+        return whole.map(|value| (value, None));
+    }
+    // The unit starts at the last place where everything before it is a number
+    let split = response
+        .char_indices()
+        .rev()
+        .filter(|(index, c)| *index > 0 && (c.is_alphabetic() || matches!(c, '%' | '°' | 'Ω' | 'µ')))
+        .filter_map(|(index, _)| {
+            let value = Expression::parse(&response[..index]).and_then(|expression| expression.constant()).ok()?;
+            Some((index, value))
+        })
+        .next();
+    match split {
+        Some((index, value)) => Ok((value, Some(response[index..].split_whitespace().collect()))),
+        None => whole.map(|value| (value, None)),
+    }
+}
+
+/// Factor taking a value in `given` units to `expected` units, if they match
+fn unit_scale(given: &str, expected: &str) -> Option<f64> {
+    let readings = |unit: &str| {
+        let mut readings = vec![(1.0, unit.to_string())];
+        for (prefix, scale) in PREFIXES {
+            if let Some(base) = unit.strip_prefix(prefix).filter(|base| !base.is_empty()) {
+                readings.push((*scale, base.to_string()));
+            }
+        }
+        readings
+    };
+    let expected = readings(expected);
+    readings(given).into_iter().find_map(|(given_scale, base)| {
+        expected
+            .iter()
+            .find(|(_, expected_base)| *expected_base == base)
+            .map(|(expected_scale, _)| given_scale / expected_scale)
+    })
+}
+
+fn check_numeric(value: f64, tolerance: &Tolerance, unit: Option<&str>, response: &str) -> AnswerCheck {
+    let (number, given_unit) = match parse_quantity(response) {
+        Ok(quantity) => quantity,
+        Err(err) => return AnswerCheck::wrong(Some(err.to_string())),
+    };
+    let (scale, mut credit, mut feedback) = match (unit, given_unit.as_deref()) {
+        (Some(unit), None) => (1.0, 0.5, Some(format!("include the unit ({})", unit))),
+        (Some(unit), Some(given)) => match unit_scale(given, unit) {
+            Some(scale) => (scale, 1.0, None),
+            None => return AnswerCheck::wrong(Some(format!("expected a value in {}, not {}", unit, given))),
+        },
+        (None, Some(_)) => (1.0, 1.0, Some("no unit is needed here".to_string())),
+        (None, None) => (1.0, 1.0, None),
+    };
+
+    let actual = number * scale;
+    if !tolerance.accepts(value, actual) {
+        credit = 0.0;
+        feedback = if value != 0.0 && tolerance.accepts(-value, actual) {
+            Some("check the sign".to_string())
+        } else if value != 0.0 && [1e3, 1e6, 1e-3, 1e-6].iter().any(|factor| tolerance.accepts(value * factor, actual)) {
+            Some("check the unit prefix or powers of ten".to_string())
+        } else {
+            feedback.filter(|_| unit.is_some() && given_unit.is_none())
+        };
+    }
+    AnswerCheck { credit, feedback }
+}
+
+fn check_expression(expected: &str, variables: &[VariableRange], tolerance: f64, response: &str) -> AnswerCheck {
+    let expected = match Expression::parse(expected) {
+        Ok(expected) => expected,
+        Err(err) => {
+            tracing::warn!("question has an unreadable expected expression: {}", err);
+            return AnswerCheck::wrong(None);
+        }
+    };
+    let ranges: Vec<VariableRange> = if variables.is_empty() {
+        expected
+            .variables()
+            .iter()
+            .map(|name| VariableRange::new(name, DEFAULT_RANGE.0, DEFAULT_RANGE.1))
+            .collect()
+    } else {
+        variables.to_vec()
+    };
+    let names: Vec<&str> = ranges.iter().map(|range| range.name.as_str()).collect();
+    let given = match Expression::parse_with_variables(response, &names) {
+        Ok(given) => given,
+        Err(err) => return AnswerCheck::wrong(Some(err.to_string())),
+    };
+    if let Some(unknown) = given.variables().into_iter().find(|name| !ranges.iter().any(|range| &range.name == name)) {
+        return AnswerCheck::wrong(Some(format!("unknown variable '{}'", unknown)));
+    }
+
+    let mut rng = SplitMix64::new(EXPRESSION_SEED);
+    let mut compared = 0;
+    for _ in 0..EXPRESSION_SAMPLES * 4 {
+        let point: HashMap<String, f64> = ranges
+            .iter()
+            .map(|range| (range.name.clone(), range.min + (range.max - range.min) * rng.next_f64()))
+            .collect();
+        let want = match expected.eval(&point) {
+            Ok(want) if want.is_finite() => want,
+            // Outside the expected answer's domain; try another point
+            _ => continue,
+        };
+        let got = given.eval(&point).unwrap_or(f64::NAN);
+        let error = (got - want).abs();
+        if !got.is_finite() || error > tolerance * want.abs().max(got.abs()).max(1e-12) {
+            return AnswerCheck::wrong(None);
+        }
+        compared += 1;
+        if compared == EXPRESSION_SAMPLES {
+            break;
+        }
+    }
+    if compared == 0 {
+        return AnswerCheck::wrong(Some("this answer could not be checked".to_string()));
+    }
+    AnswerCheck::right()
+}
+
+/// Lowercase words without punctuation or leading articles
+fn normalise_text(text: &str) -> String {
+    let cleaned: String = text
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' { c } else { ' ' })
+        .collect();
+    let words: Vec<&str> = cleaned
+        .split_whitespace()
+        .map(|word| word.trim_matches('.'))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let words = match words.first() {
+        Some(&("a" | "an" | "the")) if words.len() > 1 => &words[1..],
+        _ => &words[..],
+    };
+    words.join(" ")
+}
+
+/// 0..=1; the better of in-order and any-order word matching
+fn text_similarity(accepted: &str, response: &str) -> f64 {
+    let (accepted, response) = (normalise_text(accepted), normalise_text(response));
+    if accepted.is_empty() || response.is_empty() {
+        return 0.0;
+    }
+    let sorted = |text: &str| {
+        let mut words: Vec<&str> = text.split(' ').collect();
+        words.sort_unstable();
+        words.join(" ")
+    };
+    strsim::normalized_damerau_levenshtein(&accepted, &response)
+        .max(strsim::normalized_damerau_levenshtein(&sorted(&accepted), &sorted(&response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resistance() -> AnswerSpec {
+        AnswerSpec::numeric(4700.0, Tolerance::default(), Some("Ω"))
+    }
+
+    #[test]
+    fn numeric_answers_convert_unit_prefixes() {
+        assert!(resistance().check("4.7 kΩ").is_correct());
+        assert!(resistance().check("4700Ω").is_correct());
+        assert!(resistance().check("0.0047 MΩ").is_correct());
+
+        let current = AnswerSpec::numeric(0.002, Tolerance::default(), Some("A"));
+        assert!(current.check("2 mA").is_correct());
+        assert!(current.check("2000 µA").is_correct());
+        assert!(current.check("2000 uA").is_correct());
+    }
+
+    #[test]
+    fn numeric_answers_explain_common_mistakes() {
+        let wrong_sign = resistance().check("-4.7 kΩ");
+        assert_eq!(wrong_sign.credit, 0.0);
+        assert_eq!(wrong_sign.feedback.as_deref(), Some("check the sign"));
+
+        let wrong_prefix = resistance().check("4.7 Ω");
+        assert_eq!(wrong_prefix.credit, 0.0);
+        assert_eq!(wrong_prefix.feedback.as_deref(), Some("check the unit prefix or powers of ten"));
+
+        let no_unit = resistance().check("4700");
+        assert_eq!(no_unit.credit, 0.5);
+        assert_eq!(no_unit.feedback.as_deref(), Some("include the unit (Ω)"));
+
+        assert_eq!(resistance().check("4.7 kV").credit, 0.0);
+    }
+
+    #[test]
+    fn numeric_answers_accept_fractions_within_tolerance() {
+        let probability = AnswerSpec::numeric(0.375, Tolerance::absolute(0.001), None);
+        assert!(probability.check("3/8").is_correct());
+        assert!(probability.check("0.3751").is_correct());
+        assert!(!probability.check("0.38").is_correct());
+    }
+
+    #[test]
+    fn expressions_are_compared_by_value() {
+        let variance = AnswerSpec::expression("n*p*(1-p)").unwrap();
+        assert!(variance.check("np(1-p)").is_correct());
+        assert!(variance.check("n*p - n*p^2").is_correct());
+        assert!(variance.check("n p (1 - p)").is_correct());
+        assert!(!variance.check("np").is_correct());
+        assert_eq!(variance.check("np(1-q)").feedback.as_deref(), Some("unknown variable 'q'"));
+
+        let identity = AnswerSpec::expression("sin(x)^2 + cos(x)^2").unwrap();
+        assert!(identity.check("1").is_correct());
+    }
+
+    #[test]
+    fn free_text_tolerates_typos_and_order() {
+        let answer = AnswerSpec::free_text(&["Nyquist frequency"]);
+        assert!(answer.check("the nyquist frequency").is_correct());
+        assert!(answer.check("nyqist frequency").is_correct());
+        assert!(answer.check("frequency Nyquist").is_correct());
+        assert!(!answer.check("sampling rate").is_correct());
+    }
+
+    #[test]
+    fn multi_part_answers_earn_weighted_partial_credit() {
+        let spec = AnswerSpec::MultiPart {
+            parts: vec![
+                QuestionPart::new("mean", AnswerSpec::expression("n*p").unwrap()),
+                QuestionPart::new("variance", AnswerSpec::expression("n*p*(1-p)").unwrap()).with_weight(3.0),
+            ],
+        };
+        let (overall, parts) = spec.check_parts(&[Some("np".to_string()), None]);
+        assert_eq!(overall.credit, 0.25);
+        assert!(parts[0].is_correct() && !parts[1].is_correct());
+
+        let (overall, _) = spec.grade(Some(&Response::Parts(vec![Some("np".to_string()), Some("np(1-p)".to_string())])));
+        assert!(overall.is_correct());
+        assert_eq!(spec.grade(None).0.credit, 0.0);
+    }
+}
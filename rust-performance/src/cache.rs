@@ -0,0 +1,238 @@
+/*!
+ * gunnchAI3k Performance Engine - Message Cache
+ * Bounded LRU cache of processed messages with TTL and per-intent opt-out
+ */
+
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::ProcessedMessage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub capacity: usize,
+    pub ttl: Duration,
+    /// Messages carrying any of these intents are never cached
+    pub uncached_intents: HashSet<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            ttl: Duration::from_secs(300),
+            uncached_intents: HashSet::new(),
+        }
+    }
+}
+
+/// Why an entry left the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheRemoval {
+    Evicted,
+    Expired,
+}
+
+struct CacheEntry {
+    message: ProcessedMessage,
+    expires_at: Instant,
+}
+
+pub struct MessageCache {
+    entries: LruCache<String, CacheEntry>,
+    config: CacheConfig,
+}
+
+impl MessageCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            entries: LruCache::new(Self::capacity(&config)),
+            config,
+        }
+    }
+
+    fn capacity(config: &CacheConfig) -> NonZeroUsize {
+        NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN)
+    }
+
+    /// Replies are localised, so the same message in another locale is a different entry
+    pub fn key(author_id: &str, channel_id: &str, locale: Option<&str>, message: &str) -> String {
+        format!("{}:{}:{}:{}", author_id, channel_id, locale.unwrap_or_default().to_lowercase(), message)
+    }
+
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Applies a new config, evicting least-recently-used entries if it shrinks.
+    /// Returns how many were evicted.
+    pub fn set_config(&mut self, config: CacheConfig) -> usize {
+        let before = self.entries.len();
+        self.entries.resize(Self::capacity(&config));
+        self.config = config;
+        before - self.entries.len()
+    }
+
+    pub fn is_cacheable(&self, message: &ProcessedMessage) -> bool {
+        let uncached = &self.config.uncached_intents;
+        !uncached.contains(&message.intent) && !message.intents.iter().any(|score| uncached.contains(&score.intent))
+    }
+
+    /// Looks up a live entry and marks it most recently used. An expired entry
+    /// is removed and reported as `Err(CacheRemoval::Expired)`.
+    pub fn get(&mut self, key: &str) -> Result<Option<ProcessedMessage>, CacheRemoval> {
+        let now = Instant::now();
+        match self.entries.get(key) {
+            Some(entry) if entry.expires_at > now => Ok(Some(entry.message.clone())),
+            Some(_) => {
+                self.entries.pop(key);
+                Err(CacheRemoval::Expired)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a message unless its intent is opted out. Returns true when an
+    /// older entry had to be evicted to make room.
+    pub fn insert(&mut self, key: String, message: &ProcessedMessage) -> bool {
+        if !self.is_cacheable(message) {
+            return false;
+        }
+
+        let entry = CacheEntry {
+            message: message.clone(),
+            expires_at: Instant::now() + self.config.ttl,
+        };
+        matches!(self.entries.push(key.clone(), entry), Some((evicted_key, _)) if evicted_key != key)
+    }
+
+    /// Drops every expired entry, returning how many were removed
+    pub fn purge_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            self.entries.pop(key);
+        }
+        expired.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for MessageCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntentScore, SafetyScores, SentimentScores, SpamVerdict};
+
+    fn message(intent: &str, secondary: &[&str]) -> ProcessedMessage {
+        ProcessedMessage {
+            id: "m".to_string(),
+            content: "hello".to_string(),
+            author_id: "u".to_string(),
+            channel_id: "c".to_string(),
+            sentiment: 0.0,
+            sentiment_scores: SentimentScores::default(),
+            intent: intent.to_string(),
+            intents: secondary.iter().map(|intent| IntentScore { intent: intent.to_string(), confidence: 0.5 }).collect(),
+            entities: Vec::new(),
+            priority: 1,
+            processing_time: Duration::ZERO,
+            response: "hi".to_string(),
+            stages: Vec::new(),
+            from_cache: false,
+            referent: None,
+            safety: SafetyScores::default(),
+            spam: SpamVerdict::clean(),
+        }
+    }
+
+    fn cache(capacity: usize, ttl: Duration) -> MessageCache {
+        MessageCache::new(CacheConfig {
+            capacity,
+            ttl,
+            ..CacheConfig::default()
+        })
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let mut cache = cache(2, Duration::from_secs(60));
+        assert!(!cache.insert("a".to_string(), &message("help", &[])));
+        assert!(!cache.insert("b".to_string(), &message("help", &[])));
+        // Reading "a" makes "b" the oldest
+        assert!(cache.get("a").unwrap().is_some());
+        assert!(cache.insert("c".to_string(), &message("help", &[])));
+        assert!(matches!(cache.get("b"), Ok(None)));
+        assert!(cache.get("a").unwrap().is_some());
+        // Replacing an entry in place evicts nothing
+        assert!(!cache.insert("c".to_string(), &message("study", &[])));
+        assert_eq!(cache.get("c").unwrap().unwrap().intent, "study");
+    }
+
+    #[test]
+    fn shrinking_evicts_the_oldest_entries() {
+        let mut cache = cache(3, Duration::from_secs(60));
+        for key in ["a", "b", "c"] {
+            cache.insert(key.to_string(), &message("help", &[]));
+        }
+        assert_eq!(cache.set_config(CacheConfig { capacity: 1, ..cache.config().clone() }), 2);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("c").unwrap().is_some());
+    }
+
+    #[test]
+    fn expired_entries_are_reported_once_and_removed() {
+        let mut cache = cache(4, Duration::ZERO);
+        cache.insert("a".to_string(), &message("help", &[]));
+        assert!(matches!(cache.get("a"), Err(CacheRemoval::Expired)));
+        assert!(matches!(cache.get("a"), Ok(None)));
+
+        cache.insert("b".to_string(), &message("help", &[]));
+        cache.insert("c".to_string(), &message("help", &[]));
+        assert_eq!(cache.purge_expired(), 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn opted_out_intents_are_never_stored() {
+        let mut cache = MessageCache::new(CacheConfig {
+            uncached_intents: HashSet::from(["music".to_string()]),
+            ..CacheConfig::default()
+        });
+        cache.insert("top".to_string(), &message("music", &[]));
+        cache.insert("secondary".to_string(), &message("help", &["music"]));
+        cache.insert("other".to_string(), &message("help", &["study"]));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("other").unwrap().is_some());
+    }
+
+    #[test]
+    fn keys_differ_by_locale_case_insensitively() {
+        assert_eq!(MessageCache::key("u", "c", Some("en-US"), "hi"), MessageCache::key("u", "c", Some("en-us"), "hi"));
+        assert_ne!(MessageCache::key("u", "c", Some("fr"), "hi"), MessageCache::key("u", "c", None, "hi"));
+    }
+}
@@ -0,0 +1,479 @@
+/*!
+ * gunnchAI3k Performance Engine - Conversation Memory
+ * Rolling per-channel and per-thread windows of recent turns with summaries and coreference
+ */
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{Entity, EntityKind};
+use crate::ProcessedMessage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// Turns kept per conversation before the oldest are summarised away
+    pub max_turns: usize,
+    /// Approximate token budget (about four characters per token) per window
+    pub max_tokens: usize,
+    /// Conversations untouched for this long are forgotten
+    pub idle_ttl: Duration,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: 20,
+            max_tokens: 2_000,
+            idle_ttl: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// A channel, or a thread inside one
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationKey {
+    pub channel_id: String,
+    pub thread_id: Option<String>,
+}
+
+impl ConversationKey {
+    pub fn new(channel_id: &str, thread_id: Option<&str>) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+            thread_id: thread_id.map(str::to_string),
+        }
+    }
+}
+
+/// One message and the bot's reply to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    pub message_id: String,
+    pub author_id: String,
+    pub content: String,
+    pub intent: String,
+    pub entities: Vec<Entity>,
+    pub response: String,
+    pub timestamp: u64,
+    pub tokens: usize,
+}
+
+impl Turn {
+    pub fn from_processed(message: &ProcessedMessage) -> Self {
+        Self {
+            message_id: message.id.clone(),
+            author_id: message.author_id.clone(),
+            content: message.content.clone(),
+            intent: message.intent.clone(),
+            entities: message.entities.clone(),
+            response: message.response.clone(),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            tokens: estimate_tokens(&message.content) + estimate_tokens(&message.response),
+        }
+    }
+}
+
+/// Rough token count; about four characters per token for English text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Condenses turns that fall out of the window into a running summary
+#[async_trait]
+pub trait ConversationSummarizer: Send + Sync {
+    async fn summarize(&self, previous: Option<&str>, evicted: &[Turn]) -> String;
+}
+
+/// Keeps a running list of topics and entities; no model required
+pub struct TopicSummarizer;
+
+#[async_trait]
+impl ConversationSummarizer for TopicSummarizer {
+    async fn summarize(&self, previous: Option<&str>, evicted: &[Turn]) -> String {
+        let mut topics: Vec<String> = previous
+            .and_then(|previous| previous.strip_prefix("Earlier topics: "))
+            .map(|list| list.split("; ").map(str::to_string).collect())
+            .unwrap_or_default();
+
+        for turn in evicted {
+            let mut topic = turn.intent.clone();
+            let mentioned: Vec<&str> = turn
+                .entities
+                .iter()
+                .filter(|entity| is_referable(entity))
+                .map(|entity| entity.text.as_str())
+                .collect();
+            if !mentioned.is_empty() {
+                topic = format!("{} ({})", topic, mentioned.join(", "));
+            }
+            if !topics.contains(&topic) {
+                topics.push(topic);
+            }
+        }
+
+        // Only the most recent topics are worth carrying forward
+        let skip = topics.len().saturating_sub(10);
+        format!("Earlier topics: {}", topics[skip..].join("; "))
+    }
+}
+
+/// An entity from an earlier turn that a phrase in the current message points at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Coreference {
+    pub phrase: String,
+    pub entity: Entity,
+    pub message_id: String,
+}
+
+/// What analyzers and response generation see of the conversation so far
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversationContext {
+    pub summary: Option<String>,
+    /// Oldest first
+    pub turns: Vec<Turn>,
+    pub referent: Option<Coreference>,
+}
+
+impl ConversationContext {
+    pub fn last_turn(&self) -> Option<&Turn> {
+        self.turns.last()
+    }
+
+    /// Most recent referable entity, newest turn and rightmost mention first
+    pub fn last_mentioned(&self) -> Option<&Entity> {
+        self.turns
+            .iter()
+            .rev()
+            .flat_map(|turn| turn.entities.iter().rev())
+            .find(|entity| is_referable(entity))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConversationWindow {
+    turns: VecDeque<Turn>,
+    tokens: usize,
+    summary: Option<String>,
+    last_active: Instant,
+}
+
+impl ConversationWindow {
+    fn new() -> Self {
+        Self {
+            turns: VecDeque::new(),
+            tokens: 0,
+            summary: None,
+            last_active: Instant::now(),
+        }
+    }
+}
+
+/// Rolling windows of recent turns, keyed by channel and thread
+pub struct ConversationMemory {
+    windows: HashMap<ConversationKey, ConversationWindow>,
+    config: MemoryConfig,
+    summarizer: Option<Arc<dyn ConversationSummarizer>>,
+}
+
+impl ConversationMemory {
+    pub fn new(config: MemoryConfig) -> Self {
+        Self {
+            windows: HashMap::new(),
+            config,
+            summarizer: Some(Arc::new(TopicSummarizer)),
+        }
+    }
+
+    pub fn config(&self) -> &MemoryConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: MemoryConfig) {
+        self.config = config;
+    }
+
+    /// Replaces the summariser; `None` drops evicted turns without a summary
+    pub fn set_summarizer(&mut self, summarizer: Option<Arc<dyn ConversationSummarizer>>) {
+        self.summarizer = summarizer;
+    }
+
+    pub fn summarizer(&self) -> Option<Arc<dyn ConversationSummarizer>> {
+        self.summarizer.clone()
+    }
+
+    /// Snapshot of a conversation, with `message` resolved against it
+    pub fn context(&self, key: &ConversationKey, message: &str) -> ConversationContext {
+        let Some(window) = self.windows.get(key).filter(|window| window.last_active.elapsed() < self.config.idle_ttl) else {
+            return ConversationContext::default();
+        };
+
+        let mut context = ConversationContext {
+            summary: window.summary.clone(),
+            turns: window.turns.iter().cloned().collect(),
+            referent: None,
+        };
+        context.referent = resolve_reference(message, &context);
+        context
+    }
+
+    /// Appends a turn and trims the window, returning the turns that fell out
+    /// so the caller can summarise them without holding the memory lock
+    pub fn record(&mut self, key: ConversationKey, turn: Turn) -> Vec<Turn> {
+        let idle_ttl = self.config.idle_ttl;
+        let window = self.windows.entry(key).or_insert_with(ConversationWindow::new);
+        if window.last_active.elapsed() >= idle_ttl {
+            *window = ConversationWindow::new();
+        }
+
+        window.tokens += turn.tokens;
+        window.turns.push_back(turn);
+        window.last_active = Instant::now();
+
+        let mut evicted = Vec::new();
+        while window.turns.len() > 1 && (window.turns.len() > self.config.max_turns || window.tokens > self.config.max_tokens) {
+            if let Some(oldest) = window.turns.pop_front() {
+                window.tokens -= oldest.tokens;
+                evicted.push(oldest);
+            }
+        }
+        evicted
+    }
+
+    pub fn set_summary(&mut self, key: &ConversationKey, summary: String) {
+        if let Some(window) = self.windows.get_mut(key) {
+            window.summary = Some(summary);
+        }
+    }
+
+    pub fn summary(&self, key: &ConversationKey) -> Option<String> {
+        self.windows.get(key).and_then(|window| window.summary.clone())
+    }
+
+    pub fn clear(&mut self, key: &ConversationKey) -> bool {
+        self.windows.remove(key).is_some()
+    }
+
+    /// Forgets idle conversations, returning how many were dropped
+    pub fn purge_idle(&mut self) -> usize {
+        let before = self.windows.len();
+        let idle_ttl = self.config.idle_ttl;
+        self.windows.retain(|_, window| window.last_active.elapsed() < idle_ttl);
+        before - self.windows.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+}
+
+impl Default for ConversationMemory {
+    fn default() -> Self {
+        Self::new(MemoryConfig::default())
+    }
+}
+
+/// Things a pronoun like "it" can stand for; mentions of people and emoji are left out
+fn is_referable(entity: &Entity) -> bool {
+    !matches!(
+        entity.kind,
+        EntityKind::UserMention { .. } | EntityKind::RoleMention { .. } | EntityKind::CustomEmoji { .. }
+    )
+}
+
+static ORDINAL_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\bthe (first|second|third|fourth|fifth|last|other) (?:one|link|question|problem|course|class|assignment|date)\b").unwrap()
+});
+
+static PRONOUN_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(that one|this one|that|it|this|those|them|him|her)\b('\w+|\s+\w+)?").unwrap());
+
+/// Words that may follow "this"/"that" when they stand alone as pronouns
+/// ("explain that to me"); any other following word makes them determiners
+/// ("this exam")
+const PRONOUN_FOLLOWERS: &[&str] = &["to", "for", "again", "please", "with", "in", "on", "about", "is", "was", "means", "mean", "work", "works"];
+
+/// Whether a match is a pronoun rather than "it's", "that's" or "this exam"
+fn is_pronoun_use(pronoun: &str, next: Option<&str>) -> bool {
+    let Some(next) = next else { return true };
+    if next.starts_with('\'') {
+        return false;
+    }
+    let next = next.trim().to_lowercase();
+    match pronoun {
+        "this" | "that" | "those" => PRONOUN_FOLLOWERS.contains(&next.as_str()),
+        _ => true,
+    }
+}
+
+/// Resolves "it", "that one", "the second one" and similar against earlier
+/// turns. Ordinals pick from the newest turn that mentioned enough entities.
+pub fn resolve_reference(message: &str, context: &ConversationContext) -> Option<Coreference> {
+    if let Some(captures) = ORDINAL_REFERENCE.captures(message) {
+        let ordinal = captures[1].to_lowercase();
+        for turn in context.turns.iter().rev() {
+            let candidates: Vec<&Entity> = turn.entities.iter().filter(|entity| is_referable(entity)).collect();
+            let index = match ordinal.as_str() {
+                "first" => Some(0),
+                "second" | "other" => Some(1),
+                "third" => Some(2),
+                "fourth" => Some(3),
+                "fifth" => Some(4),
+                _ => candidates.len().checked_sub(1),
+            };
+            if let Some(entity) = index.and_then(|index| candidates.get(index)) {
+                return Some(Coreference {
+                    phrase: captures[0].to_string(),
+                    entity: (*entity).clone(),
+                    message_id: turn.message_id.clone(),
+                });
+            }
+        }
+        return None;
+    }
+
+    let captures = PRONOUN_REFERENCE.captures_iter(message).find(|captures| {
+        is_pronoun_use(&captures[1].to_lowercase(), captures.get(2).map(|next| next.as_str()))
+    })?;
+    let phrase = captures[1].to_lowercase();
+    let wants_person = matches!(phrase.as_str(), "him" | "her");
+
+    for turn in context.turns.iter().rev() {
+        let found = turn.entities.iter().rev().find(|entity| {
+            if wants_person {
+                matches!(entity.kind, EntityKind::UserMention { .. })
+            } else {
+                is_referable(entity)
+            }
+        });
+        if let Some(entity) = found {
+            return Some(Coreference {
+                phrase: captures[1].to_string(),
+                entity: entity.clone(),
+                message_id: turn.message_id.clone(),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(kind: EntityKind, text: &str) -> Entity {
+        Entity { kind, text: text.to_string(), start: 0, end: text.len() }
+    }
+
+    fn url(text: &str) -> Entity {
+        entity(EntityKind::Url, text)
+    }
+
+    fn mention(id: &str) -> Entity {
+        entity(EntityKind::UserMention { id: id.to_string() }, &format!("<@{}>", id))
+    }
+
+    fn turn(id: &str, content: &str, entities: Vec<Entity>) -> Turn {
+        Turn {
+            message_id: id.to_string(),
+            author_id: "u1".to_string(),
+            content: content.to_string(),
+            intent: "study".to_string(),
+            entities,
+            response: String::new(),
+            timestamp: 0,
+            tokens: estimate_tokens(content),
+        }
+    }
+
+    fn context(turns: Vec<Turn>) -> ConversationContext {
+        ConversationContext { summary: None, turns, referent: None }
+    }
+
+    fn resolved(message: &str, context: &ConversationContext) -> Option<String> {
+        resolve_reference(message, context).map(|reference| reference.entity.text)
+    }
+
+    #[test]
+    fn windows_trim_by_turns_and_tokens() {
+        let key = ConversationKey::new("c1", None);
+        let mut memory = ConversationMemory::new(MemoryConfig { max_turns: 2, max_tokens: 1_000, ..MemoryConfig::default() });
+        assert!(memory.record(key.clone(), turn("1", "one", Vec::new())).is_empty());
+        assert!(memory.record(key.clone(), turn("2", "two", Vec::new())).is_empty());
+        let evicted = memory.record(key.clone(), turn("3", "three", Vec::new()));
+        assert_eq!(evicted.iter().map(|turn| turn.message_id.as_str()).collect::<Vec<_>>(), vec!["1"]);
+
+        // 40 characters is 10 tokens, so only one such turn fits in 15
+        let mut memory = ConversationMemory::new(MemoryConfig { max_turns: 10, max_tokens: 15, ..MemoryConfig::default() });
+        let long = "x".repeat(40);
+        memory.record(key.clone(), turn("1", &long, Vec::new()));
+        assert_eq!(memory.record(key.clone(), turn("2", &long, Vec::new())).len(), 1);
+        // The newest turn is kept even when it alone is over budget
+        assert_eq!(memory.record(key.clone(), turn("3", &"x".repeat(400), Vec::new())).len(), 1);
+        assert_eq!(memory.context(&key, "").turns.len(), 1);
+    }
+
+    #[test]
+    fn idle_conversations_are_forgotten() {
+        let key = ConversationKey::new("c1", Some("t1"));
+        let mut memory = ConversationMemory::new(MemoryConfig { idle_ttl: Duration::from_millis(20), ..MemoryConfig::default() });
+        memory.record(key.clone(), turn("1", "one", Vec::new()));
+        memory.set_summary(&key, "Earlier topics: study".to_string());
+        assert_eq!(memory.context(&key, "").turns.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(memory.context(&key, "").turns.is_empty());
+        memory.record(key.clone(), turn("2", "two", Vec::new()));
+        let context = memory.context(&key, "");
+        assert_eq!(context.turns.len(), 1);
+        assert_eq!(context.summary, None, "a stale window starts over");
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(memory.purge_idle(), 1);
+        assert!(memory.is_empty());
+    }
+
+    #[test]
+    fn ordinals_pick_from_the_newest_turn_with_enough_entities() {
+        let context = context(vec![
+            turn("1", "links", vec![url("https://a.example"), url("https://b.example"), url("https://c.example")]),
+            turn("2", "one link", vec![url("https://d.example")]),
+        ]);
+        assert_eq!(resolved("open the second one", &context).as_deref(), Some("https://b.example"));
+        assert_eq!(resolved("and the first link?", &context).as_deref(), Some("https://d.example"));
+        assert_eq!(resolved("the last one please", &context).as_deref(), Some("https://d.example"));
+        assert_eq!(resolved("the fifth one", &context), None);
+    }
+
+    #[test]
+    fn pronouns_are_told_apart_from_contractions_and_determiners() {
+        let context = context(vec![turn("1", "see this", vec![url("https://a.example")])]);
+        assert_eq!(resolved("can you explain it", &context).as_deref(), Some("https://a.example"));
+        assert_eq!(resolved("explain this", &context).as_deref(), Some("https://a.example"));
+        assert_eq!(resolved("explain this to me", &context).as_deref(), Some("https://a.example"));
+        assert_eq!(resolved("it's fine", &context), None);
+        assert_eq!(resolved("when is this exam", &context), None);
+        assert_eq!(resolved("that's all", &context), None);
+    }
+
+    #[test]
+    fn him_and_her_prefer_user_mentions() {
+        let context = context(vec![
+            turn("1", "ask <@7>", vec![mention("7")]),
+            turn("2", "and this", vec![url("https://a.example"), mention("8")]),
+        ]);
+        assert_eq!(resolved("ping him", &context).as_deref(), Some("<@8>"));
+        // Mentions are not things, so "it" skips them
+        assert_eq!(resolved("open it", &context).as_deref(), Some("https://a.example"));
+
+        let no_people = ConversationContext { turns: vec![turn("1", "see", vec![url("https://a.example")])], ..ConversationContext::default() };
+        assert_eq!(resolved("ask her", &no_people), None);
+    }
+}
@@ -0,0 +1,918 @@
+/*!
+ * gunnchAI3k Performance Engine - Deck Import/Export
+ * Flashcards from and to Anki packages, CSV/TSV files and Markdown notes
+ */
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use rusqlite::{params, Connection, OpenFlags};
+use anyhow::{bail, Context, Result};
+
+use crate::Flashcard;
+
+/// Separates note fields in an Anki collection
+const ANKI_FIELD_SEPARATOR: char = '\x1f';
+/// Anki tag that carries a card's difficulty through a round trip
+const DIFFICULTY_TAG: &str = "difficulty::";
+/// Anki ease factors (permille) mapped onto difficulty 10..=1
+const EASE_RANGE: (f64, f64) = (1300.0, 3500.0);
+
+/// Largest collection an uploaded package may decompress to
+const MAX_COLLECTION_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Fallbacks for whatever a source doesn't say about a card
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub subject: String,
+    pub topic: Option<String>,
+    pub difficulty: u8,
+}
+
+impl ImportOptions {
+    pub fn new(subject: &str) -> Self {
+        Self {
+            subject: subject.to_string(),
+            topic: None,
+            difficulty: 5,
+        }
+    }
+
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = Some(topic.to_string());
+        self
+    }
+
+    pub fn with_difficulty(mut self, difficulty: u8) -> Self {
+        self.difficulty = difficulty.clamp(1, 10);
+        self
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::new("general")
+    }
+}
+
+/// A row (CSV line, Markdown line or Anki note) that couldn't be converted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowError {
+    /// 1-based line number, or note number for Anki packages
+    pub row: usize,
+    pub message: String,
+}
+
+impl RowError {
+    fn new(row: usize, message: impl Into<String>) -> Self {
+        Self {
+            row,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub cards: Vec<Flashcard>,
+    /// Cards skipped because the subject already had the same question and answer
+    pub duplicates: usize,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    pub data: Vec<u8>,
+    pub exported: usize,
+    /// Cards left out; `row` is the card's 1-based position in the export
+    pub errors: Vec<RowError>,
+}
+
+/// A CSV column, by header name or 0-based position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+/// Which CSV columns hold which card fields. Named optional columns that are
+/// missing from the header are ignored; required ones are an error.
+#[derive(Debug, Clone)]
+pub struct CsvMapping {
+    pub question: Column,
+    pub answer: Column,
+    pub subject: Option<Column>,
+    pub topic: Option<Column>,
+    pub difficulty: Option<Column>,
+    pub delimiter: u8,
+    pub has_headers: bool,
+}
+
+impl CsvMapping {
+    /// Comma-separated with the header `export_csv` writes
+    pub fn new() -> Self {
+        Self {
+            question: "question".into(),
+            answer: "answer".into(),
+            subject: Some("subject".into()),
+            topic: Some("topic".into()),
+            difficulty: Some("difficulty".into()),
+            delimiter: b',',
+            has_headers: true,
+        }
+    }
+
+    /// Like `new`, tab-separated
+    pub fn tsv() -> Self {
+        Self::new().with_delimiter(b'\t')
+    }
+
+    /// Headerless, with only question and answer columns
+    pub fn by_position(question: usize, answer: usize) -> Self {
+        Self {
+            question: question.into(),
+            answer: answer.into(),
+            subject: None,
+            topic: None,
+            difficulty: None,
+            delimiter: b',',
+            has_headers: false,
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_question(mut self, column: impl Into<Column>) -> Self {
+        self.question = column.into();
+        self
+    }
+
+    pub fn with_answer(mut self, column: impl Into<Column>) -> Self {
+        self.answer = column.into();
+        self
+    }
+
+    pub fn with_subject(mut self, column: impl Into<Column>) -> Self {
+        self.subject = Some(column.into());
+        self
+    }
+
+    pub fn with_topic(mut self, column: impl Into<Column>) -> Self {
+        self.topic = Some(column.into());
+        self
+    }
+
+    pub fn with_difficulty(mut self, column: impl Into<Column>) -> Self {
+        self.difficulty = Some(column.into());
+        self
+    }
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads cards from CSV or TSV. Fails only when the mapping doesn't fit the
+/// header; bad rows are reported and skipped.
+pub fn read_csv(data: &[u8], mapping: &CsvMapping, options: &ImportOptions) -> Result<ImportReport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(mapping.delimiter)
+        .has_headers(mapping.has_headers)
+        .flexible(true)
+        .from_reader(data);
+    let headers = if mapping.has_headers { Some(reader.headers()?.clone()) } else { None };
+    let resolve = |column: &Column| -> Option<usize> {
+        match column {
+            Column::Index(index) => Some(*index),
+            Column::Name(name) => headers
+                .as_ref()?
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(name)),
+        }
+    };
+    let required = |column: &Column, field: &str| match resolve(column) {
+        Some(index) => Ok(index),
+        None => bail!("no {} column {:?} in the CSV header", field, column),
+    };
+    let question = required(&mapping.question, "question")?;
+    let answer = required(&mapping.answer, "answer")?;
+    let subject = mapping.subject.as_ref().and_then(resolve);
+    let topic = mapping.topic.as_ref().and_then(resolve);
+    let difficulty = mapping.difficulty.as_ref().and_then(resolve);
+
+    let mut report = ImportReport::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let row = error.position().map(|position| position.line() as usize).unwrap_or(0);
+                report.errors.push(RowError::new(row, error.to_string()));
+                continue;
+            }
+        };
+        let row = record.position().map(|position| position.line() as usize).unwrap_or(0);
+        if record.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |index: Option<usize>| index.and_then(|index| record.get(index)).map(str::trim).filter(|value| !value.is_empty());
+        let card = CardFields {
+            question: field(Some(question)),
+            answer: field(Some(answer)),
+            subject: field(subject),
+            topic: field(topic),
+            difficulty: field(difficulty),
+        };
+        match card.into_flashcard(options) {
+            Ok(card) => report.cards.push(card),
+            Err(message) => report.errors.push(RowError::new(row, message)),
+        }
+    }
+    Ok(report)
+}
+
+/// Reads Q/A blocks:
+///
+/// ```text
+/// # Subject
+/// ## Topic
+/// Q: What is the mean of Poisson(λ)?
+/// A: λ
+/// Difficulty: 3
+/// ```
+///
+/// Headings are optional and apply to the cards after them; questions and
+/// answers run on until the next marker or heading.
+pub fn read_markdown(text: &str, options: &ImportOptions) -> ImportReport {
+    #[derive(PartialEq)]
+    enum Part {
+        Question,
+        Answer,
+    }
+    struct Block {
+        line: usize,
+        question: String,
+        answer: Option<String>,
+        difficulty: Option<String>,
+        part: Part,
+    }
+
+    let mut report = ImportReport::default();
+    let mut subject: Option<String> = None;
+    let mut topic: Option<String> = None;
+    let mut block: Option<Block> = None;
+    let finish = |block: Option<Block>, subject: &Option<String>, topic: &Option<String>, report: &mut ImportReport| {
+        let Some(block) = block else { return };
+        let card = CardFields {
+            question: Some(block.question.trim()).filter(|value| !value.is_empty()),
+            answer: block.answer.as_deref().map(str::trim).filter(|value| !value.is_empty()),
+            subject: subject.as_deref(),
+            topic: topic.as_deref(),
+            difficulty: block.difficulty.as_deref(),
+        };
+        match card.into_flashcard(options) {
+            Ok(card) => report.cards.push(card),
+            Err(message) => report.errors.push(RowError::new(block.line, message)),
+        }
+    };
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            finish(block.take(), &subject, &topic, &mut report);
+            topic = Some(heading.trim().to_string());
+        } else if let Some(heading) = trimmed.strip_prefix("# ") {
+            finish(block.take(), &subject, &topic, &mut report);
+            subject = Some(heading.trim().to_string());
+            topic = None;
+        } else if let Some(question) = strip_marker(trimmed, &["Q", "Question"]) {
+            finish(block.take(), &subject, &topic, &mut report);
+            block = Some(Block {
+                line: number,
+                question: question.to_string(),
+                answer: None,
+                difficulty: None,
+                part: Part::Question,
+            });
+        } else if let Some(answer) = strip_marker(trimmed, &["A", "Answer"]) {
+            match block.as_mut() {
+                Some(current) if current.answer.is_none() => {
+                    current.answer = Some(answer.to_string());
+                    current.part = Part::Answer;
+                }
+                Some(_) => report.errors.push(RowError::new(number, "second answer for one question")),
+                None => report.errors.push(RowError::new(number, "answer without a question")),
+            }
+        } else if let Some(difficulty) = strip_marker(trimmed, &["D", "Difficulty"]) {
+            match block.as_mut() {
+                Some(current) => current.difficulty = Some(difficulty.to_string()),
+                None => report.errors.push(RowError::new(number, "difficulty without a question")),
+            }
+        } else if let Some(current) = block.as_mut() {
+            let target = match current.part {
+                Part::Question => &mut current.question,
+                Part::Answer => current.answer.get_or_insert_with(String::new),
+            };
+            target.push('\n');
+            target.push_str(line);
+        }
+    }
+    finish(block.take(), &subject, &topic, &mut report);
+    report
+}
+
+/// Reads the notes of an Anki `.apkg` (legacy collection format). The deck
+/// gives the subject, a sub-deck the topic, and the difficulty comes from
+/// a `difficulty::N` tag or else the card's ease.
+pub fn read_apkg(data: &[u8], options: &ImportOptions) -> Result<ImportReport> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).context("not a zip archive")?;
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let Some(name) = ["collection.anki21", "collection.anki2"]
+        .into_iter()
+        .find(|name| names.iter().any(|entry| entry == name))
+    else {
+        if names.iter().any(|entry| entry == "collection.anki21b") {
+            bail!("this package uses Anki's newer compressed format; export it again with \"Support older Anki versions\" checked");
+        }
+        bail!("no Anki collection in the package");
+    };
+    // The declared size can lie, so the read is capped as well
+    let entry = archive.by_name(name)?;
+    if entry.size() > MAX_COLLECTION_BYTES {
+        bail!("Anki collection is larger than {} MiB", MAX_COLLECTION_BYTES / (1024 * 1024));
+    }
+    let mut collection = Vec::new();
+    entry.take(MAX_COLLECTION_BYTES + 1).read_to_end(&mut collection)?;
+    if collection.len() as u64 > MAX_COLLECTION_BYTES {
+        bail!("Anki collection is larger than {} MiB", MAX_COLLECTION_BYTES / (1024 * 1024));
+    }
+
+    let file = TempFile::new("anki2");
+    std::fs::write(&file.0, &collection)?;
+    let conn = Connection::open_with_flags(&file.0, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let decks = anki_decks(&conn)?;
+
+    let mut report = ImportReport::default();
+    let mut seen = HashSet::new();
+    let mut statement = conn.prepare(
+        "SELECT n.id, n.tags, n.flds, c.did, c.factor, c.reps
+         FROM notes n JOIN cards c ON c.nid = n.id
+         ORDER BY n.id, c.ord",
+    )?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+            row.get::<_, i64>(4)?,
+            row.get::<_, i64>(5)?,
+        ))
+    })?;
+    for row in rows {
+        let (note, tags, fields, deck, factor, reps) = row?;
+        // One flashcard per note, however many cards Anki made from it
+        if !seen.insert(note) {
+            continue;
+        }
+        let number = seen.len();
+        let fields: Vec<String> = fields.split(ANKI_FIELD_SEPARATOR).map(html_to_text).collect();
+        if fields.len() < 2 {
+            report.errors.push(RowError::new(number, format!("note {} has only one field", note)));
+            continue;
+        }
+        let deck = decks.get(&deck).map(String::as_str).filter(|name| *name != "Default");
+        let (subject, topic) = match deck.and_then(|name| name.split_once("::")) {
+            Some((subject, topic)) => (Some(subject), Some(topic)),
+            None => (deck, None),
+        };
+        let tagged = tags.split_whitespace().find_map(|tag| tag.strip_prefix(DIFFICULTY_TAG));
+        let from_ease = (reps > 0 && factor > 0).then(|| {
+            let share = ((factor as f64 - EASE_RANGE.0) / (EASE_RANGE.1 - EASE_RANGE.0)).clamp(0.0, 1.0);
+            (10.0 - share * 9.0).round().to_string()
+        });
+        let card = CardFields {
+            question: Some(fields[0].trim()).filter(|value| !value.is_empty()),
+            answer: Some(fields[1].trim()).filter(|value| !value.is_empty()),
+            subject,
+            topic,
+            difficulty: tagged.or(from_ease.as_deref()),
+        };
+        match card.into_flashcard(options) {
+            Ok(card) => report.cards.push(card),
+            Err(message) => report.errors.push(RowError::new(number, format!("note {}: {}", note, message))),
+        }
+    }
+    Ok(report)
+}
+
+/// Writes cards with the header `CsvMapping::new` reads
+pub fn write_csv(cards: &[Flashcard], delimiter: u8) -> Result<ExportReport> {
+    let mut report = ExportReport::default();
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(Vec::new());
+    writer.write_record(["question", "answer", "subject", "topic", "difficulty"])?;
+    for (index, card) in cards.iter().enumerate() {
+        if let Err(message) = check_exportable(card) {
+            report.errors.push(RowError::new(index + 1, message));
+            continue;
+        }
+        writer.write_record([
+            card.question.as_str(),
+            card.answer.as_str(),
+            card.subject.as_str(),
+            card.topic.as_deref().unwrap_or(""),
+            &card.difficulty.to_string(),
+        ])?;
+        report.exported += 1;
+    }
+    report.data = writer.into_inner().map_err(|error| error.into_error())?;
+    Ok(report)
+}
+
+/// Writes an Anki package with a deck per subject (and sub-deck per topic),
+/// tagging each note with its difficulty
+pub fn write_apkg(cards: &[Flashcard]) -> Result<ExportReport> {
+    let mut report = ExportReport::default();
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let now = now_ms / 1000;
+    let model_id = now_ms;
+
+    let mut exportable = Vec::with_capacity(cards.len());
+    for (index, card) in cards.iter().enumerate() {
+        match check_exportable(card) {
+            Ok(()) if card.question.contains(ANKI_FIELD_SEPARATOR) || card.answer.contains(ANKI_FIELD_SEPARATOR) => {
+                report.errors.push(RowError::new(index + 1, "contains Anki's field separator (U+001F)"));
+            }
+            Ok(()) => exportable.push(card),
+            Err(message) => report.errors.push(RowError::new(index + 1, message)),
+        }
+    }
+
+    let mut deck_ids: BTreeMap<String, i64> = BTreeMap::new();
+    for card in &exportable {
+        let next = now_ms + 1 + deck_ids.len() as i64;
+        deck_ids.entry(deck_name(card)).or_insert(next);
+    }
+    let mut decks = serde_json::Map::new();
+    decks.insert("1".to_string(), anki_deck(1, "Default", now));
+    for (name, id) in &deck_ids {
+        decks.insert(id.to_string(), anki_deck(*id, name, now));
+    }
+
+    let file = TempFile::new("anki2");
+    let mut conn = Connection::open(&file.0)?;
+    conn.execute_batch(ANKI_SCHEMA)?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![
+            now,
+            now_ms,
+            anki_conf(model_id, exportable.len()).to_string(),
+            json!({ model_id.to_string(): anki_model(model_id, now) }).to_string(),
+            serde_json::Value::Object(decks).to_string(),
+            json!({ "1": anki_deck_config(now) }).to_string(),
+        ],
+    )?;
+    for (position, card) in exportable.iter().enumerate() {
+        let id = now_ms + position as i64;
+        let front = text_to_html(&card.question);
+        let mut tags = vec![format!("{}{}", DIFFICULTY_TAG, card.difficulty)];
+        if let Some(topic) = &card.topic {
+            tags.push(topic.split_whitespace().collect::<Vec<_>>().join("_"));
+        }
+        tx.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![
+                id,
+                card.id,
+                model_id,
+                now,
+                format!(" {} ", tags.join(" ")),
+                format!("{}{}{}", front, ANKI_FIELD_SEPARATOR, text_to_html(&card.answer)),
+                card.question,
+                field_checksum(&card.question),
+            ],
+        )?;
+        tx.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_ids[&deck_name(card)], now, position as i64 + 1],
+        )?;
+    }
+    tx.commit()?;
+    drop(conn);
+    let collection = std::fs::read(&file.0)?;
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("collection.anki2", deflated)?;
+    zip.write_all(&collection)?;
+    zip.start_file("media", deflated)?;
+    zip.write_all(b"{}")?;
+    report.data = zip.finish()?.into_inner();
+    report.exported = exportable.len();
+    Ok(report)
+}
+
+/// Raw card fields from any source, checked and filled in from `ImportOptions`
+struct CardFields<'a> {
+    question: Option<&'a str>,
+    answer: Option<&'a str>,
+    subject: Option<&'a str>,
+    topic: Option<&'a str>,
+    difficulty: Option<&'a str>,
+}
+
+impl CardFields<'_> {
+    fn into_flashcard(self, options: &ImportOptions) -> std::result::Result<Flashcard, String> {
+        let question = self.question.ok_or("missing question")?;
+        let answer = self.answer.ok_or("missing answer")?;
+        let difficulty = match self.difficulty {
+            Some(raw) => match raw.trim().parse::<u8>() {
+                Ok(value) if (1..=10).contains(&value) => value,
+                _ => return Err(format!("difficulty '{}' is not a whole number from 1 to 10", raw)),
+            },
+            None => options.difficulty,
+        };
+        Ok(Flashcard {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: question.to_string(),
+            answer: answer.to_string(),
+            subject: self.subject.unwrap_or(&options.subject).to_string(),
+            difficulty,
+            created_at: chrono::Utc::now().timestamp() as u64,
+            topic: self.topic.map(str::to_string).or_else(|| options.topic.clone()),
+        })
+    }
+}
+
+fn check_exportable(card: &Flashcard) -> std::result::Result<(), String> {
+    if card.question.trim().is_empty() {
+        return Err(format!("card {} has no question", card.id));
+    }
+    if card.answer.trim().is_empty() {
+        return Err(format!("card {} has no answer", card.id));
+    }
+    if !(1..=10).contains(&card.difficulty) {
+        return Err(format!("card {} has difficulty {} outside 1..=10", card.id, card.difficulty));
+    }
+    Ok(())
+}
+
+/// "Q: text" or "**Question:** text" to "text". Words match in any case,
+/// single letters only as capitals, so "a: ..." can start an answer line.
+fn strip_marker<'a>(line: &'a str, markers: &[&str]) -> Option<&'a str> {
+    let (marker, rest) = line.trim_start_matches('*').split_once(':')?;
+    let marker = marker.trim();
+    markers
+        .iter()
+        .any(|candidate| marker == *candidate || (candidate.len() > 1 && marker.eq_ignore_ascii_case(candidate)))
+        .then(|| rest.trim_start_matches('*').trim())
+}
+
+fn deck_name(card: &Flashcard) -> String {
+    match &card.topic {
+        Some(topic) => format!("{}::{}", card.subject, topic),
+        None => card.subject.clone(),
+    }
+}
+
+/// Deck names by id, from the legacy JSON column or the newer decks table
+fn anki_decks(conn: &Connection) -> Result<BTreeMap<i64, String>> {
+    let raw: String = conn.query_row("SELECT decks FROM col", [], |row| row.get(0))?;
+    let mut decks: BTreeMap<i64, String> = serde_json::from_str::<BTreeMap<String, serde_json::Value>>(&raw)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, deck)| Some((id.parse().ok()?, deck.get("name")?.as_str()?.to_string())))
+        .collect();
+    if decks.is_empty() {
+        if let Ok(mut statement) = conn.prepare("SELECT id, name FROM decks") {
+            let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+            for row in rows {
+                let (id, name) = row?;
+                decks.insert(id, name.replace(ANKI_FIELD_SEPARATOR, "::"));
+            }
+        }
+    }
+    Ok(decks)
+}
+
+/// Plain text from a note field: line breaks kept, other markup dropped
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_ascii_lowercase();
+        let name = tag.trim_start_matches('/').split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        let block = matches!(name, "div" | "p" | "li") && !text.is_empty() && !text.ends_with('\n');
+        if name == "br" || block {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    decode_entities(&text)
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|end| *end <= 8).map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn text_to_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\n', "<br>")
+}
+
+/// Anki's duplicate check: the first 8 hex digits of the field's SHA-1
+fn field_checksum(field: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(field).digest().bytes();
+    i64::from(u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]))
+}
+
+/// A scratch file for SQLite, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(extension: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("gunnchai3k-{}.{}", uuid::Uuid::new_v4(), extension)))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// The legacy (schema 11) collection layout every Anki version can import
+const ANKI_SCHEMA: &str = "
+    CREATE TABLE col (
+        id INTEGER PRIMARY KEY, crt INTEGER NOT NULL, mod INTEGER NOT NULL, scm INTEGER NOT NULL,
+        ver INTEGER NOT NULL, dty INTEGER NOT NULL, usn INTEGER NOT NULL, ls INTEGER NOT NULL,
+        conf TEXT NOT NULL, models TEXT NOT NULL, decks TEXT NOT NULL, dconf TEXT NOT NULL, tags TEXT NOT NULL
+    );
+    CREATE TABLE notes (
+        id INTEGER PRIMARY KEY, guid TEXT NOT NULL, mid INTEGER NOT NULL, mod INTEGER NOT NULL,
+        usn INTEGER NOT NULL, tags TEXT NOT NULL, flds TEXT NOT NULL, sfld INTEGER NOT NULL,
+        csum INTEGER NOT NULL, flags INTEGER NOT NULL, data TEXT NOT NULL
+    );
+    CREATE TABLE cards (
+        id INTEGER PRIMARY KEY, nid INTEGER NOT NULL, did INTEGER NOT NULL, ord INTEGER NOT NULL,
+        mod INTEGER NOT NULL, usn INTEGER NOT NULL, type INTEGER NOT NULL, queue INTEGER NOT NULL,
+        due INTEGER NOT NULL, ivl INTEGER NOT NULL, factor INTEGER NOT NULL, reps INTEGER NOT NULL,
+        lapses INTEGER NOT NULL, left INTEGER NOT NULL, odue INTEGER NOT NULL, odid INTEGER NOT NULL,
+        flags INTEGER NOT NULL, data TEXT NOT NULL
+    );
+    CREATE TABLE revlog (
+        id INTEGER PRIMARY KEY, cid INTEGER NOT NULL, usn INTEGER NOT NULL, ease INTEGER NOT NULL,
+        ivl INTEGER NOT NULL, lastIvl INTEGER NOT NULL, factor INTEGER NOT NULL, time INTEGER NOT NULL,
+        type INTEGER NOT NULL
+    );
+    CREATE TABLE graves (usn INTEGER NOT NULL, oid INTEGER NOT NULL, type INTEGER NOT NULL);
+    CREATE INDEX ix_notes_usn ON notes (usn);
+    CREATE INDEX ix_cards_usn ON cards (usn);
+    CREATE INDEX ix_revlog_usn ON revlog (usn);
+    CREATE INDEX ix_cards_nid ON cards (nid);
+    CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+    CREATE INDEX ix_revlog_cid ON revlog (cid);
+    CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+fn anki_conf(model_id: i64, cards: usize) -> serde_json::Value {
+    json!({
+        "activeDecks": [1],
+        "curDeck": 1,
+        "curModel": model_id,
+        "nextPos": cards + 1,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    })
+}
+
+/// A basic front/back note type
+fn anki_model(id: i64, now: i64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": "Basic (gunnchAI3k)",
+        "type": 0,
+        "mod": now,
+        "usn": -1,
+        "sortf": 0,
+        "did": 1,
+        "tags": [],
+        "vers": [],
+        "flds": [
+            { "name": "Front", "ord": 0, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": [] },
+            { "name": "Back", "ord": 1, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": [] },
+        ],
+        "tmpls": [{
+            "name": "Card 1",
+            "ord": 0,
+            "qfmt": "{{Front}}",
+            "afmt": "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}",
+            "bqfmt": "",
+            "bafmt": "",
+            "did": null,
+        }],
+        "css": ".card { font-family: arial; font-size: 20px; text-align: center; color: black; background-color: white; }",
+        "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+        "latexPost": "\\end{document}",
+        "req": [[0, "any", [0]]],
+    })
+}
+
+fn anki_deck(id: i64, name: &str, now: i64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": now,
+        "usn": -1,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "collapsed": false,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+        "extendNew": 10,
+        "extendRev": 50,
+    })
+}
+
+fn anki_deck_config(now: i64) -> serde_json::Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": now,
+        "usn": -1,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": { "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1, "perDay": 20, "bury": false },
+        "rev": { "perDay": 200, "ease4": 1.3, "ivlFct": 1, "maxIvl": 36500, "bury": false, "hardFactor": 1.2 },
+        "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 1 },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(question: &str, answer: &str, subject: &str, topic: Option<&str>, difficulty: u8) -> Flashcard {
+        Flashcard {
+            id: uuid::Uuid::new_v4().to_string(),
+            question: question.to_string(),
+            answer: answer.to_string(),
+            subject: subject.to_string(),
+            difficulty,
+            created_at: 0,
+            topic: topic.map(str::to_string),
+        }
+    }
+
+    fn deck() -> Vec<Flashcard> {
+        vec![
+            card("Mean of Poisson(λ)?", "λ", "probability", Some("poisson"), 3),
+            card("Var of \"Binomial\", n, p?", "np(1-p)\nfor n trials", "probability", Some("binomial distribution"), 7),
+            card("Nyquist rate for a 4 kHz signal?", "8 kHz", "signals", None, 10),
+        ]
+    }
+
+    /// (question, answer, subject, topic, difficulty), sorted so order doesn't matter
+    fn fields(cards: &[Flashcard]) -> Vec<(String, String, String, Option<String>, u8)> {
+        let mut fields: Vec<_> = cards
+            .iter()
+            .map(|card| (card.question.clone(), card.answer.clone(), card.subject.clone(), card.topic.clone(), card.difficulty))
+            .collect();
+        fields.sort();
+        fields
+    }
+
+    #[test]
+    fn csv_round_trip_keeps_every_field() {
+        for (delimiter, mapping) in [(b',', CsvMapping::new()), (b'\t', CsvMapping::new().with_delimiter(b'\t'))] {
+            let export = write_csv(&deck(), delimiter).unwrap();
+            assert_eq!(export.exported, 3);
+            let import = read_csv(&export.data, &mapping, &ImportOptions::new("fallback")).unwrap();
+            assert!(import.errors.is_empty(), "{:?}", import.errors);
+            assert_eq!(fields(&import.cards), fields(&deck()));
+        }
+    }
+
+    #[test]
+    fn csv_reports_bad_rows_and_fills_defaults() {
+        let data = "question,answer,difficulty\nWhat is 2+2?,4,\nNo answer,,3\nToo hard,x,11\n";
+        let options = ImportOptions::new("math").with_topic("arithmetic").with_difficulty(2);
+        let import = read_csv(data.as_bytes(), &CsvMapping::new(), &options).unwrap();
+
+        assert_eq!(import.cards.len(), 1);
+        assert_eq!(import.cards[0].subject, "math");
+        assert_eq!(import.cards[0].topic.as_deref(), Some("arithmetic"));
+        assert_eq!(import.cards[0].difficulty, 2);
+        let rows: Vec<usize> = import.errors.iter().map(|error| error.row).collect();
+        assert_eq!(rows, [3, 4]);
+
+        assert!(read_csv(b"front,back\n", &CsvMapping::new(), &options).is_err());
+    }
+
+    #[test]
+    fn apkg_round_trip_keeps_subject_topic_and_difficulty() {
+        let export = write_apkg(&deck()).unwrap();
+        assert_eq!(export.exported, 3);
+        let import = read_apkg(&export.data, &ImportOptions::new("fallback")).unwrap();
+        assert!(import.errors.is_empty(), "{:?}", import.errors);
+        assert_eq!(fields(&import.cards), fields(&deck()));
+    }
+
+    #[test]
+    fn apkg_without_a_collection_is_rejected() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("media", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"{}").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+        assert!(read_apkg(&data, &ImportOptions::default()).is_err());
+        assert!(read_apkg(b"not a zip", &ImportOptions::default()).is_err());
+    }
+
+    #[test]
+    fn markdown_headings_set_subject_and_topic() {
+        let text = "# Probability\n## Poisson\nQ: Mean of Poisson(λ)?\nA: λ\nDifficulty: 3\n\nQ: Variance?\nA: also λ\nA: again\n# Signals\nQ: Nyquist rate\nfor 4 kHz?\nA: 8 kHz\n";
+        let import = read_markdown(text, &ImportOptions::default());
+
+        assert_eq!(import.cards.len(), 3);
+        assert_eq!(import.cards[0].topic.as_deref(), Some("Poisson"));
+        assert_eq!(import.cards[0].difficulty, 3);
+        assert_eq!(import.cards[2].subject, "Signals");
+        assert_eq!(import.cards[2].question, "Nyquist rate\nfor 4 kHz?");
+        assert_eq!(import.errors, [RowError::new(9, "second answer for one question")]);
+    }
+}
@@ -0,0 +1,394 @@
+/*!
+ * gunnchAI3k Performance Engine - Entity Extraction
+ * Typed, span-carrying entities for Discord study messages
+ */
+
+use std::sync::LazyLock;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// An entity found in a message. `start`/`end` are byte offsets into the original text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub kind: EntityKind,
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssignmentKind {
+    Homework,
+    Quiz,
+    Exam,
+    Lab,
+    Project,
+    Lecture,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntityKind {
+    UserMention { id: String },
+    RoleMention { id: String },
+    ChannelMention { id: String },
+    CustomEmoji { name: String, id: String, animated: bool },
+    Url,
+    CodeBlock { language: Option<String>, code: String },
+    InlineCode { code: String },
+    CourseCode { department: String, number: String },
+    Assignment { kind: AssignmentKind, number: u32 },
+    /// Calendar date; `year` is None when the message omits it
+    Date { year: Option<i32>, month: u32, day: u32 },
+    /// Time relative to when the message was sent, left unresolved so callers
+    /// can apply the user's timezone
+    RelativeTime {
+        days_from_now: Option<i64>,
+        weekday: Option<String>,
+        hour: Option<u32>,
+        minute: Option<u32>,
+        offset_minutes: Option<i64>,
+    },
+    Quantity { value: f64, unit: String },
+}
+
+impl Entity {
+    /// Short label for logs and keyword matching, e.g. "course_code"
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            EntityKind::UserMention { .. } => "user_mention",
+            EntityKind::RoleMention { .. } => "role_mention",
+            EntityKind::ChannelMention { .. } => "channel_mention",
+            EntityKind::CustomEmoji { .. } => "custom_emoji",
+            EntityKind::Url => "url",
+            EntityKind::CodeBlock { .. } => "code_block",
+            EntityKind::InlineCode { .. } => "inline_code",
+            EntityKind::CourseCode { .. } => "course_code",
+            EntityKind::Assignment { .. } => "assignment",
+            EntityKind::Date { .. } => "date",
+            EntityKind::RelativeTime { .. } => "relative_time",
+            EntityKind::Quantity { .. } => "quantity",
+        }
+    }
+}
+
+static CODE_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)```([A-Za-z0-9_+\-]*)\n?(.*?)```").unwrap());
+static INLINE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`([^`\n]+)`").unwrap());
+static USER_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<@!?(\d{15,21})>").unwrap());
+static ROLE_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<@&(\d{15,21})>").unwrap());
+static CHANNEL_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<#(\d{15,21})>").unwrap());
+static CUSTOM_EMOJI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<(a?):(\w{2,32}):(\d{15,21})>").unwrap());
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"https?://[^\s<>"'`]+[^\s<>"'`.,;:!?)\]]"#).unwrap());
+static COURSE_CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b([A-Z]{2,4})[ -]?(\d{3,4}[A-Z]?)\b").unwrap());
+static ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(hw|homework|quiz|exam|midterm|final|lab|project|lecture)\s*(?:#|no\.?\s*)?\s*(\d{1,3})\b").unwrap()
+});
+// A bare "3/4" is usually a fraction, so short dates need a cue word or a year
+static NUMERIC_DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d{1,2})/(\d{1,2})/(\d{2}|\d{4})\b").unwrap());
+static CUED_NUMERIC_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:on|due|by|before|until|after)\s+((\d{1,2})/(\d{1,2}))\b").unwrap()
+});
+static NAMED_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.?\s+(\d{1,2})(?:st|nd|rd|th)?(?:,?\s+(\d{4}))?\b").unwrap()
+});
+static RELATIVE_DAY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(today|tonight|tomorrow|tmrw|tmr|yesterday|(?:next\s+|this\s+)?(?:monday|tuesday|wednesday|thursday|friday|saturday|sunday))\b(?:\s+(?:at|@)\s*(\d{1,2})(?::(\d{2}))?(?:\s*(am|pm))?)?").unwrap()
+});
+static CLOCK_TIME: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\b(?:at|@)\s*(\d{1,2})(?::(\d{2}))?(?:\s*(am|pm))?\b").unwrap());
+static OFFSET_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\bin\s+(\d+|an?)\s+(minutes?|mins?|hours?|hrs?|days?|weeks?)\b").unwrap()
+});
+static QUANTITY: LazyLock<Regex> = LazyLock::new(|| {
+    // Single-letter units must be attached ("5s", "3V") to avoid reading "2 a" as amperes
+    Regex::new(r"(?:^|[^\w.])(-?\d+(?:\.\d+)?(?:[eE]-?\d+)?)(?:\s?((?i:khz|mhz|ghz|hz|kbps|mbps|dbm|db|ms|ns|us|μs|secs?|mins?|minutes|hours?|hrs?|kg|mg|km|cm|mm|mv|kv|ma|kw|mw|ohms?|kΩ|Ω|uf|nf|pf|°c|°f|bpm)\b|%)|([smgVAWF])\b)").unwrap()
+});
+
+/// Extracts all entities, sorted by position with overlaps removed.
+/// Nothing inside a code block or inline code span is extracted except the code itself.
+pub fn extract_entities(message: &str) -> Vec<Entity> {
+    let mut candidates: Vec<Entity> = Vec::new();
+
+    for caps in CODE_BLOCK.captures_iter(message) {
+        let language = caps.get(1).map(|m| m.as_str()).filter(|lang| !lang.is_empty()).map(str::to_string);
+        let code = caps.get(2).map(|m| m.as_str()).unwrap_or_default().to_string();
+        candidates.push(entity(message, &caps, EntityKind::CodeBlock { language, code }));
+    }
+    // Blank out code so nothing inside it is matched; offsets stay the same
+    let masked = mask(message, &candidates);
+    for caps in INLINE_CODE.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::InlineCode { code: caps[1].to_string() }));
+    }
+    let masked = mask(message, &candidates);
+    for caps in USER_MENTION.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::UserMention { id: caps[1].to_string() }));
+    }
+    for caps in ROLE_MENTION.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::RoleMention { id: caps[1].to_string() }));
+    }
+    for caps in CHANNEL_MENTION.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::ChannelMention { id: caps[1].to_string() }));
+    }
+    for caps in CUSTOM_EMOJI.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::CustomEmoji {
+            name: caps[2].to_string(),
+            id: caps[3].to_string(),
+            animated: &caps[1] == "a",
+        }));
+    }
+    for caps in URL.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::Url));
+    }
+    for caps in ASSIGNMENT.captures_iter(&masked) {
+        let Ok(number) = caps[2].parse::<u32>() else { continue };
+        candidates.push(entity(message, &caps, EntityKind::Assignment {
+            kind: assignment_kind(&caps[1]),
+            number,
+        }));
+    }
+    for caps in COURSE_CODE.captures_iter(&masked) {
+        candidates.push(entity(message, &caps, EntityKind::CourseCode {
+            department: caps[1].to_string(),
+            number: caps[2].to_string(),
+        }));
+    }
+    for caps in NAMED_DATE.captures_iter(&masked) {
+        let (Some(month), Ok(day)) = (month_number(&caps[1]), caps[2].parse::<u32>()) else { continue };
+        if !(1..=31).contains(&day) {
+            continue;
+        }
+        let year = caps.get(3).and_then(|m| m.as_str().parse().ok());
+        candidates.push(entity(message, &caps, EntityKind::Date { year, month, day }));
+    }
+    for caps in NUMERIC_DATE.captures_iter(&masked) {
+        let (Some((month, day)), Ok(year)) = (month_day(&caps[1], &caps[2]), caps[3].parse::<i32>()) else { continue };
+        let year = if year < 100 { 2000 + year } else { year };
+        candidates.push(entity(message, &caps, EntityKind::Date { year: Some(year), month, day }));
+    }
+    for caps in CUED_NUMERIC_DATE.captures_iter(&masked) {
+        let Some((month, day)) = month_day(&caps[2], &caps[3]) else { continue };
+        let date = caps.get(1).unwrap();
+        candidates.push(Entity {
+            kind: EntityKind::Date { year: None, month, day },
+            text: date.as_str().to_string(),
+            start: date.start(),
+            end: date.end(),
+        });
+    }
+    for caps in RELATIVE_DAY.captures_iter(&masked) {
+        let day = caps[1].to_lowercase();
+        let (days_from_now, weekday) = match day.as_str() {
+            "today" | "tonight" => (Some(0), None),
+            "tomorrow" | "tmrw" | "tmr" => (Some(1), None),
+            "yesterday" => (Some(-1), None),
+            other => (None, Some(other.split_whitespace().collect::<Vec<_>>().join(" "))),
+        };
+        let (mut hour, minute) = clock(caps.get(2), caps.get(3), caps.get(4));
+        // "tonight at 8" means 20:00
+        if day == "tonight" && caps.get(4).is_none() {
+            hour = hour.map(|hour| if hour < 12 { hour + 12 } else { hour });
+        }
+        candidates.push(entity(message, &caps, EntityKind::RelativeTime {
+            days_from_now,
+            weekday,
+            hour,
+            minute,
+            offset_minutes: None,
+        }));
+    }
+    for caps in CLOCK_TIME.captures_iter(&masked) {
+        let (hour, minute) = clock(caps.get(1), caps.get(2), caps.get(3));
+        if hour.is_none() {
+            continue;
+        }
+        candidates.push(entity(message, &caps, EntityKind::RelativeTime {
+            days_from_now: None,
+            weekday: None,
+            hour,
+            minute,
+            offset_minutes: None,
+        }));
+    }
+    for caps in OFFSET_TIME.captures_iter(&masked) {
+        let amount = match caps[1].to_lowercase().as_str() {
+            "a" | "an" => 1,
+            digits => match digits.parse::<i64>() {
+                Ok(amount) => amount,
+                Err(_) => continue,
+            },
+        };
+        let unit = caps[2].to_lowercase();
+        let minutes_per_unit = if unit.starts_with("min") {
+            1
+        } else if unit.starts_with('h') {
+            60
+        } else if unit.starts_with('d') {
+            60 * 24
+        } else {
+            60 * 24 * 7
+        };
+        candidates.push(entity(message, &caps, EntityKind::RelativeTime {
+            days_from_now: None,
+            weekday: None,
+            hour: None,
+            minute: None,
+            offset_minutes: Some(amount * minutes_per_unit),
+        }));
+    }
+    for caps in QUANTITY.captures_iter(&masked) {
+        let Ok(value) = caps[1].parse::<f64>() else { continue };
+        let number = caps.get(1).unwrap();
+        let Some(unit) = caps.get(2).or_else(|| caps.get(3)) else { continue };
+        candidates.push(Entity {
+            kind: EntityKind::Quantity { value, unit: unit.as_str().to_string() },
+            text: message[number.start()..unit.end()].to_string(),
+            start: number.start(),
+            end: unit.end(),
+        });
+    }
+
+    resolve_overlaps(candidates)
+}
+
+fn mask(message: &str, code: &[Entity]) -> String {
+    let mut bytes = message.as_bytes().to_vec();
+    for entity in code {
+        bytes[entity.start..entity.end].fill(b' ');
+    }
+    String::from_utf8(bytes).expect("masking whole entities keeps UTF-8 valid")
+}
+
+fn entity(message: &str, caps: &Captures<'_>, kind: EntityKind) -> Entity {
+    let whole = caps.get(0).unwrap();
+    Entity {
+        kind,
+        text: message[whole.start()..whole.end()].to_string(),
+        start: whole.start(),
+        end: whole.end(),
+    }
+}
+
+// Earlier-starting entities win; ties go to the longer match
+fn resolve_overlaps(mut candidates: Vec<Entity>) -> Vec<Entity> {
+    candidates.sort_by(|a, b| a.start.cmp(&b.start).then((b.end - b.start).cmp(&(a.end - a.start))));
+
+    let mut entities: Vec<Entity> = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        if entities.last().map(|last| candidate.start < last.end).unwrap_or(false) {
+            continue;
+        }
+        entities.push(candidate);
+    }
+    entities
+}
+
+fn assignment_kind(word: &str) -> AssignmentKind {
+    match word.to_lowercase().as_str() {
+        "hw" | "homework" => AssignmentKind::Homework,
+        "quiz" => AssignmentKind::Quiz,
+        "exam" | "midterm" | "final" => AssignmentKind::Exam,
+        "lab" => AssignmentKind::Lab,
+        "project" => AssignmentKind::Project,
+        _ => AssignmentKind::Lecture,
+    }
+}
+
+fn month_day(month: &str, day: &str) -> Option<(u32, u32)> {
+    let (month, day) = (month.parse::<u32>().ok()?, day.parse::<u32>().ok()?);
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((month, day))
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    let month = match &name.to_lowercase()[..3] {
+        "jan" => 1,
+        "feb" => 2,
+        "mar" => 3,
+        "apr" => 4,
+        "may" => 5,
+        "jun" => 6,
+        "jul" => 7,
+        "aug" => 8,
+        "sep" => 9,
+        "oct" => 10,
+        "nov" => 11,
+        "dec" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+// Converts captured hour/minute/meridiem to 24h time
+fn clock(hour: Option<regex::Match<'_>>, minute: Option<regex::Match<'_>>, meridiem: Option<regex::Match<'_>>) -> (Option<u32>, Option<u32>) {
+    let Some(hour) = hour.and_then(|m| m.as_str().parse::<u32>().ok()) else {
+        return (None, None);
+    };
+    let minute = minute.and_then(|m| m.as_str().parse::<u32>().ok()).unwrap_or(0);
+    if hour > 23 || minute > 59 {
+        return (None, None);
+    }
+
+    let hour = match meridiem.map(|m| m.as_str().to_lowercase()) {
+        Some(ref meridiem) if meridiem == "pm" && hour < 12 => hour + 12,
+        Some(ref meridiem) if meridiem == "am" && hour == 12 => 0,
+        _ => hour,
+    };
+    (Some(hour), Some(minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(message: &str) -> Vec<&'static str> {
+        extract_entities(message).iter().map(Entity::label).collect()
+    }
+
+    #[test]
+    fn spans_are_byte_offsets_into_the_original_text() {
+        let message = "café 🎉 ¿CS 101 mañana? see https://example.org/ñ/notes.";
+        let entities = extract_entities(message);
+        assert!(!entities.is_empty());
+        for entity in &entities {
+            assert_eq!(&message[entity.start..entity.end], entity.text);
+        }
+
+        let course = entities.iter().find(|entity| entity.label() == "course_code").unwrap();
+        assert_eq!((course.start, course.text.as_str()), (message.find("CS 101").unwrap(), "CS 101"));
+        let url = entities.iter().find(|entity| entity.label() == "url").unwrap();
+        assert_eq!(url.text, "https://example.org/ñ/notes");
+    }
+
+    #[test]
+    fn nothing_inside_code_is_extracted() {
+        let message = "```py\n# quiz 3, see <@123456789012345678> and CS 101\nprint('5 ms')\n``` then `HW 2` and quiz 4";
+        let entities = extract_entities(message);
+        assert_eq!(kinds(message), ["code_block", "inline_code", "assignment"]);
+        assert_eq!(entities[0].kind, EntityKind::CodeBlock {
+            language: Some("py".to_string()),
+            code: "# quiz 3, see <@123456789012345678> and CS 101\nprint('5 ms')\n".to_string(),
+        });
+        assert_eq!(entities[1].kind, EntityKind::InlineCode { code: "HW 2".to_string() });
+        assert_eq!(entities[2].kind, EntityKind::Assignment { kind: AssignmentKind::Quiz, number: 4 });
+    }
+
+    #[test]
+    fn the_wider_relative_time_absorbs_its_clock_time() {
+        let entities = extract_entities("quiz tomorrow at 5pm");
+        assert_eq!(entities.len(), 1, "{entities:?}");
+        assert_eq!(entities[0].text, "tomorrow at 5pm");
+        assert!(matches!(entities[0].kind, EntityKind::RelativeTime { days_from_now: Some(1), hour: Some(17), minute: Some(0), .. }));
+    }
+
+    #[test]
+    fn overlaps_keep_the_earliest_then_the_longest() {
+        let candidate = |start: usize, end: usize| Entity {
+            kind: EntityKind::Url,
+            text: String::new(),
+            start,
+            end,
+        };
+        let kept = resolve_overlaps(vec![candidate(4, 6), candidate(2, 5), candidate(0, 3), candidate(0, 2), candidate(6, 8), candidate(7, 9)]);
+        let spans: Vec<(usize, usize)> = kept.iter().map(|entity| (entity.start, entity.end)).collect();
+        assert_eq!(spans, [(0, 3), (4, 6), (6, 8)]);
+    }
+}
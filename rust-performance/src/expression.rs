@@ -0,0 +1,701 @@
+/*!
+ * gunnchAI3k Performance Engine - Expression Evaluation
+ * Parses and evaluates student math answers: arithmetic, functions and variables
+ */
+
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+/// Longest expression accepted, in characters
+const MAX_LENGTH: usize = 500;
+/// Deepest nesting accepted, so hostile input can't exhaust the stack
+const MAX_DEPTH: usize = 64;
+/// Most work one evaluation may do, counted in nodes plus loop iterations,
+/// so a graded answer can never hold up the store for long
+const MAX_EVAL_STEPS: usize = 20_000;
+/// Largest `k` for which `choose` multiplies exactly; beyond it uses ln Γ
+const CHOOSE_EXACT_LIMIT: f64 = 1000.0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ExpressionError {
+    #[error("could not read the expression: {0}")]
+    Syntax(String),
+    #[error("unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("{0} takes {1} argument(s)")]
+    Arity(String, usize),
+    #[error("the expression is too long or too deeply nested")]
+    TooComplex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Log2,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Abs,
+    Floor,
+    Ceil,
+    Erf,
+    Erfc,
+    Q,
+    Phi,
+    Choose,
+    Min,
+    Max,
+}
+
+impl Function {
+    /// Names are case-sensitive so `Q(x)` never swallows a variable `q`
+    fn lookup(name: &str) -> Option<Self> {
+        Some(match name {
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" | "log" => Function::Ln,
+            "log10" => Function::Log10,
+            "log2" => Function::Log2,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "asin" | "arcsin" => Function::Asin,
+            "acos" | "arccos" => Function::Acos,
+            "atan" | "arctan" => Function::Atan,
+            "sinh" => Function::Sinh,
+            "cosh" => Function::Cosh,
+            "tanh" => Function::Tanh,
+            "abs" => Function::Abs,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "erf" => Function::Erf,
+            "erfc" => Function::Erfc,
+            "Q" => Function::Q,
+            "Phi" | "Φ" => Function::Phi,
+            "choose" | "C" | "nCr" | "binom" => Function::Choose,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Choose | Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+
+    /// Evaluation steps a call takes, for the `MAX_EVAL_STEPS` budget
+    fn cost(&self, args: &[f64]) -> usize {
+        match self {
+            Function::Choose => 1 + choose_steps(args[0], args[1]),
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        let x = args[0];
+        match self {
+            Function::Sqrt => x.sqrt(),
+            Function::Exp => x.exp(),
+            Function::Ln => x.ln(),
+            Function::Log10 => x.log10(),
+            Function::Log2 => x.log2(),
+            Function::Sin => x.sin(),
+            Function::Cos => x.cos(),
+            Function::Tan => x.tan(),
+            Function::Asin => x.asin(),
+            Function::Acos => x.acos(),
+            Function::Atan => x.atan(),
+            Function::Sinh => x.sinh(),
+            Function::Cosh => x.cosh(),
+            Function::Tanh => x.tanh(),
+            Function::Abs => x.abs(),
+            Function::Floor => x.floor(),
+            Function::Ceil => x.ceil(),
+            Function::Erf => 1.0 - erfc(x),
+            Function::Erfc => erfc(x),
+            Function::Q => q_function(x),
+            Function::Phi => normal_cdf(x),
+            Function::Choose => choose(x, args[1]),
+            Function::Min => x.min(args[1]),
+            Function::Max => x.max(args[1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Factorial(Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+/// A parsed expression such as `n*p*(1-p)` or `Q((x - mu)/sigma)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Accepts `^` or `**` for powers, postfix `!`, implicit multiplication
+    /// (`2pi`, `3(x+1)`), `pi`/`π` and `e`. `log` is the natural logarithm.
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        Self::parse_with_variables(source, &[])
+    }
+
+    /// Like `parse`, but reads a run of known variable names written together
+    /// as their product, so `np(1-p)` means `n*p*(1-p)` when n and p are known
+    pub fn parse_with_variables(source: &str, variables: &[&str]) -> Result<Self, ExpressionError> {
+        if source.chars().count() > MAX_LENGTH {
+            return Err(ExpressionError::TooComplex);
+        }
+        let mut tokens = tokenize(source)?;
+        if !variables.is_empty() {
+            tokens = tokens
+                .into_iter()
+                .flat_map(|token| match token {
+                    Token::Name(name) if Function::lookup(&name).is_none() && !variables.contains(&name.as_str()) => {
+                        match split_names(&name, variables) {
+                            Some(parts) => parts.into_iter().map(Token::Name).collect(),
+                            None => vec![Token::Name(name)],
+                        }
+                    }
+                    token => vec![token],
+                })
+                .collect();
+        }
+        if tokens.is_empty() {
+            return Err(ExpressionError::Syntax("it is empty".to_string()));
+        }
+        let mut parser = Parser { tokens, position: 0, depth: 0 };
+        let root = parser.expression()?;
+        match parser.peek() {
+            None => Ok(Self { root }),
+            Some(token) => Err(ExpressionError::Syntax(format!("unexpected {}", describe(token)))),
+        }
+    }
+
+    /// Names the expression reads, other than the constants `pi` and `e`
+    pub fn variables(&self) -> BTreeSet<String> {
+        fn walk(node: &Node, names: &mut BTreeSet<String>) {
+            match node {
+                Node::Number(_) => {}
+                Node::Variable(name) => {
+                    if constant(name).is_none() {
+                        names.insert(name.clone());
+                    }
+                }
+                Node::Negate(inner) | Node::Factorial(inner) => walk(inner, names),
+                Node::Binary(_, left, right) => {
+                    walk(left, names);
+                    walk(right, names);
+                }
+                Node::Call(_, args) => args.iter().for_each(|arg| walk(arg, names)),
+            }
+        }
+        let mut names = BTreeSet::new();
+        walk(&self.root, &mut names);
+        names
+    }
+
+    /// Evaluates with the given variables; they take precedence over `pi` and `e`
+    pub fn eval(&self, variables: &HashMap<String, f64>) -> Result<f64, ExpressionError> {
+        let mut steps = 0;
+        eval(&self.root, variables, &mut steps)
+    }
+
+    /// Evaluates an expression with no variables, e.g. a numeric answer like `3/8`
+    pub fn constant(&self) -> Result<f64, ExpressionError> {
+        self.eval(&HashMap::new())
+    }
+}
+
+fn charge(steps: &mut usize, cost: usize) -> Result<(), ExpressionError> {
+    *steps += cost;
+    if *steps > MAX_EVAL_STEPS {
+        return Err(ExpressionError::TooComplex);
+    }
+    Ok(())
+}
+
+fn eval(node: &Node, variables: &HashMap<String, f64>, steps: &mut usize) -> Result<f64, ExpressionError> {
+    charge(steps, 1)?;
+    Ok(match node {
+        Node::Number(value) => *value,
+        Node::Variable(name) => match variables.get(name) {
+            Some(value) => *value,
+            None => constant(name).ok_or_else(|| ExpressionError::UnknownVariable(name.clone()))?,
+        },
+        Node::Negate(inner) => -eval(inner, variables, steps)?,
+        Node::Factorial(inner) => {
+            let n = eval(inner, variables, steps)?;
+            // The exact product runs up to 170 times
+            charge(steps, n.clamp(0.0, 170.0) as usize)?;
+            factorial(n)
+        }
+        Node::Binary(op, left, right) => {
+            let (left, right) = (eval(left, variables, steps)?, eval(right, variables, steps)?);
+            match op {
+                '+' => left + right,
+                '-' => left - right,
+                '*' => left * right,
+                '/' => left / right,
+                _ => left.powf(right),
+            }
+        }
+        Node::Call(function, args) => {
+            let args = args.iter().map(|arg| eval(arg, variables, steps)).collect::<Result<Vec<_>, _>>()?;
+            charge(steps, function.cost(&args))?;
+            function.apply(&args)
+        }
+    })
+}
+
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" | "π" => Some(std::f64::consts::PI),
+        "e" => Some(std::f64::consts::E),
+        _ => None,
+    }
+}
+
+/// Splits `name` into known variables and constants, if it is made only of them
+fn split_names(name: &str, variables: &[&str]) -> Option<Vec<String>> {
+    if name.is_empty() {
+        return Some(Vec::new());
+    }
+    // Longest names first, so `mu` wins over `m` followed by `u`
+    let mut candidates: Vec<&str> = variables.iter().copied().chain(["pi", "e"]).collect();
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.len()));
+    candidates.into_iter().find_map(|candidate| {
+        let rest = name.strip_prefix(candidate).filter(|_| !candidate.is_empty())?;
+        let mut parts = split_names(rest, variables)?;
+        parts.insert(0, candidate.to_string());
+        Some(parts)
+    })
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExpressionError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent only if digits follow, so `2e` stays 2 times e
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text
+                .parse()
+                .map_err(|_| ExpressionError::Syntax(format!("'{}' is not a number", text)))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(chars[start..i].iter().collect()));
+        } else {
+            let symbol = match c {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 1;
+                    '^'
+                }
+                '×' | '·' | '⋅' => '*',
+                '÷' => '/',
+                '−' | '–' => '-',
+                '[' | '{' => '(',
+                ']' | '}' => ')',
+                '√' => {
+                    tokens.push(Token::Name("sqrt".to_string()));
+                    i += 1;
+                    continue;
+                }
+                '+' | '-' | '*' | '/' | '^' | '(' | ')' | ',' | '!' => c,
+                _ => return Err(ExpressionError::Syntax(format!("unexpected '{}'", c))),
+            };
+            tokens.push(Token::Symbol(symbol));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => format!("number {}", value),
+        Token::Name(name) => format!("'{}'", name),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ExpressionError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(ExpressionError::Syntax(format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn descend(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExpressionError::TooComplex);
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self) -> Result<Node, ExpressionError> {
+        self.descend()?;
+        let mut node = self.term()?;
+        while let Some(Token::Symbol(op @ ('+' | '-'))) = self.peek() {
+            let op = *op;
+            self.position += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.term()?));
+        }
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn term(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('*' | '/'))) => {
+                    let op = *op;
+                    self.position += 1;
+                    op
+                }
+                // Implicit multiplication: 2x, 3(x+1), x y
+                Some(Token::Number(_) | Token::Name(_) | Token::Symbol('(')) => '*',
+                _ => break,
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        self.descend()?;
+        let node = if self.eat('-') {
+            Node::Negate(Box::new(self.unary()?))
+        } else if self.eat('+') {
+            self.unary()?
+        } else {
+            self.power()?
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.postfix()?;
+        if self.eat('^') {
+            // Right-associative, and allows 2^-1
+            return Ok(Node::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.primary()?;
+        while self.eat('!') {
+            node = Node::Factorial(Box::new(node));
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Symbol('(')) => {
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) => {
+                let Some(function) = Function::lookup(&name) else {
+                    return Ok(Node::Variable(name));
+                };
+                let args = if self.eat('(') {
+                    let mut args = vec![self.expression()?];
+                    while self.eat(',') {
+                        args.push(self.expression()?);
+                    }
+                    self.expect(')')?;
+                    args
+                } else {
+                    // sqrt 2, sin x
+                    self.descend()?;
+                    let arg = self.power()?;
+                    self.depth -= 1;
+                    vec![arg]
+                };
+                if args.len() != function.arity() {
+                    return Err(ExpressionError::Arity(name, function.arity()));
+                }
+                Ok(Node::Call(function, args))
+            }
+            Some(token) => Err(ExpressionError::Syntax(format!("unexpected {}", describe(&token)))),
+            None => Err(ExpressionError::Syntax("it ends too soon".to_string())),
+        }
+    }
+}
+
+/// Complementary error function, accurate to about 1e-15 relative
+pub fn erfc(x: f64) -> f64 {
+    if x.is_nan() {
+        return f64::NAN;
+    }
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    if x < 2.5 {
+        // erf(x) = 2/sqrt(pi) e^(-x^2) sum x^(2n+1) 2^n / (1*3*...*(2n+1)); all terms positive
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 0.0;
+        while term > sum * 1e-17 {
+            n += 1.0;
+            term *= 2.0 * x * x / (2.0 * n + 1.0);
+            sum += term;
+        }
+        return 1.0 - 2.0 / std::f64::consts::PI.sqrt() * (-x * x).exp() * sum;
+    }
+    // Continued fraction x + (1/2)/(x + 1/(x + (3/2)/(x + ...))), by modified Lentz
+    let tiny = 1e-300;
+    let mut f = x;
+    let (mut c, mut d) = (x, 0.0);
+    for n in 1..500 {
+        let a = f64::from(n) / 2.0;
+        d = x + a * d;
+        d = if d.abs() < tiny { 1.0 / tiny } else { 1.0 / d };
+        c = x + a / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        let delta = c * d;
+        f *= delta;
+        if (delta - 1.0).abs() < 1e-16 {
+            break;
+        }
+    }
+    (-x * x).exp() / (std::f64::consts::PI.sqrt() * f)
+}
+
+/// Standard normal tail probability P(Z > x)
+pub fn q_function(x: f64) -> f64 {
+    0.5 * erfc(x / std::f64::consts::SQRT_2)
+}
+
+/// Standard normal CDF P(Z <= x)
+pub fn normal_cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// ln Γ(x) for x > 0 (Lanczos, g = 7)
+pub fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).abs().ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// n! exactly for small whole numbers, Γ(n + 1) otherwise
+pub fn factorial(n: f64) -> f64 {
+    if n < 0.0 && n.fract() == 0.0 {
+        return f64::NAN;
+    }
+    if n.fract() == 0.0 && n <= 170.0 {
+        return (1..=n as u32).fold(1.0, |product, k| product * f64::from(k));
+    }
+    let gamma = ln_gamma(n + 1.0).exp();
+    if n < 0.0 && (n.floor() as i64) % 2 != 0 {
+        // Γ is negative on (-1, 0), (-3, -2), ...
+        -gamma
+    } else {
+        gamma
+    }
+}
+
+/// Iterations of the exact product in `choose`, 0 when it takes the ln Γ path
+fn choose_steps(n: f64, k: f64) -> usize {
+    if n.fract() != 0.0 || k.fract() != 0.0 || k < 0.0 || k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    if k <= CHOOSE_EXACT_LIMIT {
+        k as usize
+    } else {
+        0
+    }
+}
+
+/// Binomial coefficient; exact for whole numbers with a small `k`, via ln Γ
+/// otherwise so the work never grows with the arguments
+pub fn choose(n: f64, k: f64) -> f64 {
+    if n.fract() == 0.0 && k.fract() == 0.0 {
+        if k < 0.0 || k > n {
+            return 0.0;
+        }
+        let k = k.min(n - k);
+        if k <= CHOOSE_EXACT_LIMIT {
+            return (1..=k as u64).fold(1.0, |product, i| product * (n - k + i as f64) / i as f64).round();
+        }
+        return (ln_gamma(n + 1.0) - ln_gamma(k + 1.0) - ln_gamma(n - k + 1.0)).exp().round();
+    }
+    (ln_gamma(n + 1.0) - ln_gamma(k + 1.0) - ln_gamma(n - k + 1.0)).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn choose_with_huge_arguments_returns_quickly() {
+        let start = Instant::now();
+        assert!(Expression::parse("choose(4e9, 2e9)").unwrap().constant().unwrap().is_infinite());
+        assert!(Expression::parse("choose(1e300, 5e299)").unwrap().constant().unwrap().is_infinite());
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        assert_eq!(choose(52.0, 5.0), 2_598_960.0);
+        assert_eq!(choose(1e9, 2.0), 499_999_999_500_000_000.0);
+        let large = choose(3000.0, 1500.0);
+        assert!(large.is_infinite() || large > 1e300);
+    }
+
+    #[test]
+    fn many_exact_products_exceed_the_evaluation_budget() {
+        let source = vec!["C(2e3,1e3)"; 30].join("+");
+        assert!(source.len() <= MAX_LENGTH);
+        let start = Instant::now();
+        assert_eq!(Expression::parse(&source).unwrap().constant(), Err(ExpressionError::TooComplex));
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn nesting_and_length_are_limited() {
+        let deep = format!("{}1{}", "(".repeat(MAX_DEPTH + 1), ")".repeat(MAX_DEPTH + 1));
+        assert_eq!(Expression::parse(&deep), Err(ExpressionError::TooComplex));
+        let shallow = format!("{}1{}", "(".repeat(10), ")".repeat(10));
+        assert_eq!(Expression::parse(&shallow).unwrap().constant(), Ok(1.0));
+
+        assert_eq!(Expression::parse(&"-".repeat(MAX_DEPTH + 1)), Err(ExpressionError::TooComplex));
+        assert_eq!(Expression::parse(&"1+".repeat(MAX_LENGTH)), Err(ExpressionError::TooComplex));
+    }
+
+    #[test]
+    fn parses_implicit_products_and_powers() {
+        let value = |source: &str| Expression::parse(source).unwrap().constant().unwrap();
+        assert_eq!(value("2^3^2"), 512.0);
+        assert_eq!(value("2**-1"), 0.5);
+        assert_eq!(value("3(1+1)"), 6.0);
+        assert_eq!(value("5!"), 120.0);
+        assert!((value("2pi") - 2.0 * std::f64::consts::PI).abs() < 1e-12);
+        assert!(matches!(Expression::parse("sqrt(1, 2)"), Err(ExpressionError::Arity(_, 1))));
+    }
+
+    #[test]
+    fn splits_runs_of_known_variables() {
+        let expression = Expression::parse_with_variables("np(1-p)", &["n", "p"]).unwrap();
+        let variables = HashMap::from([("n".to_string(), 10.0), ("p".to_string(), 0.3)]);
+        assert!((expression.eval(&variables).unwrap() - 2.1).abs() < 1e-12);
+        assert!(matches!(
+            Expression::parse("np(1-p)").unwrap().eval(&variables),
+            Err(ExpressionError::UnknownVariable(name)) if name == "np"
+        ));
+    }
+
+    #[test]
+    fn special_functions_match_reference_values() {
+        assert!((q_function(1.96) - 0.024_997_895_148_220_4).abs() < 1e-12);
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-15);
+        assert!((erfc(3.0) - 2.209_049_699_858_544e-5).abs() < 1e-17);
+        assert!((ln_gamma(10.0) - 362_880f64.ln()).abs() < 1e-12);
+        assert_eq!(factorial(10.0), 3_628_800.0);
+    }
+}
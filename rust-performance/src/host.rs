@@ -0,0 +1,275 @@
+/*!
+ * gunnchAI3k Performance Engine - Host Metrics
+ * Samples process and system usage from /proc and derives message rates from analytics
+ */
+
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tracing::warn;
+use anyhow::{Context, Result};
+
+use crate::{AnalyticsEngine, PerformanceMetrics};
+
+/// Process and system usage read from /proc at one point in time
+#[derive(Debug, Clone, Default)]
+pub struct HostSample {
+    /// CPU used by this process, in percent of one core
+    pub process_cpu: f64,
+    /// Busy share of all cores, 0..100
+    pub system_cpu: f64,
+    pub rss_bytes: u64,
+    pub memory_total_bytes: u64,
+    pub open_fds: u64,
+    pub threads: u64,
+}
+
+impl HostSample {
+    /// RSS as a percentage of physical memory
+    pub fn memory_percent(&self) -> f64 {
+        if self.memory_total_bytes == 0 {
+            0.0
+        } else {
+            self.rss_bytes as f64 / self.memory_total_bytes as f64 * 100.0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CpuTimes {
+    process: u64,
+    total: u64,
+    idle: u64,
+    cores: usize,
+}
+
+/// Reads /proc; CPU percentages are computed from the change since the previous read
+#[derive(Debug, Default)]
+pub struct ProcReader {
+    previous: Option<CpuTimes>,
+}
+
+impl ProcReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails on systems without /proc. The first sample reports 0% CPU.
+    pub fn sample(&mut self) -> Result<HostSample> {
+        let times = read_cpu_times()?;
+        let (process_cpu, system_cpu) = match self.previous {
+            Some(previous) if times.total > previous.total => {
+                let total = (times.total - previous.total) as f64;
+                let idle = times.idle.saturating_sub(previous.idle) as f64;
+                let process = times.process.saturating_sub(previous.process) as f64;
+                // Jiffies per core over the interval, so one busy core reads as 100%
+                let per_core = total / times.cores.max(1) as f64;
+                (process / per_core * 100.0, (1.0 - idle / total).clamp(0.0, 1.0) * 100.0)
+            }
+            _ => (0.0, 0.0),
+        };
+        self.previous = Some(times);
+
+        let status = std::fs::read_to_string("/proc/self/status").context("failed to read /proc/self/status")?;
+        let meminfo = std::fs::read_to_string("/proc/meminfo").context("failed to read /proc/meminfo")?;
+        let open_fds = std::fs::read_dir("/proc/self/fd")
+            .context("failed to read /proc/self/fd")?
+            .count() as u64;
+
+        Ok(HostSample {
+            process_cpu,
+            system_cpu,
+            rss_bytes: status_field(&status, "VmRSS:") * 1024,
+            memory_total_bytes: status_field(&meminfo, "MemTotal:") * 1024,
+            open_fds,
+            threads: status_field(&status, "Threads:"),
+        })
+    }
+}
+
+fn read_cpu_times() -> Result<CpuTimes> {
+    let stat = std::fs::read_to_string("/proc/self/stat").context("failed to read /proc/self/stat")?;
+    let system = std::fs::read_to_string("/proc/stat").context("failed to read /proc/stat")?;
+    Ok(cpu_times(&stat, &system))
+}
+
+/// Combines `/proc/self/stat` and `/proc/stat` contents
+fn cpu_times(process_stat: &str, system_stat: &str) -> CpuTimes {
+    // The command name may contain spaces and ')', so count fields after its last closing paren
+    let fields: Vec<u64> = process_stat
+        .rsplit_once(')')
+        .map(|(_, rest)| rest)
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|field| field.parse().unwrap_or(0))
+        .collect();
+    let utime = fields.get(11).copied().unwrap_or(0);
+    let stime = fields.get(12).copied().unwrap_or(0);
+
+    let mut total = 0;
+    let mut idle = 0;
+    let mut cores = 0;
+    for line in system_stat.lines() {
+        if let Some(rest) = line.strip_prefix("cpu ") {
+            let values: Vec<u64> = rest.split_whitespace().map(|value| value.parse().unwrap_or(0)).collect();
+            // user nice system idle iowait irq softirq steal; guest time is already in user
+            total = values.iter().take(8).sum();
+            idle = values.get(3).copied().unwrap_or(0) + values.get(4).copied().unwrap_or(0);
+        } else if line.starts_with("cpu") {
+            cores += 1;
+        }
+    }
+
+    CpuTimes {
+        process: utime + stime,
+        total,
+        idle,
+        cores,
+    }
+}
+
+/// Leading number of a "Key:   1234 kB" style /proc line
+fn status_field(contents: &str, key: &str) -> u64 {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(key))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct AnalyticsCounters {
+    messages: u64,
+    errors: u64,
+}
+
+/// Builds full `PerformanceMetrics` from /proc, the tokio runtime and, when
+/// attached, the message processor's analytics
+pub struct MetricsSampler {
+    proc: ProcReader,
+    analytics: Option<Arc<Mutex<AnalyticsEngine>>>,
+    previous: Option<(Instant, AnalyticsCounters)>,
+    host_unavailable: bool,
+}
+
+impl MetricsSampler {
+    pub fn new(analytics: Option<Arc<Mutex<AnalyticsEngine>>>) -> Self {
+        Self {
+            proc: ProcReader::new(),
+            analytics,
+            previous: None,
+            host_unavailable: false,
+        }
+    }
+
+    /// Takes a sample. Rates cover the time since the previous call; host
+    /// fields stay zero where /proc is unavailable.
+    pub async fn sample(&mut self) -> PerformanceMetrics {
+        let mut metrics = PerformanceMetrics::default();
+
+        match self.proc.sample() {
+            Ok(host) => {
+                metrics.cpu_usage = host.process_cpu;
+                metrics.system_cpu_usage = host.system_cpu;
+                metrics.memory_usage = host.memory_percent();
+                metrics.rss_bytes = host.rss_bytes;
+                metrics.open_fds = host.open_fds;
+                metrics.threads = host.threads;
+            }
+            Err(err) => {
+                if !self.host_unavailable {
+                    warn!("Host metrics unavailable: {:#}", err);
+                    self.host_unavailable = true;
+                }
+            }
+        }
+
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let runtime = handle.metrics();
+            metrics.tokio_workers = runtime.num_workers() as u64;
+            metrics.tokio_alive_tasks = runtime.num_alive_tasks() as u64;
+            metrics.tokio_global_queue_depth = runtime.global_queue_depth() as u64;
+        }
+
+        if let Some(analytics) = &self.analytics {
+            let analytics = analytics.lock().await;
+            let now = Instant::now();
+            let counters = AnalyticsCounters {
+                messages: analytics.message_count,
+                errors: analytics.error_count,
+            };
+
+            if let Some((at, previous)) = self.previous {
+                let elapsed = now.duration_since(at).as_secs_f64();
+                let messages = counters.messages.saturating_sub(previous.messages);
+                let errors = counters.errors.saturating_sub(previous.errors);
+                if elapsed > 0.0 {
+                    metrics.message_processing_rate = messages as f64 / elapsed;
+                }
+                if messages + errors > 0 {
+                    metrics.error_rate = errors as f64 / (messages + errors) as f64 * 100.0;
+                }
+            }
+            metrics.average_response_time = analytics.windows.last_minute().latency.mean;
+            metrics.cache_hit_rate = analytics.get_cache_hit_rate();
+            self.previous = Some((now, counters));
+        }
+
+        metrics
+    }
+}
+
+/// A running background sampler; stops when shut down or dropped
+pub struct SamplerHandle {
+    pub(crate) task: tokio::task::JoinHandle<()>,
+}
+
+impl SamplerHandle {
+    pub fn shutdown(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for SamplerHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM_STAT: &str = "cpu  100 5 50 800 20 3 2 0 40 0\ncpu0 50 2 25 400 10 1 1 0 20 0\ncpu1 50 3 25 400 10 2 1 0 20 0\nintr 12345\nctxt 678\n";
+
+    #[test]
+    fn process_times_are_counted_after_the_last_paren() {
+        // A name with spaces and ')' would shift every field if split naively
+        let stat = "4242 (my bot) (v2)) S 1 4242 4242 0 -1 4194560 1500 0 3 0 731 214 0 0 20 0 9 0 8812 123456789 2048 18446744073709551615";
+        let times = cpu_times(stat, SYSTEM_STAT);
+        assert_eq!(times.process, 731 + 214);
+    }
+
+    #[test]
+    fn system_times_sum_the_aggregate_line_and_count_cores() {
+        let times = cpu_times("1 (bot) S 1 1 1 0 -1 0 0 0 0 0 7 3", SYSTEM_STAT);
+        assert_eq!(times.process, 10);
+        // Guest time (the last two columns) is already part of user time
+        assert_eq!((times.total, times.idle, times.cores), (980, 820, 2));
+    }
+
+    #[test]
+    fn truncated_input_reads_as_zero() {
+        let times = cpu_times("1 (bot", "");
+        assert_eq!((times.process, times.total, times.idle, times.cores), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn status_fields_take_the_leading_number() {
+        let status = "Name:\tbot\nVmRSS:\t   20480 kB\nThreads:\t9\n";
+        assert_eq!(status_field(status, "VmRSS:"), 20_480);
+        assert_eq!(status_field(status, "Threads:"), 9);
+        assert_eq!(status_field(status, "VmSwap:"), 0);
+    }
+}
@@ -0,0 +1,360 @@
+/*!
+ * gunnchAI3k Performance Engine - Intent Classification
+ * Multi-label intent scores from weighted rules and an optional statistical model
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntentScore {
+    pub intent: String,
+    pub confidence: f64,
+}
+
+/// Keywords that vote for an intent. Alphanumeric keywords match whole words
+/// (or word sequences); anything else, like "?", matches as a substring.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentRule {
+    pub intent: String,
+    pub keywords: Vec<String>,
+    #[serde(default = "default_rule_weight")]
+    pub weight: f64,
+}
+
+fn default_rule_weight() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentRuleSet {
+    pub rules: Vec<IntentRule>,
+    /// Intents below this confidence are dropped from the result
+    #[serde(default = "default_min_confidence")]
+    pub min_confidence: f64,
+    /// Reported when nothing clears `min_confidence`
+    #[serde(default = "default_fallback_intent")]
+    pub fallback_intent: String,
+}
+
+fn default_min_confidence() -> f64 {
+    0.3
+}
+
+fn default_fallback_intent() -> String {
+    "general".to_string()
+}
+
+impl IntentRuleSet {
+    pub fn from_json(raw: &str) -> Result<Self> {
+        serde_json::from_str(raw).context("invalid intent rule set")
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read intent rules {}", path.display()))?;
+        Self::from_json(&raw)
+    }
+
+    /// Confidence per intent: each matched keyword adds `weight`, and the
+    /// total is squashed with 1 - e^-x so one strong hit is ~0.63.
+    fn score(&self, message: &str) -> HashMap<String, f64> {
+        let lower = message.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let mut raw: HashMap<String, f64> = HashMap::new();
+        for rule in &self.rules {
+            let hits = rule
+                .keywords
+                .iter()
+                .filter(|keyword| keyword_matches(&lower, &words, keyword))
+                .count();
+            if hits > 0 {
+                *raw.entry(rule.intent.clone()).or_insert(0.0) += hits as f64 * rule.weight;
+            }
+        }
+
+        raw.into_iter()
+            .map(|(intent, score)| (intent, 1.0 - (-score).exp()))
+            .collect()
+    }
+}
+
+impl Default for IntentRuleSet {
+    fn default() -> Self {
+        let rule = |intent: &str, keywords: &[&str]| IntentRule {
+            intent: intent.to_string(),
+            keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+            weight: 1.0,
+        };
+
+        Self {
+            rules: vec![
+                rule("help", &["help", "?", "how do", "explain", "stuck", "confused", "what is"]),
+                rule("music", &["play", "music", "song", "playlist", "lofi", "queue", "skip", "listen"]),
+                rule("study", &["study", "learn", "quiz", "flashcard", "flashcards", "exam", "homework", "hw", "practice", "midterm", "review"]),
+                rule("entertainment", &["joke", "funny", "game", "meme", "bored"]),
+            ],
+            min_confidence: default_min_confidence(),
+            fallback_intent: default_fallback_intent(),
+        }
+    }
+}
+
+fn keyword_matches(lower: &str, words: &[&str], keyword: &str) -> bool {
+    let keyword = keyword.to_lowercase();
+    if !keyword.chars().all(|c| c.is_alphanumeric() || c.is_whitespace() || c == '\'') {
+        return lower.contains(&keyword);
+    }
+
+    let parts: Vec<&str> = keyword.split_whitespace().collect();
+    !parts.is_empty() && words.windows(parts.len()).any(|window| window == parts.as_slice())
+}
+
+/// A statistical intent model. Scores should be in [0, 1].
+pub trait IntentModel: Send + Sync {
+    fn predict(&self, message: &str) -> Vec<IntentScore>;
+}
+
+/// Multinomial naive Bayes over lowercase word counts with Laplace smoothing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NaiveBayesIntentModel {
+    doc_counts: HashMap<String, u32>,
+    word_counts: HashMap<String, HashMap<String, u32>>,
+    total_words: HashMap<String, u32>,
+    vocabulary: HashSet<String>,
+}
+
+impl NaiveBayesIntentModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn train(&mut self, message: &str, intent: &str) {
+        *self.doc_counts.entry(intent.to_string()).or_insert(0) += 1;
+        let counts = self.word_counts.entry(intent.to_string()).or_default();
+        for word in tokenize(message) {
+            *counts.entry(word.clone()).or_insert(0) += 1;
+            *self.total_words.entry(intent.to_string()).or_insert(0) += 1;
+            self.vocabulary.insert(word);
+        }
+    }
+
+    /// Trains from `(message, intent)` pairs
+    pub fn from_examples<'a>(examples: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut model = Self::new();
+        for (message, intent) in examples {
+            model.train(message, intent);
+        }
+        model
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self> {
+        serde_json::from_str(raw).context("invalid intent model")
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+impl IntentModel for NaiveBayesIntentModel {
+    fn predict(&self, message: &str) -> Vec<IntentScore> {
+        let total_docs: u32 = self.doc_counts.values().sum();
+        if total_docs == 0 {
+            return Vec::new();
+        }
+
+        let words = tokenize(message);
+        let vocabulary_size = self.vocabulary.len().max(1) as f64;
+        let log_posteriors: Vec<(String, f64)> = self
+            .doc_counts
+            .iter()
+            .map(|(intent, docs)| {
+                let counts = self.word_counts.get(intent);
+                let total = *self.total_words.get(intent).unwrap_or(&0) as f64;
+                let mut log_p = (*docs as f64 / total_docs as f64).ln();
+                for word in &words {
+                    let count = counts.and_then(|counts| counts.get(word)).copied().unwrap_or(0) as f64;
+                    log_p += ((count + 1.0) / (total + vocabulary_size)).ln();
+                }
+                (intent.clone(), log_p)
+            })
+            .collect();
+
+        // Softmax in log space for numerical stability
+        let max = log_posteriors.iter().map(|(_, log_p)| *log_p).fold(f64::NEG_INFINITY, f64::max);
+        let normalizer: f64 = log_posteriors.iter().map(|(_, log_p)| (log_p - max).exp()).sum();
+        log_posteriors
+            .into_iter()
+            .map(|(intent, log_p)| IntentScore {
+                intent,
+                confidence: (log_p - max).exp() / normalizer,
+            })
+            .collect()
+    }
+}
+
+fn tokenize(message: &str) -> Vec<String> {
+    message
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Blends rule scores with an optional model into a ranked intent list
+#[derive(Clone)]
+pub struct IntentClassifier {
+    rules: IntentRuleSet,
+    model: Option<Arc<dyn IntentModel>>,
+    /// Share of the final score taken from the model, 0..1
+    model_weight: f64,
+}
+
+impl IntentClassifier {
+    pub fn new(rules: IntentRuleSet) -> Self {
+        Self {
+            rules,
+            model: None,
+            model_weight: 0.0,
+        }
+    }
+
+    pub fn with_model(mut self, model: Arc<dyn IntentModel>, model_weight: f64) -> Self {
+        self.model = Some(model);
+        self.model_weight = model_weight.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn fallback_intent(&self) -> &str {
+        &self.rules.fallback_intent
+    }
+
+    /// Returns intents sorted by confidence, highest first. Never empty: falls
+    /// back to the rule set's fallback intent.
+    pub fn classify(&self, message: &str) -> Vec<IntentScore> {
+        let mut combined: HashMap<String, f64> = HashMap::new();
+        let rule_weight = if self.model.is_some() { 1.0 - self.model_weight } else { 1.0 };
+
+        for (intent, confidence) in self.rules.score(message) {
+            *combined.entry(intent).or_insert(0.0) += confidence * rule_weight;
+        }
+        if let Some(model) = &self.model {
+            for score in model.predict(message) {
+                *combined.entry(score.intent).or_insert(0.0) += score.confidence * self.model_weight;
+            }
+        }
+
+        let mut intents: Vec<IntentScore> = combined
+            .into_iter()
+            .filter(|(_, confidence)| *confidence >= self.rules.min_confidence)
+            .map(|(intent, confidence)| IntentScore { intent, confidence })
+            .collect();
+        intents.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then_with(|| a.intent.cmp(&b.intent)));
+
+        if intents.is_empty() {
+            intents.push(IntentScore {
+                intent: self.rules.fallback_intent.clone(),
+                confidence: 1.0,
+            });
+        }
+        intents
+    }
+}
+
+impl Default for IntentClassifier {
+    fn default() -> Self {
+        Self::new(IntentRuleSet::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Always predicts the same scores
+    struct FixedModel(Vec<(&'static str, f64)>);
+
+    impl IntentModel for FixedModel {
+        fn predict(&self, _message: &str) -> Vec<IntentScore> {
+            self.0
+                .iter()
+                .map(|(intent, confidence)| IntentScore { intent: intent.to_string(), confidence: *confidence })
+                .collect()
+        }
+    }
+
+    fn ranked(scores: &[IntentScore]) -> Vec<(&str, f64)> {
+        scores.iter().map(|score| (score.intent.as_str(), (score.confidence * 1e4).round() / 1e4)).collect()
+    }
+
+    #[test]
+    fn every_intent_that_clears_the_threshold_is_reported() {
+        let classifier = IntentClassifier::default();
+        let two_hits = 1.0 - (-2.0f64).exp();
+        let scores = classifier.classify("can you help me study for the exam?");
+        assert_eq!(ranked(&scores), [("help", 0.8647), ("study", 0.8647)]);
+        assert!((scores[0].confidence - two_hits).abs() < 1e-12);
+
+        // Ranked by confidence: three study keywords beat one help keyword
+        let scores = classifier.classify("quiz and flashcards for the midterm, explain pls");
+        assert_eq!(ranked(&scores), [("study", 0.9502), ("help", 0.6321)]);
+    }
+
+    #[test]
+    fn word_keywords_only_match_whole_words() {
+        let classifier = IntentClassifier::default();
+        assert_eq!(ranked(&classifier.classify("that was helpful, shows how to display")), [("general", 1.0)]);
+        assert_eq!(ranked(&classifier.classify("how do I")), [("help", 0.6321)]);
+    }
+
+    #[test]
+    fn model_scores_blend_in_by_weight() {
+        let model = Arc::new(FixedModel(vec![("study", 1.0), ("music", 0.0)]));
+        let classifier = IntentClassifier::default().with_model(model.clone(), 0.4);
+        // music: 0.8647 * 0.6 from the rules, study: 1.0 * 0.4 from the model
+        assert_eq!(ranked(&classifier.classify("play some music")), [("music", 0.5188), ("study", 0.4)]);
+
+        let model_only = IntentClassifier::default().with_model(model, 7.0);
+        assert_eq!(ranked(&model_only.classify("play some music")), [("study", 1.0)]);
+    }
+
+    #[test]
+    fn falls_back_when_nothing_is_confident() {
+        let rules = IntentRuleSet::from_json(r#"{"rules": [{"intent": "greeting", "keywords": ["hi"], "weight": 0.2}], "fallback_intent": "chat"}"#).unwrap();
+        let classifier = IntentClassifier::new(rules);
+        assert_eq!(classifier.fallback_intent(), "chat");
+        // One weak hit is 1 - e^-0.2 = 0.18, under the default 0.3
+        assert_eq!(ranked(&classifier.classify("hi there")), [("chat", 1.0)]);
+        assert_eq!(ranked(&classifier.classify("hi hi")), [("chat", 1.0)]);
+        assert_eq!(ranked(&IntentClassifier::default().classify("")), [("general", 1.0)]);
+    }
+
+    #[test]
+    fn naive_bayes_prefers_the_intent_its_words_came_from() {
+        let model = NaiveBayesIntentModel::from_examples([
+            ("queue up some lofi beats", "music"),
+            ("skip this track", "music"),
+            ("make me flashcards on mitosis", "study"),
+            ("quiz me on the krebs cycle", "study"),
+        ]);
+        let scores = model.predict("flashcards for the krebs cycle");
+        let total: f64 = scores.iter().map(|score| score.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        let best = scores.iter().max_by(|a, b| a.confidence.total_cmp(&b.confidence)).unwrap();
+        assert_eq!(best.intent, "study");
+
+        let restored = NaiveBayesIntentModel::from_json(&model.to_json().unwrap()).unwrap();
+        assert_eq!(restored.predict("skip the lofi").len(), 2);
+        assert!(NaiveBayesIntentModel::new().predict("anything").is_empty());
+    }
+}
@@ -1,276 +1,661 @@
-/**
+/*!
  * gunnchAI3k Performance Engine - Rust Core
  * High-performance backend for gunnchAI3k Discord bot
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use tracing::{info, warn, error, debug};
+
+pub mod pipeline;
+pub mod analyzers;
+pub mod sentiment;
+pub mod entities;
+pub mod intent;
+pub mod cache;
+pub mod rate_limit;
+pub mod metrics;
+pub mod prometheus;
+pub mod host;
+pub mod alerts;
+pub mod notify;
+pub mod scheduler;
+pub mod conversation;
+pub mod templates;
+pub mod spam;
+pub mod safety;
+pub mod storage;
+pub mod review;
+pub mod sessions;
+pub mod progress;
+pub mod expression;
+pub mod answers;
+pub mod problems;
+pub mod decks;
+
+pub use pipeline::{AnalysisInput, AnalyzerOutput, AnalyzerPipeline, MessageAnalysis, MessageAnalyzer, StageReport, StageStatus};
+pub use sentiment::{SentimentLexicon, SentimentScores};
+pub use entities::{AssignmentKind, Entity, EntityKind};
+pub use intent::{IntentClassifier, IntentModel, IntentRule, IntentRuleSet, IntentScore, NaiveBayesIntentModel};
+pub use cache::{CacheConfig, CacheRemoval, MessageCache};
+pub use rate_limit::{CommandCost, CostTable, Quota, RateLimitAlgorithm, RateLimitConfig, RateLimitDenied, RateLimitRequest, RateLimitScope, RateLimiter, ResourceClass, TierLimits};
+pub use alerts::{AlertEvent, AlertManager, AlertMetric, AlertRule, AlertRuleSet, Comparator, RuleFileWatcher};
+pub use conversation::{ConversationContext, ConversationKey, ConversationMemory, ConversationSummarizer, Coreference, MemoryConfig, TopicSummarizer, Turn};
+pub use templates::{IntentTemplates, ResponseCatalog, ResponseRequest, ResponseTemplates, Tone, VariantSelection};
+pub use storage::{AttemptUpdate, InMemoryStudyStore, ProgressUpdate, ResultUpdate, ReviewUpdate, SqliteStudyStore, StudyStore};
+pub use sessions::{AttemptResult, GradedQuestion, ServedQuestion, SessionError, SessionOptions, TestAttempt};
+pub use progress::{ReviewSuggestion, TopicMastery, WeeklyStats};
+pub use expression::{Expression, ExpressionError};
+pub use answers::{AnswerCheck, AnswerFormat, AnswerSpec, PartFormat, QuestionPart, Response, Tolerance, VariableRange};
+pub use problems::{Difficulty, GeneratedProblem, Problem, ProblemGenerator, ProblemRng, ProblemTemplate, MAX_PROBLEMS};
+pub use decks::{Column, CsvMapping, ExportReport, ImportOptions, ImportReport, RowError};
+pub use review::{CardState, DueCard, Grade, ReviewAlgorithm, ReviewConfig, ReviewLogEntry, ReviewOutcome, ReviewScheduler};
+pub use safety::{LogEscalation, SafetyCategory, SafetyClassifier, SafetyConfig, SafetyEscalation, SafetyIncident, SafetyLexicon, SafetyMatch, SafetyScores};
+pub use spam::{SpamConfig, SpamDetector, SpamSignal, SpamVerdict, SuggestedAction};
+pub use scheduler::{QueueStats, SchedulerConfig, SchedulerError, WorkerPool};
+pub use notify::{AlertDispatcher, AlertSink, DiscordWebhookSink, HttpWebhookSink, LogSink, MemorySink, RetryPolicy};
+pub use host::{HostSample, MetricsSampler, ProcReader, SamplerHandle};
+pub use prometheus::{MetricsServer, PrometheusExporter};
+pub use metrics::{LatencyHistogram, LatencySummary, RollingWindows, WindowSummary};
+use analyzers::{message_priority, EntityAnalyzer, IntentAnalyzer, PriorityAnalyzer, SafetyAnalyzer, SentimentAnalyzer};
 
 /// High-performance message processing engine
 pub struct MessageProcessor {
-    cache: Arc<RwLock<HashMap<String, CachedMessage>>>,
+    cache: Arc<Mutex<MessageCache>>,
     rate_limiter: Arc<Mutex<RateLimiter>>,
     analytics: Arc<Mutex<AnalyticsEngine>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CachedMessage {
-    pub id: String,
-    pub content: String,
-    pub author_id: String,
-    pub channel_id: String,
-    pub timestamp: u64,
-    pub processed: bool,
-    pub metadata: MessageMetadata,
+    pipeline: Arc<RwLock<AnalyzerPipeline>>,
+    memory: Arc<Mutex<ConversationMemory>>,
+    templates: Arc<RwLock<ResponseTemplates>>,
+    spam: Arc<Mutex<SpamDetector>>,
+    safety: Arc<RwLock<SafetyClassifier>>,
+    intents: Arc<RwLock<IntentClassifier>>,
+    escalations: Arc<RwLock<Vec<Arc<dyn SafetyEscalation>>>>,
+    /// One summarisation at a time per conversation, so concurrent messages
+    /// can't each fold into the same old summary and overwrite one another
+    summarizing: Arc<parking_lot::Mutex<HashMap<ConversationKey, Arc<Mutex<()>>>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageMetadata {
     pub sentiment: f64,
+    pub sentiment_scores: SentimentScores,
     pub intent: String,
-    pub entities: Vec<String>,
+    pub intents: Vec<IntentScore>,
+    pub entities: Vec<Entity>,
     pub priority: u8,
     pub processing_time: Duration,
+    #[serde(default)]
+    pub safety: SafetyScores,
 }
 
-#[derive(Debug, Clone)]
-pub struct RateLimiter {
-    limits: HashMap<String, RateLimit>,
-    global_limit: u32,
-    window_size: Duration,
+/// Where a message came from; guild and roles feed channel/guild limits and role tiers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageContext {
+    pub author_id: String,
+    pub channel_id: String,
+    pub guild_id: Option<String>,
+    /// Thread inside `channel_id`; threads get their own conversation memory
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    pub roles: Vec<String>,
+    /// Reply language, e.g. "es" or "es-MX"; the default catalog when unset
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct RateLimit {
-    pub count: u32,
-    pub reset_time: Instant,
-    pub max_requests: u32,
+impl MessageContext {
+    pub fn new(author_id: &str, channel_id: &str) -> Self {
+        Self {
+            author_id: author_id.to_string(),
+            channel_id: channel_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn in_guild(mut self, guild_id: &str) -> Self {
+        self.guild_id = Some(guild_id.to_string());
+        self
+    }
+
+    pub fn in_thread(mut self, thread_id: &str) -> Self {
+        self.thread_id = Some(thread_id.to_string());
+        self
+    }
+
+    pub fn with_locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    pub fn conversation_key(&self) -> ConversationKey {
+        ConversationKey::new(&self.channel_id, self.thread_id.as_deref())
+    }
+
+    pub fn with_roles(mut self, roles: Vec<String>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    fn rate_limit_request(&self) -> RateLimitRequest<'_> {
+        RateLimitRequest {
+            user_id: &self.author_id,
+            channel_id: Some(&self.channel_id),
+            guild_id: self.guild_id.as_deref(),
+            roles: &self.roles,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct AnalyticsEngine {
     pub message_count: u64,
-    pub processing_times: Vec<Duration>,
+    pub processing_times: LatencyHistogram,
+    /// Failed `process_message` calls, excluding rate-limit denials
     pub error_count: u64,
+    pub rate_limited_count: u64,
     pub cache_hits: u64,
     pub cache_misses: u64,
+    pub cache_evictions: u64,
+    pub cache_expirations: u64,
+    pub windows: RollingWindows,
+    pub by_intent: HashMap<String, IntentMetrics>,
+    pub by_guild: HashMap<String, GuildMetrics>,
+}
+
+/// Per-intent message counts and latency, keyed by the top intent
+#[derive(Debug, Clone, Default)]
+pub struct IntentMetrics {
+    pub messages: u64,
+    pub cache_hits: u64,
+    pub latency: LatencyHistogram,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GuildMetrics {
+    pub messages: u64,
+    pub errors: u64,
+    pub rate_limited: u64,
 }
 
 impl MessageProcessor {
     pub fn new() -> Self {
+        Self::with_pipeline(Self::default_pipeline())
+    }
+
+    /// Stages named "safety" and "intent" are swapped for ones sharing the
+    /// processor's classifiers, so `configure_safety` and `configure_intents`
+    /// reach them and worker-pool triage agrees with the pipeline
+    pub fn with_pipeline(mut pipeline: AnalyzerPipeline) -> Self {
+        let safety = Arc::new(RwLock::new(SafetyClassifier::default()));
+        pipeline
+            .replace_stage("safety", Arc::new(SafetyAnalyzer::shared(safety.clone())))
+            .expect("the safety stage has no dependencies");
+        let intents = Arc::new(RwLock::new(IntentClassifier::default()));
+        pipeline
+            .replace_stage("intent", Arc::new(IntentAnalyzer::shared(intents.clone())))
+            .expect("the intent stage has no dependencies");
         Self {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(Mutex::new(MessageCache::default())),
             rate_limiter: Arc::new(Mutex::new(RateLimiter::new())),
             analytics: Arc::new(Mutex::new(AnalyticsEngine::new())),
+            pipeline: Arc::new(RwLock::new(pipeline)),
+            memory: Arc::new(Mutex::new(ConversationMemory::default())),
+            templates: Arc::new(RwLock::new(ResponseTemplates::default())),
+            spam: Arc::new(Mutex::new(SpamDetector::default())),
+            safety,
+            intents,
+            escalations: Arc::new(RwLock::new(vec![Arc::new(LogEscalation) as Arc<dyn SafetyEscalation>])),
+            summarizing: Arc::new(parking_lot::Mutex::new(HashMap::new())),
         }
     }
 
+    /// The built-in stages: sentiment, safety, intent and entities run concurrently, priority after intent
+    pub fn default_pipeline() -> AnalyzerPipeline {
+        AnalyzerPipeline::new()
+            .with_stage(Arc::new(SentimentAnalyzer::new()))
+            .with_stage(Arc::new(SafetyAnalyzer::new()))
+            .with_stage(Arc::new(IntentAnalyzer::new()))
+            .with_stage(Arc::new(EntityAnalyzer))
+            .with_stage(Arc::new(PriorityAnalyzer))
+    }
+
+    /// Adds (or replaces) an analyzer stage at the end of the pipeline.
+    /// Fails if it depends on a stage that isn't registered before it.
+    pub async fn register_analyzer(&self, analyzer: Arc<dyn MessageAnalyzer>, timeout: Option<Duration>) -> Result<()> {
+        self.pipeline.write().await.add_stage(analyzer, timeout)
+    }
+
+    /// Gives mutable access to reorder, disable or retime stages
+    pub async fn configure_pipeline<F: FnOnce(&mut AnalyzerPipeline)>(&self, configure: F) {
+        configure(&mut *self.pipeline.write().await);
+    }
+
+    /// Starts a priority-scheduled worker pool in front of this processor.
+    /// Submit through the pool instead of calling `process_message` inline.
+    pub fn start_workers(self: &Arc<Self>, config: SchedulerConfig) -> WorkerPool {
+        WorkerPool::start(self.clone(), config)
+    }
+
     /// Process message with high-performance optimizations
     pub async fn process_message(&self, message: &str, author_id: &str, channel_id: &str) -> Result<ProcessedMessage> {
+        self.process_message_with_context(message, &MessageContext::new(author_id, channel_id)).await
+    }
+
+    /// Like `process_message`, with guild and role information for rate limiting.
+    /// A rate-limit denial is returned as a `RateLimitDenied` inside the error.
+    pub async fn process_message_with_context(&self, message: &str, context: &MessageContext) -> Result<ProcessedMessage> {
+        let result = self.process_message_counted(message, context).await;
+        if let Err(error) = &result {
+            let mut analytics = self.analytics.lock().await;
+            let guild_id = context.guild_id.as_deref();
+            if error.downcast_ref::<RateLimitDenied>().is_some() {
+                analytics.record_rate_limited(guild_id);
+            } else {
+                analytics.record_error(guild_id);
+            }
+        }
+        result
+    }
+
+    async fn process_message_counted(&self, message: &str, context: &MessageContext) -> Result<ProcessedMessage> {
         let start_time = Instant::now();
+        let author_id = context.author_id.as_str();
+        let channel_id = context.channel_id.as_str();
+
+        // Spam detection sees every message, even ones the rate limiter will refuse
+        let spam = self.check_spam(message, context).await;
         
         // Check rate limiting
-        if !self.check_rate_limit(author_id).await {
-            return Err(anyhow::anyhow!("Rate limit exceeded"));
-        }
-
-        // Check cache first
-        let cache_key = format!("{}:{}", author_id, message);
-        if let Some(cached) = self.get_from_cache(&cache_key).await {
-            self.update_analytics(true, start_time.elapsed()).await;
+        self.check_rate_limit(context).await?;
+
+        let conversation_key = context.conversation_key();
+        let conversation = self.memory.lock().await.context(&conversation_key, message);
+
+        // Check cache first; a reply that depends on earlier turns, or that is
+        // meant to vary between deliveries, is never reused
+        let cache_key = MessageCache::key(author_id, channel_id, context.locale.as_deref(), message);
+        let random_replies = self.templates.read().await.selection() == VariantSelection::Random;
+        let cacheable = conversation.referent.is_none() && !random_replies;
+        let cached = if cacheable { self.get_from_cache(&cache_key).await } else { None };
+        if let Some(mut cached) = cached {
+            // Each delivery gets its own id; everything else is the original reply
+            cached.id = uuid::Uuid::new_v4().to_string();
+            cached.processing_time = start_time.elapsed();
+            cached.from_cache = true;
+            // A reused reply still costs what generating it would have
+            self.charge_intents(context, &cached.intents).await?;
+            self.escalate_if_needed(context, &cached).await;
+            // A repeated message always hits the cache, so spam is held back here too
+            if spam.is_spam() {
+                cached.response.clear();
+                cached.spam = spam;
+                self.update_analytics(context, true, &cached.intent, start_time.elapsed()).await;
+                return Ok(cached);
+            }
+            cached.spam = spam;
+            self.update_analytics(context, true, &cached.intent, start_time.elapsed()).await;
+            self.remember(conversation_key, &cached).await;
             return Ok(cached);
         }
 
         // Process message
-        let processed = self.process_message_internal(message, author_id, channel_id).await?;
+        let mut processed = self.process_message_internal(message, context, &conversation).await?;
+        processed.processing_time = start_time.elapsed();
+        self.escalate_if_needed(context, &processed).await;
+
+        // Spam gets no reply and stays out of the cache and conversation memory
+        if spam.is_spam() {
+            processed.response.clear();
+            processed.spam = spam;
+            self.update_analytics(context, false, &processed.intent, start_time.elapsed()).await;
+            return Ok(processed);
+        }
+        processed.spam = spam;
         
         // Cache result
-        self.cache_result(&cache_key, &processed).await;
+        if cacheable {
+            self.cache_result(&cache_key, &processed).await;
+        }
+        self.remember(conversation_key, &processed).await;
         
         // Update analytics
-        self.update_analytics(false, start_time.elapsed()).await;
+        self.update_analytics(context, false, &processed.intent, start_time.elapsed()).await;
         
         Ok(processed)
     }
 
-    async fn process_message_internal(&self, message: &str, author_id: &str, channel_id: &str) -> Result<ProcessedMessage> {
+    async fn process_message_internal(&self, message: &str, context: &MessageContext, conversation: &ConversationContext) -> Result<ProcessedMessage> {
+        let author_id = context.author_id.as_str();
+        let channel_id = context.channel_id.as_str();
+
         // High-performance message analysis
-        let sentiment = self.analyze_sentiment(message).await?;
-        let intent = self.extract_intent(message).await?;
-        let entities = self.extract_entities(message).await?;
-        let priority = self.calculate_priority(message, &intent).await?;
+        let guild_id = context.guild_id.as_deref();
+        let input = AnalysisInput { message, author_id, channel_id, guild_id, conversation };
+        let pipeline = self.pipeline.read().await.clone();
+        let (analysis, stages) = pipeline.run(&input).await;
+
+        // Expensive intents draw from their resource budgets before any reply work
+        self.charge_intents(context, &analysis.intents).await?;
+
+        let response = self.generate_response(message, context, &analysis, conversation).await?;
 
         Ok(ProcessedMessage {
             id: uuid::Uuid::new_v4().to_string(),
             content: message.to_string(),
             author_id: author_id.to_string(),
             channel_id: channel_id.to_string(),
-            sentiment,
-            intent,
-            entities,
-            priority,
+            sentiment: analysis.sentiment,
+            sentiment_scores: analysis.sentiment_scores,
+            intent: analysis.intent,
+            intents: analysis.intents,
+            entities: analysis.entities,
+            priority: analysis.priority,
+            safety: analysis.safety,
             processing_time: Duration::from_millis(0), // Will be set by caller
-            response: self.generate_response(message, &intent, &entities).await?,
+            response,
+            stages,
+            from_cache: false,
+            referent: conversation.referent.as_ref().map(|reference| reference.entity.clone()),
+            spam: SpamVerdict::clean(),
         })
     }
 
-    async fn analyze_sentiment(&self, message: &str) -> Result<f64> {
-        // High-performance sentiment analysis using parallel processing
-        let words: Vec<&str> = message.split_whitespace().collect();
-        let sentiment_scores: Vec<f64> = words.par_iter()
-            .map(|word| self.get_word_sentiment(word))
+    async fn generate_response(&self, message: &str, context: &MessageContext, analysis: &MessageAnalysis, conversation: &ConversationContext) -> Result<String> {
+        let templates = self.templates.read().await;
+        let referent = conversation.referent.as_ref().map(|reference| &reference.entity);
+        let render = |intent: &str| {
+            let request = ResponseRequest {
+                intent,
+                locale: context.locale.as_deref(),
+                author_id: &context.author_id,
+                sentiment: &analysis.sentiment_scores,
+                entities: &analysis.entities,
+                referent,
+                seed: message,
+            };
+            templates
+                .render(&request)
+                .unwrap_or_else(|| Self::intent_response(intent).to_string())
+        };
+
+        // Self-harm language always gets the support reply, whatever else was asked
+        if analysis.safety.escalate {
+            return Ok(render("self_harm_support"));
+        }
+
+        // Answer every confident intent, best first
+        let mut parts: Vec<String> = analysis
+            .intents
+            .iter()
+            .filter(|score| score.confidence >= 0.5)
+            .take(2)
+            .map(|score| render(&score.intent))
             .collect();
-        
-        let avg_sentiment = sentiment_scores.iter().sum::<f64>() / sentiment_scores.len() as f64;
-        Ok(avg_sentiment)
-    }
+        parts.dedup();
 
-    fn get_word_sentiment(&self, word: &str) -> f64 {
-        // Simplified sentiment analysis - in production, use a proper NLP library
-        let positive_words = ["good", "great", "awesome", "amazing", "love", "like", "happy", "excited"];
-        let negative_words = ["bad", "terrible", "awful", "hate", "angry", "sad", "disappointed"];
-        
-        let word_lower = word.to_lowercase();
-        
-        if positive_words.contains(&word_lower.as_str()) {
-            1.0
-        } else if negative_words.contains(&word_lower.as_str()) {
-            -1.0
-        } else {
-            0.0
+        if parts.is_empty() {
+            let top = analysis.intents.first().map(|score| score.intent.as_str()).unwrap_or("general");
+            parts.push(render(top));
         }
-    }
 
-    async fn extract_intent(&self, message: &str) -> Result<String> {
-        // High-performance intent extraction
-        let message_lower = message.to_lowercase();
-        
-        if message_lower.contains("help") || message_lower.contains("?") {
-            Ok("help".to_string())
-        } else if message_lower.contains("play") || message_lower.contains("music") {
-            Ok("music".to_string())
-        } else if message_lower.contains("study") || message_lower.contains("learn") {
-            Ok("study".to_string())
-        } else if message_lower.contains("joke") || message_lower.contains("funny") {
-            Ok("entertainment".to_string())
-        } else {
-            Ok("general".to_string())
+        let response = parts.join(" ");
+        match referent {
+            Some(entity) => Ok(templates.with_referent(context.locale.as_deref(), entity, &response)),
+            None => Ok(response),
         }
     }
 
-    async fn extract_entities(&self, message: &str) -> Result<Vec<String>> {
-        // High-performance entity extraction
-        let mut entities = Vec::new();
-        let words: Vec<&str> = message.split_whitespace().collect();
-        
-        for word in words {
-            if word.len() > 3 && word.chars().all(|c| c.is_alphabetic()) {
-                entities.push(word.to_string());
-            }
+    /// Last-resort English reply when no catalog covers an intent
+    fn intent_response(intent: &str) -> &'static str {
+        match intent {
+            "help" => "I'm here to help! What do you need assistance with?",
+            "music" => "Let me help you with music! What would you like to play?",
+            "study" => "Time to study! I can help you with flashcards, practice tests, and more!",
+            "entertainment" => "Let's have some fun! I can tell jokes, play games, or help with music!",
+            "self_harm_support" => "I'm really sorry you're feeling this way. You don't have to go through it alone: please reach out to someone you trust, or a crisis line such as 988 (US) or your local emergency number.",
+            _ => "I'm here and ready to help! What can I do for you?",
         }
-        
-        Ok(entities)
     }
 
-    async fn calculate_priority(&self, message: &str, intent: &str) -> Result<u8> {
-        // Priority calculation based on content and intent
-        let mut priority = 5; // Default priority
-        
-        if intent == "help" {
-            priority = 8;
-        } else if intent == "music" {
-            priority = 6;
-        } else if intent == "study" {
-            priority = 9;
-        }
-        
-        if message.contains("@") {
-            priority += 2;
+    /// Loads reply catalogs (`*.json`) from a directory over the built-in ones.
+    /// Cached replies were rendered from the old catalogs, so the cache is cleared.
+    pub async fn load_response_templates(&self, dir: impl AsRef<std::path::Path>) -> Result<Vec<String>> {
+        let loaded = self.templates.write().await.load_dir(dir);
+        self.clear_cache().await;
+        loaded
+    }
+
+    /// Also clears the cache, since cached replies used the old templates
+    pub async fn configure_templates<F: FnOnce(&mut ResponseTemplates)>(&self, configure: F) {
+        configure(&mut *self.templates.write().await);
+        self.clear_cache().await;
+    }
+
+    async fn check_rate_limit(&self, context: &MessageContext) -> Result<(), RateLimitDenied> {
+        let mut limiter = self.rate_limiter.lock().await;
+        limiter.check(&context.rate_limit_request())
+    }
+
+    /// Charges resource budgets for every confident intent before a reply is generated
+    async fn charge_intents(&self, context: &MessageContext, intents: &[IntentScore]) -> Result<(), RateLimitDenied> {
+        let mut limiter = self.rate_limiter.lock().await;
+        let confident = intents
+            .iter()
+            .filter(|score| score.confidence >= 0.5)
+            .map(|score| score.intent.as_str());
+        limiter.charge_intents(&context.rate_limit_request(), confident)
+    }
+
+    /// Charges an explicit command (e.g. "playlist", "practice_test") against the
+    /// caller's budget before the JS side runs it
+    pub async fn charge_command(&self, context: &MessageContext, command: &str) -> Result<(), RateLimitDenied> {
+        let mut limiter = self.rate_limiter.lock().await;
+        limiter.charge_command(&context.rate_limit_request(), command)
+    }
+
+    pub async fn configure_rate_limits(&self, config: RateLimitConfig) {
+        self.rate_limiter.lock().await.set_config(config);
+    }
+
+    /// Scores a message for floods, raids and character abuse and records it
+    /// as recent activity. `process_message` already does this; call it
+    /// directly for messages the bot does not otherwise process.
+    pub async fn check_spam(&self, message: &str, context: &MessageContext) -> SpamVerdict {
+        self.spam
+            .lock()
+            .await
+            .check(context.guild_id.as_deref(), &context.author_id, message)
+    }
+
+    /// Feeds member joins in for raid and join-and-spam detection
+    pub async fn record_member_join(&self, guild_id: &str, user_id: &str) {
+        self.spam.lock().await.record_join(guild_id, user_id);
+    }
+
+    pub async fn configure_spam(&self, config: SpamConfig) {
+        self.spam.lock().await.set_config(config);
+    }
+
+    /// Changes wordlists, allowlists or thresholds of the built-in safety stage
+    pub async fn configure_safety<F: FnOnce(&mut SafetyClassifier)>(&self, configure: F) {
+        configure(&mut *self.safety.write().await);
+    }
+
+    /// Changes the rules, model or model weight of the built-in intent stage
+    pub async fn configure_intents<F: FnOnce(&mut IntentClassifier)>(&self, configure: F) {
+        configure(&mut *self.intents.write().await);
+    }
+
+    /// Priority from an intent pass alone, without running the pipeline; the
+    /// worker pool queues messages at this priority
+    pub async fn triage_priority(&self, message: &str) -> u8 {
+        message_priority(message, &self.intents.read().await.classify(message))
+    }
+
+    /// Adds a hook that runs for every message with self-harm language
+    pub async fn add_safety_escalation(&self, hook: Arc<dyn SafetyEscalation>) {
+        self.escalations.write().await.push(hook);
+    }
+
+    /// Removes every hook with this name, returning whether any was removed
+    pub async fn remove_safety_escalation(&self, name: &str) -> bool {
+        let mut escalations = self.escalations.write().await;
+        let before = escalations.len();
+        escalations.retain(|hook| hook.name() != name);
+        escalations.len() != before
+    }
+
+    /// Runs escalation hooks in the background so a slow hook never delays the reply
+    async fn escalate_if_needed(&self, context: &MessageContext, processed: &ProcessedMessage) {
+        if !processed.safety.escalate {
+            return;
         }
-        
-        if message.contains("urgent") || message.contains("emergency") {
-            priority = 10;
+        let hooks = self.escalations.read().await.clone();
+        if hooks.is_empty() {
+            return;
         }
-        
-        Ok(priority.min(10))
+        let incident = SafetyIncident {
+            message_id: processed.id.clone(),
+            author_id: context.author_id.clone(),
+            channel_id: context.channel_id.clone(),
+            guild_id: context.guild_id.clone(),
+            content: processed.content.clone(),
+            scores: processed.safety.clone(),
+        };
+        tokio::spawn(async move {
+            for hook in hooks {
+                if let Err(err) = hook.escalate(&incident).await {
+                    tracing::warn!("Safety escalation '{}' failed: {:#}", hook.name(), err);
+                }
+            }
+        });
     }
 
-    async fn generate_response(&self, message: &str, intent: &str, entities: &[String]) -> Result<String> {
-        // High-performance response generation
-        match intent {
-            "help" => Ok("I'm here to help! What do you need assistance with?".to_string()),
-            "music" => Ok("Let me help you with music! What would you like to play?".to_string()),
-            "study" => Ok("Time to study! I can help you with flashcards, practice tests, and more!".to_string()),
-            "entertainment" => Ok("Let's have some fun! I can tell jokes, play games, or help with music!".to_string()),
-            _ => Ok("I'm here and ready to help! What can I do for you?".to_string()),
+    /// Adds a turn to its conversation and folds anything trimmed into the summary
+    async fn remember(&self, key: ConversationKey, processed: &ProcessedMessage) {
+        let (evicted, summarizer) = {
+            let mut memory = self.memory.lock().await;
+            let evicted = memory.record(key.clone(), Turn::from_processed(processed));
+            (evicted, memory.summarizer())
+        };
+        let Some(summarizer) = summarizer.filter(|_| !evicted.is_empty()) else {
+            return;
+        };
+
+        // The previous summary is read only once this conversation's gate is
+        // held, so each summarisation builds on the one before it
+        let gate = self.summarizing.lock().entry(key.clone()).or_default().clone();
+        {
+            let _summarizing = gate.lock().await;
+            let previous = self.memory.lock().await.summary(&key);
+            let summary = summarizer.summarize(previous.as_deref(), &evicted).await;
+            self.memory.lock().await.set_summary(&key, summary);
+        }
+        drop(gate);
+        let mut summarizing = self.summarizing.lock();
+        if summarizing.get(&key).is_some_and(|gate| Arc::strong_count(gate) == 1) {
+            summarizing.remove(&key);
         }
     }
 
-    async fn check_rate_limit(&self, author_id: &str) -> bool {
-        let mut limiter = self.rate_limiter.lock().await;
-        limiter.check_limit(author_id)
+    /// Recent turns for a channel or thread, e.g. to build an LLM prompt
+    pub async fn conversation(&self, channel_id: &str, thread_id: Option<&str>) -> ConversationContext {
+        self.memory.lock().await.context(&ConversationKey::new(channel_id, thread_id), "")
+    }
+
+    pub async fn clear_conversation(&self, channel_id: &str, thread_id: Option<&str>) -> bool {
+        self.memory.lock().await.clear(&ConversationKey::new(channel_id, thread_id))
+    }
+
+    pub async fn configure_memory(&self, config: MemoryConfig) {
+        self.memory.lock().await.set_config(config);
+    }
+
+    /// Replaces the hook that condenses trimmed turns; `None` disables summaries
+    pub async fn set_conversation_summarizer(&self, summarizer: Option<Arc<dyn ConversationSummarizer>>) {
+        self.memory.lock().await.set_summarizer(summarizer);
+    }
+
+    /// Forgets idle conversations; call periodically alongside `purge_expired_cache`
+    pub async fn purge_idle_conversations(&self) -> usize {
+        self.memory.lock().await.purge_idle()
+    }
+
+    /// Drops spam-tracking state for guilds that have gone quiet
+    pub async fn purge_spam_history(&self) {
+        self.spam.lock().await.collect_garbage();
     }
 
     async fn get_from_cache(&self, key: &str) -> Option<ProcessedMessage> {
-        let cache = self.cache.read().await;
-        cache.get(key).map(|cached| ProcessedMessage {
-            id: cached.id.clone(),
-            content: cached.content.clone(),
-            author_id: cached.author_id.clone(),
-            channel_id: cached.channel_id.clone(),
-            sentiment: cached.metadata.sentiment,
-            intent: cached.metadata.intent.clone(),
-            entities: cached.metadata.entities.clone(),
-            priority: cached.metadata.priority,
-            processing_time: cached.metadata.processing_time,
-            response: "Cached response".to_string(), // Simplified for demo
-        })
+        let lookup = self.cache.lock().await.get(key);
+        match lookup {
+            Ok(hit) => hit,
+            Err(removal) => {
+                self.record_cache_removals(removal, 1).await;
+                None
+            }
+        }
     }
 
     async fn cache_result(&self, key: &str, processed: &ProcessedMessage) {
-        let mut cache = self.cache.write().await;
-        cache.insert(key.to_string(), CachedMessage {
-            id: processed.id.clone(),
-            content: processed.content.clone(),
-            author_id: processed.author_id.clone(),
-            channel_id: processed.channel_id.clone(),
-            timestamp: chrono::Utc::now().timestamp() as u64,
-            processed: true,
-            metadata: MessageMetadata {
-                sentiment: processed.sentiment,
-                intent: processed.intent.clone(),
-                entities: processed.entities.clone(),
-                priority: processed.priority,
-                processing_time: processed.processing_time,
-            },
-        });
+        let evicted = self.cache.lock().await.insert(key.to_string(), processed);
+        if evicted {
+            self.record_cache_removals(CacheRemoval::Evicted, 1).await;
+        }
     }
 
-    async fn update_analytics(&self, cache_hit: bool, processing_time: Duration) {
+    async fn record_cache_removals(&self, removal: CacheRemoval, count: u64) {
+        if count == 0 {
+            return;
+        }
         let mut analytics = self.analytics.lock().await;
-        analytics.message_count += 1;
-        analytics.processing_times.push(processing_time);
-        
-        if cache_hit {
-            analytics.cache_hits += 1;
-        } else {
-            analytics.cache_misses += 1;
+        match removal {
+            CacheRemoval::Evicted => analytics.cache_evictions += count,
+            CacheRemoval::Expired => analytics.cache_expirations += count,
         }
     }
 
+    async fn update_analytics(&self, context: &MessageContext, cache_hit: bool, intent: &str, processing_time: Duration) {
+        let mut analytics = self.analytics.lock().await;
+        analytics.record_message(intent, context.guild_id.as_deref(), cache_hit, processing_time);
+    }
+
     pub async fn get_analytics(&self) -> AnalyticsEngine {
         self.analytics.lock().await.clone()
     }
 
+    /// Shared analytics, for samplers that should not clone the histograms
+    pub fn analytics_handle(&self) -> Arc<Mutex<AnalyticsEngine>> {
+        self.analytics.clone()
+    }
+
     pub async fn clear_cache(&self) {
-        let mut cache = self.cache.write().await;
+        let mut cache = self.cache.lock().await;
         cache.clear();
     }
+
+    /// Changes capacity, TTL or the intents that are never cached
+    pub async fn configure_cache(&self, config: CacheConfig) {
+        let evicted = self.cache.lock().await.set_config(config);
+        self.record_cache_removals(CacheRemoval::Evicted, evicted as u64).await;
+    }
+
+    /// Drops expired entries; call periodically to reclaim memory between hits
+    pub async fn purge_expired_cache(&self) -> usize {
+        let expired = self.cache.lock().await.purge_expired();
+        self.record_cache_removals(CacheRemoval::Expired, expired as u64).await;
+        expired
+    }
+}
+
+impl Default for MessageProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -280,40 +665,37 @@ pub struct ProcessedMessage {
     pub author_id: String,
     pub channel_id: String,
     pub sentiment: f64,
+    pub sentiment_scores: SentimentScores,
     pub intent: String,
-    pub entities: Vec<String>,
+    pub intents: Vec<IntentScore>,
+    pub entities: Vec<Entity>,
     pub priority: u8,
     pub processing_time: Duration,
     pub response: String,
+    pub stages: Vec<StageReport>,
+    #[serde(default)]
+    pub from_cache: bool,
+    /// Earlier entity this message referred to ("it", "the second one")
+    #[serde(default)]
+    pub referent: Option<Entity>,
+    #[serde(default)]
+    pub safety: SafetyScores,
+    /// Spam verdict for the moderation layer; spam gets an empty `response`
+    #[serde(default)]
+    pub spam: SpamVerdict,
 }
 
-impl RateLimiter {
-    pub fn new() -> Self {
-        Self {
-            limits: HashMap::new(),
-            global_limit: 100,
-            window_size: Duration::from_secs(60),
-        }
-    }
-
-    pub fn check_limit(&mut self, user_id: &str) -> bool {
-        let now = Instant::now();
-        let limit = self.limits.entry(user_id.to_string()).or_insert(RateLimit {
-            count: 0,
-            reset_time: now + self.window_size,
-            max_requests: 10,
-        });
-
-        if now > limit.reset_time {
-            limit.count = 0;
-            limit.reset_time = now + self.window_size;
-        }
-
-        if limit.count < limit.max_requests {
-            limit.count += 1;
-            true
-        } else {
-            false
+impl ProcessedMessage {
+    pub fn metadata(&self) -> MessageMetadata {
+        MessageMetadata {
+            sentiment: self.sentiment,
+            sentiment_scores: self.sentiment_scores,
+            intent: self.intent.clone(),
+            intents: self.intents.clone(),
+            entities: self.entities.clone(),
+            priority: self.priority,
+            processing_time: self.processing_time,
+            safety: self.safety.clone(),
         }
     }
 }
@@ -322,19 +704,79 @@ impl AnalyticsEngine {
     pub fn new() -> Self {
         Self {
             message_count: 0,
-            processing_times: Vec::new(),
+            processing_times: LatencyHistogram::new(),
             error_count: 0,
+            rate_limited_count: 0,
             cache_hits: 0,
             cache_misses: 0,
+            cache_evictions: 0,
+            cache_expirations: 0,
+            windows: RollingWindows::new(),
+            by_intent: HashMap::new(),
+            by_guild: HashMap::new(),
         }
     }
 
+    pub fn record_message(&mut self, intent: &str, guild_id: Option<&str>, cache_hit: bool, processing_time: Duration) {
+        self.message_count += 1;
+        if let Some(guild) = self.guild_metrics(guild_id) {
+            guild.messages += 1;
+        }
+        self.processing_times.record(processing_time);
+        self.windows.record_latency(processing_time);
+
+        let per_intent = self.by_intent.entry(intent.to_string()).or_default();
+        per_intent.messages += 1;
+        per_intent.latency.record(processing_time);
+
+        if cache_hit {
+            self.cache_hits += 1;
+            per_intent.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+        }
+    }
+
+    pub fn record_error(&mut self, guild_id: Option<&str>) {
+        self.error_count += 1;
+        self.windows.record_error();
+        if let Some(guild) = self.guild_metrics(guild_id) {
+            guild.errors += 1;
+        }
+    }
+
+    pub fn record_rate_limited(&mut self, guild_id: Option<&str>) {
+        self.rate_limited_count += 1;
+        self.windows.record_rate_limited();
+        if let Some(guild) = self.guild_metrics(guild_id) {
+            guild.rate_limited += 1;
+        }
+    }
+
+    fn guild_metrics(&mut self, guild_id: Option<&str>) -> Option<&mut GuildMetrics> {
+        guild_id.map(|guild_id| self.by_guild.entry(guild_id.to_string()).or_default())
+    }
+
     pub fn get_average_processing_time(&self) -> Duration {
-        if self.processing_times.is_empty() {
-            Duration::from_millis(0)
+        self.processing_times.mean()
+    }
+
+    /// p50/p90/p99/max over every message since startup
+    pub fn latency_summary(&self) -> LatencySummary {
+        self.processing_times.summary()
+    }
+
+    pub fn intent_latency_summary(&self, intent: &str) -> Option<LatencySummary> {
+        self.by_intent.get(intent).map(|metrics| metrics.latency.summary())
+    }
+
+    /// Errors per attempted message; rate-limit denials are not errors
+    pub fn get_error_rate(&self) -> f64 {
+        let total = self.message_count + self.error_count;
+        if total == 0 {
+            0.0
         } else {
-            let total: Duration = self.processing_times.iter().sum();
-            total / self.processing_times.len() as u32
+            self.error_count as f64 / total as f64
         }
     }
 
@@ -348,11 +790,17 @@ impl AnalyticsEngine {
     }
 }
 
+impl Default for AnalyticsEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// High-performance study system
 pub struct StudyEngine {
-    flashcards: Arc<RwLock<HashMap<String, Flashcard>>>,
-    practice_tests: Arc<RwLock<HashMap<String, PracticeTest>>>,
-    user_progress: Arc<RwLock<HashMap<String, UserProgress>>>,
+    store: Arc<dyn StudyStore>,
+    reviews: ReviewScheduler,
+    problems: Arc<ProblemGenerator>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -361,8 +809,12 @@ pub struct Flashcard {
     pub question: String,
     pub answer: String,
     pub subject: String,
+    /// 1 (easy) to 10 (hard); the starting point for review scheduling
     pub difficulty: u8,
     pub created_at: u64,
+    /// Finer than `subject`, e.g. "poisson" within "probability"
+    #[serde(default)]
+    pub topic: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -382,28 +834,90 @@ pub struct Question {
     pub options: Vec<String>,
     pub correct_answer: u8,
     pub explanation: String,
+    /// Finer than the test's subject; untagged questions count toward the subject
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Numeric, symbolic, free-text or multi-part answer; None for multiple choice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<AnswerSpec>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserProgress {
     pub user_id: String,
     pub subject: String,
+    /// Flashcard reviews, counting repeats
     pub flashcards_studied: u32,
     pub tests_completed: u32,
+    /// Mean of every test score
     pub average_score: f64,
+    /// Sample variance of test scores
+    #[serde(default)]
+    pub score_variance: f64,
     pub last_studied: u64,
+    /// Consecutive UTC days with study, ending on the day of `last_studied`
+    #[serde(default)]
+    pub current_streak: u32,
+    #[serde(default)]
+    pub longest_streak: u32,
+    /// Test durations plus estimated flashcard time
+    #[serde(default)]
+    pub time_on_task_secs: u64,
+    #[serde(default)]
+    pub topics: Vec<TopicMastery>,
+    /// Active weeks, oldest first, for the last year at most
+    #[serde(default)]
+    pub weekly: Vec<WeeklyStats>,
 }
 
 impl StudyEngine {
+    /// Keeps everything in memory; use `open` or `with_store` to persist
     pub fn new() -> Self {
+        Self::with_store(Arc::new(InMemoryStudyStore::new()))
+    }
+
+    pub fn with_store(store: Arc<dyn StudyStore>) -> Self {
         Self {
-            flashcards: Arc::new(RwLock::new(HashMap::new())),
-            practice_tests: Arc::new(RwLock::new(HashMap::new())),
-            user_progress: Arc::new(RwLock::new(HashMap::new())),
+            store,
+            reviews: ReviewScheduler::default(),
+            problems: Arc::new(ProblemGenerator::default()),
         }
     }
 
+    /// Picks SM-2 or FSRS and their parameters for future reviews
+    pub fn with_review_config(mut self, config: ReviewConfig) -> Self {
+        self.reviews = ReviewScheduler::new(config);
+        self
+    }
+
+    /// Replaces the built-in problem templates used by `generate_practice_test`
+    pub fn with_problem_generator(mut self, generator: ProblemGenerator) -> Self {
+        self.problems = Arc::new(generator);
+        self
+    }
+
+    /// Persists to a SQLite database file, creating or migrating it as needed
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        Ok(Self::with_store(Arc::new(SqliteStudyStore::open(path)?)))
+    }
+
+    pub fn store(&self) -> Arc<dyn StudyStore> {
+        self.store.clone()
+    }
+
     pub async fn create_flashcard(&self, question: &str, answer: &str, subject: &str, difficulty: u8) -> Result<String> {
+        self.create_flashcard_in_topic(question, answer, subject, None, difficulty).await
+    }
+
+    /// Like `create_flashcard`, tagging the card with a topic for mastery tracking
+    pub async fn create_flashcard_in_topic(
+        &self,
+        question: &str,
+        answer: &str,
+        subject: &str,
+        topic: Option<&str>,
+        difficulty: u8,
+    ) -> Result<String> {
         let id = uuid::Uuid::new_v4().to_string();
         let flashcard = Flashcard {
             id: id.clone(),
@@ -412,20 +926,83 @@ impl StudyEngine {
             subject: subject.to_string(),
             difficulty,
             created_at: chrono::Utc::now().timestamp() as u64,
+            topic: topic.map(str::to_string),
         };
 
-        let mut flashcards = self.flashcards.write().await;
-        flashcards.insert(id.clone(), flashcard);
+        self.store.insert_flashcard(&flashcard).await?;
         
         Ok(id)
     }
 
     pub async fn get_flashcards_by_subject(&self, subject: &str) -> Result<Vec<Flashcard>> {
-        let flashcards = self.flashcards.read().await;
-        Ok(flashcards.values()
-            .filter(|card| card.subject == subject)
-            .cloned()
-            .collect())
+        self.store.flashcards_by_subject(subject).await
+    }
+
+    /// Imports cards from CSV or TSV; see `CsvMapping` for column selection
+    pub async fn import_csv(&self, data: &[u8], mapping: &CsvMapping, options: &ImportOptions) -> Result<ImportReport> {
+        let report = decks::read_csv(data, mapping, options)?;
+        self.store_imported(report).await
+    }
+
+    /// Imports `Q:`/`A:` blocks; `#` and `##` headings set subject and topic
+    pub async fn import_markdown(&self, text: &str, options: &ImportOptions) -> Result<ImportReport> {
+        self.store_imported(decks::read_markdown(text, options)).await
+    }
+
+    /// Imports the notes of an Anki `.apkg`
+    pub async fn import_apkg(&self, data: Vec<u8>, options: ImportOptions) -> Result<ImportReport> {
+        let report = tokio::task::spawn_blocking(move || decks::read_apkg(&data, &options)).await??;
+        self.store_imported(report).await
+    }
+
+    /// The subjects' cards as CSV (or TSV with a tab delimiter), readable by
+    /// `import_csv` with `CsvMapping::new`
+    pub async fn export_csv(&self, subjects: &[&str], delimiter: u8) -> Result<ExportReport> {
+        decks::write_csv(&self.flashcards_in(subjects).await?, delimiter)
+    }
+
+    /// The subjects' cards as an Anki package, a deck per subject
+    pub async fn export_apkg(&self, subjects: &[&str]) -> Result<ExportReport> {
+        let cards = self.flashcards_in(subjects).await?;
+        tokio::task::spawn_blocking(move || decks::write_apkg(&cards)).await?
+    }
+
+    async fn flashcards_in(&self, subjects: &[&str]) -> Result<Vec<Flashcard>> {
+        let mut cards = Vec::new();
+        for subject in subjects {
+            cards.extend(self.store.flashcards_by_subject(subject).await?);
+        }
+        Ok(cards)
+    }
+
+    /// Saves parsed cards in one batch, skipping any whose question and answer
+    /// the subject already has
+    async fn store_imported(&self, parsed: ImportReport) -> Result<ImportReport> {
+        let mut existing: HashMap<String, HashSet<(String, String)>> = HashMap::new();
+        let mut report = ImportReport {
+            errors: parsed.errors,
+            ..ImportReport::default()
+        };
+        for card in parsed.cards {
+            if !existing.contains_key(&card.subject) {
+                let known = self
+                    .store
+                    .flashcards_by_subject(&card.subject)
+                    .await?
+                    .into_iter()
+                    .map(|known| (known.question, known.answer))
+                    .collect();
+                existing.insert(card.subject.clone(), known);
+            }
+            let known = existing.get_mut(&card.subject).expect("subject loaded above");
+            if !known.insert((card.question.clone(), card.answer.clone())) {
+                report.duplicates += 1;
+                continue;
+            }
+            report.cards.push(card);
+        }
+        self.store.insert_flashcards(&report.cards).await?;
+        Ok(report)
     }
 
     pub async fn create_practice_test(&self, title: &str, questions: Vec<Question>, subject: &str, difficulty: u8) -> Result<String> {
@@ -439,36 +1016,280 @@ impl StudyEngine {
             time_limit: 1800, // 30 minutes
         };
 
-        let mut tests = self.practice_tests.write().await;
-        tests.insert(id.clone(), test);
+        self.store.insert_practice_test(&test).await?;
         
         Ok(id)
     }
 
+    /// Stores a test of `count` freshly generated problems on `topics` (template
+    /// names or topics; every template if empty). The same seed gives the same
+    /// questions; without one a random seed is used.
+    pub async fn generate_practice_test(
+        &self,
+        title: &str,
+        subject: &str,
+        topics: &[&str],
+        count: usize,
+        difficulty: Difficulty,
+        seed: Option<u64>,
+    ) -> Result<String> {
+        let seed = seed.unwrap_or_else(|| uuid::Uuid::new_v4().as_u128() as u64);
+        let test = self.problems.practice_test(title, subject, topics, count, difficulty, seed)?;
+        self.store.insert_practice_test(&test).await?;
+
+        Ok(test.id)
+    }
+
     pub async fn get_practice_test(&self, test_id: &str) -> Result<Option<PracticeTest>> {
-        let tests = self.practice_tests.read().await;
-        Ok(tests.get(test_id).cloned())
+        self.store.practice_test(test_id).await
     }
 
     pub async fn update_user_progress(&self, user_id: &str, subject: &str, score: f64) -> Result<()> {
-        let mut progress = self.user_progress.write().await;
-        let key = format!("{}:{}", user_id, subject);
-        
-        let user_progress = progress.entry(key).or_insert(UserProgress {
-            user_id: user_id.to_string(),
-            subject: subject.to_string(),
-            flashcards_studied: 0,
-            tests_completed: 0,
-            average_score: 0.0,
-            last_studied: chrono::Utc::now().timestamp() as u64,
-        });
-
-        user_progress.tests_completed += 1;
-        user_progress.average_score = (user_progress.average_score + score) / 2.0;
-        user_progress.last_studied = chrono::Utc::now().timestamp() as u64;
+        self.store
+            .update_user_progress(
+                user_id,
+                subject,
+                Box::new(move |user_progress| user_progress.record_test_score(score, chrono::Utc::now().timestamp() as u64)),
+            )
+            .await?;
         
         Ok(())
     }
+
+    pub async fn get_user_progress(&self, user_id: &str, subject: &str) -> Result<Option<UserProgress>> {
+        self.store.user_progress(user_id, subject).await
+    }
+
+    /// Every subject the user has studied
+    pub async fn get_all_progress(&self, user_id: &str) -> Result<Vec<UserProgress>> {
+        self.store.progress_for_user(user_id).await
+    }
+
+    /// The last `weeks` weeks of study in a subject, oldest first, idle weeks included
+    pub async fn get_weekly_history(&self, user_id: &str, subject: &str, weeks: usize) -> Result<Vec<WeeklyStats>> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        Ok(self
+            .store
+            .user_progress(user_id, subject)
+            .await?
+            .map(|progress| progress.weekly_history(weeks, now))
+            .unwrap_or_default())
+    }
+
+    /// What to review next: the weakest topics below `MASTERY_TARGET`, each
+    /// with a few due cards to start on
+    pub async fn recommend_review(&self, user_id: &str, subject: Option<&str>, limit: usize) -> Result<Vec<ReviewSuggestion>> {
+        const MASTERY_TARGET: f64 = 0.8;
+        const CARDS_PER_TOPIC: usize = 5;
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let progress = match subject {
+            Some(subject) => self.store.user_progress(user_id, subject).await?.into_iter().collect(),
+            None => self.store.progress_for_user(user_id).await?,
+        };
+        let mut weak: Vec<(String, TopicMastery)> = progress
+            .iter()
+            .flat_map(|progress| {
+                progress
+                    .weak_topics(MASTERY_TARGET, now)
+                    .into_iter()
+                    .map(|mastery| (progress.subject.clone(), mastery))
+            })
+            .collect();
+        weak.sort_by(|(_, a), (_, b)| a.mastery().total_cmp(&b.mastery()));
+        weak.truncate(limit);
+
+        let mut suggestions = Vec::with_capacity(weak.len());
+        for (subject, mastery) in weak {
+            let cards = self
+                .store
+                .due_cards(user_id, Some(&subject), now, 500)
+                .await?
+                .into_iter()
+                .map(|due| due.card)
+                .filter(|card| card.topic.as_deref().unwrap_or(&card.subject) == mastery.topic)
+                .take(CARDS_PER_TOPIC)
+                .collect();
+            suggestions.push(ReviewSuggestion {
+                subject,
+                topic: mastery.topic.clone(),
+                mastery: mastery.mastery(),
+                confidence: mastery.confidence(),
+                cards,
+            });
+        }
+        Ok(suggestions)
+    }
+
+    /// Cards a user should review now, most overdue first, then new cards
+    pub async fn get_due_flashcards(&self, user_id: &str, subject: Option<&str>, limit: usize) -> Result<Vec<DueCard>> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.store.due_cards(user_id, subject, now, limit).await
+    }
+
+    /// Grades one review, schedules the card's next one and counts it in the
+    /// user's progress, in one store transaction
+    pub async fn review_flashcard(&self, user_id: &str, card_id: &str, grade: Grade) -> Result<ReviewOutcome> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        let scheduler = self.reviews.clone();
+        let user = user_id.to_string();
+        self.store
+            .apply_review(
+                user_id,
+                card_id,
+                Box::new(move |card, state, progress| {
+                    progress.record_review(card.topic.as_deref().unwrap_or(&card.subject), grade, now);
+                    scheduler.review(card, state, &user, grade, now)
+                }),
+            )
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("flashcard {} not found", card_id))
+    }
+
+    pub async fn get_flashcard_state(&self, user_id: &str, card_id: &str) -> Result<Option<CardState>> {
+        self.store.card_state(user_id, card_id).await
+    }
+
+    /// Most recent reviews first, optionally for one card
+    pub async fn get_review_log(&self, user_id: &str, card_id: Option<&str>, limit: usize) -> Result<Vec<ReviewLogEntry>> {
+        self.store.review_log(user_id, card_id, limit).await
+    }
+
+    /// Starts a timed attempt; the clock runs from now, on the server
+    pub async fn start_practice_test(&self, user_id: &str, test_id: &str, options: SessionOptions) -> Result<TestAttempt> {
+        let test = self.load_test(test_id).await?;
+        let attempt = TestAttempt::start(&test, user_id, &options, chrono::Utc::now().timestamp() as u64);
+        self.store.insert_attempt(&attempt).await?;
+        Ok(attempt)
+    }
+
+    pub async fn get_attempt(&self, attempt_id: &str) -> Result<Option<TestAttempt>> {
+        self.store.attempt(attempt_id).await
+    }
+
+    /// Questions in the attempt's served order, without answers. Fails with
+    /// `SessionError::TimeUp` once the deadline passes, grading what was answered.
+    pub async fn get_attempt_questions(&self, attempt_id: &str) -> Result<Vec<ServedQuestion>> {
+        let attempt = self.load_attempt(attempt_id).await?;
+        if attempt.is_expired(chrono::Utc::now().timestamp() as u64) {
+            if !attempt.is_submitted() {
+                self.submit_practice_test(attempt_id).await?;
+            }
+            return Err(SessionError::TimeUp.into());
+        }
+        let test = self.load_test(&attempt.test_id).await?;
+        Ok(attempt.served_questions(&test)?)
+    }
+
+    /// Answers the question at a served position with a served option index
+    pub async fn answer_question(&self, attempt_id: &str, position: usize, option: u8) -> Result<()> {
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.record_answer(attempt_id, Box::new(move |attempt| Ok(attempt.answer(position, option, now)?)))
+            .await
+    }
+
+    /// Answers a numeric, expression or free-text question, e.g. "4.7 kΩ" or "n*p*(1-p)"
+    pub async fn answer_question_text(&self, attempt_id: &str, position: usize, text: &str) -> Result<()> {
+        let attempt = self.load_attempt(attempt_id).await?;
+        let test = self.load_test(&attempt.test_id).await?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        let text = text.to_string();
+        self.record_answer(
+            attempt_id,
+            Box::new(move |attempt| Ok(attempt.answer_text(&test, position, &text, now)?)),
+        )
+        .await
+    }
+
+    /// Answers a multi-part question, one entry per part (None to leave one blank)
+    pub async fn answer_question_parts(&self, attempt_id: &str, position: usize, parts: Vec<Option<String>>) -> Result<()> {
+        let attempt = self.load_attempt(attempt_id).await?;
+        let test = self.load_test(&attempt.test_id).await?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.record_answer(
+            attempt_id,
+            Box::new(move |attempt| Ok(attempt.answer_parts(&test, position, parts, now)?)),
+        )
+        .await
+    }
+
+    /// Applies an answer; past the deadline it grades what was answered instead
+    async fn record_answer(&self, attempt_id: &str, update: AttemptUpdate) -> Result<()> {
+        match self.store.update_attempt(attempt_id, update).await {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(SessionError::AttemptNotFound(attempt_id.to_string()).into()),
+            Err(err) if matches!(err.downcast_ref::<SessionError>(), Some(SessionError::TimeUp)) => {
+                self.submit_practice_test(attempt_id).await?;
+                Err(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Grades the attempt and records the score in the user's progress, in one
+    /// store transaction. Submitting again returns the same result without
+    /// counting it twice.
+    pub async fn submit_practice_test(&self, attempt_id: &str) -> Result<AttemptResult> {
+        let attempt = self.load_attempt(attempt_id).await?;
+        if let Some(result) = attempt.result {
+            return Ok(result);
+        }
+        let test = self.load_test(&attempt.test_id).await?;
+        let now = chrono::Utc::now().timestamp() as u64;
+
+        let submitted = self
+            .store
+            .submit_attempt(
+                attempt_id,
+                Box::new(move |attempt| {
+                    attempt.submit(&test, now)?;
+                    Ok(())
+                }),
+                Box::new(|result, progress| progress.record_test(result)),
+            )
+            .await;
+        let result = match submitted {
+            Ok(attempt) => attempt.and_then(|attempt| attempt.result),
+            // Someone else submitted first; theirs counts
+            Err(err) if matches!(err.downcast_ref::<SessionError>(), Some(SessionError::AlreadySubmitted)) => {
+                return self
+                    .load_attempt(attempt_id)
+                    .await?
+                    .result
+                    .ok_or_else(|| SessionError::AttemptNotFound(attempt_id.to_string()).into());
+            }
+            Err(err) => return Err(err),
+        }
+        .ok_or_else(|| SessionError::AttemptNotFound(attempt_id.to_string()))?;
+        Ok(result)
+    }
+
+    /// A user's attempts, newest first
+    pub async fn get_attempts(&self, user_id: &str, limit: usize) -> Result<Vec<TestAttempt>> {
+        self.store.attempts_for_user(user_id, limit).await
+    }
+
+    async fn load_test(&self, test_id: &str) -> Result<PracticeTest> {
+        Ok(self
+            .store
+            .practice_test(test_id)
+            .await?
+            .ok_or_else(|| SessionError::TestNotFound(test_id.to_string()))?)
+    }
+
+    async fn load_attempt(&self, attempt_id: &str) -> Result<TestAttempt> {
+        Ok(self
+            .store
+            .attempt(attempt_id)
+            .await?
+            .ok_or_else(|| SessionError::AttemptNotFound(attempt_id.to_string()))?)
+    }
+}
+
+impl Default for StudyEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// High-performance music integration system
@@ -704,32 +1525,59 @@ impl MusicEngine {
     }
 }
 
+impl Default for MusicEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// High-performance analytics and monitoring
 pub struct PerformanceMonitor {
     metrics: Arc<Mutex<PerformanceMetrics>>,
-    alerts: Arc<Mutex<Vec<PerformanceAlert>>>,
+    alerts: Arc<Mutex<AlertManager>>,
+    dispatcher: Arc<RwLock<AlertDispatcher>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PerformanceMetrics {
+    /// Process CPU, in percent of one core
     pub cpu_usage: f64,
+    /// Process RSS, in percent of physical memory
     pub memory_usage: f64,
+    /// Messages per second
     pub message_processing_rate: f64,
     pub average_response_time: Duration,
+    /// Percentage of messages that failed processing
     pub error_rate: f64,
+    /// Share of messages served from the cache, 0..1
     pub cache_hit_rate: f64,
+    pub system_cpu_usage: f64,
+    pub rss_bytes: u64,
+    pub open_fds: u64,
+    pub threads: u64,
+    pub tokio_workers: u64,
+    pub tokio_alive_tasks: u64,
+    pub tokio_global_queue_depth: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceAlert {
     pub id: String,
+    /// The rule that raised this alert; one active alert per key
+    pub rule_key: String,
     pub severity: AlertSeverity,
     pub message: String,
+    /// Latest value of the watched metric
+    pub value: f64,
     pub timestamp: u64,
     pub resolved: bool,
+    pub resolved_at: Option<u64>,
+    pub acknowledged: bool,
+    pub silenced: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AlertSeverity {
     Low,
     Medium,
@@ -739,22 +1587,21 @@ pub enum AlertSeverity {
 
 impl PerformanceMonitor {
     pub fn new() -> Self {
+        Self::with_alert_manager(AlertManager::default())
+    }
+
+    pub fn with_alert_manager(alerts: AlertManager) -> Self {
         Self {
-            metrics: Arc::new(Mutex::new(PerformanceMetrics {
-                cpu_usage: 0.0,
-                memory_usage: 0.0,
-                message_processing_rate: 0.0,
-                average_response_time: Duration::from_millis(0),
-                error_rate: 0.0,
-                cache_hit_rate: 0.0,
-            })),
-            alerts: Arc::new(Mutex::new(Vec::new())),
+            metrics: Arc::new(Mutex::new(PerformanceMetrics::default())),
+            alerts: Arc::new(Mutex::new(alerts)),
+            dispatcher: Arc::new(RwLock::new(AlertDispatcher::new().with_sink(Arc::new(LogSink), AlertSeverity::Low))),
         }
     }
 
     pub async fn update_metrics(&self, metrics: PerformanceMetrics) -> Result<()> {
         let mut current_metrics = self.metrics.lock().await;
-        *current_metrics = metrics;
+        *current_metrics = metrics.clone();
+        drop(current_metrics);
         
         // Check for performance issues
         self.check_performance_alerts(&metrics).await;
@@ -763,51 +1610,110 @@ impl PerformanceMonitor {
     }
 
     async fn check_performance_alerts(&self, metrics: &PerformanceMetrics) {
-        let mut alerts = self.alerts.lock().await;
-        
-        if metrics.cpu_usage > 80.0 {
-            alerts.push(PerformanceAlert {
-                id: uuid::Uuid::new_v4().to_string(),
-                severity: AlertSeverity::High,
-                message: format!("High CPU usage: {}%", metrics.cpu_usage),
-                timestamp: chrono::Utc::now().timestamp() as u64,
-                resolved: false,
-            });
-        }
-        
-        if metrics.memory_usage > 90.0 {
-            alerts.push(PerformanceAlert {
-                id: uuid::Uuid::new_v4().to_string(),
-                severity: AlertSeverity::Critical,
-                message: format!("High memory usage: {}%", metrics.memory_usage),
-                timestamp: chrono::Utc::now().timestamp() as u64,
-                resolved: false,
-            });
-        }
-        
-        if metrics.error_rate > 5.0 {
-            alerts.push(PerformanceAlert {
-                id: uuid::Uuid::new_v4().to_string(),
-                severity: AlertSeverity::Medium,
-                message: format!("High error rate: {}%", metrics.error_rate),
-                timestamp: chrono::Utc::now().timestamp() as u64,
-                resolved: false,
-            });
-        }
+        let events = self.alerts.lock().await.evaluate(metrics);
+        self.dispatcher.read().await.dispatch_in_background(events);
+    }
+
+    /// Sends alert events at or above `min_severity` to `sink`. A log sink is
+    /// registered by default.
+    pub async fn add_alert_sink(&self, sink: Arc<dyn AlertSink>, min_severity: AlertSeverity, retry: RetryPolicy) {
+        self.dispatcher.write().await.add_sink(sink, min_severity, retry);
+    }
+
+    pub async fn remove_alert_sink(&self, name: &str) -> bool {
+        self.dispatcher.write().await.remove_sink(name)
+    }
+
+    /// Replaces the alert rules with the contents of a JSON rules file
+    pub async fn load_alert_rules(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let rule_set = AlertRuleSet::from_file(path)?;
+        self.set_alert_rules(rule_set.rules).await;
+        Ok(())
+    }
+
+    /// Loads `path` now and again whenever its modification time changes.
+    /// A file that fails to parse is logged and the previous rules stay active.
+    pub async fn watch_alert_rules(self: &Arc<Self>, path: impl Into<std::path::PathBuf>, poll_interval: Duration) -> Result<RuleFileWatcher> {
+        let path = path.into();
+        self.load_alert_rules(&path).await?;
+        let mut last_modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+
+        let monitor = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(poll_interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(monitor) = monitor.upgrade() else { break };
+                let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                match monitor.load_alert_rules(&path).await {
+                    Ok(()) => tracing::info!("Reloaded alert rules from {}", path.display()),
+                    Err(err) => tracing::warn!("Keeping previous alert rules: {:#}", err),
+                }
+            }
+        });
+        Ok(RuleFileWatcher { task })
+    }
+
+    /// Samples /proc, the tokio runtime and the processor's analytics every
+    /// `interval`, feeding each sample through `update_metrics`. Stops when
+    /// the handle or the monitor is dropped.
+    pub fn start_sampler(self: &Arc<Self>, interval: Duration, processor: Option<&MessageProcessor>) -> SamplerHandle {
+        let monitor = Arc::downgrade(self);
+        let mut sampler = MetricsSampler::new(processor.map(MessageProcessor::analytics_handle));
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let metrics = sampler.sample().await;
+                let Some(monitor) = monitor.upgrade() else { break };
+                let _ = monitor.update_metrics(metrics).await;
+            }
+        });
+        SamplerHandle { task }
     }
 
     pub async fn get_metrics(&self) -> PerformanceMetrics {
         self.metrics.lock().await.clone()
     }
 
+    /// Alerts that are currently firing
     pub async fn get_alerts(&self) -> Vec<PerformanceAlert> {
-        self.alerts.lock().await.clone()
+        self.alerts.lock().await.active_alerts()
+    }
+
+    /// Recently resolved alerts, oldest first
+    pub async fn get_alert_history(&self) -> Vec<PerformanceAlert> {
+        self.alerts.lock().await.history()
+    }
+
+    pub async fn set_alert_rules(&self, rules: Vec<AlertRule>) {
+        let events = self.alerts.lock().await.set_rules(rules);
+        self.dispatcher.read().await.dispatch_in_background(events);
+    }
+
+    /// Acknowledges an active alert by id or rule key
+    pub async fn acknowledge_alert(&self, id_or_key: &str) -> bool {
+        self.alerts.lock().await.acknowledge(id_or_key)
+    }
+
+    pub async fn silence_alert(&self, rule_key: &str, duration: Duration) {
+        self.alerts.lock().await.silence(rule_key, duration);
+    }
+
+    pub async fn unsilence_alert(&self, rule_key: &str) -> bool {
+        self.alerts.lock().await.unsilence(rule_key)
     }
 }
 
-// Export the main structs for use in Node.js
-pub use MessageProcessor;
-pub use StudyEngine;
-pub use MusicEngine;
-pub use PerformanceMonitor;
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
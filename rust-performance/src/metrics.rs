@@ -0,0 +1,408 @@
+/*!
+ * gunnchAI3k Performance Engine - Latency Metrics
+ * Constant-memory log-linear histograms and rolling time windows
+ */
+
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// Sub-buckets per power of two; 16 keeps relative error under ~6%
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Buckets cover values up to ~2^44 µs (~200 days); anything larger lands in the top bucket
+const MAX_EXPONENT: usize = 40;
+const BUCKET_COUNT: usize = (MAX_EXPONENT + 1) * SUB_BUCKETS;
+
+/// HDR-style histogram of durations at microsecond resolution. Memory is fixed
+/// at a few KB regardless of how many values are recorded.
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum_micros: u128,
+    max_micros: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKET_COUNT],
+            count: 0,
+            sum_micros: 0,
+            max_micros: 0,
+        }
+    }
+
+    fn bucket_index(micros: u64) -> usize {
+        if micros < SUB_BUCKETS as u64 {
+            return micros as usize;
+        }
+        let exponent = (63 - micros.leading_zeros()) as usize;
+        let shift = exponent as u32 - SUB_BUCKET_BITS;
+        let sub_bucket = ((micros >> shift) as usize) & (SUB_BUCKETS - 1);
+        let index = (exponent - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS + sub_bucket;
+        index.min(BUCKET_COUNT - 1)
+    }
+
+    /// Upper bound of the values that map to `index`
+    fn bucket_upper_bound(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let exponent = index / SUB_BUCKETS + SUB_BUCKET_BITS as usize - 1;
+        let sub_bucket = (index % SUB_BUCKETS) as u64;
+        let shift = exponent as u32 - SUB_BUCKET_BITS;
+        ((SUB_BUCKETS as u64 + sub_bucket + 1) << shift) - 1
+    }
+
+    pub fn record(&mut self, value: Duration) {
+        let micros = u64::try_from(value.as_micros()).unwrap_or(u64::MAX);
+        self.counts[Self::bucket_index(micros)] += 1;
+        self.count += 1;
+        self.sum_micros += micros as u128;
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        self.count += other.count;
+        self.sum_micros += other.sum_micros;
+        self.max_micros = self.max_micros.max(other.max_micros);
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.count = 0;
+        self.sum_micros = 0;
+        self.max_micros = 0;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(u64::try_from(self.sum_micros).unwrap_or(u64::MAX))
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::from_millis(0)
+        } else {
+            Duration::from_micros((self.sum_micros / self.count as u128) as u64)
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_micros)
+    }
+
+    /// Value at quantile `q` (0.0..=1.0), never above the recorded maximum
+    pub fn percentile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::from_millis(0);
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(Self::bucket_upper_bound(index).min(self.max_micros));
+            }
+        }
+        self.max()
+    }
+
+    /// Non-empty buckets as (upper bound, count), for exporters
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (Duration::from_micros(Self::bucket_upper_bound(index)), *count))
+    }
+
+    /// Values known to be at or below `bound`; a bucket straddling the bound is
+    /// left out, so cumulative exporter buckets never over-count
+    pub fn count_at_most(&self, bound: Duration) -> u64 {
+        let bound = u64::try_from(bound.as_micros()).unwrap_or(u64::MAX);
+        self.counts
+            .iter()
+            .enumerate()
+            .take_while(|(index, _)| Self::bucket_upper_bound(*index) <= bound)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count,
+            mean: self.mean(),
+            p50: self.percentile(0.50),
+            p90: self.percentile(0.90),
+            p99: self.percentile(0.99),
+            max: self.max(),
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct WindowSlot {
+    epoch: u64,
+    latency: LatencyHistogram,
+    errors: u64,
+    rate_limited: u64,
+}
+
+impl WindowSlot {
+    fn new() -> Self {
+        Self {
+            epoch: u64::MAX,
+            latency: LatencyHistogram::new(),
+            errors: 0,
+            rate_limited: 0,
+        }
+    }
+}
+
+/// Ring of fixed-length time slots; a window is the merge of the newest slots
+#[derive(Debug, Clone)]
+struct SlotRing {
+    slots: Vec<WindowSlot>,
+    slot_length: Duration,
+}
+
+impl SlotRing {
+    fn new(slot_count: usize, slot_length: Duration) -> Self {
+        Self {
+            slots: vec![WindowSlot::new(); slot_count],
+            slot_length,
+        }
+    }
+
+    fn current(&mut self, elapsed: Duration) -> &mut WindowSlot {
+        let epoch = (elapsed.as_nanos() / self.slot_length.as_nanos()) as u64;
+        let len = self.slots.len();
+        let slot = &mut self.slots[(epoch % len as u64) as usize];
+        if slot.epoch != epoch {
+            slot.epoch = epoch;
+            slot.latency.clear();
+            slot.errors = 0;
+            slot.rate_limited = 0;
+        }
+        slot
+    }
+
+    fn summarize(&self, elapsed: Duration, window: Duration) -> WindowSummary {
+        let current_epoch = (elapsed.as_nanos() / self.slot_length.as_nanos()) as u64;
+        let slot_count = ((window.as_nanos() / self.slot_length.as_nanos()) as u64).clamp(1, self.slots.len() as u64);
+        let oldest_epoch = (current_epoch + 1).saturating_sub(slot_count);
+
+        let mut latency = LatencyHistogram::new();
+        let mut errors = 0;
+        let mut rate_limited = 0;
+        for slot in self.slots.iter().filter(|slot| slot.epoch != u64::MAX && slot.epoch >= oldest_epoch && slot.epoch <= current_epoch) {
+            latency.merge(&slot.latency);
+            errors += slot.errors;
+            rate_limited += slot.rate_limited;
+        }
+
+        WindowSummary {
+            window,
+            messages: latency.count(),
+            errors,
+            rate_limited,
+            latency: latency.summary(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowSummary {
+    pub window: Duration,
+    pub messages: u64,
+    pub errors: u64,
+    pub rate_limited: u64,
+    pub latency: LatencySummary,
+}
+
+/// Rolling 1 minute, 5 minute and 1 hour views. The minute view uses 10s slots,
+/// the longer views share 1 minute slots.
+#[derive(Debug, Clone)]
+pub struct RollingWindows {
+    started: Instant,
+    fine: SlotRing,
+    coarse: SlotRing,
+}
+
+impl RollingWindows {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            fine: SlotRing::new(6, Duration::from_secs(10)),
+            coarse: SlotRing::new(60, Duration::from_secs(60)),
+        }
+    }
+
+    pub fn record_latency(&mut self, value: Duration) {
+        let elapsed = self.started.elapsed();
+        self.fine.current(elapsed).latency.record(value);
+        self.coarse.current(elapsed).latency.record(value);
+    }
+
+    pub fn record_error(&mut self) {
+        let elapsed = self.started.elapsed();
+        self.fine.current(elapsed).errors += 1;
+        self.coarse.current(elapsed).errors += 1;
+    }
+
+    pub fn record_rate_limited(&mut self) {
+        let elapsed = self.started.elapsed();
+        self.fine.current(elapsed).rate_limited += 1;
+        self.coarse.current(elapsed).rate_limited += 1;
+    }
+
+    pub fn last_minute(&self) -> WindowSummary {
+        self.fine.summarize(self.started.elapsed(), Duration::from_secs(60))
+    }
+
+    pub fn last_five_minutes(&self) -> WindowSummary {
+        self.coarse.summarize(self.started.elapsed(), Duration::from_secs(300))
+    }
+
+    pub fn last_hour(&self) -> WindowSummary {
+        self.coarse.summarize(self.started.elapsed(), Duration::from_secs(3600))
+    }
+}
+
+impl Default for RollingWindows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn micros(value: u64) -> Duration {
+        Duration::from_micros(value)
+    }
+
+    fn recorded(values: impl IntoIterator<Item = u64>) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::new();
+        for value in values {
+            histogram.record(micros(value));
+        }
+        histogram
+    }
+
+    #[test]
+    fn buckets_tile_the_range_within_the_error_bound() {
+        for index in 0..BUCKET_COUNT - 1 {
+            let upper = LatencyHistogram::bucket_upper_bound(index);
+            assert_eq!(LatencyHistogram::bucket_index(upper), index, "upper bound of {index}");
+            assert_eq!(LatencyHistogram::bucket_index(upper + 1), index + 1, "first value past {index}");
+        }
+        for value in [1u64, 15, 16, 31, 32, 33, 1_000, 65_535, 65_536, 123_456_789] {
+            let upper = LatencyHistogram::bucket_upper_bound(LatencyHistogram::bucket_index(value));
+            assert!(upper >= value && (upper - value) as f64 <= value as f64 / SUB_BUCKETS as f64, "{value} -> {upper}");
+        }
+        assert_eq!(LatencyHistogram::bucket_index(u64::MAX), BUCKET_COUNT - 1);
+    }
+
+    #[test]
+    fn percentiles_stay_within_a_bucket_of_the_exact_value() {
+        let histogram = recorded(1..=10_000);
+        for (q, exact) in [(0.5, 5_000.0), (0.9, 9_000.0), (0.99, 9_900.0), (0.001, 10.0)] {
+            let estimate = histogram.percentile(q).as_micros() as f64;
+            assert!(estimate >= exact && estimate <= exact * (1.0 + 1.0 / SUB_BUCKETS as f64), "p{q}: {estimate} vs {exact}");
+        }
+        assert_eq!(histogram.percentile(1.0), micros(10_000));
+        assert_eq!(histogram.mean(), micros(5_000));
+        assert_eq!(histogram.summary().max, micros(10_000));
+
+        // Never above the largest value seen, even when it sits low in its bucket
+        let pair = recorded([40_000, 40_001]);
+        assert_eq!(pair.percentile(0.99), micros(40_001));
+        assert_eq!(LatencyHistogram::new().percentile(0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn count_at_most_leaves_out_straddling_buckets() {
+        // 32 and 33 share a bucket; 34 starts the next one, which ends at 35
+        let histogram = recorded([5, 32, 33, 34]);
+        assert_eq!(histogram.count_at_most(micros(4)), 0);
+        assert_eq!(histogram.count_at_most(micros(5)), 1);
+        assert_eq!(histogram.count_at_most(micros(32)), 1);
+        assert_eq!(histogram.count_at_most(micros(33)), 3);
+        assert_eq!(histogram.count_at_most(micros(34)), 3);
+        assert_eq!(histogram.count_at_most(micros(35)), 4);
+        assert_eq!(histogram.count_at_most(Duration::MAX), 4);
+        assert_eq!(histogram.buckets().collect::<Vec<_>>(), [(micros(5), 1), (micros(33), 2), (micros(35), 1)]);
+    }
+
+    #[test]
+    fn merging_matches_recording_everything_in_one() {
+        let mut merged = recorded(1..=500);
+        merged.merge(&recorded(501..=1_000));
+        let whole = recorded(1..=1_000);
+        assert_eq!(merged.summary(), whole.summary());
+        assert_eq!(merged.sum(), whole.sum());
+    }
+
+    #[test]
+    fn window_slots_expire_as_time_moves_on() {
+        let mut ring = SlotRing::new(6, Duration::from_secs(10));
+        let at = Duration::from_secs;
+        ring.current(at(5)).latency.record(micros(100));
+        ring.current(at(15)).errors += 1;
+
+        let summary = ring.summarize(at(59), at(60));
+        assert_eq!((summary.messages, summary.errors), (1, 1));
+        // The slot for 0-10s has left the minute; 10-20s is still in it
+        let summary = ring.summarize(at(65), at(60));
+        assert_eq!((summary.messages, summary.errors), (0, 1));
+        // Narrower windows look at fewer slots
+        assert_eq!(ring.summarize(at(25), at(10)).errors, 0);
+
+        // Reusing a slot clears what it held a lap ago
+        ring.current(at(65)).latency.record(micros(200));
+        let summary = ring.summarize(at(65), at(60));
+        assert_eq!((summary.messages, summary.latency.max), (1, micros(200)));
+        assert_eq!(ring.summarize(at(200), at(60)).messages, 0);
+    }
+
+    #[test]
+    fn coarse_windows_cover_five_minutes_and_an_hour() {
+        let mut ring = SlotRing::new(60, Duration::from_secs(60));
+        let at = Duration::from_secs;
+        ring.current(at(30)).rate_limited += 1;
+        assert_eq!(ring.summarize(at(299), at(300)).rate_limited, 1);
+        assert_eq!(ring.summarize(at(300), at(300)).rate_limited, 0);
+        assert_eq!(ring.summarize(at(3_599), at(3_600)).rate_limited, 1);
+        assert_eq!(ring.summarize(at(3_600), at(3_600)).rate_limited, 0);
+    }
+}
@@ -0,0 +1,285 @@
+/*!
+ * gunnchAI3k Performance Engine - Alert Notifications
+ * Pluggable alert sinks with severity filters and retrying delivery
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::json;
+use tracing::{error, info, warn};
+use anyhow::Result;
+
+use crate::{AlertEvent, AlertSeverity};
+
+/// Somewhere alert events get delivered
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn deliver(&self, event: &AlertEvent) -> Result<()>;
+}
+
+/// Posts alerts to a Discord channel webhook as embeds
+pub struct DiscordWebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl DiscordWebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn color(event: &AlertEvent) -> u32 {
+        if event.is_resolved() {
+            return 0x2ecc71;
+        }
+        match event.alert().severity {
+            AlertSeverity::Low => 0x3498db,
+            AlertSeverity::Medium => 0xf1c40f,
+            AlertSeverity::High => 0xe67e22,
+            AlertSeverity::Critical => 0xe74c3c,
+        }
+    }
+}
+
+#[async_trait]
+impl AlertSink for DiscordWebhookSink {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn deliver(&self, event: &AlertEvent) -> Result<()> {
+        let alert = event.alert();
+        let title = if event.is_resolved() {
+            format!("Resolved: {}", alert.rule_key)
+        } else {
+            format!("{:?} alert: {}", alert.severity, alert.rule_key)
+        };
+        let body = json!({
+            "embeds": [{
+                "title": title,
+                "description": alert.message,
+                "color": Self::color(event),
+                "timestamp": chrono::DateTime::from_timestamp(alert.timestamp as i64, 0).map(|time| time.to_rfc3339()),
+            }]
+        });
+        self.client.post(&self.url).json(&body).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Posts `{"status": "firing"|"resolved", "alert": {...}}` to any URL
+pub struct HttpWebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl HttpWebhookSink {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: Vec::new(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Adds a header to every request, e.g. an Authorization token
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[async_trait]
+impl AlertSink for HttpWebhookSink {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn deliver(&self, event: &AlertEvent) -> Result<()> {
+        let status = if event.is_resolved() { "resolved" } else { "firing" };
+        let mut request = self
+            .client
+            .post(&self.url)
+            .json(&json!({ "status": status, "alert": event.alert() }));
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Writes alerts to the tracing log
+pub struct LogSink;
+
+#[async_trait]
+impl AlertSink for LogSink {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn deliver(&self, event: &AlertEvent) -> Result<()> {
+        let alert = event.alert();
+        match event {
+            AlertEvent::Resolved(_) => info!("Alert {} resolved", alert.rule_key),
+            AlertEvent::Fired(_) if alert.severity >= AlertSeverity::High => {
+                error!("Alert {} fired ({:?}): {}", alert.rule_key, alert.severity, alert.message)
+            }
+            AlertEvent::Fired(_) => warn!("Alert {} fired ({:?}): {}", alert.rule_key, alert.severity, alert.message),
+        }
+        Ok(())
+    }
+}
+
+/// Keeps delivered events in memory, for tests
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    events: Arc<Mutex<Vec<AlertEvent>>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<AlertEvent> {
+        self.events.lock().clone()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().clear();
+    }
+}
+
+#[async_trait]
+impl AlertSink for MemorySink {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn deliver(&self, event: &AlertEvent) -> Result<()> {
+        self.events.lock().push(event.clone());
+        Ok(())
+    }
+}
+
+/// Exponential backoff between delivery attempts
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay after the given failed attempt (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SinkRoute {
+    sink: Arc<dyn AlertSink>,
+    min_severity: AlertSeverity,
+    retry: RetryPolicy,
+}
+
+/// Fans alert events out to every sink whose minimum severity they meet
+#[derive(Clone, Default)]
+pub struct AlertDispatcher {
+    routes: Vec<SinkRoute>,
+}
+
+impl AlertDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sink(mut self, sink: Arc<dyn AlertSink>, min_severity: AlertSeverity) -> Self {
+        self.add_sink(sink, min_severity, RetryPolicy::default());
+        self
+    }
+
+    pub fn add_sink(&mut self, sink: Arc<dyn AlertSink>, min_severity: AlertSeverity, retry: RetryPolicy) {
+        self.routes.push(SinkRoute { sink, min_severity, retry });
+    }
+
+    /// Removes every sink with this name, returning whether any was removed
+    pub fn remove_sink(&mut self, name: &str) -> bool {
+        let before = self.routes.len();
+        self.routes.retain(|route| route.sink.name() != name);
+        self.routes.len() != before
+    }
+
+    pub fn sink_names(&self) -> Vec<String> {
+        self.routes.iter().map(|route| route.sink.name().to_string()).collect()
+    }
+
+    /// Delivers every event to every matching sink, retrying failures, and
+    /// waits for all deliveries to finish
+    pub async fn dispatch(&self, events: &[AlertEvent]) {
+        let deliveries = events.iter().flat_map(|event| {
+            self.routes
+                .iter()
+                .filter(move |route| event.alert().severity >= route.min_severity)
+                .map(move |route| Self::deliver_with_retry(route, event))
+        });
+        futures::future::join_all(deliveries).await;
+    }
+
+    /// Like `dispatch`, without waiting; used from the metrics path so slow
+    /// webhooks never hold up sampling
+    pub fn dispatch_in_background(&self, events: Vec<AlertEvent>) {
+        if events.is_empty() || self.routes.is_empty() {
+            return;
+        }
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            dispatcher.dispatch(&events).await;
+        });
+    }
+
+    async fn deliver_with_retry(route: &SinkRoute, event: &AlertEvent) {
+        let attempts = route.retry.max_attempts.max(1);
+        for attempt in 1..=attempts {
+            match route.sink.deliver(event).await {
+                Ok(()) => return,
+                Err(err) if attempt < attempts => {
+                    let delay = route.retry.backoff(attempt);
+                    warn!("Alert sink '{}' failed (attempt {}), retrying in {:?}: {:#}", route.sink.name(), attempt, delay, err);
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    error!("Alert sink '{}' gave up after {} attempts: {:#}", route.sink.name(), attempts, err);
+                }
+            }
+        }
+    }
+}
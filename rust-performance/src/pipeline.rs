@@ -0,0 +1,556 @@
+/*!
+ * gunnchAI3k Performance Engine - Analyzer Pipeline
+ * Ordered, configurable message analysis stages
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::conversation::ConversationContext;
+use crate::entities::Entity;
+use crate::intent::IntentScore;
+use crate::safety::SafetyScores;
+use crate::sentiment::SentimentScores;
+
+/// Read-only view of the message being analyzed
+#[derive(Debug, Clone)]
+pub struct AnalysisInput<'a> {
+    pub message: &'a str,
+    pub author_id: &'a str,
+    pub channel_id: &'a str,
+    pub guild_id: Option<&'a str>,
+    /// Recent turns in the same channel or thread
+    pub conversation: &'a ConversationContext,
+}
+
+/// Accumulated result of all stages that have run so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageAnalysis {
+    pub sentiment: f64,
+    pub sentiment_scores: SentimentScores,
+    /// Top-ranked entry of `intents`
+    pub intent: String,
+    pub intents: Vec<IntentScore>,
+    pub entities: Vec<Entity>,
+    pub priority: u8,
+    #[serde(default)]
+    pub safety: SafetyScores,
+    pub extras: HashMap<String, serde_json::Value>,
+}
+
+impl Default for MessageAnalysis {
+    fn default() -> Self {
+        Self {
+            sentiment: 0.0,
+            sentiment_scores: SentimentScores::default(),
+            intent: "general".to_string(),
+            intents: Vec::new(),
+            entities: Vec::new(),
+            priority: 5,
+            safety: SafetyScores::default(),
+            extras: HashMap::new(),
+        }
+    }
+}
+
+/// A single field produced by an analyzer
+#[derive(Debug, Clone)]
+pub enum AnalyzerOutput {
+    Sentiment(SentimentScores),
+    Intent(String),
+    /// Ranked intents, highest confidence first
+    Intents(Vec<IntentScore>),
+    Entities(Vec<Entity>),
+    Priority(u8),
+    Safety(SafetyScores),
+    Extra(String, serde_json::Value),
+}
+
+impl MessageAnalysis {
+    fn apply(&mut self, output: AnalyzerOutput) {
+        match output {
+            AnalyzerOutput::Sentiment(scores) => {
+                self.sentiment = scores.compound;
+                self.sentiment_scores = scores;
+            }
+            AnalyzerOutput::Intent(intent) => {
+                self.intents = vec![IntentScore { intent: intent.clone(), confidence: 1.0 }];
+                self.intent = intent;
+            }
+            AnalyzerOutput::Intents(intents) => {
+                if let Some(top) = intents.first() {
+                    self.intent = top.intent.clone();
+                }
+                self.intents = intents;
+            }
+            AnalyzerOutput::Entities(entities) => self.entities = entities,
+            AnalyzerOutput::Priority(priority) => self.priority = priority,
+            AnalyzerOutput::Safety(safety) => self.safety = safety,
+            AnalyzerOutput::Extra(key, value) => {
+                self.extras.insert(key, value);
+            }
+        }
+    }
+}
+
+/// A pluggable analysis stage. Implement this outside the crate and register it
+/// with `AnalyzerPipeline::add_stage` or `MessageProcessor::register_analyzer`.
+#[async_trait]
+pub trait MessageAnalyzer: Send + Sync {
+    /// Unique stage name, used for dependencies and timing reports
+    fn name(&self) -> &str;
+
+    /// Stages whose output this analyzer reads; it will run after them
+    fn depends_on(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>>;
+}
+
+#[derive(Clone)]
+pub struct PipelineStage {
+    pub analyzer: Arc<dyn MessageAnalyzer>,
+    pub timeout: Duration,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StageStatus {
+    Completed,
+    Failed(String),
+    TimedOut,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageReport {
+    pub name: String,
+    pub status: StageStatus,
+    pub duration: Duration,
+}
+
+/// Ordered set of stages. Consecutive stages that don't depend on each other
+/// run concurrently; a failing or slow stage leaves its fields at their defaults.
+#[derive(Clone)]
+pub struct AnalyzerPipeline {
+    stages: Vec<PipelineStage>,
+    default_timeout: Duration,
+}
+
+impl AnalyzerPipeline {
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            default_timeout: Duration::from_millis(250),
+        }
+    }
+
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Builder form of `add_stage`.
+    ///
+    /// # Panics
+    /// If the analyzer depends on a stage that isn't registered before it.
+    pub fn with_stage(mut self, analyzer: Arc<dyn MessageAnalyzer>) -> Self {
+        if let Err(err) = self.add_stage(analyzer, None) {
+            panic!("{}", err);
+        }
+        self
+    }
+
+    /// Appends a stage, replacing any existing stage with the same name in place.
+    /// Fails if the resulting order would run a stage before one it depends on.
+    pub fn add_stage(&mut self, analyzer: Arc<dyn MessageAnalyzer>, timeout: Option<Duration>) -> Result<()> {
+        let stage = PipelineStage {
+            timeout: timeout.unwrap_or(self.default_timeout),
+            analyzer,
+            enabled: true,
+        };
+
+        let mut stages = self.stages.clone();
+        match self.position(stage.analyzer.name()) {
+            Some(index) => stages[index] = stage,
+            None => stages.push(stage),
+        }
+        self.commit(stages)
+    }
+
+    /// Inserts a stage directly before `before`, or at the end if it isn't present
+    pub fn insert_stage_before(&mut self, before: &str, analyzer: Arc<dyn MessageAnalyzer>, timeout: Option<Duration>) -> Result<()> {
+        let stage = PipelineStage {
+            timeout: timeout.unwrap_or(self.default_timeout),
+            analyzer,
+            enabled: true,
+        };
+
+        let mut stages = self.stages.clone();
+        stages.retain(|existing| existing.analyzer.name() != stage.analyzer.name());
+        match stages.iter().position(|existing| existing.analyzer.name() == before) {
+            Some(index) => stages.insert(index, stage),
+            None => stages.push(stage),
+        }
+        self.commit(stages)
+    }
+
+    /// Swaps the analyzer of an existing stage, keeping its place, timeout and
+    /// enabled flag. Returns false if there is no stage with that name.
+    pub fn replace_stage(&mut self, name: &str, analyzer: Arc<dyn MessageAnalyzer>) -> Result<bool> {
+        let Some(index) = self.position(name) else {
+            return Ok(false);
+        };
+        let mut stages = self.stages.clone();
+        stages[index].analyzer = analyzer;
+        self.commit(stages)?;
+        Ok(true)
+    }
+
+    pub fn remove_stage(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.stages.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.stages[index].enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_timeout(&mut self, name: &str, timeout: Duration) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.stages[index].timeout = timeout;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn stage_names(&self) -> Vec<String> {
+        self.stages.iter().map(|stage| stage.analyzer.name().to_string()).collect()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.stages.iter().position(|stage| stage.analyzer.name() == name)
+    }
+
+    /// Accepts a new stage order only if every dependency that is registered
+    /// comes before the stage reading it. Missing dependencies are allowed so a
+    /// built-in stage can be removed without breaking the ones after it.
+    fn commit(&mut self, stages: Vec<PipelineStage>) -> Result<()> {
+        for (index, stage) in stages.iter().enumerate() {
+            for dependency in stage.analyzer.depends_on() {
+                let position = stages.iter().position(|other| other.analyzer.name() == dependency);
+                if matches!(position, Some(found) if found >= index) {
+                    bail!(
+                        "stage '{}' depends on '{}', which is not registered before it",
+                        stage.analyzer.name(),
+                        dependency
+                    );
+                }
+            }
+        }
+        self.stages = stages;
+        Ok(())
+    }
+
+    /// Groups stages into waves: a stage starts a new wave when it depends on
+    /// something in the current one, so ordering is always respected.
+    fn waves(&self) -> Vec<Vec<&PipelineStage>> {
+        let mut waves: Vec<Vec<&PipelineStage>> = Vec::new();
+        let mut current: Vec<&PipelineStage> = Vec::new();
+        let mut current_names: HashSet<&str> = HashSet::new();
+
+        for stage in self.stages.iter().filter(|stage| stage.enabled) {
+            let blocked = stage
+                .analyzer
+                .depends_on()
+                .iter()
+                .any(|dependency| current_names.contains(dependency.as_str()));
+
+            if blocked {
+                waves.push(std::mem::take(&mut current));
+                current_names.clear();
+            }
+
+            current_names.insert(stage.analyzer.name());
+            current.push(stage);
+        }
+
+        if !current.is_empty() {
+            waves.push(current);
+        }
+        waves
+    }
+
+    /// Each stage runs on the blocking pool, so a CPU-heavy analyzer can't stall
+    /// the others or the timeout, and a panic is reported as a failed stage. A
+    /// stage that overruns is abandoned; synchronous work already started keeps
+    /// its thread until it returns, but its output is discarded.
+    pub async fn run(&self, input: &AnalysisInput<'_>) -> (MessageAnalysis, Vec<StageReport>) {
+        let mut analysis = MessageAnalysis::default();
+        let mut reports = Vec::with_capacity(self.stages.len());
+        let owned = Arc::new(OwnedInput::from(input));
+
+        for wave in self.waves() {
+            let snapshot = Arc::new(analysis.clone());
+            let results = futures::future::join_all(wave.iter().map(|stage| {
+                let analyzer = stage.analyzer.clone();
+                let timeout = stage.timeout;
+                let owned = owned.clone();
+                let snapshot = snapshot.clone();
+                async move {
+                    let name = analyzer.name().to_string();
+                    let started = Instant::now();
+                    let handle = tokio::runtime::Handle::current();
+                    let task = tokio::task::spawn_blocking(move || {
+                        handle.block_on(tokio::time::timeout(timeout, async {
+                            analyzer.analyze(&owned.view(), &snapshot).await
+                        }))
+                    });
+                    let status = match tokio::time::timeout(timeout, task).await {
+                        Ok(Ok(Ok(result))) => Ok(result),
+                        Ok(Ok(Err(_))) | Err(_) => Err(StageStatus::TimedOut),
+                        Ok(Err(join_error)) => Err(StageStatus::Failed(panic_message(join_error))),
+                    };
+                    (name, status, started.elapsed())
+                }
+            }))
+            .await;
+
+            for (name, outcome, duration) in results {
+                let status = match outcome {
+                    Ok(Ok(outputs)) => {
+                        for output in outputs {
+                            analysis.apply(output);
+                        }
+                        StageStatus::Completed
+                    }
+                    Ok(Err(err)) => {
+                        warn!("Analyzer stage '{}' failed: {}", name, err);
+                        StageStatus::Failed(err.to_string())
+                    }
+                    Err(StageStatus::TimedOut) => {
+                        warn!("Analyzer stage '{}' timed out after {:?}", name, duration);
+                        StageStatus::TimedOut
+                    }
+                    Err(status) => {
+                        warn!("Analyzer stage '{}' failed: {:?}", name, status);
+                        status
+                    }
+                };
+                reports.push(StageReport { name, status, duration });
+            }
+        }
+
+        for stage in self.stages.iter().filter(|stage| !stage.enabled) {
+            reports.push(StageReport {
+                name: stage.analyzer.name().to_string(),
+                status: StageStatus::Skipped,
+                duration: Duration::from_millis(0),
+            });
+        }
+
+        (analysis, reports)
+    }
+}
+
+/// `AnalysisInput` copied so stages can run on other threads
+struct OwnedInput {
+    message: String,
+    author_id: String,
+    channel_id: String,
+    guild_id: Option<String>,
+    conversation: ConversationContext,
+}
+
+impl OwnedInput {
+    fn from(input: &AnalysisInput<'_>) -> Self {
+        Self {
+            message: input.message.to_string(),
+            author_id: input.author_id.to_string(),
+            channel_id: input.channel_id.to_string(),
+            guild_id: input.guild_id.map(str::to_string),
+            conversation: input.conversation.clone(),
+        }
+    }
+
+    fn view(&self) -> AnalysisInput<'_> {
+        AnalysisInput {
+            message: &self.message,
+            author_id: &self.author_id,
+            channel_id: &self.channel_id,
+            guild_id: self.guild_id.as_deref(),
+            conversation: &self.conversation,
+        }
+    }
+}
+
+fn panic_message(error: tokio::task::JoinError) -> String {
+    if !error.is_panic() {
+        return "stage was cancelled".to_string();
+    }
+    let payload = error.into_panic();
+    let detail = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown payload".to_string());
+    format!("stage panicked: {}", detail)
+}
+
+impl Default for AnalyzerPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum Behaviour {
+        Priority(u8),
+        /// Reads the priority set by an earlier stage and records it
+        CopyPriority,
+        Fail,
+        Panic,
+        Sleep(Duration),
+        Spin(Duration),
+    }
+
+    struct TestAnalyzer {
+        name: &'static str,
+        depends_on: Vec<&'static str>,
+        behaviour: Behaviour,
+    }
+
+    fn stage(name: &'static str, behaviour: Behaviour) -> Arc<dyn MessageAnalyzer> {
+        Arc::new(TestAnalyzer { name, depends_on: Vec::new(), behaviour })
+    }
+
+    fn dependent(name: &'static str, depends_on: &'static str, behaviour: Behaviour) -> Arc<dyn MessageAnalyzer> {
+        Arc::new(TestAnalyzer { name, depends_on: vec![depends_on], behaviour })
+    }
+
+    #[async_trait]
+    impl MessageAnalyzer for TestAnalyzer {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn depends_on(&self) -> Vec<String> {
+            self.depends_on.iter().map(|name| name.to_string()).collect()
+        }
+
+        async fn analyze(&self, _input: &AnalysisInput<'_>, analysis: &MessageAnalysis) -> Result<Vec<AnalyzerOutput>> {
+            match &self.behaviour {
+                Behaviour::Priority(priority) => Ok(vec![AnalyzerOutput::Priority(*priority)]),
+                Behaviour::CopyPriority => Ok(vec![AnalyzerOutput::Extra(
+                    "seen_priority".to_string(),
+                    serde_json::json!(analysis.priority),
+                )]),
+                Behaviour::Fail => anyhow::bail!("model unavailable"),
+                Behaviour::Panic => panic!("analyzer bug"),
+                Behaviour::Sleep(duration) => {
+                    tokio::time::sleep(*duration).await;
+                    Ok(Vec::new())
+                }
+                Behaviour::Spin(duration) => {
+                    std::thread::sleep(*duration);
+                    Ok(Vec::new())
+                }
+            }
+        }
+    }
+
+    async fn run(pipeline: &AnalyzerPipeline) -> (MessageAnalysis, HashMap<String, StageStatus>) {
+        let conversation = ConversationContext::default();
+        let input = AnalysisInput {
+            message: "hello",
+            author_id: "u1",
+            channel_id: "c1",
+            guild_id: None,
+            conversation: &conversation,
+        };
+        let (analysis, reports) = pipeline.run(&input).await;
+        (analysis, reports.into_iter().map(|report| (report.name, report.status)).collect())
+    }
+
+    #[tokio::test]
+    async fn slow_stages_time_out_without_holding_up_the_rest() {
+        let mut pipeline = AnalyzerPipeline::new().with_stage(stage("fast", Behaviour::Priority(8)));
+        pipeline.add_stage(stage("sleepy", Behaviour::Sleep(Duration::from_secs(5))), Some(Duration::from_millis(30))).unwrap();
+        pipeline.add_stage(stage("spinner", Behaviour::Spin(Duration::from_millis(600))), Some(Duration::from_millis(30))).unwrap();
+
+        let started = Instant::now();
+        let (analysis, statuses) = run(&pipeline).await;
+        assert!(started.elapsed() < Duration::from_millis(400), "{:?}", started.elapsed());
+        assert_eq!(statuses["fast"], StageStatus::Completed);
+        assert_eq!(statuses["sleepy"], StageStatus::TimedOut);
+        assert_eq!(statuses["spinner"], StageStatus::TimedOut);
+        assert_eq!(analysis.priority, 8);
+    }
+
+    #[tokio::test]
+    async fn failing_and_panicking_stages_are_isolated() {
+        let pipeline = AnalyzerPipeline::new()
+            .with_stage(stage("broken", Behaviour::Fail))
+            .with_stage(stage("buggy", Behaviour::Panic))
+            .with_stage(stage("fine", Behaviour::Priority(2)));
+
+        let (analysis, statuses) = run(&pipeline).await;
+        assert_eq!(statuses["broken"], StageStatus::Failed("model unavailable".to_string()));
+        match &statuses["buggy"] {
+            StageStatus::Failed(message) => assert!(message.contains("analyzer bug"), "{message}"),
+            other => panic!("expected a failure, got {other:?}"),
+        }
+        assert_eq!(statuses["fine"], StageStatus::Completed);
+        assert_eq!(analysis.priority, 2);
+    }
+
+    #[tokio::test]
+    async fn dependent_stages_see_earlier_output() {
+        let pipeline = AnalyzerPipeline::new()
+            .with_stage(stage("first", Behaviour::Priority(9)))
+            .with_stage(dependent("second", "first", Behaviour::CopyPriority));
+
+        let (analysis, _) = run(&pipeline).await;
+        assert_eq!(analysis.extras["seen_priority"], serde_json::json!(9));
+    }
+
+    #[test]
+    fn dependencies_on_later_stages_are_rejected() {
+        let mut pipeline = AnalyzerPipeline::new().with_stage(stage("first", Behaviour::Priority(1)));
+        pipeline.add_stage(dependent("second", "first", Behaviour::CopyPriority), None).unwrap();
+
+        let error = pipeline.insert_stage_before("first", dependent("early", "second", Behaviour::CopyPriority), None);
+        assert!(error.is_err());
+        assert!(pipeline.replace_stage("first", dependent("first", "second", Behaviour::CopyPriority)).is_err());
+        assert!(pipeline.add_stage(dependent("loop", "loop", Behaviour::CopyPriority), None).is_err());
+        assert_eq!(pipeline.stage_names(), vec!["first", "second"]);
+
+        // A dependency registered later would run after its reader
+        let mut pipeline = AnalyzerPipeline::new().with_stage(dependent("reader", "source", Behaviour::CopyPriority));
+        assert!(pipeline.add_stage(stage("source", Behaviour::Priority(1)), None).is_err());
+        pipeline.insert_stage_before("reader", stage("source", Behaviour::Priority(1)), None).unwrap();
+        assert_eq!(pipeline.stage_names(), vec!["source", "reader"]);
+    }
+}
@@ -0,0 +1,1200 @@
+/*!
+ * gunnchAI3k Performance Engine - Problem Generator
+ * Seeded, parametric probability and random-process problems with worked solutions
+ */
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use anyhow::{bail, Result};
+
+use crate::answers::{format_significant, AnswerSpec, QuestionPart, Tolerance, VariableRange};
+use crate::expression::{choose, factorial, q_function};
+use crate::sessions::SplitMix64;
+use crate::{PracticeTest, Question};
+
+/// Answers are accepted within 1%, enough for three significant figures
+const ANSWER_TOLERANCE: f64 = 0.01;
+
+/// Most problems one set or practice test may ask for
+pub const MAX_PROBLEMS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            other => bail!("unknown difficulty '{}' (expected easy, medium or hard)", other),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// On the 1..10 scale `PracticeTest` and `Flashcard` use
+    pub fn level(&self) -> u8 {
+        match self {
+            Difficulty::Easy => 3,
+            Difficulty::Medium => 5,
+            Difficulty::Hard => 8,
+        }
+    }
+
+    /// Time allowed per generated question
+    fn minutes(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 3,
+            Difficulty::Medium => 5,
+            Difficulty::Hard => 8,
+        }
+    }
+}
+
+/// Seeded randomness for templates; the same seed always gives the same problem
+pub struct ProblemRng(SplitMix64);
+
+impl ProblemRng {
+    pub fn new(seed: u64) -> Self {
+        Self(SplitMix64::new(seed))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0.next()
+    }
+
+    /// A whole number in `low..=high`
+    pub fn int(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.0.next() % (high - low + 1) as u64) as i64
+    }
+
+    /// One of `low`, `low + step`, ..., `high`, without floating-point noise
+    pub fn step(&mut self, low: f64, high: f64, step: f64) -> f64 {
+        let steps = ((high - low) / step).round() as i64;
+        let value = low + self.int(0, steps) as f64 * step;
+        (value * 1e9).round() / 1e9
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.int(0, items.len() as i64 - 1) as usize]
+    }
+}
+
+/// What a template produces: the question, its answer and how to get there
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    pub statement: String,
+    pub answer: AnswerSpec,
+    pub solution: Vec<String>,
+}
+
+/// A problem with a record of how it was made, so it can be regenerated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratedProblem {
+    pub template: String,
+    pub topic: String,
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub statement: String,
+    pub answer: AnswerSpec,
+    /// Worked solution, one step per entry
+    pub solution: Vec<String>,
+}
+
+impl GeneratedProblem {
+    /// The id is stable for a template and seed, so repeats are easy to spot
+    pub fn into_question(self) -> Question {
+        let mut question = Question::new(&self.statement, self.answer, &self.solution.join("\n")).with_topic(&self.topic);
+        question.id = format!("{}-{}-{:016x}", self.template, self.difficulty.as_str(), self.seed);
+        question
+    }
+}
+
+/// A family of randomised problems on one topic
+pub trait ProblemTemplate: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Used as the question topic, so mastery is tracked per template family
+    fn topic(&self) -> &str;
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem;
+}
+
+/// Picks templates and seeds and turns their problems into practice tests
+pub struct ProblemGenerator {
+    templates: Vec<Arc<dyn ProblemTemplate>>,
+}
+
+impl ProblemGenerator {
+    /// Every built-in template
+    pub fn new() -> Self {
+        Self {
+            templates: vec![
+                Arc::new(BinomialTemplate),
+                Arc::new(PoissonTemplate),
+                Arc::new(GaussianTailTemplate),
+                Arc::new(BinaryChannelTemplate),
+                Arc::new(JointDensityTemplate),
+                Arc::new(MomentsTemplate),
+                Arc::new(StationaryProcessTemplate),
+            ],
+        }
+    }
+
+    /// Adds a template, replacing any with the same name
+    pub fn register(&mut self, template: Arc<dyn ProblemTemplate>) {
+        self.templates.retain(|existing| existing.name() != template.name());
+        self.templates.push(template);
+    }
+
+    pub fn template_names(&self) -> Vec<&str> {
+        self.templates.iter().map(|template| template.name()).collect()
+    }
+
+    pub fn generate(&self, template: &str, difficulty: Difficulty, seed: u64) -> Result<GeneratedProblem> {
+        let Some(template) = self.templates.iter().find(|candidate| candidate.name() == template) else {
+            bail!("unknown problem template '{}'", template);
+        };
+        Ok(Self::run(template.as_ref(), difficulty, seed))
+    }
+
+    /// `count` problems drawn in turn from templates matching `topics` (by
+    /// name or topic; all templates if empty). At most `MAX_PROBLEMS`.
+    pub fn generate_set(&self, topics: &[&str], count: usize, difficulty: Difficulty, seed: u64) -> Result<Vec<GeneratedProblem>> {
+        if count > MAX_PROBLEMS {
+            bail!("asked for {} problems, at most {} can be generated at once", count, MAX_PROBLEMS);
+        }
+        let mut chosen: Vec<&Arc<dyn ProblemTemplate>> = self
+            .templates
+            .iter()
+            .filter(|template| topics.is_empty() || topics.iter().any(|topic| *topic == template.name() || *topic == template.topic()))
+            .collect();
+        if chosen.is_empty() {
+            bail!("no problem templates match {:?}", topics);
+        }
+        let mut rng = SplitMix64::new(seed);
+        rng.shuffle(&mut chosen);
+        Ok((0..count)
+            .map(|index| Self::run(chosen[index % chosen.len()].as_ref(), difficulty, rng.next()))
+            .collect())
+    }
+
+    /// A ready-to-store practice test of freshly generated problems
+    pub fn practice_test(
+        &self,
+        title: &str,
+        subject: &str,
+        topics: &[&str],
+        count: usize,
+        difficulty: Difficulty,
+        seed: u64,
+    ) -> Result<PracticeTest> {
+        let questions: Vec<Question> = self
+            .generate_set(topics, count, difficulty, seed)?
+            .into_iter()
+            .map(GeneratedProblem::into_question)
+            .collect();
+        Ok(PracticeTest {
+            id: uuid::Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            time_limit: difficulty.minutes() * 60 * questions.len().max(1) as u32,
+            questions,
+            subject: subject.to_string(),
+            difficulty: difficulty.level(),
+        })
+    }
+
+    fn run(template: &dyn ProblemTemplate, difficulty: Difficulty, seed: u64) -> GeneratedProblem {
+        let problem = template.generate(&mut ProblemRng::new(seed), difficulty);
+        GeneratedProblem {
+            template: template.name().to_string(),
+            topic: template.topic().to_string(),
+            difficulty,
+            seed,
+            statement: problem.statement,
+            answer: problem.answer,
+            solution: problem.solution,
+        }
+    }
+}
+
+impl Default for ProblemGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Four significant figures, for statements and solutions
+fn show(value: f64) -> String {
+    format_significant(value, 4)
+}
+
+/// "1 error", "3 errors"
+fn plural(count: u32, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+fn arrivals(count: u32) -> String {
+    if count == 1 {
+        "1 packet arrives".to_string()
+    } else {
+        format!("{} packets arrive", count)
+    }
+}
+
+fn numeric(value: f64) -> AnswerSpec {
+    AnswerSpec::numeric(value, Tolerance::relative(ANSWER_TOLERANCE), None)
+}
+
+fn binomial_pmf(n: u32, k: u32, p: f64) -> f64 {
+    choose(f64::from(n), f64::from(k)) * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)
+}
+
+fn poisson_pmf(mean: f64, k: u32) -> f64 {
+    mean.powi(k as i32) * (-mean).exp() / factorial(f64::from(k))
+}
+
+/// Bit errors in a block: exactly, at most or at least k
+pub struct BinomialTemplate;
+
+impl ProblemTemplate for BinomialTemplate {
+    fn name(&self) -> &str {
+        "binomial"
+    }
+
+    fn topic(&self) -> &str {
+        "binomial"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        let (n, p) = match difficulty {
+            Difficulty::Easy => (rng.int(4, 8) as u32, rng.step(0.1, 0.5, 0.1)),
+            Difficulty::Medium => (rng.int(8, 15) as u32, rng.step(0.05, 0.3, 0.05)),
+            Difficulty::Hard => (rng.int(15, 30) as u32, rng.step(0.02, 0.2, 0.01)),
+        };
+        let setup = format!(
+            "Each bit of a block of {} bits is received in error with probability {}, independently of the others.",
+            n,
+            show(p)
+        );
+        let model = format!("The number of errors X is Binomial(n = {}, p = {}).", n, show(p));
+        let term = |k: u32| format!("C({},{})·{}^{}·{}^{}", n, k, show(p), k, show(1.0 - p), n - k);
+
+        let (statement, answer, steps) = match difficulty {
+            Difficulty::Easy => {
+                let k = rng.int(0, i64::from(n.min(4))) as u32;
+                let answer = binomial_pmf(n, k, p);
+                (
+                    format!("{} What is the probability of exactly {}?", setup, plural(k, "error")),
+                    answer,
+                    vec![
+                        model,
+                        "P(X = k) = C(n,k)·p^k·(1-p)^(n-k)".to_string(),
+                        format!("P(X = {}) = {} = {}", k, term(k), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Medium => {
+                let k = rng.int(1, 3) as u32;
+                let answer: f64 = (0..=k).map(|i| binomial_pmf(n, i, p)).sum();
+                let terms: Vec<String> = (0..=k).map(|i| show(binomial_pmf(n, i, p))).collect();
+                (
+                    format!("{} The code corrects up to {}. What is the probability the block is decoded correctly?", setup, plural(k, "error")),
+                    answer,
+                    vec![
+                        model,
+                        format!("The block decodes when X ≤ {}, so add P(X = 0) through P(X = {}).", k, k),
+                        format!("P(X ≤ {}) = {} = {}", k, terms.join(" + "), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Hard => {
+                let k = rng.int(2, 4) as u32;
+                let below: f64 = (0..k).map(|i| binomial_pmf(n, i, p)).sum();
+                let answer = 1.0 - below;
+                (
+                    format!("{} What is the probability of at least {}?", setup, plural(k, "error")),
+                    answer,
+                    vec![
+                        model,
+                        format!("Use the complement: P(X ≥ {}) = 1 - P(X ≤ {}).", k, k - 1),
+                        format!("P(X ≤ {}) = {}", k - 1, show(below)),
+                        format!("P(X ≥ {}) = 1 - {} = {}", k, show(below), show(answer)),
+                    ],
+                )
+            }
+        };
+        Problem {
+            statement,
+            answer: numeric(answer),
+            solution: steps,
+        }
+    }
+}
+
+/// Arrivals of a Poisson process in an interval
+pub struct PoissonTemplate;
+
+impl ProblemTemplate for PoissonTemplate {
+    fn name(&self) -> &str {
+        "poisson"
+    }
+
+    fn topic(&self) -> &str {
+        "poisson"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        let (rate, seconds) = match difficulty {
+            Difficulty::Easy => (rng.step(1.0, 5.0, 0.5), 1.0),
+            Difficulty::Medium => (rng.step(0.5, 3.0, 0.5), *rng.pick(&[2.0, 3.0, 4.0])),
+            // Keep λt small enough that the answer isn't almost 1
+            Difficulty::Hard => (rng.step(0.2, 1.0, 0.2), *rng.pick(&[1.0, 2.0, 3.0])),
+        };
+        let mean = rate * seconds;
+        let setup = format!("Packets arrive at a router as a Poisson process with rate {} per second.", show(rate));
+        let model = format!("N ~ Poisson(λt) with λt = {}·{} = {}.", show(rate), show(seconds), show(mean));
+
+        let (statement, answer, steps) = match difficulty {
+            Difficulty::Easy => {
+                let k = rng.int(0, 4) as u32;
+                let answer = poisson_pmf(mean, k);
+                (
+                    format!("{} What is the probability that exactly {} in one second?", setup, arrivals(k)),
+                    answer,
+                    vec![
+                        model,
+                        "P(N = k) = (λt)^k·e^(-λt) / k!".to_string(),
+                        format!("P(N = {}) = {}^{}·e^(-{}) / {}! = {}", k, show(mean), k, show(mean), k, show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Medium => {
+                let k = rng.int(1, 4) as u32;
+                let answer: f64 = (0..=k).map(|i| poisson_pmf(mean, i)).sum();
+                let terms: Vec<String> = (0..=k).map(|i| show(poisson_pmf(mean, i))).collect();
+                (
+                    format!("{} What is the probability that at most {} in {} seconds?", setup, arrivals(k), show(seconds)),
+                    answer,
+                    vec![
+                        model,
+                        format!("P(N ≤ {}) = Σ (λt)^i·e^(-λt) / i! for i = 0..{}", k, k),
+                        format!("= {} = {}", terms.join(" + "), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Hard => {
+                let none = (-mean).exp();
+                let one = mean * none;
+                let answer = (1.0 - none - one) / (1.0 - none);
+                (
+                    format!(
+                        "{} Given that at least one packet arrives in {} seconds, what is the probability that at least two arrive?",
+                        setup,
+                        show(seconds)
+                    ),
+                    answer,
+                    vec![
+                        model,
+                        "P(N ≥ 2 | N ≥ 1) = P(N ≥ 2) / P(N ≥ 1) = (1 - P(0) - P(1)) / (1 - P(0))".to_string(),
+                        format!("P(0) = e^(-{}) = {}, P(1) = {}·e^(-{}) = {}", show(mean), show(none), show(mean), show(mean), show(one)),
+                        format!("(1 - {} - {}) / (1 - {}) = {}", show(none), show(one), show(none), show(answer)),
+                    ],
+                )
+            }
+        };
+        Problem {
+            statement,
+            answer: numeric(answer),
+            solution: steps,
+        }
+    }
+}
+
+/// Gaussian tail and interval probabilities, and BPSK bit errors
+pub struct GaussianTailTemplate;
+
+impl ProblemTemplate for GaussianTailTemplate {
+    fn name(&self) -> &str {
+        "gaussian_tail"
+    }
+
+    fn topic(&self) -> &str {
+        "gaussian"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        let mean = rng.int(-5, 5) as f64;
+        let sigma = rng.int(1, 4) as f64;
+        let model = format!("Standardise: Z = (X - {})/{} is N(0, 1), and P(Z > z) = Q(z).", show(mean), show(sigma));
+
+        let (statement, answer, steps) = match difficulty {
+            Difficulty::Easy => {
+                let z = rng.step(0.5, 3.0, 0.5);
+                let threshold = mean + z * sigma;
+                let answer = q_function(z);
+                (
+                    format!(
+                        "X is Gaussian with mean {} and variance {}. Find P(X > {}).",
+                        show(mean),
+                        show(sigma * sigma),
+                        show(threshold)
+                    ),
+                    answer,
+                    vec![
+                        model,
+                        format!("z = ({} - {})/{} = {}", show(threshold), show(mean), show(sigma), show(z)),
+                        format!("P(X > {}) = Q({}) = {}", show(threshold), show(z), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Medium => {
+                let low = rng.step(-2.0, 0.0, 0.5);
+                let high = rng.step(0.5, 2.5, 0.5);
+                let (a, b) = (mean + low * sigma, mean + high * sigma);
+                let answer = q_function(low) - q_function(high);
+                (
+                    format!(
+                        "X is Gaussian with mean {} and standard deviation {}. Find P({} < X < {}).",
+                        show(mean),
+                        show(sigma),
+                        show(a),
+                        show(b)
+                    ),
+                    answer,
+                    vec![
+                        model,
+                        format!("The limits standardise to z = {} and z = {}.", show(low), show(high)),
+                        format!("P({} < Z < {}) = Q({}) - Q({})", show(low), show(high), show(low), show(high)),
+                        format!("= {} - {} = {}", show(q_function(low)), show(q_function(high)), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Hard => {
+                let snr_db = rng.int(4, 10) as f64;
+                let snr = 10f64.powf(snr_db / 10.0);
+                let argument = (2.0 * snr).sqrt();
+                let answer = q_function(argument);
+                (
+                    format!(
+                        "A BPSK link over an AWGN channel operates at Eb/N0 = {} dB. What is the bit error probability?",
+                        show(snr_db)
+                    ),
+                    answer,
+                    vec![
+                        "For BPSK, P_b = Q(√(2·Eb/N0)).".to_string(),
+                        format!("Eb/N0 = 10^({}/10) = {}", show(snr_db), show(snr)),
+                        format!("P_b = Q(√(2·{})) = Q({}) = {}", show(snr), show(argument), show(answer)),
+                    ],
+                )
+            }
+        };
+        Problem {
+            statement,
+            answer: numeric(answer),
+            solution: steps,
+        }
+    }
+}
+
+/// Total probability and Bayes' rule on a binary channel
+pub struct BinaryChannelTemplate;
+
+impl ProblemTemplate for BinaryChannelTemplate {
+    fn name(&self) -> &str {
+        "binary_channel"
+    }
+
+    fn topic(&self) -> &str {
+        "conditional_probability"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        let prior = rng.step(0.3, 0.7, 0.05);
+        let flip0 = rng.step(0.01, 0.2, 0.01);
+        let flip1 = rng.step(0.01, 0.2, 0.01);
+        let setup = format!(
+            "A binary channel carries a 1 with probability {}. A transmitted 0 is received as 1 with probability {}, \
+             and a transmitted 1 is received as 0 with probability {}.",
+            show(prior),
+            show(flip0),
+            show(flip1)
+        );
+        let receive_one = prior * (1.0 - flip1) + (1.0 - prior) * flip0;
+        let total = format!(
+            "P(R=1) = P(S=1)(1 - {}) + P(S=0)·{} = {}·{} + {}·{} = {}",
+            show(flip1),
+            show(flip0),
+            show(prior),
+            show(1.0 - flip1),
+            show(1.0 - prior),
+            show(flip0),
+            show(receive_one)
+        );
+
+        let (statement, answer, steps) = match difficulty {
+            Difficulty::Easy => (
+                format!("{} What is the probability that a 1 is received?", setup),
+                receive_one,
+                vec!["Condition on what was sent (total probability).".to_string(), total],
+            ),
+            Difficulty::Medium => {
+                let answer = prior * (1.0 - flip1) / receive_one;
+                (
+                    format!("{} Given that a 1 is received, what is the probability that a 1 was sent?", setup),
+                    answer,
+                    vec![
+                        total,
+                        "Bayes: P(S=1 | R=1) = P(R=1 | S=1)·P(S=1) / P(R=1)".to_string(),
+                        format!("= {}·{} / {} = {}", show(1.0 - flip1), show(prior), show(receive_one), show(answer)),
+                    ],
+                )
+            }
+            Difficulty::Hard => {
+                let zero = (1.0 - prior) * flip0 * flip0;
+                let one = prior * (1.0 - flip1) * (1.0 - flip1);
+                let answer = zero / (zero + one);
+                (
+                    format!(
+                        "{} The same bit is sent twice over independent uses of the channel and both copies are received as 1. \
+                         What is the probability that a 0 was sent?",
+                        setup
+                    ),
+                    answer,
+                    vec![
+                        "Given the bit, the two receptions are independent, so their likelihoods multiply.".to_string(),
+                        format!("P(S=0)·P(11 | S=0) = {}·{}² = {}", show(1.0 - prior), show(flip0), show(zero)),
+                        format!("P(S=1)·P(11 | S=1) = {}·{}² = {}", show(prior), show(1.0 - flip1), show(one)),
+                        format!("P(S=0 | 11) = {} / ({} + {}) = {}", show(zero), show(zero), show(one), show(answer)),
+                    ],
+                )
+            }
+        };
+        Problem {
+            statement,
+            answer: numeric(answer),
+            solution: steps,
+        }
+    }
+}
+
+/// Normalising constants, moments and marginals of joint densities
+pub struct JointDensityTemplate;
+
+impl ProblemTemplate for JointDensityTemplate {
+    fn name(&self) -> &str {
+        "joint_pdf"
+    }
+
+    fn topic(&self) -> &str {
+        "joint_pdf"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        if difficulty == Difficulty::Hard {
+            // Uniform on the triangle 0 ≤ y ≤ x ≤ a
+            let a = rng.int(1, 4) as f64;
+            let c = 2.0 / (a * a);
+            let marginal = format!("2*x/{}", show(a * a));
+            return Problem {
+                statement: format!(
+                    "X and Y have joint density f(x,y) = c on the triangle 0 ≤ y ≤ x ≤ {} and zero elsewhere.",
+                    show(a)
+                ),
+                answer: AnswerSpec::MultiPart {
+                    parts: vec![
+                        QuestionPart::new("Find c.", numeric(c)),
+                        QuestionPart::new(
+                            &format!("Find the marginal density f_X(x) for 0 ≤ x ≤ {}, as a function of x.", show(a)),
+                            AnswerSpec::Expression {
+                                expected: marginal.clone(),
+                                variables: vec![VariableRange::new("x", 0.0, a)],
+                                tolerance: 1e-6,
+                            },
+                        ),
+                        QuestionPart::new("Find E[Y].", numeric(a / 3.0)),
+                    ],
+                },
+                solution: vec![
+                    format!("The triangle has area {}²/2 = {}, so c = 1/{} = {}.", show(a), show(a * a / 2.0), show(a * a / 2.0), show(c)),
+                    format!("f_X(x) = ∫₀ˣ c dy = c·x = {}", marginal),
+                    format!(
+                        "f_Y(y) = c·({} - y), so E[Y] = ∫₀^{} y·c·({} - y) dy = {}/3 = {}",
+                        show(a),
+                        show(a),
+                        show(a),
+                        show(a),
+                        show(a / 3.0)
+                    ),
+                ],
+            };
+        }
+
+        let m = rng.int(0, 2) as i32;
+        let n = rng.int(0, 2) as i32;
+        let a = rng.int(1, 3) as f64;
+        let b = rng.int(1, 3) as f64;
+        let c = f64::from((m + 1) * (n + 1)) / (a.powi(m + 1) * b.powi(n + 1));
+        let density = match (m, n) {
+            (0, 0) => "c".to_string(),
+            _ => format!(
+                "c{}{}",
+                match m {
+                    0 => String::new(),
+                    1 => "·x".to_string(),
+                    m => format!("·x^{}", m),
+                },
+                match n {
+                    0 => String::new(),
+                    1 => "·y".to_string(),
+                    n => format!("·y^{}", n),
+                }
+            ),
+        };
+        let region = format!("0 ≤ x ≤ {}, 0 ≤ y ≤ {}", show(a), show(b));
+        let normalise = format!(
+            "∫∫ f = c·{}^{}/{}·{}^{}/{} = 1, so c = {}",
+            show(a),
+            m + 1,
+            m + 1,
+            show(b),
+            n + 1,
+            n + 1,
+            show(c)
+        );
+
+        match difficulty {
+            Difficulty::Easy => Problem {
+                statement: format!("X and Y have joint density f(x,y) = {} for {} and zero elsewhere. Find c.", density, region),
+                answer: numeric(c),
+                solution: vec![normalise],
+            },
+            _ => {
+                let mean_x = f64::from(m + 1) / f64::from(m + 2) * a;
+                let mean_y = f64::from(n + 1) / f64::from(n + 2) * b;
+                let answer = mean_x * mean_y;
+                Problem {
+                    statement: format!(
+                        "X and Y have joint density f(x,y) = {} for {} and zero elsewhere, with c = {}. Find E[XY].",
+                        density,
+                        region,
+                        show(c)
+                    ),
+                    answer: numeric(answer),
+                    solution: vec![
+                        normalise,
+                        "The density factors into a function of x times a function of y, so X and Y are independent and E[XY] = E[X]·E[Y].".to_string(),
+                        format!("E[X] = {}/{}·{} = {}, E[Y] = {}/{}·{} = {}", m + 1, m + 2, show(a), show(mean_x), n + 1, n + 2, show(b), show(mean_y)),
+                        format!("E[XY] = {}·{} = {}", show(mean_x), show(mean_y), show(answer)),
+                    ],
+                }
+            }
+        }
+    }
+}
+
+/// Means and variances of discrete and exponential random variables
+pub struct MomentsTemplate;
+
+impl ProblemTemplate for MomentsTemplate {
+    fn name(&self) -> &str {
+        "expectation_variance"
+    }
+
+    fn topic(&self) -> &str {
+        "expectation_variance"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        if difficulty == Difficulty::Hard {
+            let rate = rng.step(0.5, 4.0, 0.5);
+            let scale = rng.int(2, 5) as f64;
+            let offset = rng.int(-3, 3) as f64;
+            let mean = scale / rate + offset;
+            let variance = scale * scale / (rate * rate);
+            return Problem {
+                statement: format!(
+                    "X is exponential with rate λ = {}, and Y = {}X {} {}.",
+                    show(rate),
+                    show(scale),
+                    if offset < 0.0 { "-" } else { "+" },
+                    show(offset.abs())
+                ),
+                answer: AnswerSpec::MultiPart {
+                    parts: vec![
+                        QuestionPart::new("Find E[Y].", numeric(mean)),
+                        QuestionPart::new("Find Var(Y).", numeric(variance)),
+                    ],
+                },
+                solution: vec![
+                    format!("E[X] = 1/λ = {}, Var(X) = 1/λ² = {}", show(1.0 / rate), show(1.0 / (rate * rate))),
+                    format!(
+                        "E[Y] = {}·E[X] {} {} = {}",
+                        show(scale),
+                        if offset < 0.0 { "-" } else { "+" },
+                        show(offset.abs()),
+                        show(mean)
+                    ),
+                    format!("Var(Y) = {}²·Var(X) = {} (the shift doesn't change the spread)", show(scale), show(variance)),
+                ],
+            };
+        }
+
+        // Three or four distinct values with probabilities in tenths
+        let count = if difficulty == Difficulty::Easy { 3 } else { rng.int(3, 4) as usize };
+        let mut values: Vec<i64> = Vec::with_capacity(count);
+        while values.len() < count {
+            let value = rng.int(-3, 6);
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        values.sort_unstable();
+        let mut cuts: Vec<i64> = Vec::with_capacity(count - 1);
+        while cuts.len() < count - 1 {
+            let cut = rng.int(1, 9);
+            if !cuts.contains(&cut) {
+                cuts.push(cut);
+            }
+        }
+        cuts.sort_unstable();
+        let bounds: Vec<i64> = std::iter::once(0).chain(cuts).chain(std::iter::once(10)).collect();
+        let probabilities: Vec<f64> = bounds.windows(2).map(|pair| (pair[1] - pair[0]) as f64 / 10.0).collect();
+
+        let pmf = values
+            .iter()
+            .zip(&probabilities)
+            .map(|(value, probability)| format!("P(X = {}) = {}", value, show(*probability)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mean: f64 = values.iter().zip(&probabilities).map(|(value, probability)| *value as f64 * probability).sum();
+        let mean_terms = values
+            .iter()
+            .zip(&probabilities)
+            .map(|(value, probability)| format!("({})·{}", value, show(*probability)))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        let mean_step = format!("E[X] = Σ x·P(X = x) = {} = {}", mean_terms, show(mean));
+
+        match difficulty {
+            Difficulty::Easy => Problem {
+                statement: format!("A random variable X has {}. Find E[X].", pmf),
+                answer: numeric(mean),
+                solution: vec![mean_step],
+            },
+            _ => {
+                let second: f64 = values.iter().zip(&probabilities).map(|(value, probability)| (value * value) as f64 * probability).sum();
+                let variance = second - mean * mean;
+                Problem {
+                    statement: format!("A random variable X has {}. Find Var(X).", pmf),
+                    answer: numeric(variance),
+                    solution: vec![
+                        mean_step,
+                        format!("E[X²] = Σ x²·P(X = x) = {}", show(second)),
+                        format!("Var(X) = E[X²] - E[X]² = {} - {}² = {}", show(second), show(mean), show(variance)),
+                    ],
+                }
+            }
+        }
+    }
+}
+
+/// Autocorrelation and power of wide-sense stationary processes
+pub struct StationaryProcessTemplate;
+
+impl ProblemTemplate for StationaryProcessTemplate {
+    fn name(&self) -> &str {
+        "stationary_process"
+    }
+
+    fn topic(&self) -> &str {
+        "random_processes"
+    }
+
+    fn generate(&self, rng: &mut ProblemRng, difficulty: Difficulty) -> Problem {
+        if difficulty == Difficulty::Hard {
+            let power = rng.int(1, 5) as f64;
+            let alpha = rng.step(0.5, 3.0, 0.5);
+            let delay = rng.step(0.2, 2.0, 0.2);
+            let correlation = power * (-alpha * delay).exp();
+            let answer = 2.0 * (power - correlation);
+            return Problem {
+                statement: format!(
+                    "X(t) is wide-sense stationary with autocorrelation R_X(τ) = {}·e^(-{}|τ|). \
+                     Find the average power E[Y(t)²] of Y(t) = X(t) - X(t - {}).",
+                    show(power),
+                    show(alpha),
+                    show(delay)
+                ),
+                answer: numeric(answer),
+                solution: vec![
+                    "E[Y²] = E[X(t)²] - 2E[X(t)X(t-T)] + E[X(t-T)²] = 2(R_X(0) - R_X(T))".to_string(),
+                    format!("R_X(0) = {}, R_X({}) = {}·e^(-{}·{}) = {}", show(power), show(delay), show(power), show(alpha), show(delay), show(correlation)),
+                    format!("E[Y²] = 2({} - {}) = {}", show(power), show(correlation), show(answer)),
+                ],
+            };
+        }
+
+        let amplitude = rng.int(1, 5) as f64;
+        let frequency = *rng.pick(&[50.0, 100.0, 200.0, 250.0, 500.0]);
+        let half_power = amplitude * amplitude / 2.0;
+        let setup = format!(
+            "X(t) = {}·cos(2π·{}·t + Θ), where Θ is uniform on [0, 2π).",
+            show(amplitude),
+            show(frequency)
+        );
+        let derivation = format!(
+            "R_X(τ) = E[X(t)X(t+τ)] = (A²/2)·cos(2π·{}·τ); the term in 2Θ averages to zero.",
+            show(frequency)
+        );
+
+        match difficulty {
+            Difficulty::Easy => Problem {
+                statement: format!("{} Find the average power E[X(t)²].", setup),
+                answer: numeric(half_power),
+                solution: vec![derivation, format!("E[X(t)²] = R_X(0) = A²/2 = {}²/2 = {}", show(amplitude), show(half_power))],
+            },
+            _ => Problem {
+                statement: format!("{} Find the autocorrelation R_X(τ) as a function of tau (τ in seconds).", setup),
+                answer: AnswerSpec::Expression {
+                    expected: format!("{}*cos(2*pi*{}*tau)", show(half_power), show(frequency)),
+                    variables: vec![VariableRange::new("tau", 0.0, 2.0 / frequency)],
+                    tolerance: 1e-6,
+                },
+                solution: vec![derivation, format!("R_X(τ) = {}·cos(2π·{}·τ)", show(half_power), show(frequency))],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+    const SEEDS: std::ops::Range<u64> = 0..40;
+
+    /// Every standalone number in `text`, in order; "N0" and "X(t)" don't count
+    fn numbers(text: &str) -> Vec<f64> {
+        let number = Regex::new(r"(?:^|[^\w.^])(-?\d+(?:\.\d+)?)").unwrap();
+        number.captures_iter(text).map(|captures| captures[1].parse().unwrap()).collect()
+    }
+
+    fn captured(pattern: &str, text: &str) -> Vec<f64> {
+        let captures = Regex::new(pattern).unwrap().captures(text).unwrap_or_else(|| panic!("{pattern} not in {text}"));
+        captures.iter().skip(1).map(|group| group.unwrap().as_str().parse().unwrap()).collect()
+    }
+
+    fn value(spec: &AnswerSpec) -> f64 {
+        match spec {
+            AnswerSpec::Numeric { value, .. } => *value,
+            other => panic!("expected a numeric answer, got {other:?}"),
+        }
+    }
+
+    fn parts(spec: &AnswerSpec) -> &[QuestionPart] {
+        match spec {
+            AnswerSpec::MultiPart { parts } => parts,
+            other => panic!("expected a multi-part answer, got {other:?}"),
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, relative: f64, context: &str) {
+        assert!((actual - expected).abs() <= relative * expected.abs().max(1e-12), "{actual} vs {expected}: {context}");
+    }
+
+    fn each(template: &str, mut check: impl FnMut(Difficulty, &GeneratedProblem)) {
+        let generator = ProblemGenerator::new();
+        for difficulty in DIFFICULTIES {
+            for seed in SEEDS {
+                check(difficulty, &generator.generate(template, difficulty, seed).unwrap());
+            }
+        }
+    }
+
+    /// C(n, k) as a running product, independent of `expression::choose`
+    fn binomial_coefficient(n: u32, k: u32) -> f64 {
+        (1..=k).fold(1.0, |product, i| product * f64::from(n - k + i) / f64::from(i))
+    }
+
+    fn binomial_cdf(n: u32, k: u32, p: f64) -> f64 {
+        (0..=k).map(|i| binomial_coefficient(n, i) * p.powi(i as i32) * (1.0 - p).powi((n - i) as i32)).sum()
+    }
+
+    /// P(N ≤ k) for Poisson(mean), summing terms by recurrence
+    fn poisson_cdf(mean: f64, k: u32) -> f64 {
+        let mut term = (-mean).exp();
+        let mut total = term;
+        for i in 1..=k {
+            term *= mean / f64::from(i);
+            total += term;
+        }
+        total
+    }
+
+    /// Q(z) by Simpson's rule over the Gaussian density, independent of `q_function`
+    fn gaussian_tail(z: f64) -> f64 {
+        if z < 0.0 {
+            return 1.0 - gaussian_tail(-z);
+        }
+        let (upper, steps) = (z + 12.0, 20_000);
+        let width = (upper - z) / steps as f64;
+        let density = |x: f64| (-x * x / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt();
+        let inner: f64 = (1..steps).map(|i| density(z + i as f64 * width) * if i % 2 == 1 { 4.0 } else { 2.0 }).sum();
+        (density(z) + inner + density(upper)) * width / 3.0
+    }
+
+    /// Midpoint rule over 0..a × 0..b
+    fn integrate(a: f64, b: f64, f: impl Fn(f64, f64) -> f64) -> f64 {
+        let steps = 200;
+        let (dx, dy) = (a / steps as f64, b / steps as f64);
+        let mut total = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                total += f((i as f64 + 0.5) * dx, (j as f64 + 0.5) * dy);
+            }
+        }
+        total * dx * dy
+    }
+
+    #[test]
+    fn binomial_answers_match_the_binomial_distribution() {
+        each("binomial", |difficulty, problem| {
+            let found = numbers(&problem.statement);
+            let (n, p, k) = (found[0] as u32, found[1], found[2] as u32);
+            let expected = match difficulty {
+                Difficulty::Easy => binomial_cdf(n, k, p) - if k == 0 { 0.0 } else { binomial_cdf(n, k - 1, p) },
+                Difficulty::Medium => binomial_cdf(n, k, p),
+                Difficulty::Hard => 1.0 - binomial_cdf(n, k - 1, p),
+            };
+            assert_close(value(&problem.answer), expected, 1e-9, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn poisson_answers_match_the_poisson_distribution() {
+        each("poisson", |difficulty, problem| {
+            let found = numbers(&problem.statement);
+            let expected = match difficulty {
+                Difficulty::Easy => {
+                    let (rate, k) = (found[0], found[1] as u32);
+                    poisson_cdf(rate, k) - if k == 0 { 0.0 } else { poisson_cdf(rate, k - 1) }
+                }
+                Difficulty::Medium => poisson_cdf(found[0] * found[2], found[1] as u32),
+                Difficulty::Hard => {
+                    // P(N ≥ 2 | N ≥ 1)
+                    let mean = found[0] * found[1];
+                    (1.0 - poisson_cdf(mean, 1)) / (1.0 - poisson_cdf(mean, 0))
+                }
+            };
+            assert_close(value(&problem.answer), expected, 1e-9, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn gaussian_answers_match_numerical_tail_integrals() {
+        each("gaussian_tail", |difficulty, problem| {
+            let found = numbers(&problem.statement);
+            let expected = match difficulty {
+                Difficulty::Easy => gaussian_tail((found[2] - found[0]) / found[1].sqrt()),
+                Difficulty::Medium => gaussian_tail((found[2] - found[0]) / found[1]) - gaussian_tail((found[3] - found[0]) / found[1]),
+                // BPSK: Q(√(2·Eb/N0))
+                Difficulty::Hard => gaussian_tail((2.0 * 10f64.powf(found[0] / 10.0)).sqrt()),
+            };
+            assert_close(value(&problem.answer), expected, 1e-6, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn binary_channel_answers_follow_bayes_rule() {
+        each("binary_channel", |difficulty, problem| {
+            let found: Vec<f64> = Regex::new(r"probability (\d+(?:\.\d+)?)")
+                .unwrap()
+                .captures_iter(&problem.statement)
+                .map(|captures| captures[1].parse().unwrap())
+                .collect();
+            let (prior, flip0, flip1) = (found[0], found[1], found[2]);
+            // Joint probabilities of (sent, received) for one use of the channel
+            let sent_one_got_one = prior * (1.0 - flip1);
+            let sent_zero_got_one = (1.0 - prior) * flip0;
+            let expected = match difficulty {
+                Difficulty::Easy => sent_one_got_one + sent_zero_got_one,
+                Difficulty::Medium => sent_one_got_one / (sent_one_got_one + sent_zero_got_one),
+                Difficulty::Hard => {
+                    let zero = (1.0 - prior) * flip0.powi(2);
+                    zero / (zero + prior * (1.0 - flip1).powi(2))
+                }
+            };
+            assert_close(value(&problem.answer), expected, 1e-9, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn joint_density_answers_match_numerical_integrals() {
+        each("joint_pdf", |difficulty, problem| {
+            if difficulty == Difficulty::Hard {
+                let a = captured(r"x ≤ (\d+) and zero", &problem.statement)[0];
+                let on_triangle = |x: f64, y: f64| if y <= x { 1.0 } else { 0.0 };
+                let c = 1.0 / integrate(a, a, on_triangle);
+                let mean_y = integrate(a, a, |x, y| c * y * on_triangle(x, y));
+                let parts = parts(&problem.answer);
+                assert_close(value(&parts[0].answer), c, 1e-2, &problem.statement);
+                assert!(parts[1].answer.check(&format!("{}*x", 2.0 / (a * a))).is_correct());
+                assert_close(value(&parts[2].answer), mean_y, 1e-2, &problem.statement);
+                return;
+            }
+
+            let density = Regex::new(r"f\(x,y\) = (\S+) for 0 ≤ x ≤ (\d+), 0 ≤ y ≤ (\d+)").unwrap();
+            let captures = density.captures(&problem.statement).unwrap();
+            let power = |variable: char| {
+                let term = &captures[1];
+                match term.find(variable) {
+                    None => 0,
+                    Some(at) if term[at + 1..].starts_with('^') => term[at + 2..at + 3].parse::<i32>().unwrap(),
+                    Some(_) => 1,
+                }
+            };
+            let (m, n) = (power('x'), power('y'));
+            let (a, b): (f64, f64) = (captures[2].parse().unwrap(), captures[3].parse().unwrap());
+            let c = 1.0 / integrate(a, b, |x, y| x.powi(m) * y.powi(n));
+            let expected = match difficulty {
+                Difficulty::Easy => c,
+                _ => integrate(a, b, |x, y| x * y * c * x.powi(m) * y.powi(n)),
+            };
+            assert_close(value(&problem.answer), expected, 1e-3, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn moment_answers_match_direct_sums() {
+        each("expectation_variance", |difficulty, problem| {
+            if difficulty == Difficulty::Hard {
+                let found = captured(r"λ = ([\d.]+), and Y = (\d+)X [+-] (\d+)", &problem.statement);
+                let (rate, scale) = (found[0], found[1]);
+                let offset = if problem.statement.contains("X - ") { -found[2] } else { found[2] };
+                let parts = parts(&problem.answer);
+                assert_close(value(&parts[0].answer), scale / rate + offset, 1e-9, &problem.statement);
+                assert_close(value(&parts[1].answer), (scale / rate).powi(2), 1e-9, &problem.statement);
+                return;
+            }
+
+            let pmf: Vec<(f64, f64)> = Regex::new(r"P\(X = (-?\d+)\) = (\d+(?:\.\d+)?)")
+                .unwrap()
+                .captures_iter(&problem.statement)
+                .map(|captures| (captures[1].parse().unwrap(), captures[2].parse().unwrap()))
+                .collect();
+            assert_close(pmf.iter().map(|(_, p)| p).sum(), 1.0, 1e-9, &problem.statement);
+            let mean: f64 = pmf.iter().map(|(x, p)| x * p).sum();
+            let expected = match difficulty {
+                Difficulty::Easy => mean,
+                _ => pmf.iter().map(|(x, p)| (x - mean).powi(2) * p).sum(),
+            };
+            assert_close(value(&problem.answer), expected, 1e-9, &problem.statement);
+        });
+    }
+
+    #[test]
+    fn stationary_process_answers_match_their_autocorrelations() {
+        each("stationary_process", |difficulty, problem| match difficulty {
+            Difficulty::Hard => {
+                let found = captured(r"R_X\(τ\) = ([\d.]+)·e\^\(-([\d.]+)\|τ\|\).*X\(t - ([\d.]+)\)", &problem.statement);
+                let (power, alpha, delay) = (found[0], found[1], found[2]);
+                let autocorrelation = |tau: f64| power * (-alpha * tau.abs()).exp();
+                let expected = autocorrelation(0.0) - 2.0 * autocorrelation(delay) + autocorrelation(0.0);
+                assert_close(value(&problem.answer), expected, 1e-9, &problem.statement);
+            }
+            _ => {
+                let found = captured(r"X\(t\) = (\d+)·cos\(2π·(\d+)·t", &problem.statement);
+                let (amplitude, frequency) = (found[0], found[1]);
+                if difficulty == Difficulty::Easy {
+                    // Average of A²cos²(θ) over a uniform phase
+                    let steps = 10_000;
+                    let power: f64 = (0..steps)
+                        .map(|i| (amplitude * (2.0 * std::f64::consts::PI * i as f64 / steps as f64).cos()).powi(2))
+                        .sum::<f64>()
+                        / steps as f64;
+                    assert_close(value(&problem.answer), power, 1e-9, &problem.statement);
+                } else {
+                    let answer = format!("{}^2/2*cos(2*pi*{}*tau)", amplitude, frequency);
+                    assert!(problem.answer.check(&answer).is_correct(), "{answer}: {}", problem.statement);
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn same_seed_regenerates_the_same_problem() {
+        let generator = ProblemGenerator::new();
+        for template in generator.template_names() {
+            for difficulty in DIFFICULTIES {
+                let first = generator.generate(template, difficulty, 42).unwrap();
+                assert_eq!(first, generator.generate(template, difficulty, 42).unwrap());
+                let statements: std::collections::HashSet<String> =
+                    SEEDS.map(|seed| generator.generate(template, difficulty, seed).unwrap().statement).collect();
+                assert!(statements.len() > 1, "{template} {difficulty:?} ignores its seed");
+            }
+        }
+        assert_eq!(
+            generator.generate_set(&[], 10, Difficulty::Medium, 7).unwrap(),
+            generator.generate_set(&[], 10, Difficulty::Medium, 7).unwrap()
+        );
+    }
+
+    /// The value or formula a student would type for `spec`
+    fn typed(spec: &AnswerSpec) -> String {
+        match spec {
+            AnswerSpec::Numeric { value, .. } => value.to_string(),
+            AnswerSpec::Expression { expected, .. } => expected.clone(),
+            other => panic!("unexpected answer {other:?}"),
+        }
+    }
+
+    #[test]
+    fn generated_answers_grade_as_correct() {
+        let generator = ProblemGenerator::new();
+        for template in generator.template_names() {
+            for difficulty in DIFFICULTIES {
+                for seed in SEEDS {
+                    let problem = generator.generate(template, difficulty, seed).unwrap();
+                    match &problem.answer {
+                        AnswerSpec::MultiPart { parts } => {
+                            let responses: Vec<Option<String>> = parts.iter().map(|part| Some(typed(&part.answer))).collect();
+                            assert!(problem.answer.check_parts(&responses).0.is_correct(), "{}", problem.statement);
+                        }
+                        spec => {
+                            assert!(spec.check(&typed(spec)).is_correct(), "{}", problem.statement);
+                            if let AnswerSpec::Numeric { value, .. } = spec {
+                                assert!(!spec.check(&(value * 1.05).to_string()).is_correct(), "{}", problem.statement);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_sets_are_refused() {
+        let generator = ProblemGenerator::new();
+        assert_eq!(generator.generate_set(&[], MAX_PROBLEMS, Difficulty::Easy, 1).unwrap().len(), MAX_PROBLEMS);
+        assert!(generator.generate_set(&[], MAX_PROBLEMS + 1, Difficulty::Easy, 1).is_err());
+        assert!(generator.practice_test("Huge", "ECE 302", &[], usize::MAX, Difficulty::Hard, 1).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+/*!
+ * gunnchAI3k Performance Engine - Learning Statistics
+ * Running score statistics, topic mastery, study streaks and weekly history
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::review::Grade;
+use crate::sessions::AttemptResult;
+use crate::{Flashcard, UserProgress};
+
+const DAY_SECS: u64 = 86_400;
+const WEEK_SECS: u64 = 7 * DAY_SECS;
+/// The unix epoch fell on a Thursday; weeks start on Monday
+const MONDAY_OFFSET: u64 = 3 * DAY_SECS;
+/// Weekly entries kept on a progress record
+const HISTORY_WEEKS: usize = 52;
+/// A longer pause between reviews is a break, not study time
+const IDLE_GAP_SECS: u64 = 5 * 60;
+/// Credited for a review that starts a sitting, when there is no gap to measure
+const REVIEW_ESTIMATE_SECS: u64 = 15;
+/// Evidence about a topic counts half as much after this many days
+const MASTERY_HALF_LIFE_DAYS: f64 = 30.0;
+/// A flashcard review is weaker evidence of mastery than a test answer
+const REVIEW_WEIGHT: f64 = 0.5;
+
+/// How well a user knows one topic, from test answers and flashcard reviews
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopicMastery {
+    pub topic: String,
+    /// Recency-weighted credit for correct answers
+    pub correct: f64,
+    /// Recency-weighted number of answers
+    pub evidence: f64,
+    pub attempts: u32,
+    pub last_seen: u64,
+}
+
+impl TopicMastery {
+    fn new(topic: &str, now: u64) -> Self {
+        Self {
+            topic: topic.to_string(),
+            correct: 0.0,
+            evidence: 0.0,
+            attempts: 0,
+            last_seen: now,
+        }
+    }
+
+    /// Posterior mean under a uniform prior, so 0.5 with no evidence
+    pub fn mastery(&self) -> f64 {
+        (self.correct + 1.0) / (self.evidence + 2.0)
+    }
+
+    /// Approximate 95% credible interval around `mastery`
+    pub fn interval(&self) -> (f64, f64) {
+        let mastery = self.mastery();
+        let spread = 1.96 * (mastery * (1.0 - mastery) / (self.evidence + 3.0)).sqrt();
+        ((mastery - spread).max(0.0), (mastery + spread).min(1.0))
+    }
+
+    /// 0 with no evidence, approaching 1 as answers accumulate
+    pub fn confidence(&self) -> f64 {
+        let (low, high) = self.interval();
+        1.0 - (high - low)
+    }
+
+    /// This record as of `now`, with evidence faded for the time since `last_seen`
+    pub fn at(&self, now: u64) -> Self {
+        let days = now.saturating_sub(self.last_seen) as f64 / DAY_SECS as f64;
+        let keep = 0.5f64.powf(days / MASTERY_HALF_LIFE_DAYS);
+        Self {
+            correct: self.correct * keep,
+            evidence: self.evidence * keep,
+            ..self.clone()
+        }
+    }
+
+    /// `credit` runs from 0 (wrong) to 1 (right)
+    fn observe(&mut self, credit: f64, weight: f64, now: u64) {
+        *self = self.at(now.max(self.last_seen));
+        self.correct += credit.clamp(0.0, 1.0) * weight;
+        self.evidence += weight;
+        self.attempts += 1;
+        self.last_seen = now.max(self.last_seen);
+    }
+}
+
+/// Activity in one calendar week (UTC)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyStats {
+    /// Monday 00:00 UTC, unix seconds
+    pub week_start: u64,
+    pub tests_completed: u32,
+    pub average_score: f64,
+    pub flashcards_studied: u32,
+    pub time_on_task_secs: u64,
+}
+
+impl WeeklyStats {
+    fn empty(week_start: u64) -> Self {
+        Self {
+            week_start,
+            tests_completed: 0,
+            average_score: 0.0,
+            flashcards_studied: 0,
+            time_on_task_secs: 0,
+        }
+    }
+}
+
+/// A topic worth revisiting, with the cards to start on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewSuggestion {
+    pub subject: String,
+    pub topic: String,
+    pub mastery: f64,
+    pub confidence: f64,
+    /// Due or new cards for the topic, most overdue first
+    pub cards: Vec<Flashcard>,
+}
+
+/// Start of the UTC week (Monday) containing `timestamp`
+pub fn week_start(timestamp: u64) -> u64 {
+    timestamp.saturating_sub((timestamp + MONDAY_OFFSET) % WEEK_SECS)
+}
+
+impl UserProgress {
+    pub fn new(user_id: &str, subject: &str, now: u64) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            subject: subject.to_string(),
+            flashcards_studied: 0,
+            tests_completed: 0,
+            average_score: 0.0,
+            score_variance: 0.0,
+            last_studied: now,
+            current_streak: 0,
+            longest_streak: 0,
+            time_on_task_secs: 0,
+            topics: Vec::new(),
+            weekly: Vec::new(),
+        }
+    }
+
+    pub fn score_std_dev(&self) -> f64 {
+        self.score_variance.sqrt()
+    }
+
+    /// Days in a row with study as of `now`; zero once a whole day is missed
+    pub fn streak_at(&self, now: u64) -> u32 {
+        if now / DAY_SECS > self.last_studied / DAY_SECS + 1 {
+            0
+        } else {
+            self.current_streak
+        }
+    }
+
+    pub fn topic(&self, topic: &str) -> Option<&TopicMastery> {
+        self.topics.iter().find(|mastery| mastery.topic == topic)
+    }
+
+    /// Topics below `target` mastery as of `now`, weakest first
+    pub fn weak_topics(&self, target: f64, now: u64) -> Vec<TopicMastery> {
+        let mut weak: Vec<TopicMastery> = self
+            .topics
+            .iter()
+            .map(|mastery| mastery.at(now))
+            .filter(|mastery| mastery.mastery() < target)
+            .collect();
+        weak.sort_by(|a, b| {
+            a.mastery()
+                .total_cmp(&b.mastery())
+                .then_with(|| b.confidence().total_cmp(&a.confidence()))
+                .then_with(|| a.topic.cmp(&b.topic))
+        });
+        weak
+    }
+
+    /// The `weeks` weeks up to the one containing `now`, oldest first, with
+    /// empty entries for weeks without study
+    pub fn weekly_history(&self, weeks: usize, now: u64) -> Vec<WeeklyStats> {
+        let current = week_start(now);
+        (0..weeks as u64)
+            .rev()
+            .filter_map(|back| current.checked_sub(back * WEEK_SECS))
+            .map(|start| {
+                self.weekly
+                    .iter()
+                    .find(|week| week.week_start == start)
+                    .cloned()
+                    .unwrap_or_else(|| WeeklyStats::empty(start))
+            })
+            .collect()
+    }
+
+    /// Adds one test score (0..=100) to the running mean and variance
+    pub fn record_test_score(&mut self, score: f64, now: u64) {
+        // Welford's update, keeping the sample variance
+        let previous = f64::from(self.tests_completed);
+        let squares = self.score_variance * (previous - 1.0).max(0.0);
+        self.tests_completed += 1;
+        let delta = score - self.average_score;
+        self.average_score += delta / (previous + 1.0);
+        let squares = squares + delta * (score - self.average_score);
+        self.score_variance = if previous > 0.0 { squares / previous } else { 0.0 };
+
+        let week = self.week_mut(now);
+        week.tests_completed += 1;
+        week.average_score += (score - week.average_score) / f64::from(week.tests_completed);
+        self.touch(now, 0);
+    }
+
+    /// Records a graded attempt: its score, time taken and per-topic answers.
+    /// Untagged questions count toward a topic named after the subject.
+    pub fn record_test(&mut self, result: &AttemptResult) {
+        let now = result.submitted_at;
+        for question in &result.questions {
+            let topic = question.topic.as_deref().unwrap_or(&result.subject);
+            self.topic_mut(topic, now).observe(question.credit, 1.0, now);
+        }
+        self.record_test_score(result.score, now);
+        self.time_on_task_secs += result.duration_secs;
+        self.week_mut(now).time_on_task_secs += result.duration_secs;
+    }
+
+    /// Records one flashcard review. Time on task is the gap since the last
+    /// activity, unless that gap looks like a break.
+    pub fn record_review(&mut self, topic: &str, grade: Grade, now: u64) {
+        let credit = match grade {
+            Grade::Again => 0.0,
+            Grade::Hard => 0.6,
+            Grade::Good | Grade::Easy => 1.0,
+        };
+        self.topic_mut(topic, now).observe(credit, REVIEW_WEIGHT, now);
+        self.flashcards_studied += 1;
+
+        let gap = now.saturating_sub(self.last_studied);
+        let spent = if self.current_streak > 0 && gap <= IDLE_GAP_SECS { gap } else { REVIEW_ESTIMATE_SECS };
+        let week = self.week_mut(now);
+        week.flashcards_studied += 1;
+        week.time_on_task_secs += spent;
+        self.touch(now, spent);
+    }
+
+    /// Moves the streak and study clock forward to `now`
+    fn touch(&mut self, now: u64, spent_secs: u64) {
+        let (today, last) = (now / DAY_SECS, self.last_studied / DAY_SECS);
+        if self.current_streak == 0 || today > last + 1 {
+            self.current_streak = 1;
+        } else if today == last + 1 {
+            self.current_streak += 1;
+        }
+        self.longest_streak = self.longest_streak.max(self.current_streak);
+        self.time_on_task_secs += spent_secs;
+        self.last_studied = self.last_studied.max(now);
+    }
+
+    fn topic_mut(&mut self, topic: &str, now: u64) -> &mut TopicMastery {
+        let index = match self.topics.iter().position(|mastery| mastery.topic == topic) {
+            Some(index) => index,
+            None => {
+                self.topics.push(TopicMastery::new(topic, now));
+                self.topics.len() - 1
+            }
+        };
+        &mut self.topics[index]
+    }
+
+    fn week_mut(&mut self, now: u64) -> &mut WeeklyStats {
+        let start = week_start(now);
+        let index = match self.weekly.iter().position(|week| week.week_start == start) {
+            Some(index) => index,
+            None => {
+                self.weekly.push(WeeklyStats::empty(start));
+                self.weekly.sort_by_key(|week| week.week_start);
+                if self.weekly.len() > HISTORY_WEEKS {
+                    self.weekly.remove(0);
+                }
+                // A week older than everything kept counts toward the oldest one
+                self.weekly.iter().position(|week| week.week_start == start).unwrap_or(0)
+            }
+        };
+        &mut self.weekly[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday 2024-01-01 00:00 UTC
+    const MONDAY: u64 = 1_704_067_200;
+
+    fn mastery(topic: &str, correct: f64, evidence: f64, last_seen: u64) -> TopicMastery {
+        TopicMastery {
+            topic: topic.to_string(),
+            correct,
+            evidence,
+            attempts: evidence as u32,
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn running_score_statistics_match_a_direct_computation() {
+        let scores = [70.0, 85.0, 90.0, 55.0, 100.0, 62.5];
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        for score in scores {
+            progress.record_test_score(score, MONDAY);
+        }
+
+        let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+        let variance = scores.iter().map(|score| (score - mean).powi(2)).sum::<f64>() / (scores.len() - 1) as f64;
+        assert_eq!(progress.tests_completed, scores.len() as u32);
+        assert!((progress.average_score - mean).abs() < 1e-9);
+        assert!((progress.score_variance - variance).abs() < 1e-9);
+        assert!((progress.score_std_dev() - variance.sqrt()).abs() < 1e-9);
+        assert!((progress.weekly[0].average_score - mean).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_single_score_has_no_variance() {
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        progress.record_test_score(80.0, MONDAY);
+        assert_eq!((progress.average_score, progress.score_variance), (80.0, 0.0));
+    }
+
+    #[test]
+    fn weeks_start_on_monday_at_midnight() {
+        assert_eq!(week_start(MONDAY), MONDAY);
+        assert_eq!(week_start(MONDAY + 1), MONDAY);
+        // Sunday 23:59:59 still belongs to the week before
+        assert_eq!(week_start(MONDAY - 1), MONDAY - WEEK_SECS);
+        assert_eq!(week_start(MONDAY + WEEK_SECS - 1), MONDAY);
+        assert_eq!(week_start(MONDAY + WEEK_SECS), MONDAY + WEEK_SECS);
+    }
+
+    #[test]
+    fn streaks_continue_on_consecutive_days_and_reset_after_a_gap() {
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        progress.record_review("algebra", Grade::Good, MONDAY + 3600);
+        progress.record_review("algebra", Grade::Good, MONDAY + 7200);
+        assert_eq!(progress.current_streak, 1);
+        progress.record_review("algebra", Grade::Good, MONDAY + DAY_SECS);
+        progress.record_review("algebra", Grade::Good, MONDAY + 2 * DAY_SECS + 3600);
+        assert_eq!((progress.current_streak, progress.longest_streak), (3, 3));
+
+        // Still alive the day after, gone once a whole day is missed
+        assert_eq!(progress.streak_at(MONDAY + 3 * DAY_SECS + 82_800), 3);
+        assert_eq!(progress.streak_at(MONDAY + 4 * DAY_SECS), 0);
+
+        progress.record_review("algebra", Grade::Good, MONDAY + 5 * DAY_SECS);
+        assert_eq!((progress.current_streak, progress.longest_streak), (1, 3));
+    }
+
+    #[test]
+    fn idle_gaps_are_not_counted_as_study_time() {
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        progress.record_review("algebra", Grade::Good, MONDAY);
+        progress.record_review("algebra", Grade::Good, MONDAY + 60);
+        progress.record_review("algebra", Grade::Good, MONDAY + 60 + IDLE_GAP_SECS + 1);
+        assert_eq!(progress.time_on_task_secs, REVIEW_ESTIMATE_SECS + 60 + REVIEW_ESTIMATE_SECS);
+        assert_eq!(progress.weekly[0].time_on_task_secs, progress.time_on_task_secs);
+        assert_eq!(progress.weekly[0].flashcards_studied, 3);
+    }
+
+    #[test]
+    fn weekly_history_keeps_the_last_year() {
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        for week in 0..60 {
+            progress.record_test_score(50.0, MONDAY + week * WEEK_SECS);
+        }
+        assert_eq!(progress.weekly.len(), HISTORY_WEEKS);
+        assert_eq!(progress.weekly[0].week_start, MONDAY + 8 * WEEK_SECS);
+
+        // A score older than everything kept folds into the oldest week
+        progress.record_test_score(50.0, MONDAY);
+        assert_eq!(progress.weekly.len(), HISTORY_WEEKS);
+        assert_eq!(progress.weekly[0].tests_completed, 2);
+
+        let history = progress.weekly_history(4, MONDAY + 61 * WEEK_SECS);
+        let starts: Vec<u64> = history.iter().map(|week| week.week_start).collect();
+        assert_eq!(starts, [58, 59, 60, 61].map(|week| MONDAY + week * WEEK_SECS));
+        assert_eq!(history.iter().map(|week| week.tests_completed).collect::<Vec<_>>(), [1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn weak_topics_are_weakest_first() {
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        progress.topics = vec![
+            mastery("fractions", 1.0, 3.0, MONDAY),
+            mastery("algebra", 0.0, 2.0, MONDAY),
+            mastery("geometry", 4.0, 4.0, MONDAY),
+            // Same mastery as fractions on less evidence
+            mastery("decimals", 0.0, 0.5, MONDAY),
+        ];
+        let weak: Vec<String> = progress.weak_topics(0.6, MONDAY).into_iter().map(|mastery| mastery.topic).collect();
+        assert_eq!(weak, ["algebra", "fractions", "decimals"]);
+    }
+
+    #[test]
+    fn old_evidence_fades_toward_uncertainty() {
+        let strong = mastery("geometry", 8.0, 8.0, MONDAY);
+        let faded = strong.at(MONDAY + 30 * DAY_SECS);
+        assert!((faded.evidence - 4.0).abs() < 1e-9);
+        assert!((faded.correct - 4.0).abs() < 1e-9);
+        assert!(faded.confidence() < strong.confidence());
+
+        let mut progress = UserProgress::new("u", "math", MONDAY);
+        progress.topics = vec![strong];
+        assert!(progress.weak_topics(0.8, MONDAY).is_empty());
+        assert_eq!(progress.weak_topics(0.8, MONDAY + 365 * DAY_SECS).len(), 1);
+    }
+}
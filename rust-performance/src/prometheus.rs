@@ -0,0 +1,391 @@
+/*!
+ * gunnchAI3k Performance Engine - Prometheus Exposition
+ * Renders analytics and performance metrics in the Prometheus text format
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::warn;
+use anyhow::{Context, Result};
+
+use crate::{AlertSeverity, AnalyticsEngine, LatencyHistogram, MessageProcessor, PerformanceMetrics, PerformanceMonitor, QueueStats, WindowSummary, WorkerPool};
+
+/// Content type for text exposition format 0.0.4
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Every metric name starts with this prefix
+const NAMESPACE: &str = "gunnchai3k";
+
+/// Upper bounds, in seconds, of the exported latency histogram buckets
+const LATENCY_BUCKETS: [f64; 13] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Collects metrics from a message processor and, optionally, a performance monitor
+#[derive(Clone)]
+pub struct PrometheusExporter {
+    processor: Arc<MessageProcessor>,
+    monitor: Option<Arc<PerformanceMonitor>>,
+    workers: Option<Arc<WorkerPool>>,
+}
+
+impl PrometheusExporter {
+    pub fn new(processor: Arc<MessageProcessor>) -> Self {
+        Self {
+            processor,
+            monitor: None,
+            workers: None,
+        }
+    }
+
+    pub fn with_monitor(mut self, monitor: Arc<PerformanceMonitor>) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    pub fn with_worker_pool(mut self, workers: Arc<WorkerPool>) -> Self {
+        self.workers = Some(workers);
+        self
+    }
+
+    /// Current metrics in the Prometheus text format
+    pub async fn render(&self) -> String {
+        let analytics = self.processor.get_analytics().await;
+        let mut output = render_analytics(&analytics);
+        if let Some(monitor) = &self.monitor {
+            let metrics = monitor.get_metrics().await;
+            let alerts = monitor.get_alerts().await;
+            let mut active: BTreeMap<&'static str, u64> = BTreeMap::new();
+            for severity in [AlertSeverity::Low, AlertSeverity::Medium, AlertSeverity::High, AlertSeverity::Critical] {
+                active.insert(severity_label(&severity), 0);
+            }
+            for alert in &alerts {
+                *active.entry(severity_label(&alert.severity)).or_insert(0) += 1;
+            }
+            output.push_str(&render_performance(&metrics, &active));
+        }
+        if let Some(workers) = &self.workers {
+            output.push_str(&render_queue(&workers.stats()));
+        }
+        output
+    }
+
+    /// Serves `GET /metrics` on `addr` until the returned server is shut down or dropped
+    pub async fn serve(self, addr: SocketAddr) -> Result<MetricsServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind metrics endpoint on {}", addr))?;
+        let local_addr = listener.local_addr()?;
+        let exporter = Arc::new(self);
+
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        warn!("Metrics endpoint accept failed: {}", err);
+                        continue;
+                    }
+                };
+                let exporter = exporter.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &exporter).await {
+                        warn!("Metrics request failed: {}", err);
+                    }
+                });
+            }
+        });
+
+        Ok(MetricsServer { local_addr, task })
+    }
+}
+
+/// A running metrics endpoint
+pub struct MetricsServer {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// The bound address, useful when serving on port 0
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn shutdown(self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, exporter: &PrometheusExporter) -> Result<()> {
+    // Only the request line matters; scrapers send small GET requests
+    let mut buffer = [0u8; 1024];
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer))
+        .await
+        .context("metrics request timed out")??;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path.split('?').next().unwrap_or_default()) {
+        ("GET", "/metrics") => ("200 OK", CONTENT_TYPE, exporter.render().await),
+        ("GET", _) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Renders message, cache, intent and guild metrics
+pub fn render_analytics(analytics: &AnalyticsEngine) -> String {
+    let mut encoder = TextEncoder::default();
+
+    encoder.family("messages_total", "counter", "Messages processed successfully, by top intent");
+    for (intent, metrics) in sorted(&analytics.by_intent) {
+        encoder.sample("messages_total", &[("intent", intent)], metrics.messages as f64);
+    }
+
+    encoder.family("errors_total", "counter", "Messages that failed processing, excluding rate-limit denials");
+    encoder.sample("errors_total", &[], analytics.error_count as f64);
+
+    encoder.family("rate_limited_total", "counter", "Messages rejected by a rate limit or resource budget");
+    encoder.sample("rate_limited_total", &[], analytics.rate_limited_count as f64);
+
+    encoder.family("cache_hits_total", "counter", "Replies served from the message cache, by top intent");
+    for (intent, metrics) in sorted(&analytics.by_intent) {
+        encoder.sample("cache_hits_total", &[("intent", intent)], metrics.cache_hits as f64);
+    }
+
+    encoder.family("cache_misses_total", "counter", "Messages that missed the message cache");
+    encoder.sample("cache_misses_total", &[], analytics.cache_misses as f64);
+
+    encoder.family("cache_evictions_total", "counter", "Cache entries evicted to make room");
+    encoder.sample("cache_evictions_total", &[], analytics.cache_evictions as f64);
+
+    encoder.family("cache_expirations_total", "counter", "Cache entries dropped after their TTL");
+    encoder.sample("cache_expirations_total", &[], analytics.cache_expirations as f64);
+
+    encoder.family("guild_messages_total", "counter", "Messages processed successfully, by guild");
+    for (guild, metrics) in sorted(&analytics.by_guild) {
+        encoder.sample("guild_messages_total", &[("guild", guild)], metrics.messages as f64);
+    }
+
+    encoder.family("guild_errors_total", "counter", "Messages that failed processing, by guild");
+    for (guild, metrics) in sorted(&analytics.by_guild) {
+        encoder.sample("guild_errors_total", &[("guild", guild)], metrics.errors as f64);
+    }
+
+    encoder.family("guild_rate_limited_total", "counter", "Messages rejected by a rate limit, by guild");
+    for (guild, metrics) in sorted(&analytics.by_guild) {
+        encoder.sample("guild_rate_limited_total", &[("guild", guild)], metrics.rate_limited as f64);
+    }
+
+    encoder.family("message_processing_seconds", "histogram", "End-to-end message processing latency, by top intent");
+    for (intent, metrics) in sorted(&analytics.by_intent) {
+        encoder.histogram("message_processing_seconds", &[("intent", intent)], &metrics.latency);
+    }
+
+    let windows = [
+        ("1m", analytics.windows.last_minute()),
+        ("5m", analytics.windows.last_five_minutes()),
+        ("1h", analytics.windows.last_hour()),
+    ];
+    encoder.family("window_messages", "gauge", "Messages processed within the rolling window");
+    for (window, summary) in &windows {
+        encoder.sample("window_messages", &[("window", window)], summary.messages as f64);
+    }
+    encoder.family("window_errors", "gauge", "Processing errors within the rolling window");
+    for (window, summary) in &windows {
+        encoder.sample("window_errors", &[("window", window)], summary.errors as f64);
+    }
+    encoder.family("window_rate_limited", "gauge", "Rate-limit denials within the rolling window");
+    for (window, summary) in &windows {
+        encoder.sample("window_rate_limited", &[("window", window)], summary.rate_limited as f64);
+    }
+    // Windowed values rise and fall, so these are gauges; `q` rather than `quantile`
+    // keeps them from being read as summary samples
+    encoder.family("window_latency_seconds", "gauge", "Latency quantiles within the rolling window, by q");
+    for (window, summary) in &windows {
+        window_quantiles(&mut encoder, window, summary);
+    }
+
+    encoder.finish()
+}
+
+/// Renders host metrics and active alert counts from the performance monitor
+pub fn render_performance(metrics: &PerformanceMetrics, active_alerts: &BTreeMap<&str, u64>) -> String {
+    let mut encoder = TextEncoder::default();
+
+    let gauges = [
+        ("cpu_usage_percent", "Process CPU usage, in percent of one core", metrics.cpu_usage),
+        ("memory_usage_percent", "Process RSS as a percentage of physical memory", metrics.memory_usage),
+        ("message_processing_rate", "Messages processed per second", metrics.message_processing_rate),
+        ("average_response_time_seconds", "Mean response time", metrics.average_response_time.as_secs_f64()),
+        ("error_rate_percent", "Percentage of messages that failed processing", metrics.error_rate),
+        ("cache_hit_rate", "Share of messages served from the cache", metrics.cache_hit_rate),
+        ("system_cpu_usage_percent", "Busy share of all host cores", metrics.system_cpu_usage),
+        ("resident_memory_bytes", "Process resident set size", metrics.rss_bytes as f64),
+        ("open_fds", "Open file descriptors", metrics.open_fds as f64),
+        ("threads", "OS threads in the process", metrics.threads as f64),
+        ("tokio_workers", "Tokio runtime worker threads", metrics.tokio_workers as f64),
+        ("tokio_alive_tasks", "Tokio tasks that have not completed", metrics.tokio_alive_tasks as f64),
+        ("tokio_global_queue_depth", "Tasks waiting in the tokio global queue", metrics.tokio_global_queue_depth as f64),
+    ];
+    for (name, help, value) in gauges {
+        encoder.family(name, "gauge", help);
+        encoder.sample(name, &[], value);
+    }
+
+    encoder.family("active_alerts", "gauge", "Unresolved performance alerts, by severity");
+    for (severity, count) in active_alerts {
+        encoder.sample("active_alerts", &[("severity", severity)], *count as f64);
+    }
+
+    encoder.finish()
+}
+
+/// Renders worker pool queue depth, throughput and wait times
+pub fn render_queue(stats: &QueueStats) -> String {
+    let mut encoder = TextEncoder::default();
+
+    encoder.family("queue_depth", "gauge", "Messages waiting for a worker, by submitted priority");
+    for (priority, depth) in stats.depth_by_priority.iter().enumerate() {
+        encoder.sample("queue_depth", &[("priority", &priority.to_string())], *depth as f64);
+    }
+
+    let gauges = [
+        ("queue_in_flight", "Messages being processed by a worker", stats.in_flight as f64),
+        ("queue_capacity", "Maximum queued messages before load shedding", stats.capacity as f64),
+        ("queue_workers", "Worker tasks in the pool", stats.workers as f64),
+        ("queue_oldest_wait_seconds", "Age of the oldest queued message", stats.oldest_wait.as_secs_f64()),
+    ];
+    for (name, help, value) in gauges {
+        encoder.family(name, "gauge", help);
+        encoder.sample(name, &[], value);
+    }
+
+    let counters = [
+        ("queue_enqueued_total", "Messages accepted into the queue", stats.enqueued),
+        ("queue_completed_total", "Messages a worker finished processing", stats.completed),
+        ("queue_shed_total", "Queued messages dropped for higher-priority work", stats.shed),
+        ("queue_rejected_total", "Messages refused because the queue was full", stats.rejected),
+    ];
+    for (name, help, value) in counters {
+        encoder.family(name, "counter", help);
+        encoder.sample(name, &[], value as f64);
+    }
+
+    encoder.family("queue_wait_seconds", "histogram", "Time from submission until a worker picked the message up");
+    encoder.histogram("queue_wait_seconds", &[], &stats.wait);
+
+    encoder.finish()
+}
+
+fn window_quantiles(encoder: &mut TextEncoder, window: &str, summary: &WindowSummary) {
+    let quantiles = [
+        ("0.5", summary.latency.p50),
+        ("0.9", summary.latency.p90),
+        ("0.99", summary.latency.p99),
+        ("1", summary.latency.max),
+    ];
+    for (q, value) in quantiles {
+        encoder.sample("window_latency_seconds", &[("window", window), ("q", q)], value.as_secs_f64());
+    }
+}
+
+fn severity_label(severity: &AlertSeverity) -> &'static str {
+    match severity {
+        AlertSeverity::Low => "low",
+        AlertSeverity::Medium => "medium",
+        AlertSeverity::High => "high",
+        AlertSeverity::Critical => "critical",
+    }
+}
+
+/// Label values come from HashMaps; sort them so output is stable between scrapes
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&str, &V)> {
+    let mut entries: Vec<(&str, &V)> = map.iter().map(|(key, value)| (key.as_str(), value)).collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+#[derive(Default)]
+struct TextEncoder {
+    output: String,
+}
+
+impl TextEncoder {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {}_{} {}", NAMESPACE, name, help);
+        let _ = writeln!(self.output, "# TYPE {}_{} {}", NAMESPACE, name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.sample_with_suffix(name, "", labels, value);
+    }
+
+    fn sample_with_suffix(&mut self, name: &str, suffix: &str, labels: &[(&str, &str)], value: f64) {
+        let _ = write!(self.output, "{}_{}{}", NAMESPACE, name, suffix);
+        if !labels.is_empty() {
+            let rendered: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+                .collect();
+            let _ = write!(self.output, "{{{}}}", rendered.join(","));
+        }
+        let _ = writeln!(self.output, " {}", format_value(value));
+    }
+
+    fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &LatencyHistogram) {
+        for bound in LATENCY_BUCKETS {
+            let le = format_value(bound);
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &le));
+            let count = histogram.count_at_most(Duration::from_secs_f64(bound));
+            self.sample_with_suffix(name, "_bucket", &bucket_labels, count as f64);
+        }
+        let mut bucket_labels = labels.to_vec();
+        bucket_labels.push(("le", "+Inf"));
+        self.sample_with_suffix(name, "_bucket", &bucket_labels, histogram.count() as f64);
+        self.sample_with_suffix(name, "_sum", labels, histogram.sum().as_secs_f64());
+        self.sample_with_suffix(name, "_count", labels, histogram.count() as f64);
+    }
+
+    fn finish(self) -> String {
+        self.output
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
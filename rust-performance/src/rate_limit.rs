@@ -0,0 +1,697 @@
+/*!
+ * gunnchAI3k Performance Engine - Rate Limiting
+ * Token bucket, sliding log and GCRA limits per user, channel, guild and globally
+ */
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// Smooth refill at `max_requests / window`, bursts up to `max_requests`
+    TokenBucket,
+    /// Exact count of requests in the trailing window
+    SlidingLog,
+    /// Generic cell rate algorithm: token-bucket behaviour with one timestamp of state
+    Gcra,
+}
+
+/// Expensive work with its own per-user budget, separate from the message rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceClass {
+    Llm,
+    MusicSearch,
+    StudyGeneration,
+}
+
+impl fmt::Display for ResourceClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ResourceClass::Llm => "LLM",
+            ResourceClass::MusicSearch => "music search",
+            ResourceClass::StudyGeneration => "study generation",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitScope {
+    User,
+    Channel,
+    Guild,
+    Global,
+    /// A user's budget for one resource class
+    Budget(ResourceClass),
+}
+
+impl fmt::Display for RateLimitScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitScope::User => f.write_str("user rate limit"),
+            RateLimitScope::Channel => f.write_str("channel rate limit"),
+            RateLimitScope::Guild => f.write_str("guild rate limit"),
+            RateLimitScope::Global => f.write_str("global rate limit"),
+            RateLimitScope::Budget(class) => write!(f, "{} budget", class),
+        }
+    }
+}
+
+/// What one intent or command charges, and against which budget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CommandCost {
+    pub class: ResourceClass,
+    pub cost: u32,
+}
+
+impl CommandCost {
+    pub fn new(class: ResourceClass, cost: u32) -> Self {
+        Self { class, cost }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CostTable {
+    pub intents: HashMap<String, CommandCost>,
+    pub commands: HashMap<String, CommandCost>,
+    /// Per-user budget for each resource class; classes without one are unlimited
+    pub budgets: HashMap<ResourceClass, Quota>,
+}
+
+impl CostTable {
+    pub fn intent_cost(&self, intent: &str) -> Option<CommandCost> {
+        self.intents.get(intent).copied()
+    }
+
+    pub fn command_cost(&self, command: &str) -> Option<CommandCost> {
+        self.commands.get(command).copied()
+    }
+}
+
+impl Default for CostTable {
+    fn default() -> Self {
+        let intents = [
+            ("general", CommandCost::new(ResourceClass::Llm, 1)),
+            ("help", CommandCost::new(ResourceClass::Llm, 1)),
+            ("entertainment", CommandCost::new(ResourceClass::Llm, 1)),
+            ("music", CommandCost::new(ResourceClass::MusicSearch, 1)),
+            ("study", CommandCost::new(ResourceClass::StudyGeneration, 2)),
+        ];
+        let commands = [
+            ("ask", CommandCost::new(ResourceClass::Llm, 1)),
+            ("explain", CommandCost::new(ResourceClass::Llm, 2)),
+            ("play", CommandCost::new(ResourceClass::MusicSearch, 1)),
+            ("search", CommandCost::new(ResourceClass::MusicSearch, 1)),
+            ("playlist", CommandCost::new(ResourceClass::MusicSearch, 5)),
+            ("flashcards", CommandCost::new(ResourceClass::StudyGeneration, 3)),
+            ("practice_test", CommandCost::new(ResourceClass::StudyGeneration, 10)),
+        ];
+        let budgets = [
+            (ResourceClass::Llm, Quota::per_minute(20)),
+            (ResourceClass::MusicSearch, Quota::per_minute(15)),
+            (ResourceClass::StudyGeneration, Quota { max_requests: 30, window: Duration::from_secs(600) }),
+        ];
+
+        Self {
+            intents: intents.into_iter().map(|(name, cost)| (name.to_string(), cost)).collect(),
+            commands: commands.into_iter().map(|(name, cost)| (name.to_string(), cost)).collect(),
+            budgets: budgets.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Quota {
+    pub max_requests: u32,
+    pub window: Duration,
+}
+
+impl Quota {
+    pub fn per_minute(max_requests: u32) -> Self {
+        Self {
+            max_requests,
+            window: Duration::from_secs(60),
+        }
+    }
+
+    fn scaled(&self, multiplier: f64) -> Self {
+        Self {
+            max_requests: ((self.max_requests as f64) * multiplier).round().max(1.0) as u32,
+            window: self.window,
+        }
+    }
+
+    fn emission_interval(&self) -> Duration {
+        self.window / self.max_requests.max(1)
+    }
+}
+
+/// Per-role adjustment of the user quota
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TierLimits {
+    pub multiplier: f64,
+    /// Exempt users skip user, channel, guild and budget limits but still count towards global
+    pub exempt: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub algorithm: RateLimitAlgorithm,
+    pub user: Quota,
+    pub channel: Quota,
+    pub guild: Quota,
+    pub global: Quota,
+    /// Role name -> tier; when a user has several roles the most generous wins.
+    /// Tier multipliers scale resource budgets as well as the user quota.
+    pub tiers: HashMap<String, TierLimits>,
+    pub costs: CostTable,
+    /// Entries untouched for this long are dropped by garbage collection
+    pub idle_ttl: Duration,
+    pub gc_interval: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let mut tiers = HashMap::new();
+        tiers.insert("moderator".to_string(), TierLimits { multiplier: 1.0, exempt: true });
+        tiers.insert("premium".to_string(), TierLimits { multiplier: 3.0, exempt: false });
+
+        Self {
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            user: Quota::per_minute(10),
+            channel: Quota::per_minute(30),
+            guild: Quota::per_minute(60),
+            global: Quota::per_minute(100),
+            tiers,
+            costs: CostTable::default(),
+            idle_ttl: Duration::from_secs(600),
+            gc_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Who is asking; channel and guild limits apply only when their ids are given
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitRequest<'a> {
+    pub user_id: &'a str,
+    pub channel_id: Option<&'a str>,
+    pub guild_id: Option<&'a str>,
+    pub roles: &'a [String],
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{scope} exceeded, retry after {}ms", retry_after.as_millis())]
+pub struct RateLimitDenied {
+    pub scope: RateLimitScope,
+    pub retry_after: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum LimiterState {
+    TokenBucket { tokens: f64, last_refill: Instant },
+    SlidingLog { log: VecDeque<Instant> },
+    Gcra { theoretical_arrival: Instant },
+}
+
+#[derive(Debug, Clone)]
+struct LimiterEntry {
+    state: LimiterState,
+    last_seen: Instant,
+}
+
+impl LimiterState {
+    fn new(algorithm: RateLimitAlgorithm, quota: &Quota, now: Instant) -> Self {
+        match algorithm {
+            RateLimitAlgorithm::TokenBucket => LimiterState::TokenBucket {
+                tokens: quota.max_requests as f64,
+                last_refill: now,
+            },
+            RateLimitAlgorithm::SlidingLog => LimiterState::SlidingLog { log: VecDeque::new() },
+            RateLimitAlgorithm::Gcra => LimiterState::Gcra { theoretical_arrival: now },
+        }
+    }
+
+    /// Checks whether `cost` units fit without changing state; Err holds the wait time
+    fn peek(&self, now: Instant, quota: &Quota, cost: u32) -> Result<(), Duration> {
+        // A request can never cost more than the whole quota
+        let cost = cost.min(quota.max_requests.max(1));
+
+        match self {
+            LimiterState::TokenBucket { tokens, last_refill } => {
+                let rate = quota.max_requests as f64 / quota.window.as_secs_f64();
+                let available = (tokens + now.duration_since(*last_refill).as_secs_f64() * rate)
+                    .min(quota.max_requests as f64);
+                if available >= cost as f64 {
+                    Ok(())
+                } else {
+                    Err(Duration::from_secs_f64((cost as f64 - available) / rate))
+                }
+            }
+            LimiterState::SlidingLog { log } => {
+                let live: Vec<&Instant> = log.iter().filter(|at| now.duration_since(**at) < quota.window).collect();
+                let needed = (live.len() + cost as usize).saturating_sub(quota.max_requests as usize);
+                if needed == 0 {
+                    Ok(())
+                } else {
+                    // Wait until enough of the oldest requests fall out of the window
+                    let frees_at = *live[needed - 1] + quota.window;
+                    Err(frees_at.saturating_duration_since(now))
+                }
+            }
+            LimiterState::Gcra { theoretical_arrival } => {
+                let start = (*theoretical_arrival).max(now);
+                let new_arrival = start + quota.emission_interval() * cost;
+                let allowed_at = new_arrival.checked_sub(quota.window).unwrap_or(now);
+                if allowed_at <= now {
+                    Ok(())
+                } else {
+                    Err(allowed_at.duration_since(now))
+                }
+            }
+        }
+    }
+
+    fn commit(&mut self, now: Instant, quota: &Quota, cost: u32) {
+        let cost = cost.min(quota.max_requests.max(1));
+
+        match self {
+            LimiterState::TokenBucket { tokens, last_refill } => {
+                let rate = quota.max_requests as f64 / quota.window.as_secs_f64();
+                *tokens = (*tokens + now.duration_since(*last_refill).as_secs_f64() * rate)
+                    .min(quota.max_requests as f64)
+                    - cost as f64;
+                *last_refill = now;
+            }
+            LimiterState::SlidingLog { log } => {
+                while log.front().map(|at| now.duration_since(*at) >= quota.window).unwrap_or(false) {
+                    log.pop_front();
+                }
+                log.extend(std::iter::repeat_n(now, cost as usize));
+            }
+            LimiterState::Gcra { theoretical_arrival } => {
+                *theoretical_arrival = (*theoretical_arrival).max(now) + quota.emission_interval() * cost;
+            }
+        }
+    }
+
+    /// True once the limiter is back to a full quota, so dropping it loses nothing
+    fn is_idle(&self, now: Instant, quota: &Quota) -> bool {
+        match self {
+            LimiterState::TokenBucket { .. } => self.peek(now, quota, quota.max_requests).is_ok(),
+            LimiterState::SlidingLog { log } => log.back().map(|at| now.duration_since(*at) >= quota.window).unwrap_or(true),
+            LimiterState::Gcra { theoretical_arrival } => *theoretical_arrival <= now,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    limits: HashMap<(RateLimitScope, String), LimiterEntry>,
+    config: RateLimitConfig,
+    last_gc: Instant,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::with_config(RateLimitConfig::default())
+    }
+
+    pub fn with_config(config: RateLimitConfig) -> Self {
+        Self {
+            limits: HashMap::new(),
+            config,
+            last_gc: Instant::now(),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Replaces the config. Existing state is dropped if the algorithm changes.
+    pub fn set_config(&mut self, config: RateLimitConfig) {
+        if config.algorithm != self.config.algorithm {
+            self.limits.clear();
+        }
+        self.config = config;
+    }
+
+    /// Per-user check with no channel, guild or role context
+    pub fn check_limit(&mut self, user_id: &str) -> Result<(), RateLimitDenied> {
+        self.check(&RateLimitRequest {
+            user_id,
+            ..Default::default()
+        })
+    }
+
+    pub fn check(&mut self, request: &RateLimitRequest<'_>) -> Result<(), RateLimitDenied> {
+        let checks = self.message_checks(request);
+        self.apply(checks, Instant::now())
+    }
+
+    /// Charges resource budgets, all or nothing. A denial names the budget that ran out.
+    pub fn charge(&mut self, request: &RateLimitRequest<'_>, charges: &[CommandCost]) -> Result<(), RateLimitDenied> {
+        let checks = self.budget_checks(request, charges);
+        self.apply(checks, Instant::now())
+    }
+
+    /// Charges the configured cost of a named command; unknown commands are free
+    pub fn charge_command(&mut self, request: &RateLimitRequest<'_>, command: &str) -> Result<(), RateLimitDenied> {
+        match self.config.costs.command_cost(command) {
+            Some(cost) => self.charge(request, &[cost]),
+            None => Ok(()),
+        }
+    }
+
+    /// Charges every intent's cost, merging intents that share a resource class
+    pub fn charge_intents<'i>(&mut self, request: &RateLimitRequest<'_>, intents: impl IntoIterator<Item = &'i str>) -> Result<(), RateLimitDenied> {
+        let mut merged: Vec<CommandCost> = Vec::new();
+        for cost in intents.into_iter().filter_map(|intent| self.config.costs.intent_cost(intent)) {
+            match merged.iter_mut().find(|existing| existing.class == cost.class) {
+                Some(existing) => existing.cost += cost.cost,
+                None => merged.push(cost),
+            }
+        }
+        self.charge(request, &merged)
+    }
+
+    /// Applies every check, or none of them if any would deny
+    fn apply(&mut self, checks: Vec<(RateLimitScope, String, Quota, u32)>, now: Instant) -> Result<(), RateLimitDenied> {
+        if now.duration_since(self.last_gc) >= self.config.gc_interval {
+            self.collect_garbage_at(now);
+        }
+
+        // Report the longest wait so a retry after it passes every scope
+        let mut denied: Option<RateLimitDenied> = None;
+        for (scope, key, quota, cost) in &checks {
+            let result = match self.limits.get(&(*scope, key.clone())) {
+                Some(entry) => entry.state.peek(now, quota, *cost),
+                None => Ok(()),
+            };
+            if let Err(retry_after) = result {
+                if denied.as_ref().map(|d| retry_after > d.retry_after).unwrap_or(true) {
+                    denied = Some(RateLimitDenied { scope: *scope, retry_after });
+                }
+            }
+        }
+        if let Some(denied) = denied {
+            return Err(denied);
+        }
+
+        let algorithm = self.config.algorithm;
+        for (scope, key, quota, cost) in checks {
+            let entry = self.limits.entry((scope, key)).or_insert_with(|| LimiterEntry {
+                state: LimiterState::new(algorithm, &quota, now),
+                last_seen: now,
+            });
+            entry.state.commit(now, &quota, cost);
+            entry.last_seen = now;
+        }
+
+        Ok(())
+    }
+
+    fn tier_for(&self, request: &RateLimitRequest<'_>) -> Option<TierLimits> {
+        request
+            .roles
+            .iter()
+            .filter_map(|role| self.config.tiers.get(role))
+            .fold(None::<TierLimits>, |best, tier| match best {
+                Some(best) if best.exempt || (!tier.exempt && best.multiplier >= tier.multiplier) => Some(best),
+                _ => Some(*tier),
+            })
+    }
+
+    fn message_checks(&self, request: &RateLimitRequest<'_>) -> Vec<(RateLimitScope, String, Quota, u32)> {
+        let tier = self.tier_for(request);
+
+        let mut checks = Vec::with_capacity(4);
+        if !tier.map(|tier| tier.exempt).unwrap_or(false) {
+            let multiplier = tier.map(|tier| tier.multiplier).unwrap_or(1.0);
+            checks.push((RateLimitScope::User, request.user_id.to_string(), self.config.user.scaled(multiplier), 1));
+            if let Some(channel_id) = request.channel_id {
+                checks.push((RateLimitScope::Channel, channel_id.to_string(), self.config.channel, 1));
+            }
+            if let Some(guild_id) = request.guild_id {
+                checks.push((RateLimitScope::Guild, guild_id.to_string(), self.config.guild, 1));
+            }
+        }
+        checks.push((RateLimitScope::Global, String::new(), self.config.global, 1));
+        checks
+    }
+
+    fn budget_checks(&self, request: &RateLimitRequest<'_>, charges: &[CommandCost]) -> Vec<(RateLimitScope, String, Quota, u32)> {
+        let tier = self.tier_for(request);
+        if tier.map(|tier| tier.exempt).unwrap_or(false) {
+            return Vec::new();
+        }
+
+        let multiplier = tier.map(|tier| tier.multiplier).unwrap_or(1.0);
+        charges
+            .iter()
+            .filter(|charge| charge.cost > 0)
+            .filter_map(|charge| {
+                let quota = self.config.costs.budgets.get(&charge.class)?.scaled(multiplier);
+                Some((RateLimitScope::Budget(charge.class), request.user_id.to_string(), quota, charge.cost))
+            })
+            .collect()
+    }
+
+    fn quota_for(&self, scope: RateLimitScope) -> Option<Quota> {
+        match scope {
+            RateLimitScope::User => Some(self.config.user),
+            RateLimitScope::Channel => Some(self.config.channel),
+            RateLimitScope::Guild => Some(self.config.guild),
+            RateLimitScope::Global => Some(self.config.global),
+            RateLimitScope::Budget(class) => self.config.costs.budgets.get(&class).copied(),
+        }
+    }
+
+    /// Drops entries that have been idle past `idle_ttl` and hold no pending usage
+    pub fn collect_garbage(&mut self) -> usize {
+        self.collect_garbage_at(Instant::now())
+    }
+
+    fn collect_garbage_at(&mut self, now: Instant) -> usize {
+        let before = self.limits.len();
+        let idle_ttl = self.config.idle_ttl;
+        let quotas: HashMap<RateLimitScope, Quota> = self
+            .limits
+            .keys()
+            .filter_map(|(scope, _)| Some((*scope, self.quota_for(*scope)?)))
+            .collect();
+
+        // Entries whose budget was removed from the config are dropped once idle
+        self.limits.retain(|(scope, _), entry| {
+            now.duration_since(entry.last_seen) < idle_ttl
+                || quotas.get(scope).map(|quota| !entry.state.is_idle(now, quota)).unwrap_or(false)
+        });
+        self.last_gc = now;
+        before - self.limits.len()
+    }
+
+    pub fn tracked_keys(&self) -> usize {
+        self.limits.len()
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota(max_requests: u32, window_secs: u64) -> Quota {
+        Quota {
+            max_requests,
+            window: Duration::from_secs(window_secs),
+        }
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    /// Fills a fresh limiter with `count` requests at `now`
+    fn filled(algorithm: RateLimitAlgorithm, quota: &Quota, now: Instant, count: u32) -> LimiterState {
+        let mut state = LimiterState::new(algorithm, quota, now);
+        for _ in 0..count {
+            assert!(state.peek(now, quota, 1).is_ok());
+            state.commit(now, quota, 1);
+        }
+        state
+    }
+
+    fn assert_wait(result: Result<(), Duration>, expected: f64) {
+        let wait = result.expect_err("should be limited").as_secs_f64();
+        assert!((wait - expected).abs() < 1e-6, "waited {wait}s, expected {expected}s");
+    }
+
+    fn limiter(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::with_config(RateLimitConfig {
+            tiers: HashMap::from([
+                ("moderator".to_string(), TierLimits { multiplier: 1.0, exempt: true }),
+                ("premium".to_string(), TierLimits { multiplier: 3.0, exempt: false }),
+                ("booster".to_string(), TierLimits { multiplier: 2.0, exempt: false }),
+            ]),
+            ..config
+        })
+    }
+
+    fn request<'a>(user_id: &'a str, channel_id: Option<&'a str>, roles: &'a [String]) -> RateLimitRequest<'a> {
+        RateLimitRequest {
+            user_id,
+            channel_id,
+            guild_id: None,
+            roles,
+        }
+    }
+
+    #[test]
+    fn token_bucket_refills_smoothly_up_to_the_burst() {
+        let quota = quota(4, 4);
+        let start = Instant::now();
+        let state = filled(RateLimitAlgorithm::TokenBucket, &quota, start, 4);
+        assert_wait(state.peek(start, &quota, 1), 1.0);
+        assert_wait(state.peek(start + secs(0.25), &quota, 1), 0.75);
+        assert!(state.peek(start + secs(1.0), &quota, 1).is_ok());
+        assert_wait(state.peek(start + secs(1.0), &quota, 2), 1.0);
+        // Refill stops at the quota, and no request costs more than the whole quota
+        assert!(state.peek(start + secs(100.0), &quota, 9).is_ok());
+        assert!(state.is_idle(start + secs(4.0), &quota));
+        assert!(!state.is_idle(start + secs(3.0), &quota));
+    }
+
+    #[test]
+    fn sliding_log_waits_for_the_oldest_requests_to_leave_the_window() {
+        let quota = quota(3, 10);
+        let start = Instant::now();
+        let mut state = LimiterState::new(RateLimitAlgorithm::SlidingLog, &quota, start);
+        for at in [0.0, 2.0, 4.0] {
+            state.commit(start + secs(at), &quota, 1);
+        }
+        assert_wait(state.peek(start + secs(5.0), &quota, 1), 5.0);
+        assert_wait(state.peek(start + secs(5.0), &quota, 2), 7.0);
+        assert!(state.peek(start + secs(10.0), &quota, 1).is_ok());
+        assert_wait(state.peek(start + secs(10.0), &quota, 2), 2.0);
+        assert!(state.is_idle(start + secs(14.0), &quota));
+    }
+
+    #[test]
+    fn gcra_allows_a_burst_then_one_request_per_interval() {
+        let quota = quota(4, 4);
+        let start = Instant::now();
+        let mut state = filled(RateLimitAlgorithm::Gcra, &quota, start, 4);
+        assert_wait(state.peek(start, &quota, 1), 1.0);
+        assert_wait(state.peek(start + secs(0.5), &quota, 1), 0.5);
+        assert!(state.peek(start + secs(1.0), &quota, 1).is_ok());
+
+        state.commit(start + secs(1.0), &quota, 1);
+        assert_wait(state.peek(start + secs(1.0), &quota, 1), 1.0);
+        assert!(state.is_idle(start + secs(5.0), &quota));
+    }
+
+    #[test]
+    fn a_denied_request_charges_no_scope() {
+        let mut limiter = limiter(RateLimitConfig {
+            user: Quota::per_minute(5),
+            channel: Quota::per_minute(1),
+            ..RateLimitConfig::default()
+        });
+        assert!(limiter.check(&request("u1", Some("c1"), &[])).is_ok());
+        let denied = limiter.check(&request("u1", Some("c1"), &[])).unwrap_err();
+        assert_eq!(denied.scope, RateLimitScope::Channel);
+
+        // The denial left the user's quota alone: four more fit elsewhere
+        for channel in ["c2", "c3", "c4", "c5"] {
+            assert!(limiter.check(&request("u1", Some(channel), &[])).is_ok());
+        }
+        assert_eq!(limiter.check(&request("u1", Some("c6"), &[])).unwrap_err().scope, RateLimitScope::User);
+    }
+
+    #[test]
+    fn retry_after_is_the_longest_wait_across_scopes() {
+        let mut limiter = limiter(RateLimitConfig {
+            user: quota(1, 10),
+            channel: quota(1, 60),
+            ..RateLimitConfig::default()
+        });
+        limiter.check(&request("u1", Some("c1"), &[])).unwrap();
+        let denied = limiter.check(&request("u1", Some("c1"), &[])).unwrap_err();
+        assert_eq!(denied.scope, RateLimitScope::Channel);
+        assert!(denied.retry_after > Duration::from_secs(59), "{denied}");
+    }
+
+    #[test]
+    fn the_most_generous_role_sets_the_tier() {
+        let limiter = limiter(RateLimitConfig::default());
+        let tier = |roles: &[&str]| {
+            let roles: Vec<String> = roles.iter().map(|role| role.to_string()).collect();
+            limiter.tier_for(&request("u1", None, &roles))
+        };
+        assert_eq!(tier(&[]), None);
+        assert_eq!(tier(&["student"]), None);
+        assert_eq!(tier(&["booster", "premium"]).unwrap().multiplier, 3.0);
+        assert_eq!(tier(&["premium", "booster"]).unwrap().multiplier, 3.0);
+        assert!(tier(&["premium", "moderator", "booster"]).unwrap().exempt);
+    }
+
+    #[test]
+    fn tiers_scale_user_limits_and_budgets() {
+        let mut limiter = limiter(RateLimitConfig {
+            user: Quota::per_minute(2),
+            global: Quota::per_minute(1000),
+            ..RateLimitConfig::default()
+        });
+        let premium = vec!["premium".to_string()];
+        for _ in 0..6 {
+            assert!(limiter.check(&request("u1", None, &premium)).is_ok());
+        }
+        assert_eq!(limiter.check(&request("u1", None, &premium)).unwrap_err().scope, RateLimitScope::User);
+
+        // Exempt users skip budgets entirely
+        let moderator = vec!["moderator".to_string()];
+        for _ in 0..10 {
+            assert!(limiter.charge_command(&request("u2", None, &moderator), "practice_test").is_ok());
+            assert!(limiter.check(&request("u2", None, &moderator)).is_ok());
+        }
+        // Others run out of study generation after three practice tests
+        for _ in 0..3 {
+            assert!(limiter.charge_command(&request("u3", None, &[]), "practice_test").is_ok());
+        }
+        let denied = limiter.charge_command(&request("u3", None, &[]), "practice_test").unwrap_err();
+        assert_eq!(denied.scope, RateLimitScope::Budget(ResourceClass::StudyGeneration));
+    }
+
+    #[test]
+    fn garbage_collection_drops_only_idle_keys() {
+        let mut limiter = limiter(RateLimitConfig {
+            user: quota(1, 60),
+            idle_ttl: Duration::from_secs(1),
+            gc_interval: Duration::from_secs(3600),
+            ..RateLimitConfig::default()
+        });
+        let start = Instant::now();
+        let checks = |user: &str| vec![(RateLimitScope::User, user.to_string(), quota(1, 60), 1)];
+        limiter.apply(checks("u1"), start).unwrap();
+        limiter.apply(checks("u2"), start + secs(50.0)).unwrap();
+        assert_eq!(limiter.tracked_keys(), 2);
+
+        // Past idle_ttl but still refilling: kept, or the user would get a fresh quota
+        assert_eq!(limiter.collect_garbage_at(start + secs(30.0)), 0);
+        assert_eq!(limiter.collect_garbage_at(start + secs(61.0)), 1);
+        assert_eq!(limiter.tracked_keys(), 1);
+        assert_eq!(limiter.collect_garbage_at(start + secs(111.0)), 1);
+        assert_eq!(limiter.tracked_keys(), 0);
+    }
+}
@@ -0,0 +1,419 @@
+/*!
+ * gunnchAI3k Performance Engine - Spaced Repetition
+ * Per-user flashcard review scheduling with SM-2 or FSRS
+ */
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use anyhow::{bail, Result};
+
+use crate::Flashcard;
+
+const DAY_SECS: f64 = 86_400.0;
+
+/// How well a card was recalled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grade {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl Grade {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "again" | "1" => Ok(Grade::Again),
+            "hard" | "2" => Ok(Grade::Hard),
+            "good" | "3" => Ok(Grade::Good),
+            "easy" | "4" => Ok(Grade::Easy),
+            other => bail!("unknown grade '{}' (expected again, hard, good or easy)", other),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Grade::Again => "again",
+            Grade::Hard => "hard",
+            Grade::Good => "good",
+            Grade::Easy => "easy",
+        }
+    }
+
+    /// 1..=4, as FSRS numbers its ratings
+    fn rating(&self) -> f64 {
+        match self {
+            Grade::Again => 1.0,
+            Grade::Hard => 2.0,
+            Grade::Good => 3.0,
+            Grade::Easy => 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewAlgorithm {
+    /// SuperMemo 2 with Anki-style hard and easy grades
+    Sm2,
+    /// Free Spaced Repetition Scheduler (v4.5 model)
+    Fsrs,
+}
+
+impl ReviewAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewAlgorithm::Sm2 => "sm2",
+            ReviewAlgorithm::Fsrs => "fsrs",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw {
+            "sm2" => Ok(ReviewAlgorithm::Sm2),
+            "fsrs" => Ok(ReviewAlgorithm::Fsrs),
+            other => bail!("unknown review algorithm '{}'", other),
+        }
+    }
+}
+
+/// Published FSRS v4.5 default parameters
+pub const FSRS_DEFAULT_WEIGHTS: [f64; 17] = [
+    0.4872, 1.4003, 3.7145, 13.8206, 5.1618, 1.2298, 0.8975, 0.031, 1.6474, 0.1367, 1.0461, 2.1072, 0.0793, 0.3246,
+    1.587, 0.2272, 2.8755,
+];
+
+const FSRS_DECAY: f64 = -0.5;
+const FSRS_FACTOR: f64 = 19.0 / 81.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReviewConfig {
+    pub algorithm: ReviewAlgorithm,
+    /// Probability of recall FSRS schedules for
+    pub desired_retention: f64,
+    pub max_interval_days: f64,
+    /// A forgotten card comes back after this delay, whatever the algorithm
+    pub relearn_delay: Duration,
+    pub fsrs_weights: [f64; 17],
+}
+
+impl Default for ReviewConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: ReviewAlgorithm::Fsrs,
+            desired_retention: 0.9,
+            max_interval_days: 36_500.0,
+            relearn_delay: Duration::from_secs(10 * 60),
+            fsrs_weights: FSRS_DEFAULT_WEIGHTS,
+        }
+    }
+}
+
+/// One user's schedule for one card
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CardState {
+    pub user_id: String,
+    pub card_id: String,
+    pub algorithm: ReviewAlgorithm,
+    /// SM-2 ease factor, at least 1.3
+    pub ease: f64,
+    pub interval_days: f64,
+    /// Unix seconds
+    pub due: u64,
+    /// Successful reviews in a row
+    pub reps: u32,
+    pub lapses: u32,
+    /// FSRS memory stability in days
+    pub stability: f64,
+    /// FSRS difficulty, 1..10
+    pub difficulty: f64,
+    pub last_review: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewLogEntry {
+    pub id: String,
+    pub user_id: String,
+    pub card_id: String,
+    pub grade: Grade,
+    pub algorithm: ReviewAlgorithm,
+    pub reviewed_at: u64,
+    /// Days since the previous review; zero for a new card
+    pub elapsed_days: f64,
+    pub interval_before: f64,
+    pub interval_after: f64,
+    pub ease: f64,
+    pub stability: f64,
+    pub difficulty: f64,
+}
+
+/// The new schedule and the log line for one review
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewOutcome {
+    pub state: CardState,
+    pub log: ReviewLogEntry,
+}
+
+/// A card to study now; `state` is None for a card never reviewed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DueCard {
+    pub card: Flashcard,
+    pub state: Option<CardState>,
+}
+
+/// Computes schedules; storage lives in `StudyStore`
+#[derive(Debug, Clone, Default)]
+pub struct ReviewScheduler {
+    config: ReviewConfig,
+}
+
+impl ReviewScheduler {
+    pub fn new(config: ReviewConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ReviewConfig {
+        &self.config
+    }
+
+    /// Schedule for a card nobody has reviewed yet. `Flashcard.difficulty`
+    /// (1 easy .. 10 hard) sets the starting ease and FSRS difficulty.
+    pub fn initial_state(&self, user_id: &str, card: &Flashcard, now: u64) -> CardState {
+        let prior = prior(card.difficulty);
+        CardState {
+            user_id: user_id.to_string(),
+            card_id: card.id.clone(),
+            algorithm: self.config.algorithm,
+            ease: 2.8 - 0.6 * prior,
+            interval_days: 0.0,
+            due: now,
+            reps: 0,
+            lapses: 0,
+            stability: 0.0,
+            difficulty: 1.0 + 9.0 * prior,
+            last_review: None,
+        }
+    }
+
+    pub fn review(&self, card: &Flashcard, state: Option<CardState>, user_id: &str, grade: Grade, now: u64) -> ReviewOutcome {
+        let previous = state.unwrap_or_else(|| self.initial_state(user_id, card, now));
+        let elapsed_days = previous
+            .last_review
+            .map(|last| now.saturating_sub(last) as f64 / DAY_SECS)
+            .unwrap_or(0.0);
+
+        let mut state = previous.clone();
+        state.algorithm = self.config.algorithm;
+        match self.config.algorithm {
+            ReviewAlgorithm::Sm2 => self.sm2(&mut state, grade),
+            ReviewAlgorithm::Fsrs => self.fsrs(&mut state, grade, elapsed_days),
+        }
+
+        if grade == Grade::Again {
+            if previous.reps > 0 {
+                state.lapses += 1;
+            }
+            state.reps = 0;
+            state.due = now + self.config.relearn_delay.as_secs();
+        } else {
+            state.reps += 1;
+            state.interval_days = state.interval_days.clamp(1.0, self.config.max_interval_days);
+            state.due = now + (state.interval_days * DAY_SECS) as u64;
+        }
+        state.last_review = Some(now);
+
+        let log = ReviewLogEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: state.user_id.clone(),
+            card_id: state.card_id.clone(),
+            grade,
+            algorithm: state.algorithm,
+            reviewed_at: now,
+            elapsed_days,
+            interval_before: previous.interval_days,
+            interval_after: state.interval_days,
+            ease: state.ease,
+            stability: state.stability,
+            difficulty: state.difficulty,
+        };
+        ReviewOutcome { state, log }
+    }
+
+    fn sm2(&self, state: &mut CardState, grade: Grade) {
+        // SM-2 quality: again 1, hard 3, good 4, easy 5
+        let quality: f64 = match grade {
+            Grade::Again => 1.0,
+            Grade::Hard => 3.0,
+            Grade::Good => 4.0,
+            Grade::Easy => 5.0,
+        };
+        state.ease = (state.ease + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02)).max(1.3);
+
+        state.interval_days = match (grade, state.reps) {
+            (Grade::Again, _) => 0.0,
+            (_, 0) => match grade {
+                Grade::Easy => 4.0,
+                _ => 1.0,
+            },
+            (_, 1) => match grade {
+                Grade::Hard => 3.0,
+                Grade::Easy => 8.0,
+                _ => 6.0,
+            },
+            (Grade::Hard, _) => state.interval_days * 1.2,
+            (Grade::Easy, _) => state.interval_days * state.ease * 1.3,
+            _ => state.interval_days * state.ease,
+        }
+        .round();
+    }
+
+    fn fsrs(&self, state: &mut CardState, grade: Grade, elapsed_days: f64) {
+        let w = &self.config.fsrs_weights;
+        let rating = grade.rating();
+        let initial_difficulty = |rating: f64| (w[4] - (rating - 3.0) * w[5]).clamp(1.0, 10.0);
+
+        if state.last_review.is_none() || state.stability <= 0.0 {
+            if state.last_review.is_some() {
+                // Coming from SM-2: carry the learned interval and ease across
+                state.stability = state.interval_days.max(w[0]);
+                state.difficulty = (11.0 - 4.0 * (state.ease - 1.3)).clamp(1.0, 10.0);
+            } else {
+                state.stability = w[grade as usize];
+                // Blend the card's own prior with the first rating
+                state.difficulty = (0.5 * state.difficulty + 0.5 * initial_difficulty(rating)).clamp(1.0, 10.0);
+                state.interval_days = if grade == Grade::Again { 0.0 } else { self.fsrs_interval(state.stability) };
+                return;
+            }
+        }
+
+        let retrievability = (1.0 + FSRS_FACTOR * elapsed_days / state.stability).powf(FSRS_DECAY);
+        let difficulty = state.difficulty - w[6] * (rating - 3.0);
+        state.difficulty = (w[7] * initial_difficulty(3.0) + (1.0 - w[7]) * difficulty).clamp(1.0, 10.0);
+
+        state.stability = if grade == Grade::Again {
+            w[11] * state.difficulty.powf(-w[12]) * ((state.stability + 1.0).powf(w[13]) - 1.0) * (w[14] * (1.0 - retrievability)).exp()
+        } else {
+            let hard_penalty = if grade == Grade::Hard { w[15] } else { 1.0 };
+            let easy_bonus = if grade == Grade::Easy { w[16] } else { 1.0 };
+            state.stability
+                * (1.0
+                    + w[8].exp()
+                        * (11.0 - state.difficulty)
+                        * state.stability.powf(-w[9])
+                        * ((w[10] * (1.0 - retrievability)).exp() - 1.0)
+                        * hard_penalty
+                        * easy_bonus)
+        }
+        .max(0.01);
+        state.interval_days = if grade == Grade::Again { 0.0 } else { self.fsrs_interval(state.stability) };
+    }
+
+    /// Days until recall probability falls to the desired retention
+    fn fsrs_interval(&self, stability: f64) -> f64 {
+        let retention = self.config.desired_retention.clamp(0.5, 0.99);
+        (stability / FSRS_FACTOR * (retention.powf(1.0 / FSRS_DECAY) - 1.0)).round()
+    }
+}
+
+/// `Flashcard.difficulty` mapped onto 0 (easiest) ..= 1 (hardest)
+fn prior(difficulty: u8) -> f64 {
+    (f64::from(difficulty.clamp(1, 10)) - 1.0) / 9.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn card(difficulty: u8) -> Flashcard {
+        Flashcard {
+            id: "card".to_string(),
+            question: "P(A|B)?".to_string(),
+            answer: "P(A∩B)/P(B)".to_string(),
+            subject: "probability".to_string(),
+            difficulty,
+            created_at: NOW,
+            topic: None,
+        }
+    }
+
+    fn scheduler(algorithm: ReviewAlgorithm) -> ReviewScheduler {
+        ReviewScheduler::new(ReviewConfig { algorithm, ..ReviewConfig::default() })
+    }
+
+    /// Reviews in sequence, each on the day the previous one made it due
+    fn intervals(scheduler: &ReviewScheduler, card: &Flashcard, grades: &[Grade]) -> (Vec<f64>, CardState) {
+        let mut state = None;
+        let mut now = NOW;
+        let mut intervals = Vec::new();
+        for grade in grades {
+            let outcome = scheduler.review(card, state, "u1", *grade, now);
+            intervals.push(outcome.state.interval_days);
+            now = outcome.state.due;
+            state = Some(outcome.state);
+        }
+        (intervals, state.unwrap())
+    }
+
+    #[test]
+    fn fsrs_first_review_uses_published_initial_stability() {
+        // At 90% retention the FSRS interval equals the stability
+        let fsrs = scheduler(ReviewAlgorithm::Fsrs);
+        for (grade, stability, interval) in [(Grade::Hard, 1.4003, 1.0), (Grade::Good, 3.7145, 4.0), (Grade::Easy, 13.8206, 14.0)] {
+            let outcome = fsrs.review(&card(5), None, "u1", grade, NOW);
+            assert_eq!(outcome.state.stability, stability);
+            assert_eq!(outcome.state.interval_days, interval);
+            assert_eq!(outcome.state.due, NOW + interval as u64 * 86_400);
+        }
+
+        let again = fsrs.review(&card(5), None, "u1", Grade::Again, NOW);
+        assert_eq!(again.state.stability, 0.4872);
+        assert_eq!(again.state.due, NOW + 600);
+        assert_eq!(again.state.reps, 0);
+    }
+
+    #[test]
+    fn fsrs_second_review_follows_the_v4_5_stability_formula() {
+        let (intervals, state) = intervals(&scheduler(ReviewAlgorithm::Fsrs), &card(5), &[Grade::Good, Grade::Good]);
+        // D0(good) = 5.1618 blended with the card's prior of 5.0, then mean-reverted by w7;
+        // recalled at R = 0.8935 after 4 days, S' = 14.957
+        assert!((state.difficulty - 5.083_408).abs() < 1e-6, "{}", state.difficulty);
+        assert!((state.stability - 14.957_059).abs() < 1e-5, "{}", state.stability);
+        assert_eq!(intervals, [4.0, 15.0]);
+    }
+
+    #[test]
+    fn fsrs_lower_retention_gives_longer_intervals() {
+        let relaxed = ReviewScheduler::new(ReviewConfig { desired_retention: 0.8, ..ReviewConfig::default() });
+        let outcome = relaxed.review(&card(5), None, "u1", Grade::Good, NOW);
+        // S / F * (0.8^-2 - 1) = 3.7145 * 81/19 * 0.5625
+        assert_eq!(outcome.state.interval_days, 9.0);
+    }
+
+    #[test]
+    fn sm2_follows_the_classic_interval_sequence() {
+        let sm2 = scheduler(ReviewAlgorithm::Sm2);
+        // Difficulty 1 starts at ease 2.8; "good" (q = 4) leaves the ease alone
+        let (intervals, state) = intervals(&sm2, &card(1), &[Grade::Good, Grade::Good, Grade::Good, Grade::Good]);
+        assert_eq!(intervals, [1.0, 6.0, 17.0, 48.0]);
+        assert!((state.ease - 2.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sm2_ease_changes_by_the_published_deltas() {
+        let sm2 = scheduler(ReviewAlgorithm::Sm2);
+        let start = sm2.initial_state("u1", &card(1), NOW).ease;
+        let ease = |grade| sm2.review(&card(1), None, "u1", grade, NOW).state.ease - start;
+        assert!((ease(Grade::Easy) - 0.1).abs() < 1e-12);
+        assert!((ease(Grade::Hard) + 0.14).abs() < 1e-12);
+        assert!((ease(Grade::Again) + 0.54).abs() < 1e-12);
+
+        // Never below 1.3, and a lapse counts only after a success
+        let (_, state) = intervals(&sm2, &card(10), &[Grade::Good, Grade::Again, Grade::Again, Grade::Again, Grade::Again]);
+        assert_eq!(state.ease, 1.3);
+        assert_eq!(state.lapses, 1);
+        assert_eq!(state.reps, 0);
+    }
+}
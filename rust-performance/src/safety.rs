@@ -0,0 +1,606 @@
+/*!
+ * gunnchAI3k Performance Engine - Content Safety
+ * Wordlist classifier for harassment, hate, sexual content, threats and self-harm
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use anyhow::{bail, Context, Result};
+
+use crate::spam::{is_combining, is_invisible};
+
+const BUILTIN_WORDLIST: &str = include_str!("../data/safety_wordlist.tsv");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyCategory {
+    Harassment,
+    Hate,
+    Sexual,
+    Threat,
+    SelfHarm,
+    Profanity,
+}
+
+impl SafetyCategory {
+    pub const ALL: [SafetyCategory; 6] = [
+        SafetyCategory::Harassment,
+        SafetyCategory::Hate,
+        SafetyCategory::Sexual,
+        SafetyCategory::Threat,
+        SafetyCategory::SelfHarm,
+        SafetyCategory::Profanity,
+    ];
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "harassment" => Some(SafetyCategory::Harassment),
+            "hate" => Some(SafetyCategory::Hate),
+            "sexual" => Some(SafetyCategory::Sexual),
+            "threat" => Some(SafetyCategory::Threat),
+            "self_harm" => Some(SafetyCategory::SelfHarm),
+            "profanity" => Some(SafetyCategory::Profanity),
+            _ => None,
+        }
+    }
+}
+
+/// One wordlist entry, stored normalised
+#[derive(Debug, Clone)]
+struct SafetyTerm {
+    term: String,
+    words: Vec<String>,
+    /// The last word matches any word it starts
+    prefix: bool,
+    category: SafetyCategory,
+    weight: f64,
+}
+
+/// Terms and phrases with the category they signal
+#[derive(Debug, Clone)]
+pub struct SafetyLexicon {
+    terms: Vec<SafetyTerm>,
+}
+
+impl SafetyLexicon {
+    /// Parses `term<TAB>category<TAB>weight` lines; `#` starts a comment and a
+    /// trailing `*` makes the last word a prefix
+    pub fn parse(raw: &str) -> Result<Self> {
+        let mut lexicon = Self { terms: Vec::new() };
+
+        for (line_number, line) in raw.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let [term, category, weight] = columns[..] else {
+                bail!("wordlist line {}: expected term, category and weight", line_number + 1);
+            };
+            let category = SafetyCategory::parse(category)
+                .with_context(|| format!("wordlist line {}: unknown category '{}'", line_number + 1, category))?;
+            let weight = weight
+                .parse::<f64>()
+                .with_context(|| format!("wordlist line {}: invalid weight", line_number + 1))?;
+            lexicon.add_term(term, category, weight);
+        }
+
+        Ok(lexicon)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read safety wordlist {}", path.display()))?;
+        Self::parse(&raw)
+    }
+
+    pub fn add_term(&mut self, term: &str, category: SafetyCategory, weight: f64) {
+        let (body, prefix) = match term.trim().strip_suffix('*') {
+            Some(body) => (body, true),
+            None => (term.trim(), false),
+        };
+        let words = normalise(body);
+        if words.is_empty() {
+            return;
+        }
+        self.terms.push(SafetyTerm {
+            term: term.trim().to_lowercase(),
+            words,
+            prefix,
+            category,
+            weight: weight.clamp(0.0, 1.0),
+        });
+    }
+
+    /// Adds every term from another lexicon
+    pub fn extend(&mut self, other: SafetyLexicon) {
+        self.terms.extend(other.terms);
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+impl Default for SafetyLexicon {
+    fn default() -> Self {
+        Self::parse(BUILTIN_WORDLIST).expect("built-in safety wordlist is valid")
+    }
+}
+
+/// A wordlist hit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SafetyMatch {
+    pub term: String,
+    pub category: SafetyCategory,
+    pub weight: f64,
+    /// Aimed at someone ("you idiot"), which also counts as harassment
+    #[serde(default)]
+    pub targeted: bool,
+}
+
+/// Per-category scores in 0..1, combined like independent evidence
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SafetyScores {
+    pub harassment: f64,
+    pub hate: f64,
+    pub sexual: f64,
+    pub threat: f64,
+    pub self_harm: f64,
+    pub profanity: f64,
+    pub matches: Vec<SafetyMatch>,
+    /// Some category reached the flag threshold
+    pub flagged: bool,
+    /// Self-harm language; escalation hooks run and the reply points to support
+    pub escalate: bool,
+}
+
+impl SafetyScores {
+    pub fn score(&self, category: SafetyCategory) -> f64 {
+        match category {
+            SafetyCategory::Harassment => self.harassment,
+            SafetyCategory::Hate => self.hate,
+            SafetyCategory::Sexual => self.sexual,
+            SafetyCategory::Threat => self.threat,
+            SafetyCategory::SelfHarm => self.self_harm,
+            SafetyCategory::Profanity => self.profanity,
+        }
+    }
+
+    fn score_mut(&mut self, category: SafetyCategory) -> &mut f64 {
+        match category {
+            SafetyCategory::Harassment => &mut self.harassment,
+            SafetyCategory::Hate => &mut self.hate,
+            SafetyCategory::Sexual => &mut self.sexual,
+            SafetyCategory::Threat => &mut self.threat,
+            SafetyCategory::SelfHarm => &mut self.self_harm,
+            SafetyCategory::Profanity => &mut self.profanity,
+        }
+    }
+
+    /// The worst category, if any scored above zero
+    pub fn highest(&self) -> Option<(SafetyCategory, f64)> {
+        SafetyCategory::ALL
+            .into_iter()
+            .map(|category| (category, self.score(category)))
+            .filter(|(_, score)| *score > 0.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyConfig {
+    /// Category score at which a message is flagged for moderators
+    pub flag_threshold: f64,
+    /// Self-harm score at which escalation hooks run
+    pub escalation_threshold: f64,
+    /// Added to harassment when an insult or profanity is aimed at "you"
+    pub targeting_boost: f64,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            flag_threshold: 0.5,
+            escalation_threshold: 0.6,
+            targeting_boost: 0.3,
+        }
+    }
+}
+
+/// Scores messages against a wordlist, honouring per-guild allowlists
+#[derive(Debug, Clone)]
+pub struct SafetyClassifier {
+    lexicon: SafetyLexicon,
+    config: SafetyConfig,
+    allowlists: HashMap<String, HashSet<String>>,
+}
+
+const SECOND_PERSON: &[&str] = &["you", "u", "ur", "your", "youre", "yourself", "ya"];
+
+impl SafetyClassifier {
+    pub fn new(lexicon: SafetyLexicon) -> Self {
+        Self {
+            lexicon,
+            config: SafetyConfig::default(),
+            allowlists: HashMap::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: SafetyConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &SafetyConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: SafetyConfig) {
+        self.config = config;
+    }
+
+    /// Adds terms, e.g. a deployment-specific slur list
+    pub fn extend_lexicon(&mut self, lexicon: SafetyLexicon) {
+        self.lexicon.extend(lexicon);
+    }
+
+    pub fn set_lexicon(&mut self, lexicon: SafetyLexicon) {
+        self.lexicon = lexicon;
+    }
+
+    /// Stops a wordlist term (as written in the list, e.g. `nazi*`) from
+    /// counting in one guild, e.g. a history server
+    pub fn allow(&mut self, guild_id: &str, term: &str) {
+        self.allowlists
+            .entry(guild_id.to_string())
+            .or_default()
+            .insert(term.trim().to_lowercase());
+    }
+
+    pub fn disallow(&mut self, guild_id: &str, term: &str) -> bool {
+        self.allowlists
+            .get_mut(guild_id)
+            .is_some_and(|terms| terms.remove(&term.trim().to_lowercase()))
+    }
+
+    pub fn allowlist(&self, guild_id: &str) -> Vec<String> {
+        let mut terms: Vec<String> = self.allowlists.get(guild_id).into_iter().flatten().cloned().collect();
+        terms.sort();
+        terms
+    }
+
+    pub fn classify(&self, message: &str, guild_id: Option<&str>) -> SafetyScores {
+        let words = normalise(message);
+        let allowed = guild_id.and_then(|guild_id| self.allowlists.get(guild_id));
+        let mut scores = SafetyScores::default();
+
+        for term in &self.lexicon.terms {
+            if allowed.is_some_and(|allowed| allowed.contains(&term.term)) {
+                continue;
+            }
+            let Some(start) = find_phrase(&words, term) else {
+                continue;
+            };
+            let targeted = matches!(term.category, SafetyCategory::Harassment | SafetyCategory::Profanity)
+                && words[start.saturating_sub(4)..start].iter().any(|word| SECOND_PERSON.contains(&word.as_str()));
+
+            combine(scores.score_mut(term.category), term.weight);
+            if targeted {
+                combine(&mut scores.harassment, self.config.targeting_boost);
+            }
+            scores.matches.push(SafetyMatch {
+                term: term.term.clone(),
+                category: term.category,
+                weight: term.weight,
+                targeted,
+            });
+        }
+
+        scores.flagged = SafetyCategory::ALL
+            .into_iter()
+            .any(|category| scores.score(category) >= self.config.flag_threshold);
+        scores.escalate = scores.self_harm >= self.config.escalation_threshold;
+        scores
+    }
+}
+
+impl Default for SafetyClassifier {
+    fn default() -> Self {
+        Self::new(SafetyLexicon::default())
+    }
+}
+
+fn combine(score: &mut f64, weight: f64) {
+    *score = 1.0 - (1.0 - *score) * (1.0 - weight);
+}
+
+/// Index of the first word where the term's phrase matches
+fn find_phrase(words: &[String], term: &SafetyTerm) -> Option<usize> {
+    let length = term.words.len();
+    if words.len() < length {
+        return None;
+    }
+    (0..=words.len() - length).find(|&start| {
+        term.words.iter().enumerate().all(|(offset, expected)| {
+            let last = offset + 1 == length;
+            word_matches(&words[start + offset], expected, last && term.prefix)
+        })
+    })
+}
+
+/// Exact match, `*` masking ("f*ck"), or stretched letters ("fuuuck")
+fn word_matches(word: &str, expected: &str, prefix: bool) -> bool {
+    let matches = |word: &str, expected: &str| {
+        if prefix {
+            word.starts_with(expected)
+        } else {
+            word == expected
+        }
+    };
+    if matches(word, expected) {
+        return true;
+    }
+    if word.contains('*') {
+        let (word, expected): (Vec<char>, Vec<char>) = (word.chars().collect(), expected.chars().collect());
+        let fits = word.len() == expected.len() || (prefix && word.len() > expected.len());
+        return fits && word.iter().zip(&expected).all(|(a, b)| *a == '*' || a == b);
+    }
+    has_stretch(word) && matches(&squeeze(word), &squeeze(expected))
+}
+
+/// Three or more of the same letter in a row
+fn has_stretch(word: &str) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    chars.windows(3).any(|run| run[0] == run[1] && run[1] == run[2])
+}
+
+fn squeeze(word: &str) -> String {
+    let mut squeezed = String::with_capacity(word.len());
+    for c in word.chars() {
+        if !squeezed.ends_with(c) {
+            squeezed.push(c);
+        }
+    }
+    squeezed
+}
+
+/// Lowercase words with obfuscation undone: combining marks, invisible
+/// characters and apostrophes dropped, look-alike Cyrillic letters and
+/// leetspeak mapped back inside words, and four or more spelled-out letters
+/// ("f u c k", "f.u.c.k") joined
+fn normalise(text: &str) -> Vec<String> {
+    let cleaned: String = text
+        .chars()
+        .filter(|c| !is_combining(*c) && !is_invisible(*c) && !matches!(c, '\'' | '’'))
+        .flat_map(char::to_lowercase)
+        .map(unconfuse)
+        .collect();
+
+    let mut words: Vec<String> = Vec::new();
+    let mut letters = String::new();
+    let flush_letters = |letters: &mut String, words: &mut Vec<String>| {
+        if letters.chars().count() >= 4 {
+            words.push(std::mem::take(letters));
+        } else {
+            words.extend(letters.chars().map(String::from));
+            letters.clear();
+        }
+    };
+
+    for raw in cleaned.split(|c: char| !(c.is_alphanumeric() || "$@!|*+".contains(c))) {
+        let word = raw.trim_matches(|c: char| "!|*".contains(c));
+        if word.is_empty() {
+            // A single separator between spelled-out letters keeps them together
+            if raw.is_empty() {
+                continue;
+            }
+            flush_letters(&mut letters, &mut words);
+            continue;
+        }
+        let word = if word.chars().any(|c| c.is_alphabetic()) { unleet(word) } else { word.to_string() };
+        if word.chars().count() == 1 && word.chars().all(char::is_alphabetic) {
+            letters.push_str(&word);
+        } else {
+            flush_letters(&mut letters, &mut words);
+            words.push(word);
+        }
+    }
+    flush_letters(&mut letters, &mut words);
+    words
+}
+
+fn unleet(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' | '|' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' | '+' => 't',
+            '8' => 'b',
+            '9' => 'g',
+            other => other,
+        })
+        .collect()
+}
+
+/// Cyrillic and Greek letters that look like Latin ones
+fn unconfuse(c: char) -> char {
+    match c {
+        'а' | 'α' => 'a',
+        'е' | 'ε' => 'e',
+        'о' | 'ο' => 'o',
+        'р' | 'ρ' => 'p',
+        'с' => 'c',
+        'х' | 'χ' => 'x',
+        'у' => 'y',
+        'і' | 'ι' => 'i',
+        'к' | 'κ' => 'k',
+        'ѕ' => 's',
+        other => other,
+    }
+}
+
+/// Everything an escalation hook needs to follow up on a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyIncident {
+    pub message_id: String,
+    pub author_id: String,
+    pub channel_id: String,
+    pub guild_id: Option<String>,
+    pub content: String,
+    pub scores: SafetyScores,
+}
+
+/// Runs when a message contains self-harm language, e.g. to page a moderator
+/// or DM the author support resources
+#[async_trait]
+pub trait SafetyEscalation: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn escalate(&self, incident: &SafetyIncident) -> Result<()>;
+}
+
+/// Writes escalations to the tracing log
+pub struct LogEscalation;
+
+#[async_trait]
+impl SafetyEscalation for LogEscalation {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    async fn escalate(&self, incident: &SafetyIncident) -> Result<()> {
+        warn!(
+            "Self-harm language from {} in channel {} (score {:.2})",
+            incident.author_id, incident.channel_id, incident.scores.self_harm
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDLIST: &str = "\
+# term\tcategory\tweight
+fuck\tprofanity\t0.5
+ass\tprofanity\t0.4
+idiot\tharassment\t0.6
+nazi*\thate\t0.8
+kill myself\tself_harm\t0.9
+hopeless\tself_harm\t0.5
+no way out\tself_harm\t0.5
+";
+
+    fn classifier() -> SafetyClassifier {
+        SafetyClassifier::new(SafetyLexicon::parse(WORDLIST).unwrap())
+    }
+
+    fn terms(message: &str) -> Vec<String> {
+        classifier().classify(message, None).matches.into_iter().map(|hit| hit.term).collect()
+    }
+
+    #[test]
+    fn obfuscated_spellings_still_match() {
+        for message in [
+            "what the 1d10t",
+            "what an id!0t",
+            // Cyrillic і and о
+            "what an іdіоt",
+            "f*ck this",
+            "f**k this",
+            "fuuuuuck this",
+            "f u c k this",
+            "f.u.c.k this",
+            "fu\u{200B}ck th\u{0301}is",
+        ] {
+            assert_eq!(terms(message).len(), 1, "{message}");
+        }
+        assert_eq!(terms("NAZIS in the history unit"), ["nazi*"]);
+    }
+
+    #[test]
+    fn innocent_words_containing_terms_do_not_match() {
+        for message in ["class starts at 9", "we assess the passage", "a b c d e", "the cocktail was classic"] {
+            assert!(terms(message).is_empty(), "{message}");
+        }
+    }
+
+    #[test]
+    fn allowlists_apply_to_their_guild_only() {
+        let mut classifier = classifier();
+        classifier.allow("history", " NAZI* ");
+        assert_eq!(classifier.allowlist("history"), ["nazi*"]);
+        assert_eq!(classifier.classify("the nazis invaded", Some("history")).hate, 0.0);
+        assert_eq!(classifier.classify("the nazis invaded", Some("general")).hate, 0.8);
+        assert_eq!(classifier.classify("the nazis invaded", None).hate, 0.8);
+
+        assert!(classifier.disallow("history", "nazi*"));
+        assert!(!classifier.disallow("history", "nazi*"));
+        assert_eq!(classifier.classify("the nazis invaded", Some("history")).hate, 0.8);
+    }
+
+    #[test]
+    fn insults_aimed_at_someone_count_as_harassment() {
+        let classifier = classifier();
+        let untargeted = classifier.classify("that idiot driver", None);
+        assert!((untargeted.harassment - 0.6).abs() < 1e-9);
+        assert!(!untargeted.matches[0].targeted);
+
+        // 1 - (1 - 0.6) * (1 - 0.3)
+        let targeted = classifier.classify("you are such an idiot", None);
+        assert!((targeted.harassment - 0.72).abs() < 1e-9);
+        assert!(targeted.matches[0].targeted);
+
+        let profanity = classifier.classify("ur a fuck", None);
+        assert!((profanity.profanity - 0.5).abs() < 1e-9);
+        assert!((profanity.harassment - 0.3).abs() < 1e-9);
+
+        // More than four words back is no longer aimed at the reader
+        assert!(!classifier.classify("you said the other guy was an idiot", None).matches[0].targeted);
+    }
+
+    #[test]
+    fn escalation_needs_enough_self_harm_evidence() {
+        let classifier = classifier();
+        assert!(classifier.classify("i want to kill myself", None).escalate);
+        let weak = classifier.classify("feeling hopeless today", None);
+        assert!(!weak.escalate && weak.flagged);
+        // Two weaker phrases combine to 0.75
+        let combined = classifier.classify("hopeless, there is no way out", None);
+        assert!((combined.self_harm - 0.75).abs() < 1e-9);
+        assert!(combined.escalate);
+
+        let strict = classifier.with_config(SafetyConfig { escalation_threshold: 0.95, ..SafetyConfig::default() });
+        assert!(!strict.classify("i want to kill myself", None).escalate);
+    }
+
+    #[test]
+    fn flagging_follows_the_highest_category() {
+        let classifier = classifier();
+        let mild = classifier.classify("kick his ass", None);
+        assert!(!mild.flagged);
+        assert_eq!(mild.highest(), Some((SafetyCategory::Profanity, 0.4)));
+        assert!(classifier.classify("idiot", None).flagged);
+        assert_eq!(classifier.classify("hello there", None), SafetyScores::default());
+    }
+
+    #[test]
+    fn the_builtin_wordlist_parses() {
+        assert!(!SafetyLexicon::default().is_empty());
+        assert!(SafetyLexicon::parse("idiot\tharassment").is_err());
+        assert!(SafetyLexicon::parse("idiot\trude\t0.5").is_err());
+    }
+}
@@ -0,0 +1,316 @@
+/*!
+ * gunnchAI3k Performance Engine - Message Scheduler
+ * Bounded priority queue with aging, load shedding and a drainable worker pool
+ */
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use anyhow::Result;
+
+use crate::metrics::LatencyHistogram;
+use crate::{MessageContext, MessageProcessor, ProcessedMessage};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerConfig {
+    pub workers: usize,
+    /// Queued (not yet running) messages; beyond this the pool sheds load
+    pub queue_capacity: usize,
+    /// A waiting message gains one priority level per interval, so chatter
+    /// is delayed by busy periods but never starved
+    pub aging_interval: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            queue_capacity: 1024,
+            aging_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SchedulerError {
+    #[error("message queue is full")]
+    Overloaded,
+    #[error("message was shed to make room for higher-priority work")]
+    Shed,
+    #[error("worker pool is shutting down")]
+    ShuttingDown,
+    #[error("message processing panicked")]
+    Panicked,
+}
+
+/// Point-in-time view of the queue, for dashboards and exporters
+#[derive(Debug, Clone)]
+pub struct QueueStats {
+    pub depth: usize,
+    /// Queued messages by their submitted priority, index 0..=10
+    pub depth_by_priority: [usize; 11],
+    pub in_flight: usize,
+    pub capacity: usize,
+    pub workers: usize,
+    pub enqueued: u64,
+    pub completed: u64,
+    pub shed: u64,
+    pub rejected: u64,
+    pub oldest_wait: Duration,
+    /// Time from submission until a worker picked the message up
+    pub wait: LatencyHistogram,
+}
+
+struct Job {
+    message: String,
+    context: MessageContext,
+    priority: u8,
+    enqueued_at: Instant,
+    reply: oneshot::Sender<Result<ProcessedMessage>>,
+}
+
+/// Ordering key: higher runs first, earlier submission breaks ties
+type JobKey = (i128, Reverse<u64>);
+
+struct QueueState {
+    jobs: BTreeMap<JobKey, Job>,
+    next_seq: u64,
+    accepting: bool,
+    in_flight: usize,
+    enqueued: u64,
+    completed: u64,
+    shed: u64,
+    rejected: u64,
+    wait: LatencyHistogram,
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    work_available: Notify,
+    config: SchedulerConfig,
+    started: Instant,
+}
+
+impl Shared {
+    /// Linear aging keeps relative order fixed over time: at any moment the
+    /// effective priority is `priority + waited / aging_interval`, which ranks
+    /// jobs the same as `priority * aging_interval - enqueued_at`.
+    fn key(&self, priority: u8, enqueued_at: Instant, seq: u64) -> JobKey {
+        let aging = self.config.aging_interval.max(Duration::from_millis(1)).as_nanos() as i128;
+        let offset = enqueued_at.duration_since(self.started).as_nanos() as i128;
+        (i128::from(priority) * aging - offset, Reverse(seq))
+    }
+}
+
+/// Runs messages through a `MessageProcessor` on a fixed set of workers,
+/// highest priority first
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    processor: Arc<MessageProcessor>,
+}
+
+impl WorkerPool {
+    pub fn start(processor: Arc<MessageProcessor>, config: SchedulerConfig) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                jobs: BTreeMap::new(),
+                next_seq: 0,
+                accepting: true,
+                in_flight: 0,
+                enqueued: 0,
+                completed: 0,
+                shed: 0,
+                rejected: 0,
+                wait: LatencyHistogram::new(),
+            }),
+            work_available: Notify::new(),
+            config: config.clone(),
+            started: Instant::now(),
+        });
+
+        let handles = (0..config.workers.max(1))
+            .map(|_| tokio::spawn(Self::work(shared.clone(), processor.clone())))
+            .collect();
+
+        Self {
+            shared,
+            handles: Mutex::new(handles),
+            processor,
+        }
+    }
+
+    /// Priority a message would be queued at, from the processor's intent classifier
+    pub async fn triage(&self, message: &str) -> u8 {
+        self.processor.triage_priority(message).await
+    }
+
+    /// Queues a message at its triaged priority and waits for the result
+    pub async fn submit(&self, message: &str, context: &MessageContext) -> Result<ProcessedMessage> {
+        let priority = self.triage(message).await;
+        self.submit_with_priority(message, context, priority).await
+    }
+
+    /// Fails fast with `SchedulerError::Overloaded` when the queue is full of
+    /// equal or higher priority work; otherwise the lowest queued message is
+    /// shed to make room
+    pub async fn submit_with_priority(&self, message: &str, context: &MessageContext, priority: u8) -> Result<ProcessedMessage> {
+        let receiver = self.enqueue(message, context, priority.min(10))?;
+        receiver.await.map_err(|_| SchedulerError::ShuttingDown)?
+    }
+
+    fn enqueue(&self, message: &str, context: &MessageContext, priority: u8) -> Result<oneshot::Receiver<Result<ProcessedMessage>>, SchedulerError> {
+        let now = Instant::now();
+        let mut state = self.shared.state.lock();
+        if !state.accepting {
+            return Err(SchedulerError::ShuttingDown);
+        }
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let key = self.shared.key(priority, now, seq);
+
+        if state.jobs.len() >= self.shared.config.queue_capacity {
+            match state.jobs.first_key_value() {
+                Some((lowest, _)) if *lowest < key => {
+                    if let Some((_, shed)) = state.jobs.pop_first() {
+                        let _ = shed.reply.send(Err(SchedulerError::Shed.into()));
+                    }
+                    state.shed += 1;
+                }
+                _ => {
+                    state.rejected += 1;
+                    return Err(SchedulerError::Overloaded);
+                }
+            }
+        }
+
+        let (reply, receiver) = oneshot::channel();
+        state.jobs.insert(
+            key,
+            Job {
+                message: message.to_string(),
+                context: context.clone(),
+                priority,
+                enqueued_at: now,
+                reply,
+            },
+        );
+        state.enqueued += 1;
+        drop(state);
+
+        self.shared.work_available.notify_one();
+        Ok(receiver)
+    }
+
+    async fn work(shared: Arc<Shared>, processor: Arc<MessageProcessor>) {
+        loop {
+            let notified = shared.work_available.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let job = {
+                let mut state = shared.state.lock();
+                match state.jobs.pop_last() {
+                    Some((_, job)) => {
+                        state.in_flight += 1;
+                        state.wait.record(job.enqueued_at.elapsed());
+                        Some(job)
+                    }
+                    None if !state.accepting => return,
+                    None => None,
+                }
+            };
+
+            let Some(job) = job else {
+                notified.await;
+                continue;
+            };
+
+            // Released even if this worker is aborted mid-job during shutdown
+            let _in_flight = InFlight(&shared);
+
+            // A panicking message fails on its own task instead of taking the worker with it
+            let Job { message, context, reply, .. } = job;
+            let processor = processor.clone();
+            let task = tokio::spawn(async move { processor.process_message_with_context(&message, &context).await });
+            let result = match task.await {
+                Ok(result) => result,
+                Err(err) => {
+                    tracing::error!("Message processing panicked: {}", err);
+                    Err(SchedulerError::Panicked.into())
+                }
+            };
+            let _ = reply.send(result);
+            shared.state.lock().completed += 1;
+        }
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.shared.state.lock();
+        let mut depth_by_priority = [0; 11];
+        let mut oldest = None::<Instant>;
+        for job in state.jobs.values() {
+            depth_by_priority[usize::from(job.priority)] += 1;
+            oldest = Some(oldest.map_or(job.enqueued_at, |oldest| oldest.min(job.enqueued_at)));
+        }
+
+        QueueStats {
+            depth: state.jobs.len(),
+            depth_by_priority,
+            in_flight: state.in_flight,
+            capacity: self.shared.config.queue_capacity,
+            workers: self.shared.config.workers.max(1),
+            enqueued: state.enqueued,
+            completed: state.completed,
+            shed: state.shed,
+            rejected: state.rejected,
+            oldest_wait: oldest.map(|oldest| oldest.elapsed()).unwrap_or_default(),
+            wait: state.wait.clone(),
+        }
+    }
+
+    /// Stops accepting messages and lets workers finish everything already
+    /// queued. Whatever is still queued after `timeout` fails with
+    /// `SchedulerError::ShuttingDown`; returns how many messages that was.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        self.shared.state.lock().accepting = false;
+        self.shared.work_available.notify_waiters();
+
+        let handles: Vec<JoinHandle<()>> = std::mem::take(&mut *self.handles.lock());
+        let aborts: Vec<_> = handles.iter().map(JoinHandle::abort_handle).collect();
+        if tokio::time::timeout(timeout, futures::future::join_all(handles)).await.is_err() {
+            aborts.iter().for_each(|abort| abort.abort());
+        }
+
+        let mut state = self.shared.state.lock();
+        let remaining = std::mem::take(&mut state.jobs);
+        let dropped = remaining.len();
+        for (_, job) in remaining {
+            let _ = job.reply.send(Err(SchedulerError::ShuttingDown.into()));
+        }
+        dropped
+    }
+}
+
+struct InFlight<'a>(&'a Shared);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().in_flight -= 1;
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for handle in self.handles.lock().iter() {
+            handle.abort();
+        }
+    }
+}
@@ -0,0 +1,397 @@
+/*!
+ * gunnchAI3k Performance Engine - Lexicon Sentiment
+ * VADER-style scoring with negation, boosters, emphasis and emoji
+ */
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use anyhow::{Context, Result};
+
+const BUILTIN_LEXICON: &str = include_str!("../data/sentiment_lexicon.tsv");
+
+/// Empirically derived constants from the VADER paper
+const BOOSTER_INCREMENT: f64 = 0.293;
+const CAPS_INCREMENT: f64 = 0.733;
+const NEGATION_SCALAR: f64 = -0.74;
+const EXCLAMATION_INCREMENT: f64 = 0.292;
+const QUESTION_INCREMENT: f64 = 0.18;
+const NORMALIZATION_ALPHA: f64 = 15.0;
+
+const NEGATIONS: &[&str] = &[
+    "not", "no", "never", "none", "nobody", "nothing", "neither", "nor", "nowhere", "cannot",
+    "cant", "can't", "dont", "don't", "doesnt", "doesn't", "didnt", "didn't", "isnt", "isn't",
+    "wasnt", "wasn't", "arent", "aren't", "aint", "ain't", "wont", "won't", "wouldnt",
+    "wouldn't", "shouldnt", "shouldn't", "couldnt", "couldn't", "without",
+];
+
+const BOOSTERS_UP: &[&str] = &[
+    "really", "very", "so", "super", "extremely", "incredibly", "absolutely", "totally", "completely",
+    "highly", "hella", "mad", "insanely", "truly", "especially", "most", "more", "too", "quite",
+];
+
+const BOOSTERS_DOWN: &[&str] = &[
+    "kinda", "sorta", "slightly", "somewhat", "barely", "hardly", "marginally", "little",
+    "less", "partly", "occasionally",
+];
+
+/// Compound score in [-1, 1] plus the share of positive, negative and neutral signal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SentimentScores {
+    pub compound: f64,
+    pub positive: f64,
+    pub negative: f64,
+    pub neutral: f64,
+}
+
+impl Default for SentimentScores {
+    fn default() -> Self {
+        Self {
+            compound: 0.0,
+            positive: 0.0,
+            negative: 0.0,
+            neutral: 1.0,
+        }
+    }
+}
+
+/// Token valences on VADER's -4..4 scale
+#[derive(Debug, Clone)]
+pub struct SentimentLexicon {
+    entries: HashMap<String, f64>,
+}
+
+impl SentimentLexicon {
+    /// Parses `token<TAB>valence[<TAB>...]` lines; extra columns (as in the
+    /// upstream VADER lexicon) and `#` comments are ignored.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let mut entries = HashMap::new();
+
+        for (line_number, line) in raw.lines().enumerate() {
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut columns = line.split('\t');
+            let token = columns.next().unwrap_or_default().trim();
+            let valence = columns
+                .next()
+                .map(str::trim)
+                .with_context(|| format!("lexicon line {}: missing valence", line_number + 1))?
+                .parse::<f64>()
+                .with_context(|| format!("lexicon line {}: invalid valence", line_number + 1))?;
+
+            entries.insert(Self::normalize_key(token), valence);
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read sentiment lexicon {}", path.display()))?;
+        Self::parse(&raw)
+    }
+
+    /// Adds or overrides entries from another lexicon
+    pub fn extend(&mut self, other: SentimentLexicon) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn valence(&self, token: &str) -> Option<f64> {
+        self.entries
+            .get(token)
+            .or_else(|| self.entries.get(&token.to_lowercase()))
+            .copied()
+    }
+
+    // Words are stored lowercase; emoticons such as ":D" keep their case
+    fn normalize_key(token: &str) -> String {
+        if token.chars().any(|c| c.is_alphanumeric()) && !token.chars().any(|c| c.is_ascii_punctuation()) {
+            token.to_lowercase()
+        } else {
+            token.to_string()
+        }
+    }
+}
+
+impl Default for SentimentLexicon {
+    fn default() -> Self {
+        Self::parse(BUILTIN_LEXICON).expect("built-in sentiment lexicon is valid")
+    }
+}
+
+struct Token<'a> {
+    text: &'a str,
+    lower: String,
+}
+
+impl SentimentLexicon {
+    /// Splits on whitespace, keeps known emoticons intact, pulls emoji out of
+    /// words ("great😀") and strips surrounding punctuation ("great!").
+    fn tokenize<'a>(&self, message: &'a str) -> Vec<Token<'a>> {
+        let mut tokens = Vec::new();
+
+        for raw in message.split_whitespace() {
+            if self.entries.contains_key(raw) {
+                tokens.push(Token { text: raw, lower: raw.to_lowercase() });
+                continue;
+            }
+
+            let mut word_start: Option<usize> = None;
+            let mut chars = raw.char_indices().peekable();
+            while let Some((index, c)) = chars.next() {
+                let next_index = chars.peek().map(|(i, _)| *i).unwrap_or(raw.len());
+                // Include a trailing variation selector so "❤️" matches
+                let emoji_end = match chars.peek() {
+                    Some((i, '\u{FE0F}')) => i + '\u{FE0F}'.len_utf8(),
+                    _ => next_index,
+                };
+
+                if !c.is_alphanumeric() && c != '\'' && self.entries.contains_key(&raw[index..emoji_end]) {
+                    if let Some(start) = word_start.take() {
+                        Self::push_word(&mut tokens, &raw[start..index]);
+                    }
+                    tokens.push(Token { text: &raw[index..emoji_end], lower: raw[index..emoji_end].to_string() });
+                    if emoji_end != next_index {
+                        chars.next();
+                    }
+                } else if word_start.is_none() {
+                    word_start = Some(index);
+                }
+            }
+
+            if let Some(start) = word_start {
+                Self::push_word(&mut tokens, &raw[start..]);
+            }
+        }
+
+        tokens
+    }
+
+    fn push_word<'a>(tokens: &mut Vec<Token<'a>>, raw: &'a str) {
+        let text = raw.trim_matches(|c: char| c.is_ascii_punctuation() && c != '\'');
+        let text = text.trim_matches('\'');
+        if !text.is_empty() {
+            tokens.push(Token { text, lower: text.to_lowercase() });
+        }
+    }
+
+    /// Scores a message. Empty or symbol-only input is fully neutral.
+    pub fn score(&self, message: &str) -> SentimentScores {
+        let tokens = self.tokenize(message);
+        if tokens.is_empty() {
+            return SentimentScores::default();
+        }
+
+        // ALL CAPS only counts as emphasis when the rest of the message isn't shouting
+        let caps_words = tokens.iter().filter(|token| is_all_caps(token.text)).count();
+        let caps_differential = caps_words > 0 && caps_words < tokens.len();
+
+        let mut valences = Vec::with_capacity(tokens.len());
+        for (index, token) in tokens.iter().enumerate() {
+            if BOOSTERS_UP.contains(&token.lower.as_str()) || BOOSTERS_DOWN.contains(&token.lower.as_str()) {
+                valences.push(0.0);
+                continue;
+            }
+
+            let mut valence = match self.valence(&token.lower).or_else(|| self.valence(token.text)) {
+                Some(valence) => valence,
+                None => {
+                    valences.push(0.0);
+                    continue;
+                }
+            };
+
+            if caps_differential && is_all_caps(token.text) {
+                valence += CAPS_INCREMENT * valence.signum();
+            }
+
+            // Boosters and negations within the three preceding words
+            for distance in 1..=3 {
+                if index < distance {
+                    break;
+                }
+                let previous = &tokens[index - distance];
+                let damping = match distance {
+                    1 => 1.0,
+                    2 => 0.95,
+                    _ => 0.9,
+                };
+
+                let mut boost = if BOOSTERS_UP.contains(&previous.lower.as_str()) {
+                    BOOSTER_INCREMENT
+                } else if BOOSTERS_DOWN.contains(&previous.lower.as_str()) {
+                    -BOOSTER_INCREMENT
+                } else {
+                    0.0
+                };
+                if boost != 0.0 && caps_differential && is_all_caps(previous.text) {
+                    boost += CAPS_INCREMENT * boost.signum();
+                }
+                valence += boost * damping * valence.signum();
+
+                if is_negation(&previous.lower) {
+                    valence *= NEGATION_SCALAR;
+                }
+            }
+
+            valences.push(valence);
+        }
+
+        // "but" shifts weight to the clause that follows it
+        if let Some(but_index) = tokens.iter().position(|token| token.lower == "but") {
+            for (index, valence) in valences.iter_mut().enumerate() {
+                if index < but_index {
+                    *valence *= 0.5;
+                } else if index > but_index {
+                    *valence *= 1.5;
+                }
+            }
+        }
+
+        let mut sum: f64 = valences.iter().sum();
+        let emphasis = punctuation_emphasis(message);
+        if sum > 0.0 {
+            sum += emphasis;
+        } else if sum < 0.0 {
+            sum -= emphasis;
+        }
+        let compound = sum / (sum * sum + NORMALIZATION_ALPHA).sqrt();
+
+        // Proportions follow VADER: each sentiment-bearing word adds |valence| + 1
+        let mut positive = 0.0;
+        let mut negative = 0.0;
+        let mut neutral = 0.0;
+        for valence in &valences {
+            if *valence > 0.0 {
+                positive += valence + 1.0;
+            } else if *valence < 0.0 {
+                negative += valence.abs() + 1.0;
+            } else {
+                neutral += 1.0;
+            }
+        }
+        if positive > negative {
+            positive += emphasis;
+        } else if negative > positive {
+            negative += emphasis;
+        }
+
+        let total = positive + negative + neutral;
+        SentimentScores {
+            compound: compound.clamp(-1.0, 1.0),
+            positive: positive / total,
+            negative: negative / total,
+            neutral: neutral / total,
+        }
+    }
+}
+
+fn is_all_caps(text: &str) -> bool {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() > 1 && letters.iter().all(|c| c.is_uppercase())
+}
+
+fn is_negation(word: &str) -> bool {
+    NEGATIONS.contains(&word) || word.ends_with("n't")
+}
+
+fn punctuation_emphasis(message: &str) -> f64 {
+    let exclamations = message.matches('!').count().min(4) as f64;
+    let questions = message.matches('?').count();
+    let question_emphasis = match questions {
+        0 | 1 => 0.0,
+        2 | 3 => questions as f64 * QUESTION_INCREMENT,
+        _ => 0.96,
+    };
+    exclamations * EXCLAMATION_INCREMENT + question_emphasis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compound(message: &str) -> f64 {
+        SentimentLexicon::default().score(message).compound
+    }
+
+    /// The compound score VADER gives a raw valence sum
+    fn compound_of(sum: f64) -> f64 {
+        sum / (sum * sum + NORMALIZATION_ALPHA).sqrt()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn matches_the_vader_reference_sentence() {
+        // Upstream valences for the words the built-in lexicon leaves out
+        let mut lexicon = SentimentLexicon::default();
+        lexicon.extend(SentimentLexicon::parse("handsome\t2.2\nfunny\t1.9").unwrap());
+        let scores = lexicon.score("VADER is smart, handsome, and funny.");
+        assert_close(scores.compound, 0.8316);
+        assert_close(scores.positive + scores.negative + scores.neutral, 1.0);
+        assert_eq!(scores.negative, 0.0);
+    }
+
+    #[test]
+    fn single_words_use_the_normalised_valence() {
+        assert_close(compound("The food is good"), 0.4404);
+        assert_eq!(SentimentLexicon::default().score("the table is brown"), SentimentScores {
+            neutral: 1.0,
+            ..SentimentScores::default()
+        });
+        assert_eq!(SentimentLexicon::default().score("?!"), SentimentScores::default());
+    }
+
+    #[test]
+    fn negation_flips_words_up_to_three_tokens_later() {
+        let good = compound("good");
+        assert_close(compound("not good"), compound_of(1.9 * NEGATION_SCALAR));
+        assert!(compound("isn't very good") < 0.0);
+        assert!(compound("not the food good") < 0.0);
+        assert_close(compound("not the food here good"), good);
+    }
+
+    #[test]
+    fn but_shifts_weight_to_the_second_clause() {
+        assert!(compound("the food is good and the service is bad") > compound("the food is good but the service is bad"));
+        assert!(compound("the service is bad but the food is good") > 0.0);
+        assert_close(compound("good but bad"), compound_of(1.9 * 0.5 - 2.5 * 1.5));
+    }
+
+    #[test]
+    fn all_caps_emphasises_only_against_lowercase_text() {
+        assert_close(compound("the food is GOOD"), compound_of(1.9 + CAPS_INCREMENT));
+        assert_close(compound("THE FOOD IS GOOD"), compound("the food is good"));
+    }
+
+    #[test]
+    fn boosters_strengthen_or_soften_the_next_word() {
+        assert_close(compound("very good"), compound_of(1.9 + BOOSTER_INCREMENT));
+        assert_close(compound("slightly good"), compound_of(1.9 - BOOSTER_INCREMENT));
+        assert_close(compound("very bad"), compound_of(-2.5 - BOOSTER_INCREMENT));
+        // Two words back is damped
+        assert_close(compound("very, food good"), compound_of(1.9 + BOOSTER_INCREMENT * 0.95));
+    }
+
+    #[test]
+    fn exclamations_add_emphasis_up_to_four() {
+        assert_close(compound("good!!!"), compound_of(1.9 + 3.0 * EXCLAMATION_INCREMENT));
+        assert_close(compound("bad!!!"), compound_of(-2.5 - 3.0 * EXCLAMATION_INCREMENT));
+        assert_close(compound("good!!!!!!!"), compound("good!!!!"));
+        assert!(compound("good!") < compound("good!!!"));
+    }
+}
@@ -0,0 +1,385 @@
+/*!
+ * gunnchAI3k Performance Engine - Practice Test Sessions
+ * Timed attempts with shuffling, server-side deadlines and auto-grading
+ */
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::answers::{AnswerFormat, AnswerSpec, Response};
+use crate::{PracticeTest, Question};
+
+/// Longest typed answer accepted, in characters across all parts
+const MAX_RESPONSE_CHARS: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionOptions {
+    pub shuffle_questions: bool,
+    /// Shuffles each question's options; answers are still graded against the original order
+    pub shuffle_options: bool,
+    /// Fixes the shuffle, e.g. so a whole class gets the same order
+    pub seed: Option<u64>,
+    /// Slack after the deadline for answers already in flight
+    pub grace: Duration,
+    /// Overrides the test's own `time_limit`
+    pub time_limit: Option<Duration>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            shuffle_questions: false,
+            shuffle_options: false,
+            seed: None,
+            grace: Duration::from_secs(2),
+            time_limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SessionError {
+    #[error("practice test {0} not found")]
+    TestNotFound(String),
+    #[error("test attempt {0} not found")]
+    AttemptNotFound(String),
+    #[error("time is up for this attempt")]
+    TimeUp,
+    #[error("this attempt has already been submitted")]
+    AlreadySubmitted,
+    #[error("question {0} is not part of this attempt")]
+    UnknownQuestion(usize),
+    #[error("option {0} is not valid for this question")]
+    InvalidOption(u8),
+    #[error("the test changed since this attempt started")]
+    TestChanged,
+    #[error("question {0} takes a different kind of answer")]
+    WrongAnswerKind(usize),
+    #[error("answers are limited to {0} characters")]
+    ResponseTooLong(usize),
+}
+
+/// A question as the student sees it: served order, no answer or explanation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServedQuestion {
+    /// Position in this attempt, starting at 0
+    pub position: usize,
+    pub question_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// Served index of the option already chosen, if any
+    pub chosen: Option<u8>,
+    pub format: AnswerFormat,
+    /// A typed or multi-part answer already given; choices are in `chosen`
+    pub response: Option<Response>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradedQuestion {
+    pub position: usize,
+    pub question_id: String,
+    pub question: String,
+    pub chosen: Option<String>,
+    pub correct: String,
+    pub is_correct: bool,
+    pub explanation: String,
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// 0..=1; partial for some parts right or a missing unit
+    #[serde(default)]
+    pub credit: f64,
+    #[serde(default)]
+    pub feedback: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<GradedPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GradedPart {
+    pub prompt: String,
+    pub chosen: Option<String>,
+    pub correct: String,
+    pub credit: f64,
+    pub feedback: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptResult {
+    pub attempt_id: String,
+    pub test_id: String,
+    pub user_id: String,
+    pub subject: String,
+    /// Percentage of questions answered correctly, 0..=100
+    pub score: f64,
+    pub correct: usize,
+    pub total: usize,
+    pub unanswered: usize,
+    pub questions: Vec<GradedQuestion>,
+    pub started_at: u64,
+    pub submitted_at: u64,
+    pub duration_secs: u64,
+    /// Submitted automatically, or late, after the deadline
+    pub timed_out: bool,
+}
+
+/// One student's run through a practice test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestAttempt {
+    pub id: String,
+    pub test_id: String,
+    pub user_id: String,
+    pub subject: String,
+    /// Unix seconds
+    pub started_at: u64,
+    pub deadline: u64,
+    pub grace_secs: u64,
+    /// Served position → index into the test's questions
+    pub question_order: Vec<usize>,
+    /// Per served position: served option → original option index
+    pub option_order: Vec<Vec<usize>>,
+    /// Per served position; choices hold the original option index
+    pub answers: Vec<Option<Response>>,
+    pub result: Option<AttemptResult>,
+}
+
+impl TestAttempt {
+    pub fn start(test: &PracticeTest, user_id: &str, options: &SessionOptions, now: u64) -> Self {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut rng = SplitMix64::new(options.seed.unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0));
+
+        let mut question_order: Vec<usize> = (0..test.questions.len()).collect();
+        if options.shuffle_questions {
+            rng.shuffle(&mut question_order);
+        }
+        let option_order = question_order
+            .iter()
+            .map(|&index| {
+                let mut order: Vec<usize> = (0..test.questions[index].choices().len()).collect();
+                if options.shuffle_options {
+                    rng.shuffle(&mut order);
+                }
+                order
+            })
+            .collect();
+
+        let time_limit = options
+            .time_limit
+            .map(|limit| limit.as_secs())
+            .unwrap_or(u64::from(test.time_limit));
+        Self {
+            id,
+            test_id: test.id.clone(),
+            user_id: user_id.to_string(),
+            subject: test.subject.clone(),
+            started_at: now,
+            deadline: now + time_limit,
+            grace_secs: options.grace.as_secs(),
+            answers: vec![None; question_order.len()],
+            question_order,
+            option_order,
+            result: None,
+        }
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        self.result.is_some()
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.deadline + self.grace_secs
+    }
+
+    pub fn time_remaining(&self, now: u64) -> Duration {
+        Duration::from_secs(self.deadline.saturating_sub(now))
+    }
+
+    fn question<'a>(&self, test: &'a PracticeTest, position: usize) -> Result<&'a Question, SessionError> {
+        let index = *self.question_order.get(position).ok_or(SessionError::UnknownQuestion(position))?;
+        test.questions.get(index).ok_or(SessionError::TestChanged)
+    }
+
+    pub fn served_question(&self, test: &PracticeTest, position: usize) -> Result<ServedQuestion, SessionError> {
+        let question = self.question(test, position)?;
+        let order = &self.option_order[position];
+        let choices = question.choices();
+        let options = order
+            .iter()
+            .map(|&index| choices.get(index).cloned().ok_or(SessionError::TestChanged))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (chosen, response) = match &self.answers[position] {
+            Some(Response::Choice(original)) => (
+                order
+                    .iter()
+                    .position(|&index| index == usize::from(*original))
+                    .map(|served| served as u8),
+                None,
+            ),
+            other => (None, other.clone()),
+        };
+        let format = match question.answer_spec() {
+            AnswerSpec::Choice { .. } => AnswerFormat::Choice { options: options.clone() },
+            spec => spec.format(),
+        };
+        Ok(ServedQuestion {
+            position,
+            question_id: question.id.clone(),
+            question: question.question.clone(),
+            options,
+            chosen,
+            format,
+            response,
+        })
+    }
+
+    pub fn served_questions(&self, test: &PracticeTest) -> Result<Vec<ServedQuestion>, SessionError> {
+        (0..self.question_order.len())
+            .map(|position| self.served_question(test, position))
+            .collect()
+    }
+
+    /// Records (or changes) the answer at a served position, by served option index
+    pub fn answer(&mut self, position: usize, option: u8, now: u64) -> Result<(), SessionError> {
+        self.check_open(now)?;
+        let order = self.option_order.get(position).ok_or(SessionError::UnknownQuestion(position))?;
+        let original = *order.get(usize::from(option)).ok_or(SessionError::InvalidOption(option))?;
+        self.answers[position] = Some(Response::Choice(original as u8));
+        Ok(())
+    }
+
+    /// Records a typed answer to a numeric, expression or free-text question
+    pub fn answer_text(&mut self, test: &PracticeTest, position: usize, text: &str, now: u64) -> Result<(), SessionError> {
+        self.check_open(now)?;
+        match self.question(test, position)?.answer_spec() {
+            AnswerSpec::Choice { .. } | AnswerSpec::MultiPart { .. } => return Err(SessionError::WrongAnswerKind(position)),
+            _ => {}
+        }
+        if text.chars().count() > MAX_RESPONSE_CHARS {
+            return Err(SessionError::ResponseTooLong(MAX_RESPONSE_CHARS));
+        }
+        self.answers[position] = Some(Response::Text(text.trim().to_string()));
+        Ok(())
+    }
+
+    /// Records the answers to a multi-part question, one entry per part
+    pub fn answer_parts(&mut self, test: &PracticeTest, position: usize, parts: Vec<Option<String>>, now: u64) -> Result<(), SessionError> {
+        self.check_open(now)?;
+        match self.question(test, position)?.answer_spec() {
+            AnswerSpec::MultiPart { parts: expected } if parts.len() <= expected.len() => {}
+            _ => return Err(SessionError::WrongAnswerKind(position)),
+        }
+        let length: usize = parts.iter().flatten().map(|part| part.chars().count()).sum();
+        if length > MAX_RESPONSE_CHARS {
+            return Err(SessionError::ResponseTooLong(MAX_RESPONSE_CHARS));
+        }
+        self.answers[position] = Some(Response::Parts(parts));
+        Ok(())
+    }
+
+    fn check_open(&self, now: u64) -> Result<(), SessionError> {
+        if self.is_submitted() {
+            return Err(SessionError::AlreadySubmitted);
+        }
+        if self.is_expired(now) {
+            return Err(SessionError::TimeUp);
+        }
+        Ok(())
+    }
+
+    /// Grades every question and stores the result on the attempt
+    pub fn submit(&mut self, test: &PracticeTest, now: u64) -> Result<&AttemptResult, SessionError> {
+        if self.is_submitted() {
+            return Err(SessionError::AlreadySubmitted);
+        }
+
+        let mut questions = Vec::with_capacity(self.question_order.len());
+        for position in 0..self.question_order.len() {
+            let question = self.question(test, position)?;
+            let spec = question.answer_spec();
+            let response = self.answers[position].as_ref();
+            let (check, part_checks) = spec.grade(response);
+            let parts = match (&spec, response) {
+                (AnswerSpec::MultiPart { parts }, response) => parts
+                    .iter()
+                    .zip(part_checks)
+                    .enumerate()
+                    .map(|(index, (part, check))| GradedPart {
+                        prompt: part.prompt.clone(),
+                        chosen: match response {
+                            Some(Response::Parts(given)) => given.get(index).cloned().flatten(),
+                            _ => None,
+                        },
+                        correct: part.answer.display(),
+                        credit: check.credit,
+                        feedback: check.feedback,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            questions.push(GradedQuestion {
+                position,
+                question_id: question.id.clone(),
+                question: question.question.clone(),
+                chosen: response.map(|response| response.display(&spec)),
+                correct: spec.display(),
+                is_correct: check.is_correct(),
+                explanation: question.explanation.clone(),
+                topic: question.topic.clone(),
+                credit: check.credit,
+                feedback: check.feedback,
+                parts,
+            });
+        }
+
+        let total = questions.len();
+        let correct = questions.iter().filter(|question| question.is_correct).count();
+        let credit: f64 = questions.iter().map(|question| question.credit).sum();
+        let submitted_at = now.min(self.deadline + self.grace_secs).max(self.started_at);
+        self.result = Some(AttemptResult {
+            attempt_id: self.id.clone(),
+            test_id: self.test_id.clone(),
+            user_id: self.user_id.clone(),
+            subject: self.subject.clone(),
+            score: if total == 0 { 0.0 } else { 100.0 * credit / total as f64 },
+            correct,
+            total,
+            unanswered: self.answers.iter().filter(|answer| answer.is_none()).count(),
+            questions,
+            started_at: self.started_at,
+            submitted_at,
+            duration_secs: submitted_at - self.started_at,
+            timed_out: now > self.deadline,
+        });
+        Ok(self.result.as_ref().expect("result was just set"))
+    }
+}
+
+/// Small seedable generator for shuffles and sampling; not for anything security-related
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fisher-Yates
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
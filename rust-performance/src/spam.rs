@@ -0,0 +1,393 @@
+/*!
+ * gunnchAI3k Performance Engine - Spam and Raid Detection
+ * Scored verdicts for duplicate floods, mention storms, link floods, raids and character abuse
+ */
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpamConfig {
+    /// How far back duplicate, mention and link history is kept
+    pub window: Duration,
+    /// Distinct accounts posting the same text within `window`
+    pub duplicate_accounts: usize,
+    /// Shorter text only counts as a cross-account duplicate when it carries a link or
+    /// mention or comes from a newcomer, so "thanks" or "+1" from a busy channel is fine
+    pub duplicate_min_length: usize,
+    /// Times one account may repeat the same text within `window`
+    pub repeat_limit: usize,
+    /// Mentions in a single message
+    pub mention_limit: usize,
+    /// Mentions by one account across `window`
+    pub mention_burst: usize,
+    /// Links in a single message
+    pub link_limit: usize,
+    /// Links by one account across `window`
+    pub link_burst: usize,
+    /// Accounts that joined this recently are treated as newcomers
+    pub newcomer_age: Duration,
+    /// Joins within `raid_window` that mean a raid is under way
+    pub raid_joins: usize,
+    pub raid_window: Duration,
+    /// Combining marks per letter above which text counts as zalgo
+    pub zalgo_ratio: f64,
+    /// Invisible characters tolerated in one message
+    pub invisible_limit: usize,
+    /// Score needed for each action
+    pub warn_at: f64,
+    pub delete_at: f64,
+    pub timeout_at: f64,
+    pub timeout: Duration,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(30),
+            duplicate_accounts: 3,
+            duplicate_min_length: 20,
+            repeat_limit: 3,
+            mention_limit: 5,
+            mention_burst: 10,
+            link_limit: 3,
+            link_burst: 6,
+            newcomer_age: Duration::from_secs(10 * 60),
+            raid_joins: 10,
+            raid_window: Duration::from_secs(60),
+            zalgo_ratio: 0.5,
+            invisible_limit: 3,
+            warn_at: 0.4,
+            delete_at: 0.7,
+            timeout_at: 0.9,
+            timeout: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// One reason a message looks like spam
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpamSignal {
+    /// The same text from several accounts, typical of a raid script
+    DuplicateAcrossAccounts { accounts: usize },
+    RepeatedMessage { count: usize },
+    MentionStorm { mentions: usize },
+    MassMention,
+    LinkFlood { links: usize },
+    /// A newcomer posting links or mentions shortly after joining
+    JoinAndSpam { seconds_since_join: u64 },
+    RaidInProgress { recent_joins: usize },
+    Zalgo { combining_marks: usize },
+    InvisibleCharacters { count: usize },
+}
+
+impl SpamSignal {
+    /// How strongly this signal alone suggests spam, 0..1
+    fn weight(&self) -> f64 {
+        match self {
+            // Stays below the default `delete_at` unless another signal agrees
+            SpamSignal::DuplicateAcrossAccounts { accounts } => (0.4 + 0.05 * *accounts as f64).min(0.65),
+            SpamSignal::RepeatedMessage { count } => (0.3 + 0.1 * *count as f64).min(0.8),
+            SpamSignal::MentionStorm { mentions } => (0.5 + 0.05 * *mentions as f64).min(0.95),
+            SpamSignal::MassMention => 0.5,
+            SpamSignal::LinkFlood { links } => (0.4 + 0.05 * *links as f64).min(0.85),
+            SpamSignal::JoinAndSpam { .. } => 0.5,
+            SpamSignal::RaidInProgress { .. } => 0.3,
+            SpamSignal::Zalgo { .. } => 0.7,
+            SpamSignal::InvisibleCharacters { .. } => 0.4,
+        }
+    }
+}
+
+/// What the moderation layer should do, mildest first. `Timeout` implies deleting the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SuggestedAction {
+    Allow,
+    Warn,
+    Delete,
+    Timeout { seconds: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamVerdict {
+    /// Combined score, 0..1
+    pub score: f64,
+    pub signals: Vec<SpamSignal>,
+    pub action: SuggestedAction,
+}
+
+impl SpamVerdict {
+    pub fn clean() -> Self {
+        Self {
+            score: 0.0,
+            signals: Vec::new(),
+            action: SuggestedAction::Allow,
+        }
+    }
+
+    pub fn is_spam(&self) -> bool {
+        self.action >= SuggestedAction::Delete
+    }
+}
+
+impl Default for SpamVerdict {
+    fn default() -> Self {
+        Self::clean()
+    }
+}
+
+struct GuildActivity {
+    /// (when, author, normalised text)
+    messages: VecDeque<(Instant, String, String)>,
+    /// Normalised text -> author -> copies in `messages`, so duplicate checks don't scan the window
+    copies: HashMap<String, HashMap<String, usize>>,
+    joins: VecDeque<Instant>,
+    joined_at: HashMap<String, Instant>,
+    users: HashMap<String, UserActivity>,
+}
+
+#[derive(Default)]
+struct UserActivity {
+    /// (when, mentions, links)
+    events: VecDeque<(Instant, usize, usize)>,
+}
+
+impl GuildActivity {
+    fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            copies: HashMap::new(),
+            joins: VecDeque::new(),
+            joined_at: HashMap::new(),
+            users: HashMap::new(),
+        }
+    }
+
+    fn prune(&mut self, now: Instant, config: &SpamConfig) {
+        while self.messages.front().is_some_and(|(at, _, _)| now.duration_since(*at) > config.window) {
+            let Some((_, author, text)) = self.messages.pop_front() else { break };
+            self.forget_copy(&author, &text);
+        }
+        while self.joins.front().is_some_and(|at| now.duration_since(*at) > config.raid_window) {
+            self.joins.pop_front();
+        }
+        self.joined_at.retain(|_, at| now.duration_since(*at) <= config.newcomer_age);
+        self.users.retain(|_, user| {
+            while user.events.front().is_some_and(|(at, _, _)| now.duration_since(*at) > config.window) {
+                user.events.pop_front();
+            }
+            !user.events.is_empty()
+        });
+    }
+
+    fn record_message(&mut self, now: Instant, author: &str, text: String) {
+        *self.copies.entry(text.clone()).or_default().entry(author.to_string()).or_insert(0) += 1;
+        self.messages.push_back((now, author.to_string(), text));
+    }
+
+    fn forget_copy(&mut self, author: &str, text: &str) {
+        let Some(authors) = self.copies.get_mut(text) else { return };
+        if let Some(count) = authors.get_mut(author) {
+            *count -= 1;
+            if *count == 0 {
+                authors.remove(author);
+            }
+        }
+        if authors.is_empty() {
+            self.copies.remove(text);
+        }
+    }
+}
+
+static USER_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<@[!&]?\d+>").unwrap());
+static MASS_MENTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@(everyone|here)\b").unwrap());
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)\bhttps?://\S+|\bdiscord\.gg/\S+").unwrap());
+
+/// Tracks recent activity per guild and scores each message
+pub struct SpamDetector {
+    config: SpamConfig,
+    guilds: HashMap<String, GuildActivity>,
+}
+
+impl SpamDetector {
+    pub fn new(config: SpamConfig) -> Self {
+        Self {
+            config,
+            guilds: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &SpamConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: SpamConfig) {
+        self.config = config;
+    }
+
+    /// Direct messages get one bucket per author, so unrelated users never look like a raid
+    fn guild(&mut self, guild_id: Option<&str>, author_id: &str) -> &mut GuildActivity {
+        let key = match guild_id {
+            Some(guild_id) => guild_id.to_string(),
+            None => format!("@dm:{author_id}"),
+        };
+        self.guilds.entry(key).or_insert_with(GuildActivity::new)
+    }
+
+    /// Records a member join, for raid and join-and-spam detection
+    pub fn record_join(&mut self, guild_id: &str, user_id: &str) {
+        self.record_join_at(guild_id, user_id, Instant::now());
+    }
+
+    pub fn record_join_at(&mut self, guild_id: &str, user_id: &str, now: Instant) {
+        let config = self.config.clone();
+        let guild = self.guild(Some(guild_id), user_id);
+        guild.prune(now, &config);
+        guild.joins.push_back(now);
+        guild.joined_at.insert(user_id.to_string(), now);
+    }
+
+    pub fn check(&mut self, guild_id: Option<&str>, author_id: &str, message: &str) -> SpamVerdict {
+        self.check_at(guild_id, author_id, message, Instant::now())
+    }
+
+    /// Scores a message and records it as recent activity
+    pub fn check_at(&mut self, guild_id: Option<&str>, author_id: &str, message: &str, now: Instant) -> SpamVerdict {
+        let config = self.config.clone();
+        let guild = self.guild(guild_id, author_id);
+        guild.prune(now, &config);
+
+        let mut signals = Vec::new();
+        let text = normalise(message);
+        let mentions = USER_MENTION.find_iter(message).count();
+        let links = LINK.find_iter(message).count();
+
+        // Duplicates, across accounts and from the same account
+        if !text.is_empty() {
+            let substantial = text.chars().count() >= config.duplicate_min_length
+                || mentions > 0
+                || links > 0
+                || guild.joined_at.contains_key(author_id);
+            let authors = guild.copies.get(&text);
+            let repeats = authors.and_then(|authors| authors.get(author_id)).copied().unwrap_or(0);
+            let accounts = authors.map_or(0, |authors| authors.len()) + usize::from(repeats == 0);
+            if substantial && accounts >= config.duplicate_accounts {
+                signals.push(SpamSignal::DuplicateAcrossAccounts { accounts });
+            }
+            if repeats + 1 > config.repeat_limit {
+                signals.push(SpamSignal::RepeatedMessage { count: repeats + 1 });
+            }
+        }
+
+        // Mentions and links, in this message and across the window
+        if MASS_MENTION.is_match(message) {
+            signals.push(SpamSignal::MassMention);
+        }
+        let user = guild.users.entry(author_id.to_string()).or_default();
+        user.events.push_back((now, mentions, links));
+        let recent_mentions: usize = user.events.iter().map(|(_, mentions, _)| mentions).sum();
+        let recent_links: usize = user.events.iter().map(|(_, _, links)| links).sum();
+        if mentions > config.mention_limit || recent_mentions > config.mention_burst {
+            signals.push(SpamSignal::MentionStorm { mentions: recent_mentions });
+        }
+        if links > config.link_limit || recent_links > config.link_burst {
+            signals.push(SpamSignal::LinkFlood { links: recent_links });
+        }
+
+        // Newcomers and raids
+        if let Some(joined) = guild.joined_at.get(author_id) {
+            if mentions > 0 || links > 0 || MASS_MENTION.is_match(message) {
+                signals.push(SpamSignal::JoinAndSpam {
+                    seconds_since_join: now.duration_since(*joined).as_secs(),
+                });
+            }
+            if guild.joins.len() >= config.raid_joins {
+                signals.push(SpamSignal::RaidInProgress { recent_joins: guild.joins.len() });
+            }
+        }
+
+        // Character abuse
+        let (letters, combining, invisible) = character_counts(message);
+        if combining >= 3 && combining as f64 > config.zalgo_ratio * letters.max(1) as f64 {
+            signals.push(SpamSignal::Zalgo { combining_marks: combining });
+        }
+        if invisible > config.invisible_limit {
+            signals.push(SpamSignal::InvisibleCharacters { count: invisible });
+        }
+
+        guild.record_message(now, author_id, text);
+        self.verdict(signals)
+    }
+
+    fn verdict(&self, signals: Vec<SpamSignal>) -> SpamVerdict {
+        // Independent evidence: 1 - product of (1 - weight)
+        let score = 1.0 - signals.iter().map(|signal| 1.0 - signal.weight()).product::<f64>();
+        let action = if score >= self.config.timeout_at {
+            SuggestedAction::Timeout { seconds: self.config.timeout.as_secs() }
+        } else if score >= self.config.delete_at {
+            SuggestedAction::Delete
+        } else if score >= self.config.warn_at {
+            SuggestedAction::Warn
+        } else {
+            SuggestedAction::Allow
+        };
+        SpamVerdict { score, signals, action }
+    }
+
+    /// Drops guilds with no recent activity
+    pub fn collect_garbage(&mut self) {
+        let now = Instant::now();
+        let config = self.config.clone();
+        self.guilds.retain(|_, guild| {
+            guild.prune(now, &config);
+            !guild.messages.is_empty() || !guild.joins.is_empty() || !guild.joined_at.is_empty()
+        });
+    }
+}
+
+impl Default for SpamDetector {
+    fn default() -> Self {
+        Self::new(SpamConfig::default())
+    }
+}
+
+pub(crate) fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+pub(crate) fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{180E}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}'
+            | '\u{115F}' | '\u{1160}' | '\u{2800}' | '\u{3164}' | '\u{FEFF}' | '\u{FFA0}'
+    )
+}
+
+/// (letters, combining marks, invisible characters)
+fn character_counts(message: &str) -> (usize, usize, usize) {
+    message.chars().fold((0, 0, 0), |(letters, combining, invisible), c| {
+        (
+            letters + usize::from(c.is_alphanumeric() && !is_combining(c)),
+            combining + usize::from(is_combining(c)),
+            invisible + usize::from(is_invisible(c)),
+        )
+    })
+}
+
+/// Lowercase text without combining marks or invisible characters, whitespace collapsed,
+/// so trivially varied copies still match
+fn normalise(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| !is_combining(*c) && !is_invisible(*c))
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,1085 @@
+/*!
+ * gunnchAI3k Performance Engine - Study Storage
+ * Durable flashcards, practice tests and progress behind a pluggable store
+ */
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use anyhow::{Context, Result};
+
+use crate::sessions::{AttemptResult, TestAttempt};
+use crate::review::{CardState, DueCard, Grade, ReviewAlgorithm, ReviewLogEntry, ReviewOutcome};
+use crate::{Flashcard, PracticeTest, Question, UserProgress};
+
+/// Changes one user's progress record; runs inside the store's transaction
+pub type ProgressUpdate = Box<dyn FnOnce(&mut UserProgress) + Send>;
+
+/// Schedules one review from the card and its current state (None if never
+/// reviewed) and counts it in the user's progress for the card's subject;
+/// runs inside the store's transaction
+pub type ReviewUpdate = Box<dyn FnOnce(&Flashcard, Option<CardState>, &mut UserProgress) -> ReviewOutcome + Send>;
+
+/// Changes a test attempt; an error leaves the stored attempt untouched
+pub type AttemptUpdate = Box<dyn FnOnce(&mut TestAttempt) -> Result<()> + Send>;
+
+/// Counts a graded attempt in the user's progress for its subject; runs
+/// inside the store's transaction
+pub type ResultUpdate = Box<dyn FnOnce(&AttemptResult, &mut UserProgress) + Send>;
+
+/// Where `StudyEngine` keeps what students build
+#[async_trait]
+pub trait StudyStore: Send + Sync {
+    async fn insert_flashcard(&self, card: &Flashcard) -> Result<()>;
+
+    /// Stores a batch of cards atomically, so a failed import leaves no partial deck
+    async fn insert_flashcards(&self, cards: &[Flashcard]) -> Result<()>;
+
+    async fn flashcard(&self, id: &str) -> Result<Option<Flashcard>>;
+
+    async fn flashcards_by_subject(&self, subject: &str) -> Result<Vec<Flashcard>>;
+
+    async fn delete_flashcard(&self, id: &str) -> Result<bool>;
+
+    /// Stores a test and its questions atomically
+    async fn insert_practice_test(&self, test: &PracticeTest) -> Result<()>;
+
+    async fn practice_test(&self, id: &str) -> Result<Option<PracticeTest>>;
+
+    async fn user_progress(&self, user_id: &str, subject: &str) -> Result<Option<UserProgress>>;
+
+    async fn progress_for_user(&self, user_id: &str) -> Result<Vec<UserProgress>>;
+
+    /// Reads, changes and writes one progress record as a single transaction,
+    /// starting from a fresh record if there is none. Returns the new value.
+    async fn update_user_progress(&self, user_id: &str, subject: &str, update: ProgressUpdate) -> Result<UserProgress>;
+
+    async fn card_state(&self, user_id: &str, card_id: &str) -> Result<Option<CardState>>;
+
+    /// Saves the new schedule, appends to the review log and updates the
+    /// user's progress atomically. Returns None if the card does not exist.
+    async fn apply_review(&self, user_id: &str, card_id: &str, update: ReviewUpdate) -> Result<Option<ReviewOutcome>>;
+
+    /// Cards due at `now`, most overdue first, then cards never reviewed
+    async fn due_cards(&self, user_id: &str, subject: Option<&str>, now: u64, limit: usize) -> Result<Vec<DueCard>>;
+
+    /// Newest first, optionally for one card
+    async fn review_log(&self, user_id: &str, card_id: Option<&str>, limit: usize) -> Result<Vec<ReviewLogEntry>>;
+
+    async fn insert_attempt(&self, attempt: &TestAttempt) -> Result<()>;
+
+    async fn attempt(&self, id: &str) -> Result<Option<TestAttempt>>;
+
+    /// Reads, changes and writes an attempt as one transaction. Returns None
+    /// if there is no such attempt.
+    async fn update_attempt(&self, id: &str, update: AttemptUpdate) -> Result<Option<TestAttempt>>;
+
+    /// Like `update_attempt`, and if the update graded the attempt, records the
+    /// result in the user's progress in the same transaction
+    async fn submit_attempt(&self, id: &str, update: AttemptUpdate, progress: ResultUpdate) -> Result<Option<TestAttempt>>;
+
+    /// Newest first
+    async fn attempts_for_user(&self, user_id: &str, limit: usize) -> Result<Vec<TestAttempt>>;
+}
+
+/// Keeps everything in memory; nothing survives a restart. Meant for tests
+/// and for running without a database.
+#[derive(Default)]
+pub struct InMemoryStudyStore {
+    flashcards: Mutex<HashMap<String, Flashcard>>,
+    practice_tests: Mutex<HashMap<String, PracticeTest>>,
+    user_progress: Mutex<HashMap<(String, String), UserProgress>>,
+    card_states: Mutex<HashMap<(String, String), CardState>>,
+    review_log: Mutex<Vec<ReviewLogEntry>>,
+    attempts: Mutex<HashMap<String, TestAttempt>>,
+}
+
+impl InMemoryStudyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl StudyStore for InMemoryStudyStore {
+    async fn insert_flashcard(&self, card: &Flashcard) -> Result<()> {
+        self.flashcards.lock().insert(card.id.clone(), card.clone());
+        Ok(())
+    }
+
+    async fn insert_flashcards(&self, cards: &[Flashcard]) -> Result<()> {
+        let mut flashcards = self.flashcards.lock();
+        for card in cards {
+            flashcards.insert(card.id.clone(), card.clone());
+        }
+        Ok(())
+    }
+
+    async fn flashcard(&self, id: &str) -> Result<Option<Flashcard>> {
+        Ok(self.flashcards.lock().get(id).cloned())
+    }
+
+    async fn flashcards_by_subject(&self, subject: &str) -> Result<Vec<Flashcard>> {
+        let mut cards: Vec<Flashcard> = self
+            .flashcards
+            .lock()
+            .values()
+            .filter(|card| card.subject == subject)
+            .cloned()
+            .collect();
+        cards.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(cards)
+    }
+
+    /// Also drops the card's review state and log, as the SQLite cascade does
+    async fn delete_flashcard(&self, id: &str) -> Result<bool> {
+        if self.flashcards.lock().remove(id).is_none() {
+            return Ok(false);
+        }
+        self.card_states.lock().retain(|(_, card_id), _| card_id != id);
+        self.review_log.lock().retain(|entry| entry.card_id != id);
+        Ok(true)
+    }
+
+    async fn insert_practice_test(&self, test: &PracticeTest) -> Result<()> {
+        self.practice_tests.lock().insert(test.id.clone(), test.clone());
+        Ok(())
+    }
+
+    async fn practice_test(&self, id: &str) -> Result<Option<PracticeTest>> {
+        Ok(self.practice_tests.lock().get(id).cloned())
+    }
+
+    async fn user_progress(&self, user_id: &str, subject: &str) -> Result<Option<UserProgress>> {
+        Ok(self
+            .user_progress
+            .lock()
+            .get(&(user_id.to_string(), subject.to_string()))
+            .cloned())
+    }
+
+    async fn progress_for_user(&self, user_id: &str) -> Result<Vec<UserProgress>> {
+        let mut progress: Vec<UserProgress> = self
+            .user_progress
+            .lock()
+            .values()
+            .filter(|progress| progress.user_id == user_id)
+            .cloned()
+            .collect();
+        progress.sort_by(|a, b| a.subject.cmp(&b.subject));
+        Ok(progress)
+    }
+
+    async fn update_user_progress(&self, user_id: &str, subject: &str, update: ProgressUpdate) -> Result<UserProgress> {
+        let mut all = self.user_progress.lock();
+        let progress = all
+            .entry((user_id.to_string(), subject.to_string()))
+            .or_insert_with(|| UserProgress::new(user_id, subject, chrono::Utc::now().timestamp() as u64));
+        update(progress);
+        Ok(progress.clone())
+    }
+
+    async fn card_state(&self, user_id: &str, card_id: &str) -> Result<Option<CardState>> {
+        Ok(self
+            .card_states
+            .lock()
+            .get(&(user_id.to_string(), card_id.to_string()))
+            .cloned())
+    }
+
+    async fn apply_review(&self, user_id: &str, card_id: &str, update: ReviewUpdate) -> Result<Option<ReviewOutcome>> {
+        let Some(card) = self.flashcards.lock().get(card_id).cloned() else {
+            return Ok(None);
+        };
+        let mut states = self.card_states.lock();
+        let mut all = self.user_progress.lock();
+        let key = (user_id.to_string(), card_id.to_string());
+        let progress = all
+            .entry((user_id.to_string(), card.subject.clone()))
+            .or_insert_with(|| UserProgress::new(user_id, &card.subject, chrono::Utc::now().timestamp() as u64));
+        let outcome = update(&card, states.get(&key).cloned(), progress);
+        states.insert(key, outcome.state.clone());
+        self.review_log.lock().push(outcome.log.clone());
+        Ok(Some(outcome))
+    }
+
+    async fn due_cards(&self, user_id: &str, subject: Option<&str>, now: u64, limit: usize) -> Result<Vec<DueCard>> {
+        let flashcards = self.flashcards.lock();
+        let states = self.card_states.lock();
+        let mut due: Vec<DueCard> = flashcards
+            .values()
+            .filter(|card| subject.is_none_or(|subject| card.subject == subject))
+            .filter_map(|card| {
+                let state = states.get(&(user_id.to_string(), card.id.clone())).cloned();
+                match &state {
+                    Some(state) if state.due > now => None,
+                    _ => Some(DueCard { card: card.clone(), state }),
+                }
+            })
+            .collect();
+        due.sort_by(|a, b| {
+            let order = |due: &DueCard| (due.state.is_none(), due.state.as_ref().map_or(due.card.created_at, |state| state.due));
+            order(a).cmp(&order(b)).then_with(|| a.card.id.cmp(&b.card.id))
+        });
+        due.truncate(limit);
+        Ok(due)
+    }
+
+    async fn review_log(&self, user_id: &str, card_id: Option<&str>, limit: usize) -> Result<Vec<ReviewLogEntry>> {
+        Ok(self
+            .review_log
+            .lock()
+            .iter()
+            .rev()
+            .filter(|entry| entry.user_id == user_id && card_id.is_none_or(|card_id| entry.card_id == card_id))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_attempt(&self, attempt: &TestAttempt) -> Result<()> {
+        self.attempts.lock().insert(attempt.id.clone(), attempt.clone());
+        Ok(())
+    }
+
+    async fn attempt(&self, id: &str) -> Result<Option<TestAttempt>> {
+        Ok(self.attempts.lock().get(id).cloned())
+    }
+
+    async fn update_attempt(&self, id: &str, update: AttemptUpdate) -> Result<Option<TestAttempt>> {
+        let mut attempts = self.attempts.lock();
+        let Some(stored) = attempts.get_mut(id) else {
+            return Ok(None);
+        };
+        let mut attempt = stored.clone();
+        update(&mut attempt)?;
+        *stored = attempt.clone();
+        Ok(Some(attempt))
+    }
+
+    async fn submit_attempt(&self, id: &str, update: AttemptUpdate, progress: ResultUpdate) -> Result<Option<TestAttempt>> {
+        let mut attempts = self.attempts.lock();
+        let Some(stored) = attempts.get_mut(id) else {
+            return Ok(None);
+        };
+        let mut attempt = stored.clone();
+        update(&mut attempt)?;
+        if let Some(result) = &attempt.result {
+            let mut all = self.user_progress.lock();
+            let record = all
+                .entry((result.user_id.clone(), result.subject.clone()))
+                .or_insert_with(|| UserProgress::new(&result.user_id, &result.subject, chrono::Utc::now().timestamp() as u64));
+            progress(result, record);
+        }
+        *stored = attempt.clone();
+        Ok(Some(attempt))
+    }
+
+    async fn attempts_for_user(&self, user_id: &str, limit: usize) -> Result<Vec<TestAttempt>> {
+        let mut attempts: Vec<TestAttempt> = self
+            .attempts
+            .lock()
+            .values()
+            .filter(|attempt| attempt.user_id == user_id)
+            .cloned()
+            .collect();
+        attempts.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| a.id.cmp(&b.id)));
+        attempts.truncate(limit);
+        Ok(attempts)
+    }
+}
+
+/// Schema changes, applied in order; `PRAGMA user_version` records how many
+/// have run. Only ever append.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema
+    "CREATE TABLE flashcards (
+        id TEXT PRIMARY KEY,
+        question TEXT NOT NULL,
+        answer TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        difficulty INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX flashcards_by_subject ON flashcards (subject, created_at);
+
+    CREATE TABLE practice_tests (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        difficulty INTEGER NOT NULL,
+        time_limit INTEGER NOT NULL
+    );
+
+    CREATE TABLE questions (
+        test_id TEXT NOT NULL REFERENCES practice_tests (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        id TEXT NOT NULL,
+        question TEXT NOT NULL,
+        options TEXT NOT NULL,
+        correct_answer INTEGER NOT NULL,
+        explanation TEXT NOT NULL,
+        PRIMARY KEY (test_id, position)
+    );
+
+    CREATE TABLE user_progress (
+        user_id TEXT NOT NULL,
+        subject TEXT NOT NULL,
+        flashcards_studied INTEGER NOT NULL,
+        tests_completed INTEGER NOT NULL,
+        average_score REAL NOT NULL,
+        last_studied INTEGER NOT NULL,
+        PRIMARY KEY (user_id, subject)
+    );",
+    // 2: spaced-repetition state and review log
+    "CREATE TABLE card_states (
+        user_id TEXT NOT NULL,
+        card_id TEXT NOT NULL REFERENCES flashcards (id) ON DELETE CASCADE,
+        algorithm TEXT NOT NULL,
+        ease REAL NOT NULL,
+        interval_days REAL NOT NULL,
+        due INTEGER NOT NULL,
+        reps INTEGER NOT NULL,
+        lapses INTEGER NOT NULL,
+        stability REAL NOT NULL,
+        difficulty REAL NOT NULL,
+        last_review INTEGER,
+        PRIMARY KEY (user_id, card_id)
+    );
+    CREATE INDEX card_states_due ON card_states (user_id, due);
+
+    CREATE TABLE review_log (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        card_id TEXT NOT NULL REFERENCES flashcards (id) ON DELETE CASCADE,
+        grade TEXT NOT NULL,
+        algorithm TEXT NOT NULL,
+        reviewed_at INTEGER NOT NULL,
+        elapsed_days REAL NOT NULL,
+        interval_before REAL NOT NULL,
+        interval_after REAL NOT NULL,
+        ease REAL NOT NULL,
+        stability REAL NOT NULL,
+        difficulty REAL NOT NULL
+    );
+    CREATE INDEX review_log_by_user ON review_log (user_id, reviewed_at);",
+    // 3: practice test attempts; the full attempt is kept as JSON
+    "CREATE TABLE test_attempts (
+        id TEXT PRIMARY KEY,
+        user_id TEXT NOT NULL,
+        test_id TEXT NOT NULL REFERENCES practice_tests (id) ON DELETE CASCADE,
+        started_at INTEGER NOT NULL,
+        deadline INTEGER NOT NULL,
+        submitted_at INTEGER,
+        score REAL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX test_attempts_by_user ON test_attempts (user_id, started_at);",
+    // 4: topics and learning statistics; mastery and weekly history are JSON
+    "ALTER TABLE flashcards ADD COLUMN topic TEXT;
+    ALTER TABLE questions ADD COLUMN topic TEXT;
+    ALTER TABLE user_progress ADD COLUMN score_variance REAL NOT NULL DEFAULT 0;
+    ALTER TABLE user_progress ADD COLUMN current_streak INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE user_progress ADD COLUMN longest_streak INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE user_progress ADD COLUMN time_on_task_secs INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE user_progress ADD COLUMN topics TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE user_progress ADD COLUMN weekly TEXT NOT NULL DEFAULT '[]';",
+    // 5: non-multiple-choice answers, as JSON; NULL for multiple choice
+    "ALTER TABLE questions ADD COLUMN answer TEXT;",
+];
+
+/// SQLite-backed store. Queries run on the blocking thread pool so they never
+/// stall the runtime; one connection is shared behind a mutex.
+pub struct SqliteStudyStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStudyStore {
+    /// Opens (or creates) a database file and brings its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).with_context(|| format!("failed to open study database {}", path.display()))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::from_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        Self::migrate(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Applies every migration newer than the database, each in its own transaction
+    fn migrate(conn: &mut Connection) -> Result<()> {
+        let current: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if current > MIGRATIONS.len() {
+            anyhow::bail!(
+                "study database schema version {} is newer than this build supports ({})",
+                current,
+                MIGRATIONS.len()
+            );
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)
+                .with_context(|| format!("study database migration {} failed", index + 1))?;
+            tx.pragma_update(None, "user_version", index + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> Result<usize> {
+        Ok(self.conn.lock().pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    async fn call<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || work(&mut conn.lock()))
+            .await
+            .context("study database task panicked")?
+    }
+
+    fn write_flashcard(conn: &Connection, card: &Flashcard) -> Result<()> {
+        conn.prepare_cached(
+            "INSERT INTO flashcards (id, question, answer, subject, difficulty, created_at, topic)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?
+        .execute(params![card.id, card.question, card.answer, card.subject, card.difficulty, card.created_at as i64, card.topic])?;
+        Ok(())
+    }
+
+    fn flashcard_from_row(row: &Row<'_>) -> rusqlite::Result<Flashcard> {
+        Ok(Flashcard {
+            id: row.get("id")?,
+            question: row.get("question")?,
+            answer: row.get("answer")?,
+            subject: row.get("subject")?,
+            difficulty: row.get("difficulty")?,
+            created_at: row.get::<_, i64>("created_at")? as u64,
+            topic: row.get("topic")?,
+        })
+    }
+
+    /// Reads card-state columns, which may be prefixed (`s_`) in joins
+    fn card_state_from_row(row: &Row<'_>, prefix: &str) -> rusqlite::Result<CardState> {
+        let column = |name: &str| format!("{}{}", prefix, name);
+        let algorithm: String = row.get(column("algorithm").as_str())?;
+        Ok(CardState {
+            user_id: row.get(column("user_id").as_str())?,
+            card_id: row.get(column("card_id").as_str())?,
+            algorithm: ReviewAlgorithm::parse(&algorithm).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into())
+            })?,
+            ease: row.get(column("ease").as_str())?,
+            interval_days: row.get(column("interval_days").as_str())?,
+            due: row.get::<_, i64>(column("due").as_str())? as u64,
+            reps: row.get(column("reps").as_str())?,
+            lapses: row.get(column("lapses").as_str())?,
+            stability: row.get(column("stability").as_str())?,
+            difficulty: row.get(column("difficulty").as_str())?,
+            last_review: row.get::<_, Option<i64>>(column("last_review").as_str())?.map(|at| at as u64),
+        })
+    }
+
+    fn review_log_from_row(row: &Row<'_>) -> rusqlite::Result<ReviewLogEntry> {
+        let conversion = |err: anyhow::Error| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into());
+        Ok(ReviewLogEntry {
+            id: row.get("id")?,
+            user_id: row.get("user_id")?,
+            card_id: row.get("card_id")?,
+            grade: Grade::parse(&row.get::<_, String>("grade")?).map_err(conversion)?,
+            algorithm: ReviewAlgorithm::parse(&row.get::<_, String>("algorithm")?).map_err(conversion)?,
+            reviewed_at: row.get::<_, i64>("reviewed_at")? as u64,
+            elapsed_days: row.get("elapsed_days")?,
+            interval_before: row.get("interval_before")?,
+            interval_after: row.get("interval_after")?,
+            ease: row.get("ease")?,
+            stability: row.get("stability")?,
+            difficulty: row.get("difficulty")?,
+        })
+    }
+
+    fn attempt_from_row(row: &Row<'_>) -> rusqlite::Result<TestAttempt> {
+        let data: String = row.get("data")?;
+        serde_json::from_str(&data)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into()))
+    }
+
+    fn write_attempt(conn: &Connection, attempt: &TestAttempt) -> Result<()> {
+        let result = attempt.result.as_ref();
+        conn.execute(
+            "INSERT OR REPLACE INTO test_attempts (id, user_id, test_id, started_at, deadline, submitted_at, score, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                attempt.id,
+                attempt.user_id,
+                attempt.test_id,
+                attempt.started_at as i64,
+                attempt.deadline as i64,
+                result.map(|result| result.submitted_at as i64),
+                result.map(|result| result.score),
+                serde_json::to_string(attempt)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The stored record, or a fresh one if there is none
+    fn read_progress(conn: &Connection, user_id: &str, subject: &str) -> Result<UserProgress> {
+        Ok(conn
+            .query_row(
+                "SELECT * FROM user_progress WHERE user_id = ?1 AND subject = ?2",
+                [user_id, subject],
+                Self::progress_from_row,
+            )
+            .optional()?
+            .unwrap_or_else(|| UserProgress::new(user_id, subject, chrono::Utc::now().timestamp() as u64)))
+    }
+
+    fn write_progress(conn: &Connection, progress: &UserProgress) -> Result<()> {
+        conn.execute(
+            "INSERT INTO user_progress (user_id, subject, flashcards_studied, tests_completed, average_score, last_studied,
+                score_variance, current_streak, longest_streak, time_on_task_secs, topics, weekly)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+             ON CONFLICT (user_id, subject) DO UPDATE SET
+                flashcards_studied = excluded.flashcards_studied,
+                tests_completed = excluded.tests_completed,
+                average_score = excluded.average_score,
+                last_studied = excluded.last_studied,
+                score_variance = excluded.score_variance,
+                current_streak = excluded.current_streak,
+                longest_streak = excluded.longest_streak,
+                time_on_task_secs = excluded.time_on_task_secs,
+                topics = excluded.topics,
+                weekly = excluded.weekly",
+            params![
+                progress.user_id,
+                progress.subject,
+                progress.flashcards_studied,
+                progress.tests_completed,
+                progress.average_score,
+                progress.last_studied as i64,
+                progress.score_variance,
+                progress.current_streak,
+                progress.longest_streak,
+                progress.time_on_task_secs as i64,
+                serde_json::to_string(&progress.topics)?,
+                serde_json::to_string(&progress.weekly)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn progress_from_row(row: &Row<'_>) -> rusqlite::Result<UserProgress> {
+        let json = |column: &str| -> rusqlite::Result<String> { row.get(column) };
+        let conversion = |err: serde_json::Error| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err.into());
+        Ok(UserProgress {
+            user_id: row.get("user_id")?,
+            subject: row.get("subject")?,
+            flashcards_studied: row.get("flashcards_studied")?,
+            tests_completed: row.get("tests_completed")?,
+            average_score: row.get("average_score")?,
+            score_variance: row.get("score_variance")?,
+            last_studied: row.get::<_, i64>("last_studied")? as u64,
+            current_streak: row.get("current_streak")?,
+            longest_streak: row.get("longest_streak")?,
+            time_on_task_secs: row.get::<_, i64>("time_on_task_secs")? as u64,
+            topics: serde_json::from_str(&json("topics")?).map_err(conversion)?,
+            weekly: serde_json::from_str(&json("weekly")?).map_err(conversion)?,
+        })
+    }
+}
+
+#[async_trait]
+impl StudyStore for SqliteStudyStore {
+    async fn insert_flashcard(&self, card: &Flashcard) -> Result<()> {
+        let card = card.clone();
+        self.call(move |conn| Self::write_flashcard(conn, &card)).await
+    }
+
+    async fn insert_flashcards(&self, cards: &[Flashcard]) -> Result<()> {
+        let cards = cards.to_vec();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            for card in &cards {
+                Self::write_flashcard(&tx, card)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn flashcard(&self, id: &str) -> Result<Option<Flashcard>> {
+        let id = id.to_string();
+        self.call(move |conn| {
+            Ok(conn
+                .query_row("SELECT * FROM flashcards WHERE id = ?1", [id], Self::flashcard_from_row)
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn flashcards_by_subject(&self, subject: &str) -> Result<Vec<Flashcard>> {
+        let subject = subject.to_string();
+        self.call(move |conn| {
+            let mut statement = conn.prepare_cached("SELECT * FROM flashcards WHERE subject = ?1 ORDER BY created_at, id")?;
+            let cards = statement
+                .query_map([subject], Self::flashcard_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(cards)
+        })
+        .await
+    }
+
+    async fn delete_flashcard(&self, id: &str) -> Result<bool> {
+        let id = id.to_string();
+        self.call(move |conn| Ok(conn.execute("DELETE FROM flashcards WHERE id = ?1", [id])? > 0))
+            .await
+    }
+
+    async fn insert_practice_test(&self, test: &PracticeTest) -> Result<()> {
+        let test = test.clone();
+        self.call(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO practice_tests (id, title, subject, difficulty, time_limit) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![test.id, test.title, test.subject, test.difficulty, test.time_limit],
+            )?;
+            for (position, question) in test.questions.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO questions (test_id, position, id, question, options, correct_answer, explanation, topic, answer)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        test.id,
+                        position as i64,
+                        question.id,
+                        question.question,
+                        serde_json::to_string(&question.options)?,
+                        question.correct_answer,
+                        question.explanation,
+                        question.topic,
+                        question.answer.as_ref().map(serde_json::to_string).transpose()?,
+                    ],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn practice_test(&self, id: &str) -> Result<Option<PracticeTest>> {
+        let id = id.to_string();
+        self.call(move |conn| {
+            let test = conn
+                .query_row("SELECT * FROM practice_tests WHERE id = ?1", [&id], |row| {
+                    Ok(PracticeTest {
+                        id: row.get("id")?,
+                        title: row.get("title")?,
+                        questions: Vec::new(),
+                        subject: row.get("subject")?,
+                        difficulty: row.get("difficulty")?,
+                        time_limit: row.get("time_limit")?,
+                    })
+                })
+                .optional()?;
+            let Some(mut test) = test else {
+                return Ok(None);
+            };
+
+            let mut statement = conn.prepare_cached("SELECT * FROM questions WHERE test_id = ?1 ORDER BY position")?;
+            let mut rows = statement.query([&id])?;
+            while let Some(row) = rows.next()? {
+                let options: String = row.get("options")?;
+                let answer: Option<String> = row.get("answer")?;
+                test.questions.push(Question {
+                    id: row.get("id")?,
+                    question: row.get("question")?,
+                    options: serde_json::from_str(&options).context("corrupt question options")?,
+                    correct_answer: row.get("correct_answer")?,
+                    explanation: row.get("explanation")?,
+                    topic: row.get("topic")?,
+                    answer: answer
+                        .map(|answer| serde_json::from_str(&answer))
+                        .transpose()
+                        .context("corrupt question answer")?,
+                });
+            }
+            Ok(Some(test))
+        })
+        .await
+    }
+
+    async fn user_progress(&self, user_id: &str, subject: &str) -> Result<Option<UserProgress>> {
+        let (user_id, subject) = (user_id.to_string(), subject.to_string());
+        self.call(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM user_progress WHERE user_id = ?1 AND subject = ?2",
+                    [user_id, subject],
+                    Self::progress_from_row,
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn progress_for_user(&self, user_id: &str) -> Result<Vec<UserProgress>> {
+        let user_id = user_id.to_string();
+        self.call(move |conn| {
+            let mut statement = conn.prepare_cached("SELECT * FROM user_progress WHERE user_id = ?1 ORDER BY subject")?;
+            let progress = statement
+                .query_map([user_id], Self::progress_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(progress)
+        })
+        .await
+    }
+
+    async fn update_user_progress(&self, user_id: &str, subject: &str, update: ProgressUpdate) -> Result<UserProgress> {
+        let (user_id, subject) = (user_id.to_string(), subject.to_string());
+        self.call(move |conn| {
+            // IMMEDIATE takes the write lock up front so two processes never
+            // interleave their read-modify-write
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut progress = Self::read_progress(&tx, &user_id, &subject)?;
+            update(&mut progress);
+            Self::write_progress(&tx, &progress)?;
+            tx.commit()?;
+            Ok(progress)
+        })
+        .await
+    }
+
+    async fn card_state(&self, user_id: &str, card_id: &str) -> Result<Option<CardState>> {
+        let (user_id, card_id) = (user_id.to_string(), card_id.to_string());
+        self.call(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT * FROM card_states WHERE user_id = ?1 AND card_id = ?2",
+                    [user_id, card_id],
+                    |row| Self::card_state_from_row(row, ""),
+                )
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn apply_review(&self, user_id: &str, card_id: &str, update: ReviewUpdate) -> Result<Option<ReviewOutcome>> {
+        let (user_id, card_id) = (user_id.to_string(), card_id.to_string());
+        self.call(move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let Some(card) = tx
+                .query_row("SELECT * FROM flashcards WHERE id = ?1", [&card_id], Self::flashcard_from_row)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let state = tx
+                .query_row(
+                    "SELECT * FROM card_states WHERE user_id = ?1 AND card_id = ?2",
+                    [&user_id, &card_id],
+                    |row| Self::card_state_from_row(row, ""),
+                )
+                .optional()?;
+
+            let mut progress = Self::read_progress(&tx, &user_id, &card.subject)?;
+            let outcome = update(&card, state, &mut progress);
+            Self::write_progress(&tx, &progress)?;
+            let (state, log) = (&outcome.state, &outcome.log);
+            tx.execute(
+                "INSERT OR REPLACE INTO card_states
+                    (user_id, card_id, algorithm, ease, interval_days, due, reps, lapses, stability, difficulty, last_review)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    state.user_id,
+                    state.card_id,
+                    state.algorithm.as_str(),
+                    state.ease,
+                    state.interval_days,
+                    state.due as i64,
+                    state.reps,
+                    state.lapses,
+                    state.stability,
+                    state.difficulty,
+                    state.last_review.map(|at| at as i64),
+                ],
+            )?;
+            tx.execute(
+                "INSERT INTO review_log
+                    (id, user_id, card_id, grade, algorithm, reviewed_at, elapsed_days, interval_before, interval_after, ease, stability, difficulty)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    log.id,
+                    log.user_id,
+                    log.card_id,
+                    log.grade.as_str(),
+                    log.algorithm.as_str(),
+                    log.reviewed_at as i64,
+                    log.elapsed_days,
+                    log.interval_before,
+                    log.interval_after,
+                    log.ease,
+                    log.stability,
+                    log.difficulty,
+                ],
+            )?;
+            tx.commit()?;
+            Ok(Some(outcome))
+        })
+        .await
+    }
+
+    async fn due_cards(&self, user_id: &str, subject: Option<&str>, now: u64, limit: usize) -> Result<Vec<DueCard>> {
+        let (user_id, subject) = (user_id.to_string(), subject.map(str::to_string));
+        self.call(move |conn| {
+            let mut statement = conn.prepare_cached(
+                "SELECT f.*, s.user_id AS s_user_id, s.card_id AS s_card_id, s.algorithm AS s_algorithm, s.ease AS s_ease,
+                        s.interval_days AS s_interval_days, s.due AS s_due, s.reps AS s_reps, s.lapses AS s_lapses,
+                        s.stability AS s_stability, s.difficulty AS s_difficulty, s.last_review AS s_last_review
+                 FROM flashcards f
+                 LEFT JOIN card_states s ON s.card_id = f.id AND s.user_id = ?1
+                 WHERE (?2 IS NULL OR f.subject = ?2) AND (s.due IS NULL OR s.due <= ?3)
+                 ORDER BY s.due IS NULL, COALESCE(s.due, f.created_at), f.id
+                 LIMIT ?4",
+            )?;
+            let due = statement
+                .query_map(params![user_id, subject, now as i64, limit as i64], |row| {
+                    let reviewed = row.get::<_, Option<String>>("s_card_id")?.is_some();
+                    Ok(DueCard {
+                        card: Self::flashcard_from_row(row)?,
+                        state: if reviewed { Some(Self::card_state_from_row(row, "s_")?) } else { None },
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(due)
+        })
+        .await
+    }
+
+    async fn review_log(&self, user_id: &str, card_id: Option<&str>, limit: usize) -> Result<Vec<ReviewLogEntry>> {
+        let (user_id, card_id) = (user_id.to_string(), card_id.map(str::to_string));
+        self.call(move |conn| {
+            let mut statement = conn.prepare_cached(
+                "SELECT * FROM review_log WHERE user_id = ?1 AND (?2 IS NULL OR card_id = ?2)
+                 ORDER BY reviewed_at DESC, rowid DESC LIMIT ?3",
+            )?;
+            let log = statement
+                .query_map(params![user_id, card_id, limit as i64], Self::review_log_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(log)
+        })
+        .await
+    }
+
+    async fn insert_attempt(&self, attempt: &TestAttempt) -> Result<()> {
+        let attempt = attempt.clone();
+        self.call(move |conn| Self::write_attempt(conn, &attempt)).await
+    }
+
+    async fn attempt(&self, id: &str) -> Result<Option<TestAttempt>> {
+        let id = id.to_string();
+        self.call(move |conn| {
+            Ok(conn
+                .query_row("SELECT data FROM test_attempts WHERE id = ?1", [id], Self::attempt_from_row)
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn update_attempt(&self, id: &str, update: AttemptUpdate) -> Result<Option<TestAttempt>> {
+        let id = id.to_string();
+        self.call(move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let Some(mut attempt) = tx
+                .query_row("SELECT data FROM test_attempts WHERE id = ?1", [&id], Self::attempt_from_row)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            update(&mut attempt)?;
+            Self::write_attempt(&tx, &attempt)?;
+            tx.commit()?;
+            Ok(Some(attempt))
+        })
+        .await
+    }
+
+    async fn submit_attempt(&self, id: &str, update: AttemptUpdate, progress: ResultUpdate) -> Result<Option<TestAttempt>> {
+        let id = id.to_string();
+        self.call(move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let Some(mut attempt) = tx
+                .query_row("SELECT data FROM test_attempts WHERE id = ?1", [&id], Self::attempt_from_row)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            update(&mut attempt)?;
+            Self::write_attempt(&tx, &attempt)?;
+            if let Some(result) = &attempt.result {
+                let mut record = Self::read_progress(&tx, &result.user_id, &result.subject)?;
+                progress(result, &mut record);
+                Self::write_progress(&tx, &record)?;
+            }
+            tx.commit()?;
+            Ok(Some(attempt))
+        })
+        .await
+    }
+
+    async fn attempts_for_user(&self, user_id: &str, limit: usize) -> Result<Vec<TestAttempt>> {
+        let user_id = user_id.to_string();
+        self.call(move |conn| {
+            let mut statement = conn.prepare_cached(
+                "SELECT data FROM test_attempts WHERE user_id = ?1 ORDER BY started_at DESC, id LIMIT ?2",
+            )?;
+            let attempts = statement
+                .query_map(params![user_id, limit as i64], Self::attempt_from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(attempts)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StudyEngine;
+
+    /// A database file that is removed, with its WAL files, when dropped
+    struct TempDatabase(std::path::PathBuf);
+
+    impl TempDatabase {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("gunnchai3k-{}-{}-{}.db", name, std::process::id(), uuid::Uuid::new_v4()));
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDatabase {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", self.0.display(), suffix));
+            }
+        }
+    }
+
+    async fn deleting_a_card_forgets_its_reviews(store: Arc<dyn StudyStore>) {
+        let engine = StudyEngine::with_store(store.clone());
+        let card = engine.create_flashcard("2 + 2?", "4", "math", 1).await.unwrap();
+        let other = engine.create_flashcard("3 + 3?", "6", "math", 1).await.unwrap();
+        engine.review_flashcard("u1", &card, Grade::Good).await.unwrap();
+        engine.review_flashcard("u1", &other, Grade::Good).await.unwrap();
+
+        assert!(store.delete_flashcard(&card).await.unwrap());
+        assert!(!store.delete_flashcard(&card).await.unwrap());
+        assert!(store.card_state("u1", &card).await.unwrap().is_none());
+        assert!(store.review_log("u1", Some(&card), 10).await.unwrap().is_empty());
+        assert_eq!(store.review_log("u1", None, 10).await.unwrap().len(), 1);
+        assert!(store.card_state("u1", &other).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn deleting_a_card_cascades_in_both_stores() {
+        deleting_a_card_forgets_its_reviews(Arc::new(InMemoryStudyStore::new())).await;
+        deleting_a_card_forgets_its_reviews(Arc::new(SqliteStudyStore::open_in_memory().unwrap())).await;
+    }
+
+    #[tokio::test]
+    async fn a_failed_batch_stores_no_cards() {
+        let store = SqliteStudyStore::open_in_memory().unwrap();
+        let card = |id: &str| Flashcard {
+            id: id.to_string(),
+            question: format!("question {id}"),
+            answer: "answer".to_string(),
+            subject: "math".to_string(),
+            difficulty: 1,
+            created_at: 0,
+            topic: None,
+        };
+        let error = store.insert_flashcards(&[card("a"), card("b"), card("a")]).await;
+        assert!(error.is_err());
+        assert!(store.flashcards_by_subject("math").await.unwrap().is_empty());
+
+        store.insert_flashcards(&[card("a"), card("b")]).await.unwrap();
+        assert_eq!(store.flashcards_by_subject("math").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn file_backed_store_survives_reopening() {
+        let database = TempDatabase::new("reopen");
+        let card_id = {
+            let engine = StudyEngine::with_store(Arc::new(SqliteStudyStore::open(&database.0).unwrap()));
+            let card_id = engine.create_flashcard("2 + 2?", "4", "math", 1).await.unwrap();
+            engine.review_flashcard("u1", &card_id, Grade::Good).await.unwrap();
+            card_id
+        };
+
+        let store = SqliteStudyStore::open(&database.0).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        assert_eq!(store.flashcard(&card_id).await.unwrap().unwrap().answer, "4");
+        assert_eq!(store.card_state("u1", &card_id).await.unwrap().unwrap().reps, 1);
+        assert_eq!(store.user_progress("u1", "math").await.unwrap().unwrap().flashcards_studied, 1);
+    }
+
+    #[tokio::test]
+    async fn older_databases_are_migrated_in_place() {
+        let database = TempDatabase::new("migrate");
+        {
+            let conn = Connection::open(&database.0).unwrap();
+            for migration in &MIGRATIONS[..3] {
+                conn.execute_batch(migration).unwrap();
+            }
+            conn.pragma_update(None, "user_version", 3).unwrap();
+            conn.execute(
+                "INSERT INTO flashcards (id, question, answer, subject, difficulty, created_at) VALUES ('c1', '2 + 2?', '4', 'math', 1, 100)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO user_progress (user_id, subject, flashcards_studied, tests_completed, average_score, last_studied)
+                 VALUES ('u1', 'math', 3, 2, 75.0, 100)",
+                [],
+            )
+            .unwrap();
+        }
+
+        let store = SqliteStudyStore::open(&database.0).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len());
+        let card = store.flashcard("c1").await.unwrap().unwrap();
+        assert_eq!((card.answer.as_str(), card.topic), ("4", None));
+        let progress = store.user_progress("u1", "math").await.unwrap().unwrap();
+        assert_eq!((progress.flashcards_studied, progress.tests_completed, progress.average_score), (3, 2, 75.0));
+        assert_eq!(progress.current_streak, 0);
+        assert!(progress.topics.is_empty());
+    }
+
+    #[test]
+    fn databases_from_a_newer_build_are_refused() {
+        let database = TempDatabase::new("newer");
+        {
+            let conn = Connection::open(&database.0).unwrap();
+            conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1).unwrap();
+        }
+        let error = SqliteStudyStore::open(&database.0).err().expect("newer schema should be refused");
+        assert!(error.to_string().contains("newer than this build supports"), "{error:#}");
+    }
+}
@@ -0,0 +1,419 @@
+/*!
+ * gunnchAI3k Performance Engine - Response Templates
+ * Localised reply catalogs with variables, variants and sentiment-driven tone
+ */
+
+use std::collections::HashMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use anyhow::{bail, Context, Result};
+
+use crate::entities::Entity;
+use crate::sentiment::SentimentScores;
+
+/// Which register a reply is written in, picked from the message's sentiment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tone {
+    Positive,
+    Neutral,
+    Negative,
+}
+
+impl Tone {
+    pub fn from_sentiment(scores: &SentimentScores) -> Self {
+        if scores.compound >= 0.3 {
+            Tone::Positive
+        } else if scores.compound <= -0.3 {
+            Tone::Negative
+        } else {
+            Tone::Neutral
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Tone::Positive => "positive",
+            Tone::Neutral => "neutral",
+            Tone::Negative => "negative",
+        }
+    }
+}
+
+/// Reply variants for one intent. Missing tones fall back to `neutral`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntentTemplates {
+    #[serde(default)]
+    pub neutral: Vec<String>,
+    #[serde(default)]
+    pub positive: Vec<String>,
+    #[serde(default)]
+    pub negative: Vec<String>,
+}
+
+impl IntentTemplates {
+    fn for_tone(&self, tone: Tone) -> &[String] {
+        let toned = match tone {
+            Tone::Positive => &self.positive,
+            Tone::Neutral => &self.neutral,
+            Tone::Negative => &self.negative,
+        };
+        if toned.is_empty() { &self.neutral } else { toned }
+    }
+}
+
+/// Every reply for one locale. Templates use `{user}`, `{intent}`,
+/// `{sentiment}`, `{entities}`, `{referent}` and `{entity.<label>}` (e.g.
+/// `{entity.course_code}`); `{name|fallback}` supplies text when a variable
+/// is missing. A variant with a missing variable and no fallback is skipped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseCatalog {
+    pub locale: String,
+    #[serde(default = "default_referent_prefix")]
+    pub referent_prefix: String,
+    pub intents: HashMap<String, IntentTemplates>,
+}
+
+fn default_referent_prefix() -> String {
+    "About {referent}: ".to_string()
+}
+
+impl ResponseCatalog {
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let catalog: Self = serde_json::from_str(raw).context("invalid response catalog")?;
+        for (intent, templates) in &catalog.intents {
+            for template in templates.neutral.iter().chain(&templates.positive).chain(&templates.negative) {
+                parse_template(template).with_context(|| format!("bad template for '{}' in {}", intent, catalog.locale))?;
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read response catalog {}", path.display()))?;
+        Self::from_json(&raw)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantSelection {
+    /// The same message always gets the same variant
+    Deterministic,
+    Random,
+}
+
+/// Everything a template can draw on for one reply
+#[derive(Debug, Clone)]
+pub struct ResponseRequest<'a> {
+    pub intent: &'a str,
+    pub locale: Option<&'a str>,
+    pub author_id: &'a str,
+    pub sentiment: &'a SentimentScores,
+    pub entities: &'a [Entity],
+    pub referent: Option<&'a Entity>,
+    /// Picks the variant in deterministic mode, e.g. the message text
+    pub seed: &'a str,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResponseTemplates {
+    catalogs: HashMap<String, ResponseCatalog>,
+    default_locale: String,
+    selection: VariantSelection,
+}
+
+impl ResponseTemplates {
+    /// The English and Spanish catalogs shipped in data/responses
+    pub fn new() -> Self {
+        let mut templates = Self {
+            catalogs: HashMap::new(),
+            default_locale: "en".to_string(),
+            selection: VariantSelection::Deterministic,
+        };
+        for raw in [include_str!("../data/responses/en.json"), include_str!("../data/responses/es.json")] {
+            templates.add_catalog(ResponseCatalog::from_json(raw).expect("built-in response catalog is valid"));
+        }
+        templates
+    }
+
+    pub fn with_selection(mut self, selection: VariantSelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub fn set_selection(&mut self, selection: VariantSelection) {
+        self.selection = selection;
+    }
+
+    pub fn selection(&self) -> VariantSelection {
+        self.selection
+    }
+
+    pub fn set_default_locale(&mut self, locale: &str) {
+        self.default_locale = locale.to_lowercase();
+    }
+
+    /// Adds or replaces the catalog for its locale
+    pub fn add_catalog(&mut self, catalog: ResponseCatalog) {
+        self.catalogs.insert(catalog.locale.to_lowercase(), catalog);
+    }
+
+    /// Loads every `*.json` catalog in `dir`, replacing built-ins with the same
+    /// locale. Returns the loaded locales.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<Vec<String>> {
+        let dir = dir.as_ref();
+        let mut loaded = Vec::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let catalog = ResponseCatalog::from_file(&path)?;
+            loaded.push(catalog.locale.clone());
+            self.add_catalog(catalog);
+        }
+        if loaded.is_empty() {
+            bail!("no response catalogs in {}", dir.display());
+        }
+        Ok(loaded)
+    }
+
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self.catalogs.keys().cloned().collect();
+        locales.sort();
+        locales
+    }
+
+    /// "es-MX" falls back to "es", then to the default locale
+    fn catalog(&self, locale: Option<&str>) -> Option<&ResponseCatalog> {
+        let locale = locale.map(str::to_lowercase);
+        locale
+            .as_deref()
+            .and_then(|locale| {
+                self.catalogs
+                    .get(locale)
+                    .or_else(|| locale.split(['-', '_']).next().and_then(|language| self.catalogs.get(language)))
+            })
+            .or_else(|| self.catalogs.get(&self.default_locale))
+    }
+
+    /// Renders a reply for one intent, or None if no catalog has a usable variant
+    pub fn render(&self, request: &ResponseRequest<'_>) -> Option<String> {
+        let catalog = self.catalog(request.locale)?;
+        let intents = catalog.intents.get(request.intent).or_else(|| catalog.intents.get("general"))?;
+        let tone = Tone::from_sentiment(request.sentiment);
+        let variables = Variables::new(request, tone);
+
+        let rendered: Vec<String> = intents
+            .for_tone(tone)
+            .iter()
+            .filter_map(|template| render_template(template, &variables))
+            .collect();
+        if rendered.is_empty() {
+            return None;
+        }
+
+        let index = match self.selection {
+            VariantSelection::Deterministic => {
+                (stable_hash(&format!("{}:{}:{}", request.seed, request.intent, request.author_id)) % rendered.len() as u64) as usize
+            }
+            VariantSelection::Random => (uuid::Uuid::new_v4().as_u128() % rendered.len() as u128) as usize,
+        };
+        rendered.into_iter().nth(index)
+    }
+
+    /// Wraps a reply with the locale's "About {referent}: " prefix
+    pub fn with_referent(&self, locale: Option<&str>, referent: &Entity, response: &str) -> String {
+        let prefix = self
+            .catalog(locale)
+            .map(|catalog| catalog.referent_prefix.replace("{referent}", &referent.text))
+            .unwrap_or_default();
+        format!("{}{}", prefix, response)
+    }
+}
+
+impl Default for ResponseTemplates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Variables<'a> {
+    request: &'a ResponseRequest<'a>,
+    tone: Tone,
+}
+
+impl<'a> Variables<'a> {
+    fn new(request: &'a ResponseRequest<'a>, tone: Tone) -> Self {
+        Self { request, tone }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        let request = self.request;
+        match name {
+            "user" => Some(format!("<@{}>", request.author_id)),
+            "intent" => Some(request.intent.to_string()),
+            "sentiment" => Some(self.tone.label().to_string()),
+            "referent" => request.referent.map(|entity| entity.text.clone()),
+            "entities" => {
+                let texts: Vec<&str> = request.entities.iter().map(|entity| entity.text.as_str()).collect();
+                (!texts.is_empty()).then(|| texts.join(", "))
+            }
+            _ => {
+                let label = name.strip_prefix("entity.")?;
+                request
+                    .entities
+                    .iter()
+                    .find(|entity| entity.label() == label)
+                    .map(|entity| entity.text.clone())
+            }
+        }
+    }
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Variable { name: &'a str, fallback: Option<&'a str> },
+}
+
+/// Splits a template into text and `{variable|fallback}` pieces; `{{` and `}}` are literal braces
+fn parse_template(template: &str) -> Result<Vec<Piece<'_>>> {
+    let mut pieces = Vec::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("{{") {
+            pieces.push(Piece::Text("{"));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("}}") {
+            pieces.push(Piece::Text("}"));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let Some(end) = after.find('}') else {
+                bail!("unclosed '{{' in \"{}\"", template);
+            };
+            let (name, fallback) = match after[..end].split_once('|') {
+                Some((name, fallback)) => (name.trim(), Some(fallback)),
+                None => (after[..end].trim(), None),
+            };
+            if name.is_empty() {
+                bail!("empty variable in \"{}\"", template);
+            }
+            pieces.push(Piece::Variable { name, fallback });
+            rest = &after[end + 1..];
+        } else if rest.starts_with('}') {
+            bail!("unmatched '}}' in \"{}\"", template);
+        } else {
+            let end = rest.find(['{', '}']).unwrap_or(rest.len());
+            pieces.push(Piece::Text(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+    Ok(pieces)
+}
+
+fn render_template(template: &str, variables: &Variables<'_>) -> Option<String> {
+    let mut output = String::new();
+    for piece in parse_template(template).ok()? {
+        match piece {
+            Piece::Text(text) => output.push_str(text),
+            Piece::Variable { name, fallback } => output.push_str(&variables.get(name).or(fallback.map(str::to_string))?),
+        }
+    }
+    Some(output)
+}
+
+/// FNV-1a; stable across runs, unlike the std hasher
+fn stable_hash(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::EntityKind;
+
+    fn templates(catalogs: &[&str]) -> ResponseTemplates {
+        let mut templates = ResponseTemplates {
+            catalogs: HashMap::new(),
+            default_locale: "en".to_string(),
+            selection: VariantSelection::Deterministic,
+        };
+        for raw in catalogs {
+            templates.add_catalog(ResponseCatalog::from_json(raw).unwrap());
+        }
+        templates
+    }
+
+    fn render(templates: &ResponseTemplates, locale: Option<&str>, compound: f64, entities: &[Entity]) -> Option<String> {
+        let sentiment = SentimentScores { compound, ..SentimentScores::default() };
+        templates.render(&ResponseRequest {
+            intent: "help",
+            locale,
+            author_id: "42",
+            sentiment: &sentiment,
+            entities,
+            referent: None,
+            seed: "hello",
+        })
+    }
+
+    fn rendered(template: &str, entities: &[Entity]) -> Option<String> {
+        let catalog = format!(r#"{{"locale": "en", "intents": {{"help": {{"neutral": [{}]}}}}}}"#, serde_json::json!(template));
+        render(&templates(&[&catalog]), None, 0.0, entities)
+    }
+
+    fn link() -> Entity {
+        Entity { kind: EntityKind::Url, text: "https://example.com".to_string(), start: 0, end: 19 }
+    }
+
+    #[test]
+    fn malformed_templates_are_rejected() {
+        for template in ["Hi {user", "Hi user}", "Hi {}", "Hi { |there}"] {
+            assert!(parse_template(template).is_err(), "{template}");
+        }
+        let catalog = r#"{"locale": "en", "intents": {"help": {"neutral": ["Hi {user"]}}}"#;
+        assert!(ResponseCatalog::from_json(catalog).is_err());
+        assert!(parse_template("{{literal}} {user}").is_ok());
+    }
+
+    #[test]
+    fn variables_render_with_fallbacks_and_literal_braces() {
+        assert_eq!(rendered("Hi {user}, {{ok}}", &[]).as_deref(), Some("Hi <@42>, {ok}"));
+        assert_eq!(rendered("See {entity.url|the docs}", &[]).as_deref(), Some("See the docs"));
+        assert_eq!(rendered("See {entity.url|the docs}", &[link()]).as_deref(), Some("See https://example.com"));
+        assert_eq!(rendered("Feeling {sentiment}", &[]).as_deref(), Some("Feeling neutral"));
+    }
+
+    #[test]
+    fn variants_missing_a_variable_are_skipped() {
+        let catalog = r#"{"locale": "en", "intents": {"help": {"neutral": ["Open {entity.url}", "Open {entity.course_code}"]}}}"#;
+        let templates = templates(&[catalog]);
+        assert_eq!(render(&templates, None, 0.0, &[link()]).as_deref(), Some("Open https://example.com"));
+        assert_eq!(render(&templates, None, 0.0, &[]), None);
+    }
+
+    #[test]
+    fn regional_locales_fall_back_to_language_then_default() {
+        let en = r#"{"locale": "en", "intents": {"help": {"neutral": ["help"]}}}"#;
+        let es = r#"{"locale": "es", "intents": {"help": {"neutral": ["ayuda"]}}}"#;
+        let templates = templates(&[en, es]);
+        assert_eq!(render(&templates, Some("es-MX"), 0.0, &[]).as_deref(), Some("ayuda"));
+        assert_eq!(render(&templates, Some("ES_mx"), 0.0, &[]).as_deref(), Some("ayuda"));
+        assert_eq!(render(&templates, Some("fr-CA"), 0.0, &[]).as_deref(), Some("help"));
+        assert_eq!(render(&templates, None, 0.0, &[]).as_deref(), Some("help"));
+    }
+
+    #[test]
+    fn tone_follows_sentiment_and_falls_back_to_neutral() {
+        let catalog = r#"{"locale": "en", "intents": {"help": {"neutral": ["calm"], "negative": ["sorry"]}}}"#;
+        let templates = templates(&[catalog]);
+        assert_eq!(render(&templates, None, -0.6, &[]).as_deref(), Some("sorry"));
+        assert_eq!(render(&templates, None, 0.1, &[]).as_deref(), Some("calm"));
+        // No positive variants, so neutral is used
+        assert_eq!(render(&templates, None, 0.8, &[]).as_deref(), Some("calm"));
+    }
+}
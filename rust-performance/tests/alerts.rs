@@ -0,0 +1,190 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use gunnchai3k_performance::{
+    AlertDispatcher, AlertEvent, AlertManager, AlertMetric, AlertRule, AlertRuleSet, AlertSeverity, AlertSink, Comparator, MemorySink,
+    PerformanceMetrics, PerformanceMonitor, RetryPolicy,
+};
+
+fn cpu(value: f64) -> PerformanceMetrics {
+    PerformanceMetrics { cpu_usage: value, ..Default::default() }
+}
+
+fn manager() -> AlertManager {
+    AlertManager::new(vec![AlertRule::new("cpu", AlertMetric::CpuUsage, Comparator::Above, 80.0, AlertSeverity::High, "CPU")])
+}
+
+#[test]
+fn silencing_a_delivered_alert_still_resolves_it() {
+    let mut alerts = manager();
+    let now = Instant::now();
+    assert!(matches!(alerts.evaluate_at(&cpu(95.0), now)[..], [AlertEvent::Fired(_)]));
+
+    alerts.silence("cpu", Duration::from_secs(3600));
+    assert!(alerts.evaluate_at(&cpu(95.0), now).is_empty());
+    assert!(alerts.active_alerts()[0].silenced);
+
+    let events = alerts.evaluate_at(&cpu(10.0), now);
+    assert!(matches!(events[..], [AlertEvent::Resolved(_)]), "{events:?}");
+}
+
+#[test]
+fn alert_fired_while_silenced_stays_quiet_then_announces_after_silence() {
+    let mut alerts = manager();
+    let now = Instant::now();
+    alerts.silence("cpu", Duration::from_secs(60));
+    assert!(alerts.evaluate_at(&cpu(95.0), now).is_empty());
+
+    let later = Instant::now() + Duration::from_secs(120);
+    let events = alerts.evaluate_at(&cpu(95.0), later);
+    assert!(matches!(events[..], [AlertEvent::Fired(_)]), "{events:?}");
+    assert!(!alerts.active_alerts()[0].silenced);
+    assert!(matches!(alerts.evaluate_at(&cpu(10.0), later)[..], [AlertEvent::Resolved(_)]));
+}
+
+#[test]
+fn alert_silenced_for_its_whole_life_never_notifies() {
+    let mut alerts = manager();
+    let now = Instant::now();
+    alerts.silence("cpu", Duration::from_secs(3600));
+    assert!(alerts.evaluate_at(&cpu(95.0), now).is_empty());
+    assert!(alerts.evaluate_at(&cpu(10.0), now).is_empty());
+    assert_eq!(alerts.history().len(), 1);
+}
+
+#[test]
+fn rule_files_accept_duration_forms_and_reject_bad_ones() {
+    let rules = |duration: &str| {
+        format!(
+            r#"{{"rules": [{{"key": "cpu", "metric": "cpu_usage", "comparator": "above", "threshold": 80,
+                "for_duration": {duration}, "severity": "high", "summary": "CPU"}}]}}"#
+        )
+    };
+
+    for (raw, expected) in [
+        ("30", Duration::from_secs(30)),
+        ("1.5", Duration::from_millis(1500)),
+        (r#""500ms""#, Duration::from_millis(500)),
+        (r#""5m""#, Duration::from_secs(300)),
+        (r#""1h""#, Duration::from_secs(3600)),
+    ] {
+        let set = AlertRuleSet::from_json(&rules(raw)).unwrap();
+        assert_eq!(set.rules[0].for_duration, expected, "{raw}");
+    }
+
+    for raw in ["-1", "1e300", r#""1e300h""#, r#""99999999999999999999999h""#, r#""5 fortnights""#] {
+        assert!(AlertRuleSet::from_json(&rules(raw)).is_err(), "{raw} should be rejected");
+    }
+
+    let duplicate = r#"{"rules": [
+        {"key": "cpu", "metric": "cpu_usage", "comparator": "above", "threshold": 80, "severity": "high", "summary": "CPU"},
+        {"key": "cpu", "metric": "cpu_usage", "comparator": "above", "threshold": 90, "severity": "critical", "summary": "CPU"}
+    ]}"#;
+    assert!(AlertRuleSet::from_json(duplicate).is_err());
+}
+
+/// Fails its first `failures` deliveries
+struct FlakySink {
+    failures: u32,
+    attempts: AtomicU32,
+    delivered: MemorySink,
+}
+
+#[async_trait::async_trait]
+impl AlertSink for FlakySink {
+    fn name(&self) -> &str {
+        "flaky"
+    }
+
+    async fn deliver(&self, event: &AlertEvent) -> anyhow::Result<()> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt <= self.failures {
+            anyhow::bail!("webhook returned 502");
+        }
+        self.delivered.deliver(event).await
+    }
+}
+
+fn fired(severity: AlertSeverity) -> Vec<AlertEvent> {
+    let rule = AlertRule::new("cpu", AlertMetric::CpuUsage, Comparator::Above, 80.0, severity, "CPU");
+    AlertManager::new(vec![rule]).evaluate(&cpu(95.0))
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(30),
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(20));
+    assert_eq!(policy.backoff(2), Duration::from_millis(30));
+    assert_eq!(RetryPolicy::default().backoff(3), Duration::from_secs(2));
+    assert_eq!(RetryPolicy::default().backoff(40), Duration::from_secs(30));
+
+    let recovers = Arc::new(FlakySink { failures: 2, attempts: AtomicU32::new(0), delivered: MemorySink::new() });
+    let mut dispatcher = AlertDispatcher::new();
+    dispatcher.add_sink(recovers.clone(), AlertSeverity::Low, policy);
+    let started = Instant::now();
+    dispatcher.dispatch(&fired(AlertSeverity::High)).await;
+    assert_eq!(recovers.attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(recovers.delivered.events().len(), 1);
+    assert!(started.elapsed() >= Duration::from_millis(50), "{:?}", started.elapsed());
+
+    let gives_up = Arc::new(FlakySink { failures: 10, attempts: AtomicU32::new(0), delivered: MemorySink::new() });
+    let mut dispatcher = AlertDispatcher::new();
+    dispatcher.add_sink(gives_up.clone(), AlertSeverity::Low, RetryPolicy { max_attempts: 2, ..policy });
+    dispatcher.dispatch(&fired(AlertSeverity::High)).await;
+    assert_eq!(gives_up.attempts.load(Ordering::SeqCst), 2);
+    assert!(gives_up.delivered.events().is_empty());
+}
+
+#[tokio::test]
+async fn sinks_only_receive_alerts_at_or_above_their_severity() {
+    let pager = MemorySink::new();
+    let channel = MemorySink::new();
+    let dispatcher = AlertDispatcher::new()
+        .with_sink(Arc::new(pager.clone()), AlertSeverity::Critical)
+        .with_sink(Arc::new(channel.clone()), AlertSeverity::Medium);
+
+    dispatcher.dispatch(&fired(AlertSeverity::Low)).await;
+    dispatcher.dispatch(&fired(AlertSeverity::High)).await;
+    dispatcher.dispatch(&fired(AlertSeverity::Critical)).await;
+
+    assert_eq!(pager.events().len(), 1);
+    assert_eq!(pager.events()[0].alert().severity, AlertSeverity::Critical);
+    assert_eq!(channel.events().len(), 2);
+}
+
+#[tokio::test]
+async fn watched_rule_file_reloads_and_keeps_rules_on_a_bad_edit() {
+    let path = std::env::temp_dir().join(format!("gunnchai3k-alert-rules-{}.json", std::process::id()));
+    let write = |threshold: f64| {
+        let rules = format!(
+            r#"{{"rules": [{{"key": "cpu", "metric": "cpu_usage", "comparator": "above", "threshold": {threshold},
+                "severity": "high", "summary": "CPU"}}]}}"#
+        );
+        std::fs::write(&path, rules).unwrap();
+    };
+    let settle = || tokio::time::sleep(Duration::from_millis(150));
+
+    write(80.0);
+    let monitor = Arc::new(PerformanceMonitor::new());
+    let watcher = monitor.watch_alert_rules(&path, Duration::from_millis(20)).await.unwrap();
+    monitor.update_metrics(cpu(50.0)).await.unwrap();
+    assert!(monitor.get_alerts().await.is_empty());
+
+    settle().await;
+    write(40.0);
+    settle().await;
+    monitor.update_metrics(cpu(50.0)).await.unwrap();
+    assert_eq!(monitor.get_alerts().await.len(), 1);
+
+    std::fs::write(&path, "{ not json").unwrap();
+    settle().await;
+    monitor.update_metrics(cpu(50.0)).await.unwrap();
+    assert_eq!(monitor.get_alerts().await.len(), 1, "previous rules stay active");
+
+    watcher.shutdown();
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use gunnchai3k_performance::{
+    AnalysisInput, AnalyzerOutput, CacheConfig, ConversationSummarizer, MemoryConfig, MessageAnalysis, MessageAnalyzer, MessageContext, MessageProcessor, Quota, RateLimitConfig,
+    RateLimitDenied, RateLimitScope, SafetyLexicon, StageStatus, Turn, VariantSelection,
+};
+
+#[tokio::test]
+async fn repeated_message_gets_no_reply_from_cache() {
+    let processor = MessageProcessor::new();
+    let mut last = None;
+    for _ in 0..4 {
+        last = Some(processor.process_message("can you help me with this", "u1", "c1").await.unwrap());
+    }
+    let last = last.unwrap();
+    assert!(last.from_cache);
+    assert!(last.spam.is_spam(), "{:?}", last.spam);
+    assert!(last.response.is_empty());
+}
+
+#[tokio::test]
+async fn cached_replies_are_charged_against_budgets() {
+    let processor = MessageProcessor::new();
+    let mut config = RateLimitConfig {
+        user: Quota::per_minute(100),
+        ..RateLimitConfig::default()
+    };
+    for budget in config.costs.budgets.values_mut() {
+        budget.max_requests = 2;
+    }
+    processor.configure_rate_limits(config).await;
+
+    let context = MessageContext::new("u1", "c1");
+    let first = processor.process_message_with_context("can you help me please", &context).await.unwrap();
+    assert!(!first.from_cache);
+    let second = processor.process_message_with_context("can you help me please", &context).await.unwrap();
+    assert!(second.from_cache);
+
+    let error = processor.process_message_with_context("can you help me please", &context).await.unwrap_err();
+    let denied = error.downcast_ref::<RateLimitDenied>().expect("budget denial");
+    assert!(matches!(denied.scope, RateLimitScope::Budget(_)), "{denied:?}");
+}
+
+#[tokio::test]
+async fn custom_pipeline_safety_stage_follows_configure_safety() {
+    let processor = MessageProcessor::with_pipeline(MessageProcessor::default_pipeline());
+    let lexicon = SafetyLexicon::parse("zorblax\tharassment\t0.9").unwrap();
+    processor.configure_safety(|safety| safety.extend_lexicon(lexicon)).await;
+
+    let processed = processor.process_message("you absolute zorblax", "u1", "c1").await.unwrap();
+    assert!(processed.safety.harassment > 0.0, "{:?}", processed.safety);
+    assert!(!processed.safety.matches.is_empty());
+}
+
+struct TopicTagger;
+
+#[async_trait::async_trait]
+impl MessageAnalyzer for TopicTagger {
+    fn name(&self) -> &str {
+        "topic"
+    }
+
+    fn depends_on(&self) -> Vec<String> {
+        vec!["intent".to_string()]
+    }
+
+    async fn analyze(&self, _input: &AnalysisInput<'_>, analysis: &MessageAnalysis) -> anyhow::Result<Vec<AnalyzerOutput>> {
+        Ok(vec![AnalyzerOutput::Priority(if analysis.intents.is_empty() { 1 } else { 9 })])
+    }
+}
+
+#[tokio::test]
+async fn registered_analyzers_run_after_their_dependencies() {
+    let processor = MessageProcessor::new();
+    processor.register_analyzer(Arc::new(TopicTagger), None).await.unwrap();
+
+    let processed = processor.process_message("what is a poisson process", "u1", "c1").await.unwrap();
+    let report = processed.stages.iter().find(|stage| stage.name == "topic").expect("topic stage report");
+    assert_eq!(report.status, StageStatus::Completed);
+    assert_eq!(processed.priority, 9);
+}
+
+#[tokio::test]
+async fn cached_replies_are_per_locale_and_dropped_when_templates_change() {
+    let processor = MessageProcessor::new();
+    let english = MessageContext::new("u1", "c1");
+    let spanish = MessageContext::new("u1", "c1").with_locale("es");
+
+    let first = processor.process_message_with_context("can you help me please", &english).await.unwrap();
+    let other_locale = processor.process_message_with_context("can you help me please", &spanish).await.unwrap();
+    assert!(!other_locale.from_cache);
+    assert_ne!(first.response, other_locale.response);
+
+    processor.configure_templates(|templates| templates.set_default_locale("en")).await;
+    let after_change = processor.process_message_with_context("can you help me please", &english).await.unwrap();
+    assert!(!after_change.from_cache);
+
+    processor.configure_templates(|templates| templates.set_selection(VariantSelection::Random)).await;
+    processor.process_message_with_context("what should I study next", &english).await.unwrap();
+    let repeat = processor.process_message_with_context("what should I study next", &english).await.unwrap();
+    assert!(!repeat.from_cache);
+}
+
+/// Appends the ids of evicted turns to the previous summary, slowly enough
+/// that concurrent messages overlap
+struct AppendingSummarizer;
+
+#[async_trait::async_trait]
+impl ConversationSummarizer for AppendingSummarizer {
+    async fn summarize(&self, previous: Option<&str>, evicted: &[Turn]) -> String {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut summary = previous.unwrap_or_default().to_string();
+        for turn in evicted {
+            summary.push_str(&format!("[{}]", turn.content));
+        }
+        summary
+    }
+}
+
+#[tokio::test]
+async fn concurrent_summaries_build_on_each_other() {
+    let processor = Arc::new(MessageProcessor::new());
+    processor.configure_memory(MemoryConfig { max_turns: 1, ..MemoryConfig::default() }).await;
+    processor.set_conversation_summarizer(Some(Arc::new(AppendingSummarizer))).await;
+
+    let messages: Vec<String> = (0..6).map(|index| format!("question number {index} about entropy")).collect();
+    let tasks: Vec<_> = messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| {
+            let processor = processor.clone();
+            let message = message.clone();
+            tokio::spawn(async move { processor.process_message(&message, &format!("u{index}"), "c1").await.unwrap() })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let conversation = processor.conversation("c1", None).await;
+    let summary = conversation.summary.unwrap();
+    let kept = &conversation.turns[0].content;
+    for message in messages.iter().filter(|message| *message != kept) {
+        assert!(summary.contains(&format!("[{message}]")), "{message} missing from {summary}");
+    }
+}
+
+#[tokio::test]
+async fn cache_evictions_and_expirations_reach_analytics() {
+    let processor = MessageProcessor::new();
+    processor
+        .configure_cache(CacheConfig {
+            capacity: 1,
+            ..CacheConfig::default()
+        })
+        .await;
+    processor.process_message("can you help me with fractions", "u1", "c1").await.unwrap();
+    processor.process_message("can you help me with decimals", "u1", "c1").await.unwrap();
+    assert_eq!(processor.get_analytics().await.cache_evictions, 1);
+
+    processor
+        .configure_cache(CacheConfig {
+            capacity: 4,
+            ttl: Duration::ZERO,
+            ..CacheConfig::default()
+        })
+        .await;
+    processor.process_message("can you help me with ratios", "u1", "c1").await.unwrap();
+    let again = processor.process_message("can you help me with ratios", "u1", "c1").await.unwrap();
+    assert!(!again.from_cache);
+    let analytics = processor.get_analytics().await;
+    assert_eq!((analytics.cache_evictions, analytics.cache_expirations), (1, 1));
+}
@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use gunnchai3k_performance::{MessageContext, MessageProcessor, PerformanceMetrics, PerformanceMonitor, PrometheusExporter, Quota, RateLimitConfig};
+
+#[derive(Debug)]
+struct Sample {
+    name: String,
+    labels: BTreeMap<String, String>,
+    value: f64,
+}
+
+/// Minimal parser for the Prometheus text format; panics on anything malformed
+fn parse(text: &str) -> (HashMap<String, String>, Vec<Sample>) {
+    let mut types = HashMap::new();
+    let mut samples = Vec::new();
+
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = rest.split_once(' ').expect("TYPE line has a name and a kind");
+            assert!(["counter", "gauge", "histogram", "summary", "untyped"].contains(&kind), "unknown type in {line}");
+            assert!(types.insert(name.to_string(), kind.to_string()).is_none(), "duplicate TYPE for {name}");
+            continue;
+        }
+        if line.starts_with("# HELP ") || line.is_empty() {
+            continue;
+        }
+        assert!(!line.starts_with('#'), "unexpected comment {line}");
+
+        let (series, value) = line.rsplit_once(' ').expect("sample has a value");
+        let value = match value {
+            "+Inf" => f64::INFINITY,
+            "-Inf" => f64::NEG_INFINITY,
+            other => other.parse().unwrap_or_else(|_| panic!("bad value in {line}")),
+        };
+        let (name, labels) = match series.split_once('{') {
+            Some((name, rest)) => (name, parse_labels(rest.strip_suffix('}').expect("closing brace"))),
+            None => (series, BTreeMap::new()),
+        };
+        assert!(
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':'),
+            "invalid metric name {name}"
+        );
+        samples.push(Sample { name: name.to_string(), labels, value });
+    }
+    (types, samples)
+}
+
+fn parse_labels(raw: &str) -> BTreeMap<String, String> {
+    let mut labels = BTreeMap::new();
+    let mut chars = raw.chars().peekable();
+    while chars.peek().is_some() {
+        let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+        assert_eq!(chars.next(), Some('"'), "label value must be quoted");
+        let mut value = String::new();
+        loop {
+            match chars.next().expect("unterminated label value") {
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(c) => value.push(c),
+                    None => panic!("dangling escape"),
+                },
+                '"' => break,
+                c => value.push(c),
+            }
+        }
+        labels.insert(key, value);
+        if chars.peek() == Some(&',') {
+            chars.next();
+        }
+    }
+    labels
+}
+
+fn family_of<'a>(name: &'a str, types: &HashMap<String, String>) -> &'a str {
+    for suffix in ["_bucket", "_sum", "_count"] {
+        if let Some(base) = name.strip_suffix(suffix) {
+            if types.get(base).map(String::as_str) == Some("histogram") {
+                return base;
+            }
+        }
+    }
+    name
+}
+
+fn find<'a>(samples: &'a [Sample], name: &str, labels: &[(&str, &str)]) -> Option<&'a Sample> {
+    samples.iter().find(|sample| {
+        sample.name == name && labels.iter().all(|(key, value)| sample.labels.get(*key).map(String::as_str) == Some(*value))
+    })
+}
+
+async fn busy_processor() -> Arc<MessageProcessor> {
+    let processor = Arc::new(MessageProcessor::new());
+    processor
+        .configure_rate_limits(RateLimitConfig {
+            user: Quota { max_requests: 3, window: Duration::from_secs(60) },
+            ..RateLimitConfig::default()
+        })
+        .await;
+
+    let context = MessageContext::new("alice", "general").in_guild("guild \"one\"");
+    for message in ["can you help me", "play some lofi music", "can you help me", "one too many"] {
+        let _ = processor.process_message_with_context(message, &context).await;
+    }
+    processor
+}
+
+#[tokio::test]
+async fn renders_parseable_prometheus_text() {
+    let processor = busy_processor().await;
+    let monitor = Arc::new(PerformanceMonitor::new());
+    monitor
+        .update_metrics(PerformanceMetrics {
+            cpu_usage: 95.0,
+            memory_usage: 40.0,
+            message_processing_rate: 2.5,
+            average_response_time: Duration::from_millis(12),
+            error_rate: 0.0,
+            cache_hit_rate: 0.25,
+            ..PerformanceMetrics::default()
+        })
+        .await
+        .unwrap();
+
+    let text = PrometheusExporter::new(processor).with_monitor(monitor).render().await;
+    let (types, samples) = parse(&text);
+
+    for sample in &samples {
+        let family = family_of(&sample.name, &types);
+        assert!(types.contains_key(family), "{} has no TYPE line", sample.name);
+        if types[family] == "counter" {
+            assert!(sample.name.ends_with("_total"), "counter {} should end in _total", sample.name);
+        }
+    }
+
+    assert_eq!(find(&samples, "gunnchai3k_messages_total", &[("intent", "help")]).unwrap().value, 2.0);
+    assert_eq!(find(&samples, "gunnchai3k_messages_total", &[("intent", "music")]).unwrap().value, 1.0);
+    assert_eq!(find(&samples, "gunnchai3k_cache_hits_total", &[("intent", "help")]).unwrap().value, 1.0);
+    assert_eq!(find(&samples, "gunnchai3k_rate_limited_total", &[]).unwrap().value, 1.0);
+    assert_eq!(find(&samples, "gunnchai3k_guild_messages_total", &[("guild", "guild \"one\"")]).unwrap().value, 3.0);
+    assert_eq!(find(&samples, "gunnchai3k_guild_rate_limited_total", &[("guild", "guild \"one\"")]).unwrap().value, 1.0);
+    assert_eq!(find(&samples, "gunnchai3k_window_messages", &[("window", "1m")]).unwrap().value, 3.0);
+    assert_eq!(find(&samples, "gunnchai3k_cpu_usage_percent", &[]).unwrap().value, 95.0);
+    assert_eq!(find(&samples, "gunnchai3k_active_alerts", &[("severity", "high")]).unwrap().value, 1.0);
+    assert_eq!(types["gunnchai3k_message_processing_seconds"], "histogram");
+
+    // Gauges must not carry the `quantile` label reserved for summaries
+    assert_eq!(types["gunnchai3k_window_latency_seconds"], "gauge");
+    assert!(samples.iter().all(|sample| !sample.labels.contains_key("quantile")));
+    let p50 = find(&samples, "gunnchai3k_window_latency_seconds", &[("window", "1m"), ("q", "0.5")]).unwrap();
+    let max = find(&samples, "gunnchai3k_window_latency_seconds", &[("window", "1m"), ("q", "1")]).unwrap();
+    assert!(p50.value > 0.0 && p50.value <= max.value);
+
+    // Histogram buckets are cumulative and +Inf matches _count
+    for intent in ["help", "music"] {
+        let buckets: Vec<&Sample> = samples
+            .iter()
+            .filter(|sample| sample.name == "gunnchai3k_message_processing_seconds_bucket" && sample.labels["intent"] == intent)
+            .collect();
+        assert!(buckets.windows(2).all(|pair| pair[0].value <= pair[1].value));
+        let infinite = buckets.iter().find(|sample| sample.labels["le"] == "+Inf").unwrap();
+        let count = find(&samples, "gunnchai3k_message_processing_seconds_count", &[("intent", intent)]).unwrap();
+        assert_eq!(infinite.value, count.value);
+    }
+}
+
+#[tokio::test]
+async fn serves_metrics_over_http() {
+    let processor = busy_processor().await;
+    let server = PrometheusExporter::new(processor).serve("127.0.0.1:0".parse().unwrap()).await.unwrap();
+
+    let addr = server.local_addr();
+    let fetch = |path: &'static str| async move {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    };
+
+    let response = fetch("/metrics").await;
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+    assert!(head.contains("text/plain; version=0.0.4"));
+    let (_, samples) = parse(body);
+    assert!(find(&samples, "gunnchai3k_messages_total", &[("intent", "help")]).is_some());
+
+    assert!(fetch("/other").await.starts_with("HTTP/1.1 404"));
+    server.shutdown();
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use gunnchai3k_performance::{
+    AnalysisInput, AnalyzerOutput, MessageAnalysis, MessageAnalyzer, MessageContext, MessageProcessor, SchedulerConfig,
+    SchedulerError, WorkerPool,
+};
+use parking_lot::Mutex;
+
+/// Holds up any message containing "slow" so the queue behind it builds up
+struct SlowStage;
+
+#[async_trait::async_trait]
+impl MessageAnalyzer for SlowStage {
+    fn name(&self) -> &str {
+        "slow"
+    }
+
+    async fn analyze(&self, input: &AnalysisInput<'_>, _analysis: &MessageAnalysis) -> anyhow::Result<Vec<AnalyzerOutput>> {
+        if input.message.contains("slow") {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+        }
+        Ok(Vec::new())
+    }
+}
+
+async fn pool(config: SchedulerConfig) -> Arc<WorkerPool> {
+    let processor = Arc::new(MessageProcessor::new());
+    processor.register_analyzer(Arc::new(SlowStage), Some(Duration::from_secs(5))).await.unwrap();
+    Arc::new(processor.start_workers(SchedulerConfig { workers: 1, ..config }))
+}
+
+/// Occupies the single worker until the slow stage finishes
+async fn block(pool: &Arc<WorkerPool>) -> tokio::task::JoinHandle<anyhow::Result<()>> {
+    let blocker = {
+        let pool = pool.clone();
+        tokio::spawn(async move { pool.submit_with_priority("a slow question", &context("blocker"), 10).await.map(|_| ()) })
+    };
+    while pool.stats().in_flight == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    blocker
+}
+
+fn context(author: &str) -> MessageContext {
+    MessageContext::new(author, "c1")
+}
+
+fn submit(pool: &Arc<WorkerPool>, message: &'static str, priority: u8, order: &Arc<Mutex<Vec<&'static str>>>) -> tokio::task::JoinHandle<anyhow::Result<()>> {
+    let pool = pool.clone();
+    let order = order.clone();
+    tokio::spawn(async move {
+        pool.submit_with_priority(message, &context(message), priority).await?;
+        order.lock().push(message);
+        Ok(())
+    })
+}
+
+async fn queued(pool: &WorkerPool, depth: usize) {
+    while pool.stats().depth < depth {
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+}
+
+#[tokio::test]
+async fn higher_priority_overtakes_queued_chatter() {
+    let pool = pool(SchedulerConfig::default()).await;
+    let blocker = block(&pool).await;
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let chatter = submit(&pool, "nice weather today", 1, &order);
+    queued(&pool, 1).await;
+    let question = submit(&pool, "how do I solve this integral", 9, &order);
+    queued(&pool, 2).await;
+
+    blocker.await.unwrap().unwrap();
+    chatter.await.unwrap().unwrap();
+    question.await.unwrap().unwrap();
+    assert_eq!(*order.lock(), vec!["how do I solve this integral", "nice weather today"]);
+}
+
+#[tokio::test]
+async fn waiting_messages_age_past_newer_higher_priority_ones() {
+    let pool = pool(SchedulerConfig {
+        aging_interval: Duration::from_millis(10),
+        ..SchedulerConfig::default()
+    })
+    .await;
+    let blocker = block(&pool).await;
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let chatter = submit(&pool, "nice weather today", 1, &order);
+    queued(&pool, 1).await;
+    // Ten aging intervals is more than the eight levels between them
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let question = submit(&pool, "how do I solve this integral", 9, &order);
+    queued(&pool, 2).await;
+
+    blocker.await.unwrap().unwrap();
+    chatter.await.unwrap().unwrap();
+    question.await.unwrap().unwrap();
+    assert_eq!(*order.lock(), vec!["nice weather today", "how do I solve this integral"]);
+}
+
+#[tokio::test]
+async fn full_queue_sheds_lower_priority_and_rejects_the_rest() {
+    let pool = pool(SchedulerConfig {
+        queue_capacity: 1,
+        ..SchedulerConfig::default()
+    })
+    .await;
+    let blocker = block(&pool).await;
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let chatter = submit(&pool, "nice weather today", 1, &order);
+    queued(&pool, 1).await;
+    let question = submit(&pool, "how do I solve this integral", 9, &order);
+
+    let shed = chatter.await.unwrap().unwrap_err();
+    assert_eq!(shed.downcast_ref::<SchedulerError>(), Some(&SchedulerError::Shed));
+
+    let rejected = pool.submit_with_priority("what is entropy", &context("u3"), 9).await.unwrap_err();
+    assert_eq!(rejected.downcast_ref::<SchedulerError>(), Some(&SchedulerError::Overloaded));
+
+    blocker.await.unwrap().unwrap();
+    question.await.unwrap().unwrap();
+    let stats = pool.stats();
+    assert_eq!((stats.shed, stats.rejected, stats.completed), (1, 1, 2));
+}
+
+#[tokio::test]
+async fn shutdown_drains_queued_work_within_the_timeout() {
+    let pool = pool(SchedulerConfig::default()).await;
+    let blocker = block(&pool).await;
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let queued_job = submit(&pool, "how do I solve this integral", 5, &order);
+    queued(&pool, 1).await;
+
+    assert_eq!(pool.shutdown(Duration::from_secs(5)).await, 0);
+    blocker.await.unwrap().unwrap();
+    queued_job.await.unwrap().unwrap();
+
+    let late = pool.submit_with_priority("what is entropy", &context("u3"), 5).await.unwrap_err();
+    assert_eq!(late.downcast_ref::<SchedulerError>(), Some(&SchedulerError::ShuttingDown));
+}
+
+#[tokio::test]
+async fn shutdown_aborts_what_is_left_after_the_timeout() {
+    let pool = pool(SchedulerConfig::default()).await;
+    let blocker = block(&pool).await;
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let first = submit(&pool, "how do I solve this integral", 5, &order);
+    let second = submit(&pool, "what is entropy", 5, &order);
+    queued(&pool, 2).await;
+
+    assert_eq!(pool.shutdown(Duration::from_millis(50)).await, 2);
+    for handle in [blocker, first, second] {
+        let error = handle.await.unwrap().unwrap_err();
+        assert_eq!(error.downcast_ref::<SchedulerError>(), Some(&SchedulerError::ShuttingDown));
+    }
+    assert_eq!(pool.stats().in_flight, 0);
+    assert!(order.lock().is_empty());
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+use gunnchai3k_performance::{SpamDetector, SpamSignal, SuggestedAction};
+
+#[test]
+fn short_common_replies_from_many_accounts_are_allowed() {
+    let mut detector = SpamDetector::default();
+    let now = Instant::now();
+    for (i, author) in ["a", "b", "c", "d"].iter().enumerate() {
+        let verdict = detector.check_at(Some("g"), author, "thanks", now + Duration::from_millis(i as u64));
+        assert!(verdict.signals.is_empty(), "{verdict:?}");
+    }
+}
+
+#[test]
+fn cross_account_duplicates_alone_only_warn() {
+    let mut detector = SpamDetector::default();
+    let now = Instant::now();
+    let text = "free nitro for everyone in this server today";
+    detector.check_at(Some("g"), "a", text, now);
+    detector.check_at(Some("g"), "b", text, now);
+    let verdict = detector.check_at(Some("g"), "c", text, now);
+    assert!(matches!(verdict.signals[..], [SpamSignal::DuplicateAcrossAccounts { accounts: 3 }]));
+    assert_eq!(verdict.action, SuggestedAction::Warn);
+}
+
+#[test]
+fn duplicate_links_from_newcomers_are_deleted() {
+    let mut detector = SpamDetector::default();
+    let now = Instant::now();
+    for author in ["a", "b", "c"] {
+        detector.record_join_at("g", author, now);
+    }
+    let text = "join https://discord.gg/abc";
+    detector.check_at(Some("g"), "a", text, now);
+    detector.check_at(Some("g"), "b", text, now);
+    assert!(detector.check_at(Some("g"), "c", text, now).is_spam());
+}
+
+#[test]
+fn direct_messages_from_different_users_are_independent() {
+    let mut detector = SpamDetector::default();
+    let now = Instant::now();
+    let text = "hey can you send me the flashcards for chapter three";
+    for author in ["a", "b", "c", "d"] {
+        let verdict = detector.check_at(None, author, text, now);
+        assert!(verdict.signals.is_empty(), "{verdict:?}");
+    }
+}
+
+#[test]
+fn duplicates_and_repeats_expire_with_the_window() {
+    let mut detector = SpamDetector::default();
+    let now = Instant::now();
+    let text = "free nitro for everyone in this server today";
+    for author in ["a", "a", "a", "b"] {
+        detector.check_at(Some("g"), author, text, now);
+    }
+    let verdict = detector.check_at(Some("g"), "a", text, now);
+    assert!(verdict.signals.contains(&SpamSignal::RepeatedMessage { count: 4 }), "{verdict:?}");
+    assert!(!verdict.signals.iter().any(|signal| matches!(signal, SpamSignal::DuplicateAcrossAccounts { .. })), "{verdict:?}");
+
+    let later = now + detector.config().window + Duration::from_secs(1);
+    let verdict = detector.check_at(Some("g"), "c", text, later);
+    assert!(verdict.signals.is_empty(), "{verdict:?}");
+}
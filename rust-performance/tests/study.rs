@@ -0,0 +1,46 @@
+use std::sync::Arc;
+use gunnchai3k_performance::{Grade, Question, SessionOptions, SqliteStudyStore, StudyEngine};
+
+fn question() -> Question {
+    Question {
+        id: "q1".to_string(),
+        question: "2 + 2?".to_string(),
+        options: vec!["3".to_string(), "4".to_string()],
+        correct_answer: 1,
+        explanation: String::new(),
+        topic: None,
+        answer: None,
+    }
+}
+
+#[tokio::test]
+async fn submitting_records_progress_exactly_once() {
+    let engine = StudyEngine::with_store(Arc::new(SqliteStudyStore::open_in_memory().unwrap()));
+    let test_id = engine.create_practice_test("Arithmetic", vec![question()], "math", 1).await.unwrap();
+    let attempt = engine.start_practice_test("u1", &test_id, SessionOptions::default()).await.unwrap();
+    let served = engine.get_attempt_questions(&attempt.id).await.unwrap();
+    let option = served[0].options.iter().position(|option| option == "4").unwrap() as u8;
+    engine.answer_question(&attempt.id, served[0].position, option).await.unwrap();
+
+    let first = engine.submit_practice_test(&attempt.id).await.unwrap();
+    let second = engine.submit_practice_test(&attempt.id).await.unwrap();
+    assert_eq!(first.score, 100.0);
+    assert_eq!(first.submitted_at, second.submitted_at);
+
+    let progress = engine.get_user_progress("u1", "math").await.unwrap().unwrap();
+    assert_eq!(progress.tests_completed, 1);
+    assert_eq!(progress.average_score, 100.0);
+}
+
+#[tokio::test]
+async fn reviews_are_counted_in_progress() {
+    let engine = StudyEngine::with_store(Arc::new(SqliteStudyStore::open_in_memory().unwrap()));
+    let card_id = engine.create_flashcard("2 + 2?", "4", "math", 1).await.unwrap();
+    engine.review_flashcard("u1", &card_id, Grade::Good).await.unwrap();
+    engine.review_flashcard("u1", &card_id, Grade::Again).await.unwrap();
+
+    let progress = engine.get_user_progress("u1", "math").await.unwrap().unwrap();
+    assert_eq!(progress.flashcards_studied, 2);
+    assert_eq!(engine.get_review_log("u1", Some(&card_id), 10).await.unwrap().len(), 2);
+    assert!(engine.review_flashcard("u1", "missing", Grade::Good).await.is_err());
+}